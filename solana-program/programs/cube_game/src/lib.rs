@@ -1,224 +1,17020 @@
 use anchor_lang::prelude::*;
+use anchor_lang::solana_program::stake;
+use anchor_spl::metadata::MetadataAccount;
+use anchor_spl::token::{Mint, Token, TokenAccount};
 
 declare_id!("CubeGameXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXX"); // Replace after deployment
 
+/// Maximum number of distinct wallets that can share one cube
+pub const MAX_CUBE_CONTRIBUTORS: usize = 8;
+
+/// Maximum number of players that can share one cube's removal
+pub const MAX_SHARED_REMOVERS: usize = 8;
+
+/// `split_rate` is in lamports per token base unit times this scale
+pub const SPLIT_RATE_SCALE: u64 = 1_000_000;
+
+/// `usd_price` is in millionths of a dollar
+pub const USD_PRICE_DECIMALS: u32 = 6;
+
+/// Magic number opening every Pyth account
+pub const PYTH_MAGIC: u32 = 0xa1b2_c3d4;
+
+/// Pyth account type of a price account
+pub const PYTH_PRICE_ACCOUNT: u32 = 3;
+
+/// Pyth aggregate status of a price that is currently trading
+pub const PYTH_STATUS_TRADING: u32 = 1;
+
+/// Maximum number of cube ids an `Inventory` holds
+pub const MAX_INVENTORY_ITEMS: usize = 16;
+
+/// Maximum number of distinct wallets funding one bounty
+pub const MAX_BOUNTY_FUNDERS: usize = 8;
+
+/// Subscription tier with no removal quota
+pub const SUBSCRIPTION_UNLIMITED: u8 = 1;
+
+/// Maximum number of players waiting in one cube's removal queue
+pub const MAX_QUEUE_LEN: usize = 16;
+
+/// Scale of `StakingPool::reward_per_share`
+pub const REWARD_PER_SHARE_SCALE: u128 = 1_000_000_000_000;
+
+/// Basis points denominator (10000 bps = 100%)
+pub const BPS_DENOMINATOR: u64 = 10_000;
+
+/// Maximum number of cube records accepted by `check_cubes`
+pub const MAX_STATUS_QUERY: usize = 32;
+
+/// Number of entries kept in the activity feed ring
+pub const ACTIVITY_FEED_LEN: usize = 32;
+
+/// Number of removals kept in each player's history ring
+pub const PLAYER_HISTORY_LEN: usize = 16;
+
+/// Activity feed action types
+pub const ACTIVITY_REMOVAL: u8 = 0;
+pub const ACTIVITY_RESTORE: u8 = 1;
+pub const ACTIVITY_TRANSFER: u8 = 2;
+
+/// Maximum length of the reconciliation memo stored with a removal
+pub const MAX_MEMO_LEN: usize = 64;
+
+/// Maximum length of a public message posted to the message wall
+pub const MAX_MESSAGE_LEN: usize = 48;
+
+/// Number of messages kept on the message wall ring
+pub const MESSAGE_WALL_LEN: usize = 16;
+
+/// Maximum number of cubes `remove_cubes_batch` removes per call
+pub const MAX_BATCH_REMOVALS: usize = 8;
+
+/// Compute budget a batch is sized against (the default per-transaction
+/// limit) and rough per-step costs behind `max_safe_batch`
+pub const BATCH_COMPUTE_BUDGET: u64 = 200_000;
+pub const BATCH_BASE_CU: u64 = 40_000;
+pub const CU_PER_BATCH_REMOVAL: u64 = 12_000;
+pub const CU_PER_RECORD_CREATION: u64 = 10_000;
+
+/// Maximum number of `PlayerStats` accounts one `finalize_game` call weighs;
+/// larger games finalize over several calls
+pub const MAX_FINALIZE_PLAYERS: usize = 32;
+
+/// Maximum length of a region id
+pub const MAX_REGION_LEN: usize = 16;
+
+/// A cube has six face-adjacent neighbours
+pub const MAX_COMBO_NEIGHBORS: usize = 6;
+
+/// Maximum number of treasury milestones reported for a single payment
+pub const MAX_THRESHOLD_EVENTS: usize = 4;
+
+/// Maximum number of cube records `exit_game` closes per call
+pub const MAX_EXIT_RECORDS: usize = 16;
+
+/// Maximum number of cube records `rollback_removals` processes per call
+pub const MAX_ROLLBACK_BATCH: usize = 16;
+
+/// Maximum number of programs allowed to CPI into the removal instructions
+pub const MAX_CPI_CALLERS: usize = 8;
+
+/// Maximum number of recipients `batch_refund` pays per call
+pub const MAX_BATCH_REFUNDS: usize = 16;
+
+/// Delay before a raised (or removed) self-imposed spend cap takes effect
+pub const SPEND_CAP_RAISE_COOLDOWN: i64 = 24 * 60 * 60;
+
+/// Oldest `as_of` time `attest_stats` accepts, in seconds
+pub const MAX_ATTESTATION_AGE: i64 = 300;
+
+/// Players ranked on each `LeaderboardPage`
+pub const LEADERBOARD_PAGE_SIZE: usize = 10;
+
+/// Maximum number of pages a `LeaderboardMeta` tracks
+pub const MAX_LEADERBOARD_PAGES: usize = 32;
+
 #[program]
 pub mod cube_game {
     use super::*;
 
     /// Initialize the game state (call once)
-    pub fn initialize(ctx: Context<Initialize>, price_per_cube: u64) -> Result<()> {
+    pub fn initialize(
+        ctx: Context<Initialize>,
+        price_per_cube: u64,
+        currency_label: String,
+        currency_decimals: u8,
+        founder_cap: u64,
+    ) -> Result<()> {
+        // Treasury payouts are system transfers signed by the PDA, which
+        // only work while it is a data-free system account
+        let treasury = &ctx.accounts.treasury;
+        require_system_treasury(treasury.owner, treasury.data_len())?;
         let game = &mut ctx.accounts.game_state;
         game.authority = ctx.accounts.authority.key();
         game.price_per_cube = price_per_cube;
         game.total_cubes_removed = 0;
         game.bump = ctx.bumps.game_state;
+        game.treasury_bump = ctx.bumps.treasury;
+        game.transfer_lock_seconds = 0;
+        game.treasury_hot_cap = 0;
+        game.cold_wallet = Pubkey::default();
+        game.surge_multiplier_bps = BPS_DENOMINATOR as u16;
+        game.surge_until = 0;
+        game.withdraw_delay_slots = 0;
+        game.required_prefix = String::new();
+        game.activity_feed_enabled = false;
+        game.ends_at = 0;
+        game.anti_snipe_window = 0;
+        game.anti_snipe_extension = 0;
+        game.max_extensions = 0;
+        game.extensions_used = 0;
+        game.required_collection = None;
+        game.refund_reserve_bps = 0;
+        game.quadratic_coeff = 0;
+        game.stake_liquid_reserve = 0;
+        game.max_removals_per_tx = 0;
+        game.max_cubes = 0;
+        game.completion_seed = [0; 32];
+        game.grand_prize = 0;
+        game.winner = None;
+        game.first_removal_rebate = 0;
+        game.pow_difficulty = 0;
+        game.set_currency(currency_label, currency_decimals)?;
+        game.board_root = [0; 32];
+        game.withdraw_limit_per_window = 0;
+        game.withdraw_window_seconds = 0;
+        game.window_withdrawn = 0;
+        game.window_start = 0;
+        game.sol_burn_bps = 0;
+        game.credit_discount_bps = 0;
+        game.import_mode = false;
+        game.import_locked = false;
+        game.next_threshold = 0;
+        game.threshold_step = 0;
+        game.combo_bonus = 0;
+        game.combo_window = 0;
+        game.combo_min_neighbors = 0;
+        game.zone_count = 0;
+        game.dao_treasury = Pubkey::default();
+        game.dao_tax_bps = 0;
+        game.voucher_premium_bps = 0;
+        game.voucher_duration = 0;
+        game.allowed_cpi_callers = Vec::new();
+        game.decay_rate_bps_per_day = 0;
+        game.high_value_threshold = 0;
+        game.palette_seed = 0;
+        game.last_deposit_at = 0;
+        game.withdraw_cooldown_seconds = 0;
+        game.bomb_rate_bps = 0;
+        game.rush_window_seconds = 0;
+        game.rush_bps_per_removal = 0;
+        game.rush_max_bps = 0;
+        game.rush_window_start = 0;
+        game.recent_removal_count = 0;
+        game.insurance_bps = 0;
+        game.max_cube_records = 0;
+        game.active_cube_records = 0;
+        game.tier_cube_thresholds = [0; 3];
+        game.tier_spend_thresholds = [0; 3];
+        game.min_priority_fee = 0;
+        game.hits_required = 0;
+        game.daily_reward = 0;
+        game.region_pricing = false;
+        game.stats_display_multiplier_bps = BPS_DENOMINATOR as u16;
+        game.price_epoch = 0;
+        game.combo_timer_seconds = 0;
+        game.combo_step_bonus = 0;
+        game.combo_max_bonus = 0;
+        game.attestor = Pubkey::default();
+        game.target_hot_balance = 0;
+        game.total_players = 0;
+        game.founder_cap = founder_cap;
+        game.max_removals_per_slot = 0;
+        game.removals_this_slot = 0;
+        game.current_slot = 0;
+        game.referral_bps = 0;
+        game.min_global_interval_seconds = 0;
+        game.last_global_removal_at = 0;
+        game.reward_mint = Pubkey::default();
+        game.milestone_reward_amount = 0;
+        game.milestone_every = 0;
+        game.max_removers = 0;
+        game.inventory_ring = false;
+        game.payment_mint = Pubkey::default();
+        game.token_treasury = Pubkey::default();
+        game.split_rate = 0;
+        game.min_cubes_before_withdraw = 0;
+        game.queue_turn_seconds = 0;
+        game.queue_deposit = 0;
+        game.analytics_enabled = false;
+        game.ticket_signer = Pubkey::default();
+        game.ranked_mode = false;
+        game.price_authority = ctx.accounts.authority.key();
+        game.pause_authority = ctx.accounts.authority.key();
+        game.withdraw_authority = ctx.accounts.authority.key();
+        game.paused = false;
+        game.tip_bps = 0;
+        game.last_remover = Pubkey::default();
+        game.refund_all_mode = false;
+        game.paid_liability = 0;
+        game.subscription_prices = [0; 2];
+        game.subscription_duration = 0;
+        game.subscription_quota = 0;
+        game.low_balance_threshold = 0;
+        game.analytics_program = Pubkey::default();
+        game.last_cube_id = String::new();
+        game.last_region_id = String::new();
+        game.last_removed_at = 0;
+        game.unlock_fee = 0;
+        game.staker_reward_bps = 0;
+        game.onboarding_refund = 0;
+        game.min_player_age = 0;
+        game.min_reveal_delay_slots = 0;
+        game.ledger_enabled = false;
+        game.membership_collection = Pubkey::default();
+        game.member_discount_bps = 0;
+        game.community_goal_open = false;
+        game.ephemeral_records = false;
+        game.price_in_points = 0;
+        game.point_value_lamports = 0;
+        game.treasury_staked = 0;
+        game.burn_bps = 0;
+        game.entrant_weight = 0;
+        game.completion_slot = 0;
+        game.finalize_weight = 0;
+        game.finalize_cursor = Pubkey::default();
+        game.draw_winner = Pubkey::default();
+        game.last_remover_private = false;
+        game.price_feed = Pubkey::default();
+        game.max_price_staleness = 0;
+        game.usd_price = 0;
+        game.board_size = 0;
+
+        // Warn, without failing, when the treasury still needs
+        // `fund_treasury` to reach rent exemption
+        warn_if_treasury_unfunded(&ctx.accounts.treasury)?;
         Ok(())
     }
 
     /// Remove a cube by paying the required fee
-    pub fn remove_cube(ctx: Context<RemoveCube>, cube_id: String) -> Result<()> {
-        let game = &mut ctx.accounts.game_state;
-        let cube_record = &mut ctx.accounts.cube_record;
-        let player = &ctx.accounts.player;
+    ///
+    /// Neighbouring `CubeRecord`s may be passed in `remaining_accounts` to
+    /// claim a combo bonus.
+    pub fn remove_cube<'info>(
+        ctx: Context<'_, '_, 'info, 'info, RemoveCube<'info>>,
+        cube_id: String,
+        args: RemovalArgs,
+    ) -> Result<()> {
+        process_removal(ctx, cube_id, args, RemovalFlags::default())
+    }
 
-        // Check cube hasn't been removed already
-        require!(!cube_record.is_removed, CubeGameError::CubeAlreadyRemoved);
+    /// Like `remove_cube`, but succeeds without charging when the caller has
+    /// already removed this cube, so a retried transaction is harmless
+    pub fn remove_cube_idempotent<'info>(
+        ctx: Context<'_, '_, 'info, 'info, RemoveCube<'info>>,
+        cube_id: String,
+        args: RemovalArgs,
+    ) -> Result<()> {
+        let player = ctx.accounts.player.key();
+        if ctx
+            .accounts
+            .cube_record
+            .removed_by_caller(&cube_id, &player)?
+        {
+            return Ok(());
+        }
+        process_removal(ctx, cube_id, args, RemovalFlags::default())
+    }
+
+    /// Like `remove_cube`, but logs a compact `CubeRemovedCompact` with the
+    /// keccak hash of the cube id instead of the full `CubeRemovedEvent`
+    pub fn remove_cube_compact<'info>(
+        ctx: Context<'_, '_, 'info, 'info, RemoveCube<'info>>,
+        cube_id: String,
+        args: RemovalArgs,
+    ) -> Result<()> {
+        let flags = RemovalFlags {
+            compact_event: true,
+            ..Default::default()
+        };
+        process_removal(ctx, cube_id, args, flags)
+    }
 
-        // Transfer payment to treasury
-        let transfer_ix = anchor_lang::solana_program::system_instruction::transfer(
-            &player.key(),
-            &ctx.accounts.treasury.key(),
-            game.price_per_cube,
+    /// Remove a cube paying up to `token_amount` of the payment mint towards
+    /// the price, valued at `split_rate`, and `sol_amount` lamports for the
+    /// rest. Together they must cover the price. Only the tokens the price
+    /// needs are taken, none for a free removal; `burn_bps` of them are
+    /// burned and the rest go to the token treasury.
+    pub fn remove_cube_split<'info>(
+        ctx: Context<'_, '_, 'info, 'info, RemoveCube<'info>>,
+        cube_id: String,
+        args: RemovalArgs,
+        sol_amount: u64,
+        token_amount: u64,
+    ) -> Result<()> {
+        let game = &ctx.accounts.game_state;
+        require!(
+            game.payment_mint != Pubkey::default(),
+            CubeGameError::SplitPaymentDisabled
         );
-        anchor_lang::solana_program::program::invoke(
-            &transfer_ix,
-            &[
-                player.to_account_info(),
-                ctx.accounts.treasury.to_account_info(),
-                ctx.accounts.system_program.to_account_info(),
-            ],
+        require!(game.hits_required <= 1, CubeGameError::MultiHitBoard);
+        require!(game.max_removers <= 1, CubeGameError::SharedCubeBoard);
+        let flags = RemovalFlags {
+            split_payment: Some((sol_amount, token_amount)),
+            ..Default::default()
+        };
+        process_removal(ctx, cube_id, args, flags)
+    }
+
+    /// Remove a cube paying `usd_price` in payment-mint tokens, converted at
+    /// the `price_feed` quote. The quote must be trading, positive and at
+    /// most `max_price_staleness` seconds old, and the conversion may take
+    /// at most `max_tokens`. A free removal takes nothing. `burn_bps` of the
+    /// tokens are burned and the rest go to the token treasury.
+    pub fn remove_cube_usd<'info>(
+        ctx: Context<'_, '_, 'info, 'info, RemoveCube<'info>>,
+        cube_id: String,
+        args: RemovalArgs,
+        max_tokens: u64,
+    ) -> Result<()> {
+        let game = &ctx.accounts.game_state;
+        require!(
+            game.price_feed != Pubkey::default(),
+            CubeGameError::UsdPricingDisabled
+        );
+        require!(
+            game.payment_mint != Pubkey::default(),
+            CubeGameError::SplitPaymentDisabled
+        );
+        require!(game.hits_required <= 1, CubeGameError::MultiHitBoard);
+        require!(game.max_removers <= 1, CubeGameError::SharedCubeBoard);
+        let flags = RemovalFlags {
+            usd_payment: Some(max_tokens),
+            ..Default::default()
+        };
+        process_removal(ctx, cube_id, args, flags)
+    }
+
+    /// Remove a cube scoped to `args.region_id`. Its record lives at
+    /// `scoped_cube_record_pda`, so the same cube id in another region or on
+    /// the global board is an independent cube.
+    pub fn remove_cube_in_region<'info>(
+        ctx: Context<'_, '_, 'info, 'info, RemoveCube<'info>>,
+        cube_id: String,
+        args: RemovalArgs,
+    ) -> Result<()> {
+        require!(!args.region_id.is_empty(), CubeGameError::InvalidRegion);
+        process_removal(ctx, cube_id, args, RemovalFlags::default())
+    }
+
+    /// Remove a cube on a holder-exclusive board, proving ownership of an
+    /// NFT from the required collection
+    pub fn remove_cube_gated<'info>(
+        ctx: Context<'_, '_, 'info, 'info, RemoveCube<'info>>,
+        cube_id: String,
+        args: RemovalArgs,
+    ) -> Result<()> {
+        let collection = ctx
+            .accounts
+            .game_state
+            .required_collection
+            .ok_or(CubeGameError::CollectionNotRequired)?;
+        verify_collection_nft(
+            &ctx.accounts.player.key(),
+            ctx.accounts.nft_token_account.as_deref(),
+            ctx.accounts.nft_metadata.as_deref(),
+            &collection,
         )?;
+        let flags = RemovalFlags {
+            holds_required_nft: true,
+            ..Default::default()
+        };
+        process_removal(ctx, cube_id, args, flags)
+    }
 
-        // Mark cube as removed
-        cube_record.is_removed = true;
-        cube_record.removed_by = player.key();
-        cube_record.removed_at = Clock::get()?.unix_timestamp;
-        cube_record.cube_id = cube_id.clone();
+    /// Remove a cube at the member discount, proving ownership of an NFT from
+    /// the membership collection
+    pub fn remove_cube_member<'info>(
+        ctx: Context<'_, '_, 'info, 'info, RemoveCube<'info>>,
+        cube_id: String,
+        args: RemovalArgs,
+    ) -> Result<()> {
+        let collection = ctx.accounts.game_state.membership_collection;
+        require!(
+            collection != Pubkey::default(),
+            CubeGameError::MembershipDisabled
+        );
+        verify_collection_nft(
+            &ctx.accounts.player.key(),
+            ctx.accounts.nft_token_account.as_deref(),
+            ctx.accounts.nft_metadata.as_deref(),
+            &collection,
+        )?;
+        let flags = RemovalFlags {
+            member: true,
+            ..Default::default()
+        };
+        process_removal(ctx, cube_id, args, flags)
+    }
+
+    /// Remove a cube on a server-authoritative board, presenting a ticket:
+    /// an Ed25519 program instruction just before this one in which
+    /// `ticket_signer` signs the Borsh-encoded `RemovalTicket` for this
+    /// program, game, player, cube and `expiry` with `signature`
+    pub fn remove_cube_ticketed<'info>(
+        ctx: Context<'_, '_, 'info, 'info, RemoveCube<'info>>,
+        cube_id: String,
+        args: RemovalArgs,
+        expiry: i64,
+        signature: [u8; 64],
+    ) -> Result<()> {
+        let signer = ctx.accounts.game_state.ticket_signer;
+        require!(signer != Pubkey::default(), CubeGameError::TicketsDisabled);
+        require!(
+            Clock::get()?.unix_timestamp <= expiry,
+            CubeGameError::TicketExpired
+        );
+        let ticket = RemovalTicket::new(
+            ctx.accounts.game_state.key(),
+            ctx.accounts.player.key(),
+            &cube_id,
+            expiry,
+        )
+        .try_to_vec()?;
+        let instructions = ctx
+            .accounts
+            .instructions
+            .as_ref()
+            .ok_or(CubeGameError::InvalidSignature)?;
+        verify_ed25519_signature(instructions, &signer, &ticket, Some(&signature))?;
+        let flags = RemovalFlags {
+            ticketed: true,
+            ..Default::default()
+        };
+        process_removal(ctx, cube_id, args, flags)
+    }
+
+    /// Commit to a later `reveal_remove` with
+    /// `removal_commitment_hash(player, cube_id, salt)`, hiding the cube until
+    /// the reveal. A player holds one commitment at a time.
+    pub fn commit_removal(ctx: Context<CommitRemoval>, commitment: [u8; 32]) -> Result<()> {
+        let removal_commitment = &mut ctx.accounts.removal_commitment;
+        removal_commitment.player = ctx.accounts.player.key();
+        removal_commitment.commitment = commitment;
+        removal_commitment.commit_slot = Clock::get()?.slot;
+        removal_commitment.bump = ctx.bumps.removal_commitment;
+        Ok(())
+    }
+
+    /// Remove the cube committed to by `commit_removal`, at least
+    /// `min_reveal_delay_slots` slots after the commit. The commitment is
+    /// closed back to the player.
+    pub fn reveal_remove<'info>(
+        ctx: Context<'_, '_, 'info, 'info, RemoveCube<'info>>,
+        cube_id: String,
+        args: RemovalArgs,
+        salt: [u8; 32],
+    ) -> Result<()> {
+        let player = ctx.accounts.player.to_account_info();
+        let commitment = ctx
+            .accounts
+            .removal_commitment
+            .as_ref()
+            .ok_or(CubeGameError::CommitRequired)?;
+        commitment.check_reveal(
+            player.key,
+            &cube_id,
+            &salt,
+            Clock::get()?.slot,
+            ctx.accounts.game_state.min_reveal_delay_slots,
+        )?;
+        commitment.close(player)?;
+        let flags = RemovalFlags {
+            revealed: true,
+            ..Default::default()
+        };
+        process_removal(ctx, cube_id, args, flags)
+    }
+
+    /// Deal one hit to a cube; an alias of `remove_cube` that reads better on
+    /// boards where cubes need several hits (see `set_hits_required`)
+    pub fn hit_cube<'info>(
+        ctx: Context<'_, '_, 'info, 'info, RemoveCube<'info>>,
+        cube_id: String,
+        args: RemovalArgs,
+    ) -> Result<()> {
+        process_removal(ctx, cube_id, args, RemovalFlags::default())
+    }
+
+    /// Remove several cubes in one payment, applying `colors[i]` and
+    /// `memos[i]` to `cube_ids[i]`. Colors are ignored while a palette seed
+    /// is set.
+    ///
+    /// `remaining_accounts[i]` must be the `CubeRecord` PDA of `cube_ids[i]`;
+    /// records that do not exist yet are created. Not available on
+    /// NFT-gated boards.
+    pub fn remove_cubes_batch<'info>(
+        ctx: Context<'_, '_, 'info, 'info, RemoveCubesBatch<'info>>,
+        cube_ids: Vec<String>,
+        colors: Vec<u32>,
+        memos: Vec<String>,
+    ) -> Result<()> {
+        let count = cube_ids.len();
+        require_batch_aligned(&cube_ids, &colors, &memos, ctx.remaining_accounts.len())?;
+        require!(count <= MAX_BATCH_REMOVALS, CubeGameError::TooManyAccounts);
+        let creations = ctx
+            .remaining_accounts
+            .iter()
+            .filter(|info| info.owner != &crate::ID)
+            .count();
+        require!(
+            count <= max_safe_batch(creations),
+            CubeGameError::BatchExceedsComputeBudget
+        );
 
-        // Update game stats
-        game.total_cubes_removed += 1;
+        let now = Clock::get()?.unix_timestamp;
+        let player = ctx.accounts.player.key();
+        let fee_exempt = ctx.accounts.fee_exemption.is_some();
+        let game = &mut ctx.accounts.game_state;
+        require!(
+            game.required_collection.is_none(),
+            CubeGameError::MissingRequiredNft
+        );
+        require!(
+            !game.analytics_enabled || ctx.accounts.analytics.is_some(),
+            CubeGameError::AnalyticsRequired
+        );
+        enforce_tx_removal_limit(game, ctx.accounts.instructions.as_ref())?;
+        enforce_cpi_allowlist(game, ctx.accounts.instructions.as_ref())?;
+        // Batches carry no nonces or proofs, so they cannot satisfy a work
+        // requirement or a committed board
+        require!(game.pow_difficulty == 0, CubeGameError::InvalidProofOfWork);
+        require!(game.board_root == [0; 32], CubeGameError::NotOnBoard);
+        // Nor do they attest a priority fee
+        require!(game.min_priority_fee == 0, CubeGameError::PriorityFeeTooLow);
+        require!(game.hits_required <= 1, CubeGameError::MultiHitBoard);
+        require!(game.max_removers <= 1, CubeGameError::SharedCubeBoard);
+        require!(game.queue_turn_seconds == 0, CubeGameError::QueuedBoard);
+        require!(
+            game.ticket_signer == Pubkey::default(),
+            CubeGameError::TicketRequired
+        );
+        require!(
+            game.min_reveal_delay_slots == 0,
+            CubeGameError::CommitRequired
+        );
+        game.require_open_and_extend(now)?;
 
-        // Update player stats
         let player_stats = &mut ctx.accounts.player_stats;
-        player_stats.cubes_removed += 1;
-        player_stats.player = player.key();
+        player_stats.require_can_remove(game, now)?;
+        player_stats.apply_pending_spend_cap(now);
+        let mut new_player = player_stats.register(
+            game,
+            ctx.accounts
+                .wallet_mark
+                .as_deref_mut()
+                .map(|mark| &mut **mark),
+        )?;
+        let public_player = if player_stats.private {
+            Pubkey::default()
+        } else {
+            player
+        };
 
-        emit!(CubeRemovedEvent {
-            cube_id,
-            player: player.key(),
-            total_removed: game.total_cubes_removed,
-            timestamp: Clock::get()?.unix_timestamp,
-        });
+        let mut total_price = 0u64;
+        let mut rebate = 0u64;
+        for (i, info) in ctx.remaining_accounts.iter().enumerate() {
+            let cube_id = &cube_ids[i];
+            game.validate_cube_id(cube_id)?;
+            validate_memo(&memos[i])?;
+            game.require_board_open()?;
+            game.throttle_slot()?;
+            game.require_global_pace(now)?;
+
+            let mut record = load_or_create_cube_record(
+                info,
+                "",
+                cube_id,
+                &ctx.accounts.player.to_account_info(),
+                &ctx.accounts.system_program.to_account_info(),
+            )?;
+            record.validate(cube_id)?;
+            require!(!record.is_removed, CubeGameError::CubeAlreadyRemoved);
+            require!(record.prev_cube_id.is_none(), CubeGameError::SequencedCube);
+            require!(record.royalty_bps == 0, CubeGameError::RoyaltyCube);
+            if record.cube_id.is_empty() {
+                game.note_record_created()?;
+            }
+            require_zone_open(game, ctx.accounts.zone.as_ref(), cube_id)?;
+
+            // Priced one at a time so a quadratic curve sees each removal
+            let price = record.removal_price(game, None, fee_exempt, now)?;
+            require!(
+                !game.needs_confirmation(price),
+                CubeGameError::ConfirmationRequired
+            );
+            total_price = total_price
+                .checked_add(price)
+                .ok_or(CubeGameError::MathOverflow)?;
+            require!(
+                player_stats.spend_cap == 0
+                    || player_stats.total_spent.saturating_add(price) <= player_stats.spend_cap,
+                CubeGameError::SpendCapReached
+            );
+            player_stats.total_spent += price;
+            if record.is_fresh() && !game.ephemeral_records {
+                rebate += game.first_removal_rebate;
+            }
+
+            record.is_removed = true;
+            record.removed_by = player;
+            record.original_remover = player;
+            record.stamp_removed_at(now);
+            record.cube_id = cube_id.clone();
+            record.price_paid = price;
+            credit_share(&mut record.payments, player, price)?;
+            game.note_paid(price);
+            if let Some(analytics) = ctx.accounts.analytics.as_mut() {
+                analytics.record_removal(price, price, new_player)?;
+            }
+            new_player = false;
+            record.priority_fee = 0;
+            record.memo = memos[i].clone();
+            record.color = record.removal_color(game, cube_id, colors[i]);
+            let ranked = record.ranked_score(cube_id);
+            if game.ephemeral_records {
+                game.forget_record(record.sol_paid());
+                record.close(ctx.accounts.player.to_account_info())?;
+            } else {
+                record.exit(&crate::ID)?;
+            }
 
+            game.count_removal()?;
+            count_toward_goal(
+                game,
+                ctx.accounts.community_goal.as_deref_mut(),
+                player_stats,
+                price,
+                now,
+            )?;
+            credit_zone(
+                game,
+                ctx.accounts.zone.as_mut(),
+                ctx.accounts.next_zone.as_mut(),
+            )?;
+            record_history(
+                player_stats,
+                ctx.accounts.player_history.as_deref_mut().map(|h| &mut **h),
+                cube_id,
+                price,
+                now,
+            )?;
+            player_stats.cubes_removed += 1;
+            game.entrant_weight += 1;
+            player_stats.score = player_stats.score.saturating_add(ranked);
+            player_stats.last_removal_at = now;
+            if !player_stats.team_id.is_empty() {
+                let team = ctx
+                    .accounts
+                    .team
+                    .as_mut()
+                    .ok_or(CubeGameError::TeamAccountRequired)?;
+                team.total_cubes_removed += 1;
+            }
+            player_stats.promote_tier(game, public_player);
+
+            log_activity(
+                game,
+                ctx.accounts.activity_feed.as_mut(),
+                ACTIVITY_REMOVAL,
+                public_player,
+                cube_id,
+                now,
+            )?;
+            emit!(CubeRemovedEvent {
+                cube_id: cube_id.clone(),
+                player: public_player,
+                total_removed: game.total_cubes_removed,
+                completion_bps: game.completion_bps(),
+                timestamp: now,
+                memo: memos[i].clone(),
+                message: String::new(),
+                color: record.color,
+                tier: player_stats.tier,
+                is_founder: player_stats.is_founder,
+                score: player_stats.score,
+            });
+        }
+
+        player_stats.player = player;
+
+        collect_payment(
+            game,
+            PaymentRoute {
+                payer: &ctx.accounts.player.to_account_info(),
+                treasury: &ctx.accounts.treasury,
+                system_program: &ctx.accounts.system_program.to_account_info(),
+                refund_pool: ctx.accounts.refund_pool.as_mut(),
+                insurance_vault: ctx.accounts.insurance_vault.as_mut(),
+                incinerator: ctx.accounts.incinerator.as_deref(),
+                dao_treasury: ctx.accounts.dao_treasury.as_deref(),
+                ledger: ctx.accounts.ledger.as_deref_mut(),
+            },
+            total_price,
+        )?;
+        game.note_deposit(total_price, ctx.accounts.treasury.lamports(), now);
+
+        if rebate > 0 && game.can_pay(rebate, treasury_spendable(&ctx.accounts.treasury)?) {
+            pay_reward(
+                game,
+                &ctx.accounts.treasury,
+                &ctx.accounts.player.to_account_info(),
+                &ctx.accounts.system_program.to_account_info(),
+                ctx.accounts.ledger.as_deref_mut(),
+                rebate,
+            )?;
+        }
+
+        forward_treasury_overflow(
+            game,
+            &ctx.accounts.treasury,
+            ctx.accounts.cold_wallet.as_ref(),
+            ctx.accounts.ledger.as_deref_mut(),
+            &ctx.accounts.system_program.to_account_info(),
+        )?;
         Ok(())
     }
 
-    /// Update the price (owner only)
-    pub fn set_price(ctx: Context<SetPrice>, new_price: u64) -> Result<()> {
-        let game = &mut ctx.accounts.game_state;
-        game.price_per_cube = new_price;
+    /// Emit how many cubes one `remove_cubes_batch` call can safely remove
+    /// when `account_creation_count` of their records are new (simulate only)
+    pub fn quote_batch_limit(
+        _ctx: Context<QuoteBatchLimit>,
+        account_creation_count: u32,
+    ) -> Result<()> {
+        emit!(BatchLimitEvent {
+            account_creation_count,
+            max_batch: max_safe_batch(account_creation_count as usize) as u32,
+        });
         Ok(())
     }
 
-    /// Withdraw funds (owner only)
-    pub fn withdraw(ctx: Context<Withdraw>, amount: u64) -> Result<()> {
-        let treasury = &ctx.accounts.treasury;
-        let authority = &ctx.accounts.authority;
+    /// Escrow `amount` lamports for whoever removes `cube_id` on the global
+    /// board. Any wallet can fund, and repeat funding accumulates.
+    pub fn fund_bounty(ctx: Context<FundBounty>, cube_id: String, amount: u64) -> Result<()> {
+        require!(amount > 0, CubeGameError::ZeroContribution);
+        let game = &ctx.accounts.game_state;
+        game.validate_cube_id(&cube_id)?;
+        require!(
+            !game.is_over(Clock::get()?.unix_timestamp)
+                && !cube_is_removed(&ctx.accounts.cube_record)?,
+            CubeGameError::BountyUnavailable
+        );
 
-        **treasury.to_account_info().try_borrow_mut_lamports()? -= amount;
-        **authority.to_account_info().try_borrow_mut_lamports()? += amount;
+        let bounty = &mut ctx.accounts.bounty;
+        let funder = ctx.accounts.funder.key();
+        bounty.fund(funder, amount)?;
+        bounty.cube_id = cube_id.clone();
+        bounty.bump = ctx.bumps.bounty;
 
+        require_ledger(&ctx.accounts.game_state, ctx.accounts.ledger.as_deref())?;
+        transfer_lamports(
+            &ctx.accounts.funder.to_account_info(),
+            &bounty.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+            amount,
+        )?;
+        if let Some(ledger) = ctx.accounts.ledger.as_mut() {
+            ledger.record_escrow(amount)?;
+        }
+        emit!(BountyFundedEvent {
+            cube_id,
+            funder,
+            amount,
+            total: bounty.total,
+        });
         Ok(())
     }
-}
 
-#[derive(Accounts)]
-pub struct Initialize<'info> {
-    #[account(
-        init,
-        payer = authority,
-        space = 8 + GameState::INIT_SPACE,
-        seeds = [b"game_state"],
-        bump
-    )]
-    pub game_state: Account<'info, GameState>,
+    /// Return the caller's share of an unclaimed bounty once the game is over
+    /// or the cube was removed without claiming it. Only `remove_cube` and
+    /// its variants that pass the bounty pay it out, and paying clears every
+    /// share, so a share left after a removal was never paid.
+    pub fn refund_bounty(ctx: Context<RefundBounty>, _cube_id: String) -> Result<()> {
+        require!(
+            cube_is_removed(&ctx.accounts.cube_record)?
+                || ctx
+                    .accounts
+                    .game_state
+                    .is_over(Clock::get()?.unix_timestamp),
+            CubeGameError::GameNotOver
+        );
 
-    /// CHECK: Treasury PDA to hold funds
-    #[account(
-        seeds = [b"treasury"],
-        bump
-    )]
-    pub treasury: AccountInfo<'info>,
+        let bounty = &mut ctx.accounts.bounty;
+        let amount = bounty.withdraw_share(&ctx.accounts.funder.key())?;
 
-    #[account(mut)]
-    pub authority: Signer<'info>,
+        require_ledger(&ctx.accounts.game_state, ctx.accounts.ledger.as_deref())?;
+        **bounty.to_account_info().try_borrow_mut_lamports()? -= amount;
+        **ctx.accounts.funder.try_borrow_mut_lamports()? += amount;
+        if let Some(ledger) = ctx.accounts.ledger.as_mut() {
+            ledger.record_refund(amount, amount)?;
+        }
+        Ok(())
+    }
 
-    pub system_program: Program<'info, System>,
-}
+    /// Join the removal queue of a global-board cube, leaving
+    /// `queue_deposit` lamports in it. While queueing is on, only the player
+    /// at the front may remove the cube, until they do or their turn runs
+    /// out. Removing the cube or leaving the queue returns the deposit; a
+    /// turn that runs out forfeits it to the treasury.
+    pub fn enqueue_for_cube(ctx: Context<EnqueueForCube>, cube_id: String) -> Result<()> {
+        let game = &ctx.accounts.game_state;
+        require!(game.queue_turn_seconds > 0, CubeGameError::QueueDisabled);
+        game.validate_cube_id(&cube_id)?;
+        let now = Clock::get()?.unix_timestamp;
+        require!(!game.is_over(now), CubeGameError::GameEnded);
+        require!(
+            !cube_is_removed(&ctx.accounts.cube_record)?,
+            CubeGameError::CubeAlreadyRemoved
+        );
 
-#[derive(Accounts)]
-#[instruction(cube_id: String)]
-pub struct RemoveCube<'info> {
-    #[account(
-        mut,
-        seeds = [b"game_state"],
-        bump = game_state.bump
-    )]
-    pub game_state: Account<'info, GameState>,
+        let deposit = game.queue_deposit;
+        let queue = &mut ctx.accounts.removal_queue;
+        let player = ctx.accounts.player.key();
+        if queue.opener == Pubkey::default() {
+            queue.opener = player;
+        }
+        queue.expire_turns(now, game.queue_turn_seconds);
+        queue.join(player, deposit, now)?;
+        queue.cube_id = cube_id.clone();
+        queue.bump = ctx.bumps.removal_queue;
+        let position = queue.entries.len() as u32;
+        transfer_lamports(
+            &ctx.accounts.player.to_account_info(),
+            &ctx.accounts.removal_queue.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+            deposit,
+        )?;
 
-    #[account(
-        init_if_needed,
-        payer = player,
-        space = 8 + CubeRecord::INIT_SPACE,
-        seeds = [b"cube", cube_id.as_bytes()],
-        bump
-    )]
-    pub cube_record: Account<'info, CubeRecord>,
+        emit!(QueueJoinedEvent {
+            cube_id,
+            player,
+            position,
+        });
+        Ok(())
+    }
 
-    #[account(
-        init_if_needed,
-        payer = player,
-        space = 8 + PlayerStats::INIT_SPACE,
-        seeds = [b"player", player.key().as_ref()],
-        bump
-    )]
-    pub player_stats: Account<'info, PlayerStats>,
+    /// Leave a cube's removal queue, taking back the deposit
+    pub fn leave_queue(ctx: Context<LeaveQueue>, _cube_id: String) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let queue = &mut ctx.accounts.removal_queue;
+        queue.expire_turns(now, ctx.accounts.game_state.queue_turn_seconds);
+        let deposit = queue.leave(&ctx.accounts.player.key(), now)?;
+        let queue_info = queue.to_account_info();
+        **queue_info.try_borrow_mut_lamports()? -= deposit;
+        **ctx.accounts.player.try_borrow_mut_lamports()? += deposit;
+        Ok(())
+    }
 
-    /// CHECK: Treasury PDA
-    #[account(
-        mut,
-        seeds = [b"treasury"],
-        bump
-    )]
-    pub treasury: AccountInfo<'info>,
+    /// Close a removal queue nobody is waiting in: forfeited deposits go to
+    /// the treasury and the rent to whoever opened it. Anyone may call it.
+    pub fn close_queue(ctx: Context<CloseQueue>, _cube_id: String) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let game = &ctx.accounts.game_state;
+        let queue = &mut ctx.accounts.removal_queue;
+        queue.expire_turns(now, game.queue_turn_seconds);
+        require!(queue.entries.is_empty(), CubeGameError::QueueNotEmpty);
+        let forfeited = queue.forfeited;
+        if forfeited > 0 {
+            require_ledger(game, ctx.accounts.ledger.as_deref())?;
+            let queue_info = queue.to_account_info();
+            **queue_info.try_borrow_mut_lamports()? -= forfeited;
+            **ctx.accounts.treasury.try_borrow_mut_lamports()? += forfeited;
+            if let Some(ledger) = ctx.accounts.ledger.as_mut() {
+                ledger.record_inflow(forfeited, 0, 0, 0)?;
+            }
+        }
+        Ok(())
+    }
 
-    #[account(mut)]
-    pub player: Signer<'info>,
+    /// Create the caller's inventory, which collects the ids of cubes they
+    /// remove while it is passed to `remove_cube`
+    pub fn init_inventory(ctx: Context<InitInventory>) -> Result<()> {
+        let inventory = &mut ctx.accounts.inventory;
+        inventory.player = ctx.accounts.player.key();
+        inventory.bump = ctx.bumps.inventory;
+        Ok(())
+    }
 
-    pub system_program: Program<'info, System>,
-}
+    /// Emit `player`'s inventory (simulate only)
+    pub fn view_inventory(ctx: Context<ViewInventory>, player: Pubkey) -> Result<()> {
+        emit!(InventoryEvent {
+            player,
+            items: ctx.accounts.inventory.items.clone(),
+        });
+        Ok(())
+    }
 
-#[derive(Accounts)]
-pub struct SetPrice<'info> {
-    #[account(
-        mut,
-        seeds = [b"game_state"],
-        bump = game_state.bump,
-        has_one = authority
-    )]
-    pub game_state: Account<'info, GameState>,
+    /// Endorse a removed cube in `region_id` (empty for the global board),
+    /// once per player
+    pub fn like_cube(ctx: Context<LikeCube>, cube_id: String, _region_id: String) -> Result<()> {
+        let likes = ctx.accounts.cube_record.add_like()?;
+        ctx.accounts.like.bump = ctx.bumps.like;
+        emit!(CubeLikedEvent {
+            cube_id,
+            liker: ctx.accounts.liker.key(),
+            likes,
+        });
+        Ok(())
+    }
 
-    pub authority: Signer<'info>,
-}
+    /// Pay `unlock_fee` to have a standing cube's hidden rarity score and
+    /// colour rolled from the hash of a later slot. `reveal_cube` reads them
+    /// out once that slot has passed, and removals then use them.
+    pub fn unlock_cube(
+        ctx: Context<UnlockCube>,
+        cube_id: String,
+        _region_id: String,
+    ) -> Result<()> {
+        let game = &mut ctx.accounts.game_state;
+        require!(game.unlock_fee > 0, CubeGameError::UnlockDisabled);
+        require!(!game.ephemeral_records, CubeGameError::EphemeralBoard);
+        game.validate_cube_id(&cube_id)?;
 
-#[derive(Accounts)]
-pub struct Withdraw<'info> {
-    #[account(
-        seeds = [b"game_state"],
-        bump = game_state.bump,
-        has_one = authority
-    )]
-    pub game_state: Account<'info, GameState>,
+        let cube_record = &mut ctx.accounts.cube_record;
+        cube_record.validate(&cube_id)?;
+        require!(!cube_record.is_removed, CubeGameError::CubeAlreadyRemoved);
+        require!(
+            !cube_record.revealed && cube_record.unlock_slot == 0,
+            CubeGameError::CubeAlreadyRevealed
+        );
+        if cube_record.cube_id.is_empty() {
+            game.note_record_created()?;
+            cube_record.cube_id = cube_id.clone();
+        }
+        let unlock_slot = Clock::get()?.slot;
+        cube_record.unlock_slot = unlock_slot;
 
-    /// CHECK: Treasury PDA
-    #[account(
-        mut,
-        seeds = [b"treasury"],
-        bump
-    )]
-    pub treasury: AccountInfo<'info>,
+        let fee = game.unlock_fee;
+        collect_payment(
+            game,
+            PaymentRoute {
+                payer: &ctx.accounts.player.to_account_info(),
+                treasury: &ctx.accounts.treasury,
+                system_program: &ctx.accounts.system_program.to_account_info(),
+                refund_pool: ctx.accounts.refund_pool.as_mut(),
+                insurance_vault: ctx.accounts.insurance_vault.as_mut(),
+                incinerator: ctx.accounts.incinerator.as_deref(),
+                dao_treasury: ctx.accounts.dao_treasury.as_deref(),
+                ledger: ctx.accounts.ledger.as_deref_mut(),
+            },
+            fee,
+        )?;
+        let balance = ctx.accounts.treasury.lamports();
+        ctx.accounts
+            .game_state
+            .note_deposit(fee, balance, Clock::get()?.unix_timestamp);
 
-    #[account(mut)]
-    pub authority: Signer<'info>,
-}
+        emit!(CubeUnlockedEvent {
+            cube_id,
+            player: ctx.accounts.player.key(),
+            unlock_slot,
+            fee,
+        });
+        Ok(())
+    }
 
-#[account]
-#[derive(InitSpace)]
-pub struct GameState {
-    pub authority: Pubkey,
-    pub price_per_cube: u64,
-    pub total_cubes_removed: u64,
-    pub bump: u8,
-}
+    /// Roll an unlocked cube's hidden attributes from the hash of the first
+    /// slot after it was unlocked. Anyone may call it; if that hash has left
+    /// the SlotHashes window the unlock moves to the current slot instead.
+    pub fn reveal_cube(
+        ctx: Context<RevealCube>,
+        cube_id: String,
+        _region_id: String,
+    ) -> Result<()> {
+        use anchor_lang::solana_program::slot_hashes;
 
-#[account]
-#[derive(InitSpace)]
-pub struct CubeRecord {
-    pub is_removed: bool,
-    pub removed_by: Pubkey,
-    pub removed_at: i64,
-    #[max_len(32)]
-    pub cube_id: String,
-}
+        let cube_record = &mut ctx.accounts.cube_record;
+        require!(cube_record.unlock_slot > 0, CubeGameError::CubeNotUnlocked);
+        require!(!cube_record.revealed, CubeGameError::CubeAlreadyRevealed);
+        require!(!cube_record.is_removed, CubeGameError::CubeAlreadyRemoved);
 
-#[account]
-#[derive(InitSpace)]
-pub struct PlayerStats {
-    pub player: Pubkey,
-    pub cubes_removed: u64,
-}
+        let slot = Clock::get()?.slot;
+        let data = ctx.accounts.slot_hashes.try_borrow_data()?;
+        match slot_hash_after(&data, cube_record.unlock_slot) {
+            Some(hash) => {
+                let (rarity, color) = reveal_attributes(&hash, &cube_id);
+                cube_record.revealed = true;
+                cube_record.rarity = rarity;
+                cube_record.revealed_color = color;
+                emit!(CubeRevealedEvent {
+                    cube_id,
+                    rarity,
+                    color,
+                });
+            }
+            None => {
+                require!(
+                    slot.saturating_sub(cube_record.unlock_slot) > slot_hashes::MAX_ENTRIES as u64,
+                    CubeGameError::SlotHashUnavailable
+                );
+                cube_record.unlock_slot = slot;
+            }
+        }
+        Ok(())
+    }
 
-#[event]
-pub struct CubeRemovedEvent {
-    pub cube_id: String,
-    pub player: Pubkey,
-    pub total_removed: u64,
-    pub timestamp: i64,
-}
+    /// Contribute lamports towards removing a cube in `region_id` (empty for
+    /// the global board) together with other players
+    pub fn contribute_to_cube(
+        ctx: Context<ContributeToCube>,
+        cube_id: String,
+        region_id: String,
+        amount: u64,
+        board_proof: Vec<[u8; 32]>,
+    ) -> Result<()> {
+        let game = &mut ctx.accounts.game_state;
+        // Contributions span transactions, so they need a lasting record
+        require!(!game.ephemeral_records, CubeGameError::EphemeralBoard);
+        let cube_record = &mut ctx.accounts.cube_record;
+        let cube_shares = &mut ctx.accounts.cube_shares;
+        let contributor = &ctx.accounts.contributor;
 
-#[error_code]
-pub enum CubeGameError {
-    #[msg("This cube has already been removed")]
-    CubeAlreadyRemoved,
+        let now = Clock::get()?.unix_timestamp;
+
+        game.validate_cube_id(&cube_id)?;
+        game.verify_on_board(&cube_id, &board_proof)?;
+        require_zone_open(game, ctx.accounts.zone.as_ref(), &cube_id)?;
+        cube_record.validate(&cube_id)?;
+        require!(!cube_record.is_removed, CubeGameError::CubeAlreadyRemoved);
+        require!(
+            cube_record.prev_cube_id.is_none(),
+            CubeGameError::SequencedCube
+        );
+        require!(cube_record.royalty_bps == 0, CubeGameError::RoyaltyCube);
+        require!(amount > 0, CubeGameError::ZeroContribution);
+        require!(
+            game.required_collection.is_none(),
+            CubeGameError::MissingRequiredNft
+        );
+        require!(game.pow_difficulty == 0, CubeGameError::InvalidProofOfWork);
+        require!(game.min_priority_fee == 0, CubeGameError::PriorityFeeTooLow);
+        let player_stats = &mut ctx.accounts.player_stats;
+        player_stats.require_can_remove(game, now)?;
+        player_stats.player = contributor.key();
+        enforce_tx_removal_limit(game, ctx.accounts.instructions.as_ref())?;
+        enforce_cpi_allowlist(game, ctx.accounts.instructions.as_ref())?;
+        require!(
+            !game.analytics_enabled || ctx.accounts.analytics.is_some(),
+            CubeGameError::AnalyticsRequired
+        );
+        require!(game.hits_required <= 1, CubeGameError::MultiHitBoard);
+        require!(game.max_removers <= 1, CubeGameError::SharedCubeBoard);
+        require!(game.queue_turn_seconds == 0, CubeGameError::QueuedBoard);
+        require!(
+            game.ticket_signer == Pubkey::default(),
+            CubeGameError::TicketRequired
+        );
+        require!(
+            game.min_reveal_delay_slots == 0,
+            CubeGameError::CommitRequired
+        );
+        // Claim the id on the first contribution so the record is only
+        // counted once
+        if cube_record.cube_id.is_empty() {
+            game.note_record_created()?;
+            cube_record.cube_id = cube_id.clone();
+        }
+        game.require_open_and_extend(now)?;
+        game.require_board_open()?;
+        game.throttle_slot()?;
+
+        // Never collect more than what is still missing to reach the price
+        let (mut region, region_price) =
+            load_priced_region(game, ctx.accounts.region.as_ref(), &region_id)?;
+        let price = effective_price(game, region_price, false, now)?;
+        // Contributions cannot carry a removal intent
+        require!(
+            !game.needs_confirmation(price),
+            CubeGameError::ConfirmationRequired
+        );
+        let remaining = price.saturating_sub(cube_shares.total_contributed);
+        let accepted = amount.min(remaining);
+
+        if accepted > 0 {
+            collect_payment(
+                game,
+                PaymentRoute {
+                    payer: &contributor.to_account_info(),
+                    treasury: &ctx.accounts.treasury,
+                    system_program: &ctx.accounts.system_program.to_account_info(),
+                    refund_pool: ctx.accounts.refund_pool.as_mut(),
+                    insurance_vault: ctx.accounts.insurance_vault.as_mut(),
+                    incinerator: ctx.accounts.incinerator.as_deref(),
+                    dao_treasury: ctx.accounts.dao_treasury.as_deref(),
+                    ledger: ctx.accounts.ledger.as_deref_mut(),
+                },
+                accepted,
+            )?;
+            game.note_deposit(accepted, ctx.accounts.treasury.lamports(), now);
+            if let Some(analytics) = ctx.accounts.analytics.as_mut() {
+                analytics.record_collected(accepted)?;
+            }
+
+            credit_share(&mut cube_shares.contributions, contributor.key(), accepted)?;
+            cube_shares.total_contributed += accepted;
+            cube_shares.cube_id = cube_id.clone();
+        }
+
+        // Private players are masked in logs, the shares keep the real key
+        let public_contributor = if ctx.accounts.player_stats.private {
+            Pubkey::default()
+        } else {
+            contributor.key()
+        };
+        emit!(CubeContributionEvent {
+            cube_id: cube_id.clone(),
+            contributor: public_contributor,
+            amount: accepted,
+            total_contributed: cube_shares.total_contributed,
+        });
+
+        if cube_shares.total_contributed < price {
+            return Ok(());
+        }
+
+        // Price reached: the largest contributor owns the cube, earliest wins ties
+        let owner = cube_shares
+            .contributions
+            .iter()
+            .fold(None::<&CubeContribution>, |best, c| match best {
+                Some(b) if b.amount >= c.amount => Some(b),
+                _ => Some(c),
+            })
+            .map(|c| c.contributor)
+            .unwrap_or_else(|| contributor.key());
+        game.require_global_pace(now)?;
+        // The owner is credited with the removal, as a remover would be
+        let owner_stats = if owner == contributor.key() {
+            &mut ctx.accounts.player_stats
+        } else {
+            let stats = ctx
+                .accounts
+                .owner_stats
+                .as_mut()
+                .ok_or(CubeGameError::OwnerStatsRequired)?;
+            let (expected, _) =
+                Pubkey::find_program_address(&[b"player", owner.as_ref()], &crate::ID);
+            require_keys_eq!(stats.key(), expected, CubeGameError::OwnerStatsRequired);
+            stats
+        };
+        let owner_history = match ctx.accounts.owner_history.as_deref_mut() {
+            Some(history) => {
+                require_keys_eq!(history.player, owner, CubeGameError::PlayerHistoryRequired);
+                Some(&mut **history)
+            }
+            None => None,
+        };
+        record_history(
+            owner_stats,
+            owner_history,
+            &cube_id,
+            cube_shares.total_contributed,
+            now,
+        )?;
+        owner_stats.cubes_removed += 1;
+        game.entrant_weight += 1;
+        owner_stats.score = owner_stats
+            .score
+            .saturating_add(cube_record.ranked_score(&cube_id));
+        let (owner_tier, owner_founder, owner_score) =
+            (owner_stats.tier, owner_stats.is_founder, owner_stats.score);
+        let owner_private = owner_stats.private;
+        let public_owner = if owner_private {
+            Pubkey::default()
+        } else {
+            owner
+        };
+
+        cube_record.is_removed = true;
+        cube_record.removed_by = owner;
+        cube_record.original_remover = owner;
+        cube_record.stamp_removed_at(now);
+        cube_record.cube_id = cube_id.clone();
+        cube_record.price_paid = cube_shares.total_contributed;
+        game.note_paid(cube_shares.total_contributed);
+        cube_record.priority_fee = 0;
+        cube_record.color = cube_record.removal_color(game, &cube_id, 0);
+
+        // The shares move onto the record, leaving the cube's next round to
+        // start from nothing once it is restored
+        cube_record.payments = std::mem::take(&mut cube_shares.contributions);
+        cube_shares.total_contributed = 0;
+
+        // The contributions were counted as they came in
+        if let Some(analytics) = ctx.accounts.analytics.as_mut() {
+            analytics.record_removal(0, price, false)?;
+        }
+
+        game.count_removal()?;
+        credit_region(region.as_mut(), ctx.accounts.region.as_ref(), 1)?;
+        credit_zone(
+            game,
+            ctx.accounts.zone.as_mut(),
+            ctx.accounts.next_zone.as_mut(),
+        )?;
+
+        log_activity(
+            game,
+            ctx.accounts.activity_feed.as_mut(),
+            ACTIVITY_REMOVAL,
+            public_owner,
+            &cube_id,
+            now,
+        )?;
+
+        emit!(CubeRemovedEvent {
+            cube_id,
+            player: public_owner,
+            total_removed: game.total_cubes_removed,
+            completion_bps: game.completion_bps(),
+            timestamp: now,
+            memo: String::new(),
+            message: String::new(),
+            color: cube_record.color,
+            tier: owner_tier,
+            is_founder: owner_founder,
+            score: owner_score,
+        });
+
+        Ok(())
+    }
+
+    /// Transfer ownership of a removed cube to another wallet
+    pub fn transfer_cube(
+        ctx: Context<TransferCube>,
+        cube_id: String,
+        _region_id: String,
+        new_owner: Pubkey,
+    ) -> Result<()> {
+        let game = &ctx.accounts.game_state;
+        let cube_record = &mut ctx.accounts.cube_record;
+        let now = Clock::get()?.unix_timestamp;
+
+        require!(cube_record.is_removed, CubeGameError::CubeNotRemoved);
+        require!(
+            elapsed(now, cube_record.removed_at) >= game.transfer_lock_seconds,
+            CubeGameError::CubeLocked
+        );
+
+        let previous_owner = cube_record.removed_by;
+        cube_record.removed_by = new_owner;
+
+        log_activity(
+            game,
+            ctx.accounts.activity_feed.as_mut(),
+            ACTIVITY_TRANSFER,
+            previous_owner,
+            &cube_id,
+            now,
+        )?;
+
+        emit!(CubeTransferredEvent {
+            cube_id,
+            from: previous_owner,
+            to: new_owner,
+            timestamp: now,
+        });
+
+        Ok(())
+    }
+
+    /// Emit the game's configuration and progress (simulate only)
+    pub fn get_game_info(ctx: Context<GetGameInfo>) -> Result<()> {
+        emit!(ctx.accounts.game_state.info()?);
+        Ok(())
+    }
+
+    /// Emit `player`'s removals scaled by `stats_display_multiplier_bps`
+    /// (simulate only), so every client shows the same normalized figure
+    pub fn scaled_cubes_removed(ctx: Context<ScaledCubesRemoved>, player: Pubkey) -> Result<()> {
+        let cubes_removed = ctx.accounts.player_stats.cubes_removed;
+        emit!(ScaledStatsEvent {
+            player,
+            cubes_removed,
+            scaled_cubes_removed: ctx.accounts.game_state.scaled_stat(cubes_removed)?,
+        });
+        Ok(())
+    }
+
+    /// Emit the price `player` would pay for their next removal (simulate only)
+    pub fn quote_next_price(ctx: Context<QuoteNextPrice>, player: Pubkey) -> Result<()> {
+        let price = effective_price(
+            &ctx.accounts.game_state,
+            None,
+            ctx.accounts.fee_exemption.is_some(),
+            Clock::get()?.unix_timestamp,
+        )?;
+        emit!(PriceQuoteEvent { player, price });
+        Ok(())
+    }
+
+    /// Emit the all-in cost of `player` removing `cube_id` in `region_id`
+    /// (empty for the global board, simulate only): the price, at the
+    /// region's curve when it has one, plus the rent of whichever of the
+    /// cube record and player stats accounts the removal would create
+    pub fn quote_removal_cost(
+        ctx: Context<QuoteRemovalCost>,
+        cube_id: String,
+        region_id: String,
+        player: Pubkey,
+    ) -> Result<()> {
+        let game = &ctx.accounts.game_state;
+        let (_, region_price) = load_priced_region(game, ctx.accounts.region.as_ref(), &region_id)?;
+        let price = effective_price(
+            game,
+            region_price,
+            ctx.accounts.fee_exemption.is_some(),
+            Clock::get()?.unix_timestamp,
+        )?;
+        let (record_rent, stats_rent) = creation_rent(
+            &Rent::get()?,
+            !ctx.accounts.cube_record.data_is_empty(),
+            !ctx.accounts.player_stats.data_is_empty(),
+        );
+        emit!(RemovalCostEvent {
+            cube_id,
+            player,
+            price,
+            record_rent,
+            stats_rent,
+            total: price
+                .checked_add(record_rent)
+                .and_then(|total| total.checked_add(stats_rent))
+                .ok_or(CubeGameError::MathOverflow)?,
+        });
+        Ok(())
+    }
+
+    /// Emit the status of every `CubeRecord` passed in `remaining_accounts`
+    /// (simulate only). Accounts that are not cube records are skipped.
+    pub fn check_cubes<'info>(ctx: Context<'_, '_, 'info, 'info, CheckCubes>) -> Result<()> {
+        require!(
+            ctx.remaining_accounts.len() <= MAX_STATUS_QUERY,
+            CubeGameError::TooManyAccounts
+        );
+
+        let statuses = ctx
+            .remaining_accounts
+            .iter()
+            .filter_map(cube_status)
+            .collect();
+
+        emit!(CubeStatusBatchEvent { statuses });
+        Ok(())
+    }
+
+    /// Emit the `PlayerStats` passed in `remaining_accounts` ranked by
+    /// decayed active score, or by score in ranked mode, highest first
+    /// (simulate only). Accounts that are not player stats are skipped.
+    pub fn rank_players<'info>(
+        ctx: Context<'_, '_, 'info, 'info, RankPlayers<'info>>,
+    ) -> Result<()> {
+        require!(
+            ctx.remaining_accounts.len() <= MAX_STATUS_QUERY,
+            CubeGameError::TooManyAccounts
+        );
+        let decay_rate = ctx.accounts.game_state.decay_rate_bps_per_day;
+        let ranked = ctx.accounts.game_state.ranked_mode;
+        let now = Clock::get()?.unix_timestamp;
+
+        let mut scores: Vec<PlayerScore> = ctx
+            .remaining_accounts
+            .iter()
+            .filter_map(|info| Account::<PlayerStats>::try_from(info).ok())
+            .map(|stats| PlayerScore {
+                player: if stats.private {
+                    Pubkey::default()
+                } else {
+                    stats.player
+                },
+                cubes_removed: stats.cubes_removed,
+                active_score: stats.active_score(decay_rate, now),
+                score: stats.score,
+            })
+            .collect();
+        if ranked {
+            scores.sort_by_key(|score| std::cmp::Reverse(score.score));
+        } else {
+            scores.sort_by_key(|score| std::cmp::Reverse(score.active_score));
+        }
+
+        emit!(PlayerRankingEvent { scores });
+        Ok(())
+    }
+
+    /// Append an empty page to the bottom of the on-chain leaderboard,
+    /// creating the leaderboard on the first call (owner only)
+    pub fn add_leaderboard_page(ctx: Context<AddLeaderboardPage>) -> Result<()> {
+        let meta = &mut ctx.accounts.leaderboard_meta;
+        require!(
+            meta.pages.len() < MAX_LEADERBOARD_PAGES,
+            CubeGameError::LeaderboardFull
+        );
+        let page = &mut ctx.accounts.leaderboard_page;
+        page.page = meta.page_count;
+        page.bump = ctx.bumps.leaderboard_page;
+        meta.pages.push(PageSummary::default());
+        meta.page_count += 1;
+        meta.bump = ctx.bumps.leaderboard_meta;
+        Ok(())
+    }
+
+    /// Move `player` to the place their current score earns on the
+    /// leaderboard: removals count, or score in ranked mode. Private players
+    /// are taken off it. Anyone may call it, usually right after a removal.
+    ///
+    /// `remaining_accounts` lists consecutive `LeaderboardPage`s covering
+    /// every page the player might still be on from their last update, the
+    /// page they move to and every page below it that their arrival pushes
+    /// an entry onto. A player rising from page 2 to page 1 passes both.
+    pub fn update_leaderboard<'info>(
+        ctx: Context<'_, '_, 'info, 'info, UpdateLeaderboard<'info>>,
+        player: Pubkey,
+    ) -> Result<()> {
+        let stats = &mut ctx.accounts.player_stats;
+        let score = if stats.private {
+            0
+        } else {
+            stats.leaderboard_score(ctx.accounts.game_state.ranked_mode)
+        };
+        let mut pages = ctx
+            .remaining_accounts
+            .iter()
+            .map(Account::<LeaderboardPage>::try_from)
+            .collect::<Result<Vec<_>>>()?;
+        let mut inner: Vec<LeaderboardPage> = pages.iter().map(|page| (**page).clone()).collect();
+        stats.leaderboard_recorded = rank_on_leaderboard(
+            &mut ctx.accounts.leaderboard_meta,
+            &mut inner,
+            player,
+            stats.leaderboard_recorded,
+            score,
+        )?;
+        for (page, updated) in pages.iter_mut().zip(inner) {
+            page.set_inner(updated);
+            page.exit(&crate::ID)?;
+        }
+        Ok(())
+    }
+
+    /// Emit every program error as a `(code, name)` pair so clients can build
+    /// their mapping at runtime (simulate only)
+    pub fn get_error_catalog(_ctx: Context<GetErrorCatalog>) -> Result<()> {
+        let entries = CubeGameError::ALL
+            .iter()
+            .map(|e| ErrorCatalogEntry {
+                code: u32::from(*e),
+                name: e.name(),
+            })
+            .collect();
+
+        emit!(ErrorCatalogEvent { entries });
+        Ok(())
+    }
+
+    /// Set a cap on the player's own total spend (0 = no cap)
+    ///
+    /// Lowering the cap applies immediately, raising or removing it only
+    /// takes effect after `SPEND_CAP_RAISE_COOLDOWN`.
+    pub fn set_self_spend_cap(ctx: Context<PlayerSettings>, cap: u64) -> Result<()> {
+        let player_stats = &mut ctx.accounts.player_stats;
+        let now = Clock::get()?.unix_timestamp;
+
+        player_stats.player = ctx.accounts.player.key();
+        player_stats.apply_pending_spend_cap(now);
+
+        let current = player_stats.spend_cap;
+        let is_raise = current > 0 && (cap == 0 || cap > current);
+        if is_raise {
+            player_stats.pending_spend_cap = cap;
+            player_stats.spend_cap_raise_at = now + SPEND_CAP_RAISE_COOLDOWN;
+        } else {
+            player_stats.spend_cap = cap;
+            player_stats.pending_spend_cap = 0;
+            player_stats.spend_cap_raise_at = 0;
+        }
+
+        Ok(())
+    }
+
+    /// Hide the player's wallet from public removal events
+    pub fn set_privacy(ctx: Context<PlayerSettings>, private: bool) -> Result<()> {
+        let player_stats = &mut ctx.accounts.player_stats;
+        player_stats.player = ctx.accounts.player.key();
+        player_stats.private = private;
+        Ok(())
+    }
+
+    /// Stamp the caller's registration time, the start of the account age
+    /// `min_player_age` requires. Registering again keeps the first stamp.
+    pub fn register_player(ctx: Context<PlayerSettings>) -> Result<()> {
+        ctx.accounts
+            .player_stats
+            .stamp_registration(ctx.accounts.player.key(), Clock::get()?.unix_timestamp);
+        Ok(())
+    }
+
+    /// Switch the caller's practice sandbox on or off. While it is on, real
+    /// removals are refused and `remove_cube_practice` is free.
+    pub fn set_practice_mode(ctx: Context<PlayerSettings>, practice_mode: bool) -> Result<()> {
+        let player_stats = &mut ctx.accounts.player_stats;
+        player_stats.player = ctx.accounts.player.key();
+        player_stats.practice_mode = practice_mode;
+        Ok(())
+    }
+
+    /// Remove a cube in the caller's practice sandbox. Nothing is charged
+    /// and no global counter moves; the cube is marked in a per-player
+    /// `PracticeRecord`, so the real cube stays available to everyone.
+    pub fn remove_cube_practice(ctx: Context<RemoveCubePractice>, cube_id: String) -> Result<()> {
+        ctx.accounts.game_state.validate_cube_id(&cube_id)?;
+        let player_stats = &mut ctx.accounts.player_stats;
+        let record = &mut ctx.accounts.practice_record;
+        player_stats.practice_remove(record)?;
+        record.bump = ctx.bumps.practice_record;
+
+        emit!(PracticeRemovalEvent {
+            cube_id,
+            player: ctx.accounts.player.key(),
+            practice_removals: player_stats.practice_removals,
+        });
+        Ok(())
+    }
+
+    /// Name the player who referred the caller. Set once, and the referrer
+    /// must already have a `PlayerStats`.
+    pub fn set_referrer(ctx: Context<SetReferrer>, referrer: Pubkey) -> Result<()> {
+        let player_stats = &mut ctx.accounts.player_stats;
+        require!(
+            player_stats.referrer == Pubkey::default(),
+            CubeGameError::ReferrerAlreadySet
+        );
+        require!(
+            referrer != ctx.accounts.player.key(),
+            CubeGameError::InvalidReferrer
+        );
+        player_stats.player = ctx.accounts.player.key();
+        player_stats.referrer = referrer;
+        Ok(())
+    }
+
+    /// Pay the caller's accrued referral earnings from the treasury
+    pub fn claim_referral_earnings(ctx: Context<ClaimReferralEarnings>) -> Result<()> {
+        let amount = ctx.accounts.player_stats.take_referral_earnings()?;
+        let treasury = &ctx.accounts.treasury;
+        require_treasury_covers(&ctx.accounts.game_state, treasury, amount)?;
+        pay_reward(
+            &ctx.accounts.game_state,
+            treasury,
+            &ctx.accounts.player.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+            ctx.accounts.ledger.as_deref_mut(),
+            amount,
+        )?;
+        Ok(())
+    }
+
+    /// Pay the caller the tip accrued on a cube's record when the cube's
+    /// last removal came right after theirs
+    pub fn claim_tip(ctx: Context<ClaimTip>, _cube_id: String, _region_id: String) -> Result<()> {
+        let tip = ctx
+            .accounts
+            .cube_record
+            .take_tip(&ctx.accounts.player.key())?;
+        let treasury = &ctx.accounts.treasury;
+        require_treasury_covers(&ctx.accounts.game_state, treasury, tip)?;
+        pay_reward(
+            &ctx.accounts.game_state,
+            treasury,
+            &ctx.accounts.player.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+            ctx.accounts.ledger.as_deref_mut(),
+            tip,
+        )?;
+        Ok(())
+    }
+
+    /// Join a team, creating it if this is its first member
+    pub fn join_team(ctx: Context<JoinTeam>, team_id: String) -> Result<()> {
+        require!(
+            !team_id.is_empty() && team_id.len() <= 32,
+            CubeGameError::InvalidTeamId
+        );
+
+        let player_stats = &mut ctx.accounts.player_stats;
+        require!(
+            player_stats.team_id.is_empty(),
+            CubeGameError::AlreadyOnTeam
+        );
+        player_stats.player = ctx.accounts.player.key();
+        player_stats.team_id = team_id.clone();
+
+        let team = &mut ctx.accounts.team;
+        team.team_id = team_id;
+        team.member_count += 1;
+        team.bump = ctx.bumps.team;
+        Ok(())
+    }
+
+    /// Leave a finished game, closing the caller's `PlayerStats` and every
+    /// `CubeRecord` they removed (passed in `remaining_accounts`) to recover
+    /// the rent, and collecting their unclaimed referral earnings. The
+    /// player's weight leaves the grand-prize draw.
+    pub fn exit_game<'info>(ctx: Context<'_, '_, 'info, 'info, ExitGame<'info>>) -> Result<()> {
+        require!(
+            ctx.accounts
+                .game_state
+                .is_over(Clock::get()?.unix_timestamp),
+            CubeGameError::GameNotOver
+        );
+        require!(
+            ctx.remaining_accounts.len() <= MAX_EXIT_RECORDS,
+            CubeGameError::TooManyAccounts
+        );
+
+        require!(
+            !ctx.accounts.player_stats.frozen,
+            CubeGameError::PlayerFrozen
+        );
+        // Closed records could no longer be refunded
+        require!(
+            !ctx.accounts.game_state.refund_all_mode,
+            CubeGameError::RefundModeActive
+        );
+
+        let player = ctx.accounts.player.to_account_info();
+        let game = &mut ctx.accounts.game_state;
+        game.drop_entrant(ctx.accounts.player_stats.cubes_removed)?;
+        for info in ctx.remaining_accounts {
+            let record = Account::<CubeRecord>::try_from(info)?;
+            game.release_paid(record.exit_release(&player.key())?);
+            record.close(player.clone())?;
+        }
+        game.active_cube_records = game
+            .active_cube_records
+            .saturating_sub(ctx.remaining_accounts.len() as u64);
+
+        let bonus = ctx.accounts.player_stats.referral_earnings;
+        if bonus > 0 {
+            let treasury = &ctx.accounts.treasury;
+            require_treasury_covers(game, treasury, bonus)?;
+            pay_reward(
+                game,
+                treasury,
+                &player,
+                &ctx.accounts.system_program.to_account_info(),
+                ctx.accounts.ledger.as_deref_mut(),
+                bonus,
+            )?;
+        }
+
+        if !ctx.accounts.player_stats.team_id.is_empty() {
+            let team = ctx
+                .accounts
+                .team
+                .as_mut()
+                .ok_or(CubeGameError::TeamAccountRequired)?;
+            team.member_count = team.member_count.saturating_sub(1);
+        }
+        ctx.accounts.player_stats.close(player)?;
+        Ok(())
+    }
+
+    /// Lock the current price for the next `uses` removals until the voucher
+    /// expires, paying `voucher_premium_bps` of their locked cost up front
+    pub fn buy_price_lock(ctx: Context<BuyPriceLock>, uses: u32) -> Result<()> {
+        require!(uses > 0, CubeGameError::ZeroVoucherUses);
+        let game = &ctx.accounts.game_state;
+        require!(game.voucher_duration > 0, CubeGameError::VouchersDisabled);
+
+        let now = Clock::get()?.unix_timestamp;
+        let voucher = &ctx.accounts.voucher;
+        require!(
+            voucher.uses_remaining == 0 || now >= voucher.expires_at,
+            CubeGameError::VoucherActive
+        );
+
+        let locked_price = effective_price(game, None, false, now)?;
+        let locked_cost = locked_price
+            .checked_mul(uses as u64)
+            .ok_or(CubeGameError::MathOverflow)?;
+        let premium = apply_bps(locked_cost, game.voucher_premium_bps)?;
+        collect_payment(
+            game,
+            PaymentRoute {
+                payer: &ctx.accounts.player.to_account_info(),
+                treasury: &ctx.accounts.treasury,
+                system_program: &ctx.accounts.system_program.to_account_info(),
+                refund_pool: ctx.accounts.refund_pool.as_mut(),
+                insurance_vault: ctx.accounts.insurance_vault.as_mut(),
+                incinerator: ctx.accounts.incinerator.as_deref(),
+                dao_treasury: ctx.accounts.dao_treasury.as_deref(),
+                ledger: ctx.accounts.ledger.as_deref_mut(),
+            },
+            premium,
+        )?;
+        let balance = ctx.accounts.treasury.lamports();
+        ctx.accounts.game_state.note_deposit(premium, balance, now);
+        let game = &ctx.accounts.game_state;
+
+        let expires_at = now
+            .checked_add(game.voucher_duration)
+            .ok_or(CubeGameError::MathOverflow)?;
+        let voucher = &mut ctx.accounts.voucher;
+        voucher.player = ctx.accounts.player.key();
+        voucher.locked_price = locked_price;
+        voucher.uses_remaining = uses;
+        voucher.expires_at = expires_at;
+        voucher.bump = ctx.bumps.voucher;
+        Ok(())
+    }
+
+    /// Record the intent to remove a cube priced above
+    /// `high_value_threshold`; the removal itself must land in a later slot
+    pub fn intend_removal(ctx: Context<IntendRemoval>, cube_id: String) -> Result<()> {
+        ctx.accounts.game_state.validate_cube_id(&cube_id)?;
+        let intent = &mut ctx.accounts.removal_intent;
+        intent.player = ctx.accounts.player.key();
+        intent.cube_id = cube_id;
+        intent.slot = Clock::get()?.slot;
+        intent.bump = ctx.bumps.removal_intent;
+        Ok(())
+    }
+
+    /// Let `delegate` spend the caller's prepaid credits on removals worth
+    /// up to `spend_limit` lamports at the effective price. Re-authorizing
+    /// replaces the remaining limit.
+    pub fn authorize_delegate(
+        ctx: Context<AuthorizeDelegate>,
+        delegate: Pubkey,
+        spend_limit: u64,
+    ) -> Result<()> {
+        let delegation = &mut ctx.accounts.delegation;
+        delegation.player = ctx.accounts.player.key();
+        delegation.delegate = delegate;
+        delegation.spend_limit = spend_limit;
+        delegation.bump = ctx.bumps.delegation;
+        Ok(())
+    }
+
+    /// Withdraw a delegate's authorization, refunding its rent
+    pub fn revoke_delegate(_ctx: Context<RevokeDelegate>, _delegate: Pubkey) -> Result<()> {
+        Ok(())
+    }
+
+    /// Remove a cube for `player`, signed by their delegate and paid with one
+    /// of the player's credits, which must cover the cube's effective price.
+    /// That price is charged against the delegation's spend limit. Like batches, unavailable on
+    /// NFT-gated, proof-of-work and committed boards. Rebates, combos and
+    /// bombs do not apply.
+    pub fn remove_cube_delegated(
+        ctx: Context<RemoveCubeDelegated>,
+        cube_id: String,
+        region_id: String,
+    ) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let player = ctx.accounts.player.key();
+        let game = &mut ctx.accounts.game_state;
+        game.validate_cube_id(&cube_id)?;
+        require!(
+            game.required_collection.is_none(),
+            CubeGameError::MissingRequiredNft
+        );
+        require!(game.pow_difficulty == 0, CubeGameError::InvalidProofOfWork);
+        require!(game.board_root == [0; 32], CubeGameError::NotOnBoard);
+        require!(game.min_priority_fee == 0, CubeGameError::PriorityFeeTooLow);
+        require!(game.hits_required <= 1, CubeGameError::MultiHitBoard);
+        require!(game.max_removers <= 1, CubeGameError::SharedCubeBoard);
+        require!(game.queue_turn_seconds == 0, CubeGameError::QueuedBoard);
+        require!(
+            game.ticket_signer == Pubkey::default(),
+            CubeGameError::TicketRequired
+        );
+        require!(
+            game.min_reveal_delay_slots == 0,
+            CubeGameError::CommitRequired
+        );
+        require!(
+            !game.analytics_enabled || ctx.accounts.analytics.is_some(),
+            CubeGameError::AnalyticsRequired
+        );
+        enforce_tx_removal_limit(game, ctx.accounts.instructions.as_ref())?;
+        enforce_cpi_allowlist(game, ctx.accounts.instructions.as_ref())?;
+        game.require_open_and_extend(now)?;
+        game.require_board_open()?;
+        game.throttle_slot()?;
+        game.require_global_pace(now)?;
+
+        let cube_record = &mut ctx.accounts.cube_record;
+        cube_record.validate(&cube_id)?;
+        require!(!cube_record.is_removed, CubeGameError::CubeAlreadyRemoved);
+        require!(
+            cube_record.prev_cube_id.is_none(),
+            CubeGameError::SequencedCube
+        );
+        require!(cube_record.royalty_bps == 0, CubeGameError::RoyaltyCube);
+        if cube_record.cube_id.is_empty() {
+            game.note_record_created()?;
+        }
+        let player_stats = &mut ctx.accounts.player_stats;
+        player_stats.require_can_remove(game, now)?;
+        let new_player = player_stats.register(
+            game,
+            ctx.accounts
+                .wallet_mark
+                .as_deref_mut()
+                .map(|mark| &mut **mark),
+        )?;
+        require_zone_open(game, ctx.accounts.zone.as_ref(), &cube_id)?;
+
+        let (mut region, region_price) =
+            load_priced_region(game, ctx.accounts.region.as_ref(), &region_id)?;
+
+        // Expired cubes are cleared without spending the player's credit
+        let mut price = 0;
+        if !cube_record.is_expired(now) {
+            price = effective_price(game, region_price, false, now)?;
+            require!(
+                !game.needs_confirmation(price),
+                CubeGameError::ConfirmationRequired
+            );
+            ctx.accounts.delegation.spend(price)?;
+            let remainder = ctx.accounts.credits.spend(price)?;
+            require!(remainder == 0, CubeGameError::CreditValueExceeded);
+        }
+
+        cube_record.is_removed = true;
+        cube_record.removed_by = player;
+        cube_record.original_remover = player;
+        cube_record.stamp_removed_at(now);
+        cube_record.cube_id = cube_id.clone();
+        cube_record.price_paid = 0;
+        cube_record.priority_fee = 0;
+        cube_record.memo.clear();
+        cube_record.color = cube_record.removal_color(game, &cube_id, 0);
+        let ranked = cube_record.ranked_score(&cube_id);
+        // The credit was paid for when it was bought
+        if let Some(analytics) = ctx.accounts.analytics.as_mut() {
+            analytics.record_removal(0, price, new_player)?;
+        }
+
+        game.count_removal()?;
+        credit_region(region.as_mut(), ctx.accounts.region.as_ref(), 1)?;
+        credit_zone(
+            game,
+            ctx.accounts.zone.as_mut(),
+            ctx.accounts.next_zone.as_mut(),
+        )?;
+        player_stats.player = player;
+        record_history(
+            player_stats,
+            ctx.accounts.player_history.as_deref_mut().map(|h| &mut **h),
+            &cube_id,
+            price,
+            now,
+        )?;
+        player_stats.cubes_removed += 1;
+        game.entrant_weight += 1;
+        player_stats.score = player_stats.score.saturating_add(ranked);
+        player_stats.last_removal_at = now;
+        if !player_stats.team_id.is_empty() {
+            let team = ctx
+                .accounts
+                .team
+                .as_mut()
+                .ok_or(CubeGameError::TeamAccountRequired)?;
+            team.total_cubes_removed += 1;
+        }
+
+        let public_player = if player_stats.private {
+            Pubkey::default()
+        } else {
+            player
+        };
+        player_stats.promote_tier(game, public_player);
+        log_activity(
+            game,
+            ctx.accounts.activity_feed.as_mut(),
+            ACTIVITY_REMOVAL,
+            public_player,
+            &cube_id,
+            now,
+        )?;
+        emit!(CubeRemovedEvent {
+            cube_id,
+            player: public_player,
+            total_removed: game.total_cubes_removed,
+            completion_bps: game.completion_bps(),
+            timestamp: now,
+            memo: String::new(),
+            message: String::new(),
+            color: cube_record.color,
+            tier: player_stats.tier,
+            is_founder: player_stats.is_founder,
+            score: player_stats.score,
+        });
+
+        // The delegate paid the record's rent
+        if game.ephemeral_records {
+            game.forget_record(0);
+            cube_record.close(ctx.accounts.delegate.to_account_info())?;
+        }
+        Ok(())
+    }
+
+    /// Prepay `count` removals at the discounted credit rate. Each credit
+    /// later covers up to the base price at the time of purchase.
+    pub fn buy_credits(ctx: Context<BuyCredits>, count: u64) -> Result<()> {
+        require!(count > 0, CubeGameError::ZeroCredits);
+
+        let game = &ctx.accounts.game_state;
+        let face_value = game.base_price()?;
+        let unit_price = apply_bps(
+            face_value,
+            (BPS_DENOMINATOR - game.credit_discount_bps as u64) as u16,
+        )?;
+        let cost = unit_price
+            .checked_mul(count)
+            .ok_or(CubeGameError::MathOverflow)?;
+
+        collect_payment(
+            game,
+            PaymentRoute {
+                payer: &ctx.accounts.player.to_account_info(),
+                treasury: &ctx.accounts.treasury,
+                system_program: &ctx.accounts.system_program.to_account_info(),
+                refund_pool: ctx.accounts.refund_pool.as_mut(),
+                insurance_vault: ctx.accounts.insurance_vault.as_mut(),
+                incinerator: ctx.accounts.incinerator.as_deref(),
+                dao_treasury: ctx.accounts.dao_treasury.as_deref(),
+                ledger: ctx.accounts.ledger.as_deref_mut(),
+            },
+            cost,
+        )?;
+        let balance = ctx.accounts.treasury.lamports();
+        ctx.accounts
+            .game_state
+            .note_deposit(cost, balance, Clock::get()?.unix_timestamp);
+
+        let credits = &mut ctx.accounts.credits;
+        credits.player = ctx.accounts.player.key();
+        credits.add(count, face_value)?;
+        credits.bump = ctx.bumps.credits;
+        Ok(())
+    }
+
+    /// Buy or renew a time-boxed pass: tier 0 grants `subscription_quota`
+    /// free removals and tier 1 unlimited ones until it expires. Renewing
+    /// extends an active pass and resets its quota.
+    pub fn buy_subscription(ctx: Context<BuySubscription>, tier: u8) -> Result<()> {
+        let game = &ctx.accounts.game_state;
+        require!(
+            game.subscription_duration > 0,
+            CubeGameError::SubscriptionsDisabled
+        );
+        let cost = *game
+            .subscription_prices
+            .get(tier as usize)
+            .ok_or(CubeGameError::InvalidSubscriptionTier)?;
+
+        collect_payment(
+            game,
+            PaymentRoute {
+                payer: &ctx.accounts.player.to_account_info(),
+                treasury: &ctx.accounts.treasury,
+                system_program: &ctx.accounts.system_program.to_account_info(),
+                refund_pool: ctx.accounts.refund_pool.as_mut(),
+                insurance_vault: ctx.accounts.insurance_vault.as_mut(),
+                incinerator: ctx.accounts.incinerator.as_deref(),
+                dao_treasury: ctx.accounts.dao_treasury.as_deref(),
+                ledger: ctx.accounts.ledger.as_deref_mut(),
+            },
+            cost,
+        )?;
+        let now = Clock::get()?.unix_timestamp;
+        let balance = ctx.accounts.treasury.lamports();
+        ctx.accounts.game_state.note_deposit(cost, balance, now);
+
+        let game = &ctx.accounts.game_state;
+        let subscription = &mut ctx.accounts.subscription;
+        subscription.player = ctx.accounts.player.key();
+        subscription.tier = tier;
+        subscription.expires_at = subscription
+            .expires_at
+            .max(now)
+            .saturating_add(game.subscription_duration);
+        subscription.removals_remaining = game.subscription_quota;
+        subscription.bump = ctx.bumps.subscription;
+
+        emit!(SubscriptionPurchasedEvent {
+            player: subscription.player,
+            tier,
+            expires_at: subscription.expires_at,
+        });
+        Ok(())
+    }
+
+    /// Create the staking pool and its vault for `mint` (owner only). Stakers
+    /// of that token share `staker_reward_bps` of every removal payment.
+    pub fn init_staking_pool(ctx: Context<InitStakingPool>) -> Result<()> {
+        let pool = &mut ctx.accounts.staking_pool;
+        pool.mint = ctx.accounts.mint.key();
+        pool.vault = ctx.accounts.vault.key();
+        pool.bump = ctx.bumps.staking_pool;
+        Ok(())
+    }
+
+    /// Lock `amount` tokens in the staking pool, settling rewards earned so
+    /// far first
+    pub fn stake_tokens(ctx: Context<StakeTokens>, amount: u64) -> Result<()> {
+        require!(amount > 0, CubeGameError::ZeroStake);
+        let pool = &mut ctx.accounts.staking_pool;
+        let stake = &mut ctx.accounts.stake;
+        stake.owner = ctx.accounts.staker.key();
+        stake.bump = ctx.bumps.stake;
+        stake.settle(pool)?;
+
+        anchor_spl::token::transfer(
+            CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                anchor_spl::token::Transfer {
+                    from: ctx.accounts.staker_token_account.to_account_info(),
+                    to: ctx.accounts.vault.to_account_info(),
+                    authority: ctx.accounts.staker.to_account_info(),
+                },
+            ),
+            amount,
+        )?;
+        stake.amount = stake
+            .amount
+            .checked_add(amount)
+            .ok_or(CubeGameError::MathOverflow)?;
+        pool.total_staked = pool
+            .total_staked
+            .checked_add(amount)
+            .ok_or(CubeGameError::MathOverflow)?;
+        stake.reset_debt(pool)?;
+
+        emit!(StakeChangedEvent {
+            owner: stake.owner,
+            amount: stake.amount,
+            total_staked: pool.total_staked,
+        });
+        Ok(())
+    }
+
+    /// Withdraw `amount` staked tokens, settling rewards earned so far first
+    pub fn unstake_tokens(ctx: Context<StakeTokens>, amount: u64) -> Result<()> {
+        require!(amount > 0, CubeGameError::ZeroStake);
+        let pool = &mut ctx.accounts.staking_pool;
+        let stake = &mut ctx.accounts.stake;
+        require!(amount <= stake.amount, CubeGameError::InsufficientStake);
+        stake.settle(pool)?;
+
+        anchor_spl::token::transfer(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                anchor_spl::token::Transfer {
+                    from: ctx.accounts.vault.to_account_info(),
+                    to: ctx.accounts.staker_token_account.to_account_info(),
+                    authority: pool.to_account_info(),
+                },
+                &[&[b"staking_pool", &[pool.bump]]],
+            ),
+            amount,
+        )?;
+        stake.amount -= amount;
+        pool.total_staked -= amount;
+        stake.reset_debt(pool)?;
+
+        emit!(StakeChangedEvent {
+            owner: stake.owner,
+            amount: stake.amount,
+            total_staked: pool.total_staked,
+        });
+        Ok(())
+    }
+
+    /// Pay out the lamports a stake has earned from removal payments
+    pub fn claim_staking_rewards(ctx: Context<ClaimStakingRewards>) -> Result<()> {
+        let pool = &mut ctx.accounts.staking_pool;
+        let stake = &mut ctx.accounts.stake;
+        stake.settle(pool)?;
+        let amount = stake.pending_rewards;
+        require!(amount > 0, CubeGameError::NoStakingRewards);
+
+        // The pool is owned by this program, so it can be debited directly
+        let pool_info = pool.to_account_info();
+        let available = pool_info
+            .lamports()
+            .saturating_sub(Rent::get()?.minimum_balance(pool_info.data_len()));
+        require!(
+            amount <= available,
+            CubeGameError::InsufficientStakingRewards
+        );
+        **pool_info.try_borrow_mut_lamports()? -= amount;
+        **ctx.accounts.staker.try_borrow_mut_lamports()? += amount;
+        stake.pending_rewards = 0;
+        pool.total_claimed += amount;
+
+        emit!(StakingRewardsClaimedEvent {
+            owner: stake.owner,
+            amount,
+        });
+        Ok(())
+    }
+
+    /// Erase the caller's identifiable data from their stats while keeping
+    /// their removal count, optionally closing the account. The stored key
+    /// stays so refunds and the grand-prize draw still find the player; it
+    /// is masked in events from now on. Closing takes the player's weight
+    /// out of the draw.
+    pub fn forget_player(ctx: Context<ForgetPlayer>, close: bool) -> Result<()> {
+        if !ctx.accounts.player_stats.team_id.is_empty() {
+            let team = ctx
+                .accounts
+                .team
+                .as_mut()
+                .ok_or(CubeGameError::TeamAccountRequired)?;
+            team.member_count = team.member_count.saturating_sub(1);
+        }
+
+        let stats = &mut ctx.accounts.player_stats;
+        stats.player = ctx.accounts.player.key();
+        stats.forget();
+
+        if close {
+            // Closing would shed the freeze along with the account
+            require!(!stats.frozen, CubeGameError::PlayerFrozen);
+            ctx.accounts.game_state.drop_entrant(stats.cubes_removed)?;
+            stats.close(ctx.accounts.player.to_account_info())?;
+        }
+        Ok(())
+    }
+
+    /// Update the price (price authority only)
+    pub fn set_price(ctx: Context<SetPrice>, new_price: u64) -> Result<()> {
+        ctx.accounts.game_state.set_price(new_price);
+        Ok(())
+    }
+
+    /// Charge `fee` for `unlock_cube` reveals (price authority only, 0 turns
+    /// unlocking off)
+    pub fn set_unlock_fee(ctx: Context<SetPrice>, fee: u64) -> Result<()> {
+        ctx.accounts.game_state.unlock_fee = fee;
+        Ok(())
+    }
+
+    /// Price cubes at `points` abstract points, charged at the
+    /// `point_value_lamports` peg, instead of `price_per_cube` (price
+    /// authority only, 0 returns to lamport pricing)
+    pub fn set_price_in_points(ctx: Context<SetPrice>, points: u64) -> Result<()> {
+        let game = &mut ctx.accounts.game_state;
+        game.price_in_points = points;
+        game.price_epoch += 1;
+        game.require_point_peg()
+    }
+
+    /// Peg one point to `lamports` (price authority only, nonzero while
+    /// cubes are priced in points)
+    pub fn set_point_value(ctx: Context<SetPrice>, lamports: u64) -> Result<()> {
+        let game = &mut ctx.accounts.game_state;
+        game.point_value_lamports = lamports;
+        game.price_epoch += 1;
+        game.require_point_peg()
+    }
+
+    /// Hand pricing, pausing and withdrawing to separate keys (owner only).
+    /// A role left at the default key falls back to the owner.
+    pub fn set_roles(
+        ctx: Context<SetConfig>,
+        price_authority: Pubkey,
+        pause_authority: Pubkey,
+        withdraw_authority: Pubkey,
+    ) -> Result<()> {
+        let game = &mut ctx.accounts.game_state;
+        game.price_authority = price_authority;
+        game.pause_authority = pause_authority;
+        game.withdraw_authority = withdraw_authority;
+        Ok(())
+    }
+
+    /// Stop or resume every removal (pause authority only)
+    pub fn set_paused(ctx: Context<SetPaused>, paused: bool) -> Result<()> {
+        ctx.accounts.game_state.paused = paused;
+        Ok(())
+    }
+
+    /// Apply every provided field of `update` in one step (owner only). Each
+    /// field is validated as by its own setter, and any invalid field rejects
+    /// the whole update. Price fields also need the owner to hold the price
+    /// role.
+    pub fn update_config(ctx: Context<UpdateConfig>, update: GameConfigUpdate) -> Result<()> {
+        let game = &mut ctx.accounts.game_state;
+        if update.price_per_cube.is_some() || update.quadratic_coeff.is_some() {
+            require_keys_eq!(
+                game.role(game.price_authority),
+                ctx.accounts.authority.key(),
+                CubeGameError::Unauthorized
+            );
+        }
+        let has_refund_pool = ctx.accounts.refund_pool.is_some();
+        game.apply_config(&update, has_refund_pool)?;
+
+        emit!(ConfigUpdatedEvent {
+            authority: ctx.accounts.authority.key(),
+            update,
+        });
+        Ok(())
+    }
+
+    /// Set how long a removed cube stays non-transferable (owner only)
+    pub fn set_transfer_lock(ctx: Context<SetConfig>, transfer_lock_seconds: i64) -> Result<()> {
+        require!(transfer_lock_seconds >= 0, CubeGameError::InvalidDuration);
+        let game = &mut ctx.accounts.game_state;
+        game.transfer_lock_seconds = transfer_lock_seconds;
+        Ok(())
+    }
+
+    /// Scale the price quadratically with removals: base + k * removed^2
+    /// (price authority only, 0 keeps a flat price)
+    pub fn set_quadratic_coeff(ctx: Context<SetPrice>, coeff: u64) -> Result<()> {
+        ctx.accounts.game_state.quadratic_coeff = coeff;
+        Ok(())
+    }
+
+    /// Cap the removal instructions a single transaction may bundle
+    /// (owner only, 0 disables the check). While it is on, removals must be
+    /// top-level instructions, not CPIs.
+    pub fn set_max_removals_per_tx(ctx: Context<SetConfig>, max: u8) -> Result<()> {
+        ctx.accounts.game_state.max_removals_per_tx = max;
+        Ok(())
+    }
+
+    /// Restrict which programs may CPI into the removal instructions (owner
+    /// only, an empty list allows any caller). Top-level calls are always
+    /// allowed; an allowlisted program must invoke the removal directly from
+    /// its own top-level instruction.
+    pub fn set_cpi_allowlist(ctx: Context<SetConfig>, callers: Vec<Pubkey>) -> Result<()> {
+        require!(
+            callers.len() <= MAX_CPI_CALLERS,
+            CubeGameError::TooManyCpiCallers
+        );
+        ctx.accounts.game_state.allowed_cpi_callers = callers;
+        Ok(())
+    }
+
+    /// Derive removed cubes' colors from `seed` (owner only, 0 lets players
+    /// pick). Only future removals are re-themed.
+    pub fn set_palette_seed(ctx: Context<SetConfig>, seed: u64) -> Result<()> {
+        ctx.accounts.game_state.palette_seed = seed;
+        Ok(())
+    }
+
+    /// Require a two-step intent and removal for cubes priced above
+    /// `threshold` (owner only, 0 disables)
+    pub fn set_high_value_threshold(ctx: Context<SetConfig>, threshold: u64) -> Result<()> {
+        ctx.accounts.game_state.high_value_threshold = threshold;
+        Ok(())
+    }
+
+    /// Complete the game once `max_cubes` removals have landed (owner only,
+    /// 0 keeps the board unbounded)
+    pub fn set_max_cubes(ctx: Context<SetConfig>, max_cubes: u64) -> Result<()> {
+        let game = &mut ctx.accounts.game_state;
+        require!(
+            max_cubes == 0 || !game.ephemeral_records,
+            CubeGameError::EphemeralBoard
+        );
+        game.max_cubes = max_cubes;
+        Ok(())
+    }
+
+    /// Rebate `rebate` lamports to the first remover of each cube (owner
+    /// only, 0 disables)
+    pub fn set_first_removal_rebate(ctx: Context<SetConfig>, rebate: u64) -> Result<()> {
+        ctx.accounts.game_state.first_removal_rebate = rebate;
+        Ok(())
+    }
+
+    /// Pay `refund` lamports, roughly a transaction fee, to each wallet on its
+    /// very first removal when it paid at least that much (owner only, 0
+    /// disables). The payout is kept in the wallet's `WalletMark`, so
+    /// closing and recreating player stats does not earn a second one.
+    pub fn set_onboarding_refund(ctx: Context<SetConfig>, refund: u64) -> Result<()> {
+        ctx.accounts.game_state.onboarding_refund = refund;
+        Ok(())
+    }
+
+    /// Only let players remove cubes `min_age` seconds after
+    /// `register_player` (owner only, 0 disables the gate)
+    pub fn set_min_player_age(ctx: Context<SetConfig>, min_age: i64) -> Result<()> {
+        require!(min_age >= 0, CubeGameError::InvalidConfiguration);
+        ctx.accounts.game_state.min_player_age = min_age;
+        Ok(())
+    }
+
+    /// Require removals to go through `commit_removal` and a `reveal_remove`
+    /// at least `slots` slots later (owner only, 0 allows direct removals).
+    /// While set, every other removal entry point (`remove_cube` and its
+    /// gated, ticketed, split and member variants, batches, contributions
+    /// and delegated removals) fails with `CommitRequired`.
+    pub fn set_min_reveal_delay(ctx: Context<SetConfig>, slots: u64) -> Result<()> {
+        ctx.accounts.game_state.min_reveal_delay_slots = slots;
+        Ok(())
+    }
+
+    /// Require a proof of work with `difficulty` leading zero bits on every
+    /// removal (owner only, 0 disables)
+    pub fn set_pow_difficulty(ctx: Context<SetConfig>, difficulty: u8) -> Result<()> {
+        ctx.accounts.game_state.pow_difficulty = difficulty;
+        Ok(())
+    }
+
+    /// Commit to the board's cube_ids by their Merkle root (owner only). The
+    /// commitment can be replaced until the first removal, then it is locked.
+    ///
+    /// Records are never placed up front: a removal proving its cube is a
+    /// leaf creates the record on the spot. `board_size` is the number of
+    /// leaves, so the game completes once every committed cube is gone
+    /// (0 = not sized).
+    pub fn commit_board(
+        ctx: Context<SetConfig>,
+        board_hash: [u8; 32],
+        board_size: u64,
+    ) -> Result<()> {
+        ctx.accounts
+            .game_state
+            .commit_board_root(board_hash, board_size)
+    }
+
+    /// Change how clients should label and scale prices (owner only)
+    pub fn set_currency_meta(ctx: Context<SetConfig>, label: String, decimals: u8) -> Result<()> {
+        ctx.accounts.game_state.set_currency(label, decimals)
+    }
+
+    /// Set the treasury-funded prize awarded by `finalize_game` (owner only)
+    pub fn set_grand_prize(ctx: Context<SetConfig>, prize: u64) -> Result<()> {
+        ctx.accounts.game_state.grand_prize = prize;
+        Ok(())
+    }
+
+    /// Raise the price by `bps_per_removal` for every removal in the current
+    /// `window_seconds` window, up to `max_bps` (price authority only, a
+    /// window of 0 disables rush pricing)
+    pub fn set_rush_pricing(
+        ctx: Context<SetPrice>,
+        window_seconds: i64,
+        bps_per_removal: u16,
+        max_bps: u16,
+    ) -> Result<()> {
+        require!(window_seconds >= 0, CubeGameError::InvalidDuration);
+        require!(
+            BPS_DENOMINATOR + max_bps as u64 <= u16::MAX as u64,
+            CubeGameError::InvalidBps
+        );
+        let game = &mut ctx.accounts.game_state;
+        game.rush_window_seconds = window_seconds;
+        game.rush_bps_per_removal = bps_per_removal;
+        game.rush_max_bps = max_bps;
+        game.rush_window_start = 0;
+        game.recent_removal_count = 0;
+        Ok(())
+    }
+
+    /// Multiply prices by `multiplier_bps` until `until` (price authority only)
+    pub fn set_surge(ctx: Context<SetPrice>, multiplier_bps: u16, until: i64) -> Result<()> {
+        require!(multiplier_bps > 0, CubeGameError::InvalidBps);
+        let game = &mut ctx.accounts.game_state;
+        game.surge_multiplier_bps = multiplier_bps;
+        game.surge_until = until;
+        Ok(())
+    }
+
+    /// Return pricing to a flat `base_price`: clears surge, rush, the
+    /// quadratic curve and the credit discount (price authority only).
+    /// Region curves live in their own accounts and are left as they are.
+    pub fn reset_pricing(ctx: Context<SetPrice>, base_price: u64) -> Result<()> {
+        ctx.accounts.game_state.reset_pricing(base_price);
+        emit!(PricingResetEvent {
+            authority: ctx.accounts.authority.key(),
+            base_price,
+        });
+        Ok(())
+    }
+
+    /// Close the game to removals at `ends_at` (owner only, 0 = no end)
+    pub fn set_game_end(ctx: Context<SetConfig>, ends_at: i64) -> Result<()> {
+        let game = &mut ctx.accounts.game_state;
+        game.ends_at = ends_at;
+        game.extensions_used = 0;
+        Ok(())
+    }
+
+    /// Push `ends_at` back by `extension` seconds whenever a cube is removed
+    /// within the last `window` seconds, at most `max_extensions` times
+    /// (owner only)
+    pub fn set_anti_snipe(
+        ctx: Context<SetConfig>,
+        window: i64,
+        extension: i64,
+        max_extensions: u32,
+    ) -> Result<()> {
+        require!(
+            window >= 0 && extension >= 0,
+            CubeGameError::InvalidDuration
+        );
+        let game = &mut ctx.accounts.game_state;
+        game.anti_snipe_window = window;
+        game.anti_snipe_extension = extension;
+        game.max_extensions = max_extensions;
+        Ok(())
+    }
+
+    /// Restrict removals to holders of an NFT from `collection` (owner only,
+    /// `None` opens the board to everyone)
+    pub fn set_required_collection(
+        ctx: Context<SetConfig>,
+        collection: Option<Pubkey>,
+    ) -> Result<()> {
+        ctx.accounts.game_state.required_collection = collection;
+        Ok(())
+    }
+
+    /// Close each cube record as soon as its cube is removed, refunding the
+    /// rent to the remover (owner only). Only counters and events remain,
+    /// so a removed cube can be removed again, and neither undo nor
+    /// refund-all mode can restore it. Every removal counts, so ephemeral
+    /// boards cannot have a size or zones, and never pay the first-removal
+    /// rebate.
+    ///
+    /// The remover must be the one who paid the record's rent, so records
+    /// may only be created by the removal that closes them: the mode needs
+    /// a board with no live records, and excludes placed, unlocked,
+    /// contributed or imported cubes, multi-hit and shared cubes.
+    pub fn set_ephemeral_records(ctx: Context<SetConfig>, ephemeral: bool) -> Result<()> {
+        let game = &mut ctx.accounts.game_state;
+        if ephemeral {
+            game.require_ephemeral_compatible()?;
+        }
+        game.ephemeral_records = ephemeral;
+        Ok(())
+    }
+
+    /// Give holders of an NFT from `collection` `discount_bps` off every
+    /// removal through `remove_cube_member` (owner only, the default key
+    /// ends the membership program). The discount stays below 100%, so a
+    /// member removal always pays something.
+    pub fn set_membership(
+        ctx: Context<SetConfig>,
+        collection: Pubkey,
+        discount_bps: u16,
+    ) -> Result<()> {
+        require!(
+            (discount_bps as u64) < BPS_DENOMINATOR,
+            CubeGameError::InvalidBps
+        );
+        let game = &mut ctx.accounts.game_state;
+        game.membership_collection = collection;
+        game.member_discount_bps = discount_bps;
+        Ok(())
+    }
+
+    /// Require every cube_id to start with `prefix` (owner only, empty disables)
+    pub fn set_required_prefix(ctx: Context<SetConfig>, prefix: String) -> Result<()> {
+        require!(prefix.len() <= 16, CubeGameError::PrefixTooLong);
+        let game = &mut ctx.accounts.game_state;
+        game.required_prefix = prefix;
+        Ok(())
+    }
+
+    /// Configure forwarding of treasury funds above `hot_cap` to `cold_wallet`
+    /// (owner only, a cap of 0 disables forwarding)
+    pub fn set_treasury_overflow(
+        ctx: Context<SetConfig>,
+        hot_cap: u64,
+        cold_wallet: Pubkey,
+    ) -> Result<()> {
+        // Forwarding the treasury to itself would never drain it, and
+        // nobody holds the default key
+        require_payout_key(&cold_wallet, &ctx.accounts.authority.key())?;
+        require!(
+            hot_cap == 0 || cold_wallet != Pubkey::default(),
+            CubeGameError::InvalidConfiguration
+        );
+        let game = &mut ctx.accounts.game_state;
+        game.treasury_hot_cap = hot_cap;
+        game.cold_wallet = cold_wallet;
+        Ok(())
+    }
+
+    /// Add the next zone of the board: cubes whose id starts with `prefix`,
+    /// removable once every earlier zone is cleared (owner only). Prefixes
+    /// should not overlap.
+    pub fn create_zone(ctx: Context<CreateZone>, prefix: String, cubes_total: u64) -> Result<()> {
+        require!(
+            !prefix.is_empty() && prefix.len() <= 16 && cubes_total > 0,
+            CubeGameError::InvalidZone
+        );
+        let game = &mut ctx.accounts.game_state;
+        require!(!game.ephemeral_records, CubeGameError::EphemeralBoard);
+        let zone = &mut ctx.accounts.zone;
+        zone.zone_id = game.zone_count;
+        zone.prefix = prefix;
+        zone.cubes_total = cubes_total;
+        zone.cubes_removed = 0;
+        zone.unlocked = game.zone_count == 0;
+        zone.bump = ctx.bumps.zone;
+        game.zone_count += 1;
+        Ok(())
+    }
+
+    /// Place the cubes in `cube_ids` (records in `remaining_accounts`, same
+    /// order) on `region_id`, free to remove from `expires_at` on (owner
+    /// only, 0 = never expire). The owner pays for any records created.
+    ///
+    /// `predecessors` is empty or holds, for each cube, the cube in the same
+    /// region that must be removed before it (`None` = no predecessor).
+    /// `royalties` is empty or holds each cube's creator royalty. A cube
+    /// without a creator is credited to the owner.
+    pub fn place_cubes<'info>(
+        ctx: Context<'_, '_, 'info, 'info, PlaceCubes<'info>>,
+        region_id: String,
+        cube_ids: Vec<String>,
+        expires_at: i64,
+        predecessors: Vec<Option<String>>,
+        royalties: Vec<CubeRoyalty>,
+    ) -> Result<()> {
+        require!(
+            region_id.len() <= MAX_REGION_LEN,
+            CubeGameError::InvalidRegion
+        );
+        require!(
+            cube_ids.len() == ctx.remaining_accounts.len(),
+            CubeGameError::BatchArgMismatch
+        );
+        require!(
+            cube_ids.len() <= MAX_BATCH_REMOVALS,
+            CubeGameError::TooManyAccounts
+        );
+        require!(
+            predecessors.is_empty() || predecessors.len() == cube_ids.len(),
+            CubeGameError::BatchArgMismatch
+        );
+        require!(
+            royalties.is_empty() || royalties.len() == cube_ids.len(),
+            CubeGameError::BatchArgMismatch
+        );
+
+        let game = &mut ctx.accounts.game_state;
+        // The authority would fund records the remover gets the rent of
+        require!(!game.ephemeral_records, CubeGameError::EphemeralBoard);
+        for (i, (cube_id, info)) in cube_ids
+            .iter()
+            .zip(ctx.remaining_accounts.iter())
+            .enumerate()
+        {
+            game.validate_cube_id(cube_id)?;
+            let mut record = load_or_create_cube_record(
+                info,
+                &region_id,
+                cube_id,
+                &ctx.accounts.authority.to_account_info(),
+                &ctx.accounts.system_program.to_account_info(),
+            )?;
+            record.validate(cube_id)?;
+            require!(!record.is_removed, CubeGameError::CubeAlreadyRemoved);
+            if record.cube_id.is_empty() {
+                game.note_record_created()?;
+                record.cube_id = cube_id.clone();
+            }
+            record.expires_at = expires_at;
+            if let Some(prev) = predecessors.get(i) {
+                if let Some(prev_id) = prev {
+                    game.validate_cube_id(prev_id)?;
+                    require!(prev_id != cube_id, CubeGameError::InvalidConfiguration);
+                }
+                record.prev_cube_id = prev.clone();
+            }
+            if let Some(royalty) = royalties.get(i) {
+                require!(
+                    royalty.royalty_bps as u64 <= BPS_DENOMINATOR,
+                    CubeGameError::InvalidBps
+                );
+                record.creator = royalty.creator;
+                record.royalty_bps = royalty.royalty_bps;
+            }
+            if record.creator == Pubkey::default() {
+                record.creator = ctx.accounts.authority.key();
+            }
+            record.exit(&crate::ID)?;
+        }
+        Ok(())
+    }
+
+    /// Give `region_id` its own bonding curve, `base_price + increment *
+    /// cubes_removed` (owner only). Reconfiguring keeps the region's count.
+    pub fn configure_region(
+        ctx: Context<ConfigureRegion>,
+        region_id: String,
+        base_price: u64,
+        increment: u64,
+    ) -> Result<()> {
+        require!(
+            !region_id.is_empty() && region_id.len() <= MAX_REGION_LEN,
+            CubeGameError::InvalidRegion
+        );
+        let region = &mut ctx.accounts.region;
+        region.region_id = region_id;
+        region.base_price = base_price;
+        region.increment = increment;
+        region.bump = ctx.bumps.region;
+        Ok(())
+    }
+
+    /// Delegate `region_id` to `region_authority`, who may then reprice and
+    /// pause that region only (owner only, the default key revokes)
+    pub fn set_region_authority(
+        ctx: Context<SetRegionAuthority>,
+        _region_id: String,
+        region_authority: Pubkey,
+    ) -> Result<()> {
+        ctx.accounts.region.region_authority = region_authority;
+        Ok(())
+    }
+
+    /// Change a region's bonding curve (region authority or owner)
+    pub fn set_region_price(
+        ctx: Context<RegionAdmin>,
+        _region_id: String,
+        base_price: u64,
+        increment: u64,
+    ) -> Result<()> {
+        let region = &mut ctx.accounts.region;
+        region.base_price = base_price;
+        region.increment = increment;
+        Ok(())
+    }
+
+    /// Stop or resume removals in a region (region authority or owner)
+    pub fn set_region_paused(
+        ctx: Context<RegionAdmin>,
+        _region_id: String,
+        paused: bool,
+    ) -> Result<()> {
+        ctx.accounts.region.paused = paused;
+        Ok(())
+    }
+
+    /// Only allow removals in regions `configure_region` has set up, so every
+    /// region is priced on its own curve (owner only)
+    pub fn set_region_pricing(ctx: Context<SetConfig>, enabled: bool) -> Result<()> {
+        ctx.accounts.game_state.region_pricing = enabled;
+        Ok(())
+    }
+
+    /// Create the refund pool that reserves part of every payment for refunds
+    /// (owner only)
+    pub fn init_refund_pool(ctx: Context<InitRefundPool>) -> Result<()> {
+        ctx.accounts.refund_pool.bump = ctx.bumps.refund_pool;
+        Ok(())
+    }
+
+    /// Create the cold reserve that refills the treasury after withdrawals
+    /// (owner only)
+    pub fn init_cold_reserve(ctx: Context<InitColdReserve>) -> Result<()> {
+        ctx.accounts.cold_reserve.bump = ctx.bumps.cold_reserve;
+        Ok(())
+    }
+
+    /// Create the analytics rollup (owner only). From then on every removal
+    /// and refund must pass it so its aggregates stay complete.
+    pub fn init_analytics(ctx: Context<InitAnalytics>) -> Result<()> {
+        ctx.accounts.analytics.bump = ctx.bumps.analytics;
+        ctx.accounts.game_state.analytics_enabled = true;
+        Ok(())
+    }
+
+    /// Create the treasury ledger (owner only). From then on every
+    /// instruction that moves the game's lamports must pass it so its
+    /// totals stay complete.
+    pub fn init_ledger(ctx: Context<InitLedger>) -> Result<()> {
+        ctx.accounts.ledger.bump = ctx.bumps.ledger;
+        ctx.accounts.game_state.ledger_enabled = true;
+        Ok(())
+    }
+
+    /// Move `amount` lamports from the authority into the treasury, e.g. to
+    /// bring a fresh treasury up to rent exemption (owner only)
+    pub fn fund_treasury(ctx: Context<FundTreasury>, amount: u64) -> Result<()> {
+        require!(amount > 0, CubeGameError::ZeroFunding);
+        require_ledger(&ctx.accounts.game_state, ctx.accounts.ledger.as_deref())?;
+        transfer_lamports(
+            &ctx.accounts.authority.to_account_info(),
+            &ctx.accounts.treasury,
+            &ctx.accounts.system_program.to_account_info(),
+            amount,
+        )?;
+        if let Some(ledger) = ctx.accounts.ledger.as_mut() {
+            ledger.record_funding(amount, false)?;
+        }
+        warn_if_treasury_unfunded(&ctx.accounts.treasury)
+    }
+
+    /// Move `amount` lamports from the authority into the cold reserve
+    /// (owner only)
+    pub fn fund_cold_reserve(ctx: Context<FundColdReserve>, amount: u64) -> Result<()> {
+        require_ledger(&ctx.accounts.game_state, ctx.accounts.ledger.as_deref())?;
+        transfer_lamports(
+            &ctx.accounts.authority.to_account_info(),
+            &ctx.accounts.cold_reserve.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+            amount,
+        )?;
+        ctx.accounts.cold_reserve.total_deposited += amount;
+        if let Some(ledger) = ctx.accounts.ledger.as_mut() {
+            ledger.record_funding(amount, true)?;
+        }
+        Ok(())
+    }
+
+    /// Refill the treasury from the cold reserve up to `target` lamports
+    /// after each withdrawal (owner only, 0 disables)
+    pub fn set_target_hot_balance(ctx: Context<SetConfig>, target: u64) -> Result<()> {
+        ctx.accounts.game_state.target_hot_balance = target;
+        Ok(())
+    }
+
+    /// Create the insurance vault, locked until `locked_until` (owner only)
+    pub fn init_insurance_vault(ctx: Context<InitInsuranceVault>, locked_until: i64) -> Result<()> {
+        let vault = &mut ctx.accounts.insurance_vault;
+        vault.locked_until = locked_until;
+        vault.bump = ctx.bumps.insurance_vault;
+        Ok(())
+    }
+
+    /// Lock `insurance_bps` of every payment in the insurance vault (owner
+    /// only)
+    pub fn set_insurance(ctx: Context<SetInsurance>, insurance_bps: u16) -> Result<()> {
+        let game = &mut ctx.accounts.game_state;
+        game.insurance_bps = insurance_bps;
+        game.require_valid_split()
+    }
+
+    /// Push the insurance lock out to `locked_until` (owner only). The lock
+    /// can only be extended.
+    pub fn extend_insurance_lock(ctx: Context<ManageInsurance>, locked_until: i64) -> Result<()> {
+        let vault = &mut ctx.accounts.insurance_vault;
+        require!(
+            locked_until >= vault.locked_until,
+            CubeGameError::InsuranceLocked
+        );
+        vault.locked_until = locked_until;
+        Ok(())
+    }
+
+    /// Move `amount` from the insurance vault into the refund pool to cover
+    /// refunds (owner only, allowed while locked)
+    pub fn fund_refunds_from_insurance(
+        ctx: Context<FundRefundsFromInsurance>,
+        amount: u64,
+    ) -> Result<()> {
+        let vault_info = ctx.accounts.insurance_vault.to_account_info();
+        release_insurance(&vault_info, amount)?;
+        // Both accounts are owned by this program, so lamports move directly
+        **vault_info.try_borrow_mut_lamports()? -= amount;
+        **ctx
+            .accounts
+            .refund_pool
+            .to_account_info()
+            .try_borrow_mut_lamports()? += amount;
+        ctx.accounts.insurance_vault.total_released += amount;
+        ctx.accounts.refund_pool.total_reserved += amount;
+        Ok(())
+    }
+
+    /// Withdraw `amount` from the insurance vault once its lock has expired
+    /// (owner only)
+    pub fn withdraw_insurance(ctx: Context<ManageInsurance>, amount: u64) -> Result<()> {
+        ctx.accounts
+            .insurance_vault
+            .require_unlocked(Clock::get()?.unix_timestamp)?;
+        require_ledger(&ctx.accounts.game_state, ctx.accounts.ledger.as_deref())?;
+        let vault_info = ctx.accounts.insurance_vault.to_account_info();
+        release_insurance(&vault_info, amount)?;
+        **vault_info.try_borrow_mut_lamports()? -= amount;
+        **ctx.accounts.authority.try_borrow_mut_lamports()? += amount;
+        ctx.accounts.insurance_vault.total_released += amount;
+        if let Some(ledger) = ctx.accounts.ledger.as_mut() {
+            ledger.record_escrow_withdrawal(amount)?;
+        }
+        Ok(())
+    }
+
+    /// Route `reserve_bps` of every payment into the refund pool (owner only)
+    pub fn set_refund_reserve(ctx: Context<SetRefundReserve>, reserve_bps: u16) -> Result<()> {
+        let game = &mut ctx.accounts.game_state;
+        game.refund_reserve_bps = reserve_bps;
+        game.require_valid_split()
+    }
+
+    /// Sell prepaid removal credits at `discount_bps` off the base price
+    /// (owner only)
+    pub fn set_credit_discount(ctx: Context<SetConfig>, discount_bps: u16) -> Result<()> {
+        require!(
+            discount_bps as u64 <= BPS_DENOMINATOR,
+            CubeGameError::InvalidBps
+        );
+        ctx.accounts.game_state.credit_discount_bps = discount_bps;
+        Ok(())
+    }
+
+    /// Mirror every removal into `analytics_program` through a CPI carrying a
+    /// `RemovalExport`, with the game state and the cube's record as
+    /// read-only accounts (owner only, the default key stops exporting). A
+    /// failing export reverts the removal.
+    pub fn set_analytics_program(ctx: Context<SetConfig>, analytics_program: Pubkey) -> Result<()> {
+        require_keys_neq!(
+            analytics_program,
+            crate::ID,
+            CubeGameError::InvalidConfiguration
+        );
+        ctx.accounts.game_state.analytics_program = analytics_program;
+        Ok(())
+    }
+
+    /// Emit a `LowBalanceWarningEvent` when a removal leaves the player with
+    /// fewer than `threshold` lamports (owner only, 0 disables the warning)
+    pub fn set_low_balance_threshold(ctx: Context<SetConfig>, threshold: u64) -> Result<()> {
+        ctx.accounts.game_state.low_balance_threshold = threshold;
+        Ok(())
+    }
+
+    /// Sell subscriptions lasting `duration_seconds` at `prices` for the
+    /// quota tier and the unlimited tier, the quota tier covering `quota`
+    /// removals (owner only, a duration of 0 stops sales)
+    pub fn set_subscription_terms(
+        ctx: Context<SetConfig>,
+        prices: [u64; 2],
+        duration_seconds: i64,
+        quota: u64,
+    ) -> Result<()> {
+        require!(duration_seconds >= 0, CubeGameError::InvalidDuration);
+        let game = &mut ctx.accounts.game_state;
+        game.subscription_prices = prices;
+        game.subscription_duration = duration_seconds;
+        game.subscription_quota = quota;
+        Ok(())
+    }
+
+    /// Offer price locks lasting `duration` seconds for a `premium_bps`
+    /// premium (owner only, a duration of 0 stops new locks)
+    pub fn set_voucher_terms(
+        ctx: Context<SetConfig>,
+        premium_bps: u16,
+        duration: i64,
+    ) -> Result<()> {
+        require!(
+            premium_bps as u64 <= BPS_DENOMINATOR,
+            CubeGameError::InvalidBps
+        );
+        require!(duration >= 0, CubeGameError::InvalidDuration);
+        let game = &mut ctx.accounts.game_state;
+        game.voucher_premium_bps = premium_bps;
+        game.voucher_duration = duration;
+        Ok(())
+    }
+
+    /// Burn `burn_bps` of every payment by sending it to the incinerator
+    /// (owner only)
+    pub fn set_sol_burn(ctx: Context<SetConfig>, burn_bps: u16) -> Result<()> {
+        let game = &mut ctx.accounts.game_state;
+        game.sol_burn_bps = burn_bps;
+        game.require_valid_split()
+    }
+
+    /// Send `tax_bps` of every payment to `dao_treasury` (owner only)
+    pub fn set_dao_tax(ctx: Context<SetConfig>, dao_treasury: Pubkey, tax_bps: u16) -> Result<()> {
+        require_payout_key(&dao_treasury, &ctx.accounts.authority.key())?;
+        let game = &mut ctx.accounts.game_state;
+        game.dao_treasury = dao_treasury;
+        game.dao_tax_bps = tax_bps;
+        game.require_valid_split()
+    }
+
+    /// Tip the remover of the previous cube `tip_bps` of each removal's
+    /// payment (owner only, 0 disables tipping)
+    pub fn set_tip_bps(ctx: Context<SetConfig>, tip_bps: u16) -> Result<()> {
+        let game = &mut ctx.accounts.game_state;
+        game.tip_bps = tip_bps;
+        game.require_valid_split()
+    }
+
+    /// Route `reward_bps` of each removal's payment to token stakers through
+    /// the staking pool (owner only, 0 stops the rewards). While nothing is
+    /// staked the whole payment goes to the treasury.
+    pub fn set_staker_reward_bps(ctx: Context<SetConfig>, reward_bps: u16) -> Result<()> {
+        let game = &mut ctx.accounts.game_state;
+        game.staker_reward_bps = reward_bps;
+        game.require_valid_split()
+    }
+
+    /// Create the on-chain activity feed; once it exists every removal and
+    /// transfer must append to it (owner only)
+    pub fn init_activity_feed(ctx: Context<InitActivityFeed>) -> Result<()> {
+        ctx.accounts.game_state.activity_feed_enabled = true;
+        Ok(())
+    }
+
+    /// Create the message wall removals can post public messages to (owner
+    /// only)
+    pub fn init_message_wall(_ctx: Context<InitMessageWall>) -> Result<()> {
+        Ok(())
+    }
+
+    /// Create the caller's removal history; from then on every removal
+    /// credited to them, single, batched, delegated or crowdfunded, must
+    /// pass it so it never misses one
+    pub fn init_player_history(ctx: Context<InitPlayerHistory>) -> Result<()> {
+        let player = ctx.accounts.player.key();
+        ctx.accounts.player_stats.player = player;
+        ctx.accounts.player_stats.history_enabled = true;
+        let history = &mut ctx.accounts.player_history;
+        history.player = player;
+        history.bump = ctx.bumps.player_history;
+        Ok(())
+    }
+
+    /// Suspend or reinstate a wallet's removals (owner only). Freezing a
+    /// wallet without stats creates a frozen stub for it.
+    pub fn set_player_frozen(
+        ctx: Context<SetPlayerFrozen>,
+        player: Pubkey,
+        frozen: bool,
+    ) -> Result<()> {
+        ctx.accounts.player_stats.set_frozen(player, frozen);
+        Ok(())
+    }
+
+    /// Waive removal fees for a partner wallet (owner only)
+    pub fn grant_exemption(ctx: Context<GrantExemption>, player: Pubkey) -> Result<()> {
+        // The authority removing for free would skew the treasury's books
+        require_keys_neq!(
+            player,
+            ctx.accounts.authority.key(),
+            CubeGameError::InvalidConfiguration
+        );
+        let exemption = &mut ctx.accounts.fee_exemption;
+        exemption.player = player;
+        exemption.bump = ctx.bumps.fee_exemption;
+        Ok(())
+    }
+
+    /// Revoke a fee exemption, restoring normal charging (owner only)
+    pub fn revoke_exemption(_ctx: Context<RevokeExemption>, _player: Pubkey) -> Result<()> {
+        Ok(())
+    }
+
+    /// Restore every passed cube removed at or after `since` (owner only)
+    ///
+    /// `remaining_accounts` lists the affected `CubeRecord`s. Each record in
+    /// the window is followed by the accounts `restore_cube` takes: its
+    /// original remover's `PlayerStats`, then its `Region`, `Team` and `Zone` as
+    /// needed, then with `refund` set one wallet per payment, each refunded
+    /// from the refund pool (falling back to the treasury). A record outside
+    /// the window is passed alone.
+    pub fn rollback_removals<'info>(
+        ctx: Context<'_, '_, 'info, 'info, RollbackRemovals<'info>>,
+        since: i64,
+        refund: bool,
+    ) -> Result<()> {
+        require!(
+            !ctx.accounts.game_state.analytics_enabled || ctx.accounts.analytics.is_some(),
+            CubeGameError::AnalyticsRequired
+        );
+        require!(
+            !ctx.accounts.game_state.ledger_enabled || ctx.accounts.ledger.is_some(),
+            CubeGameError::LedgerRequired
+        );
+
+        let now = Clock::get()?.unix_timestamp;
+        let authority = ctx.accounts.authority.key();
+        let system_program = ctx.accounts.system_program.to_account_info();
+        let mut accounts = ctx.remaining_accounts.iter();
+        let mut records = 0;
+        let mut restored = 0u32;
+        let mut refunded = 0u64;
+
+        while let Some(record_info) = accounts.next() {
+            records += 1;
+            require!(
+                records <= MAX_ROLLBACK_BATCH,
+                CubeGameError::TooManyAccounts
+            );
+            let mut record = Account::<CubeRecord>::try_from(record_info)?;
+            if !record.is_removed || record.removed_at < since {
+                continue;
+            }
+            let mut stats = Account::<PlayerStats>::try_from(next_account(&mut accounts)?)?;
+
+            let game = &mut ctx.accounts.game_state;
+            let route = RefundRoute {
+                treasury_bump: game.treasury_bump,
+                refund_pool: ctx.accounts.refund_pool.as_mut(),
+                ledger: ctx.accounts.ledger.as_deref_mut(),
+                treasury: &ctx.accounts.treasury,
+                system_program: &system_program,
+            };
+            refunded += restore_cube(
+                game,
+                &mut record,
+                record_info.key(),
+                &mut stats,
+                None,
+                &mut accounts,
+                refund.then_some(route),
+            )?;
+            record.exit(&crate::ID)?;
+            stats.exit(&crate::ID)?;
+            restored += 1;
+
+            log_activity(
+                game,
+                ctx.accounts.activity_feed.as_mut(),
+                ACTIVITY_RESTORE,
+                authority,
+                &record.cube_id,
+                now,
+            )?;
+        }
+        if let Some(analytics) = ctx.accounts.analytics.as_mut() {
+            analytics.record_refund(refunded)?;
+        }
+
+        emit!(RollbackEvent {
+            since,
+            restored,
+            refunded,
+        });
+        Ok(())
+    }
+
+    /// Reverse the most recent removal (owner only): restore its cube, refund
+    /// each payer what they paid and roll back the counters. Only a removal
+    /// through `remove_cube` can be undone, and only until another cube goes.
+    ///
+    /// `remaining_accounts` holds what `restore_cube` takes after the
+    /// remover's stats: the cube's `Region`, `Team` and `Zone` as needed,
+    /// then one wallet per payment.
+    pub fn undo_last_removal<'info>(
+        ctx: Context<'_, '_, 'info, 'info, UndoLastRemoval<'info>>,
+    ) -> Result<()> {
+        let game = &mut ctx.accounts.game_state;
+        require!(
+            !game.last_cube_id.is_empty(),
+            CubeGameError::NoRemovalToUndo
+        );
+        require!(
+            !game.analytics_enabled || ctx.accounts.analytics.is_some(),
+            CubeGameError::AnalyticsRequired
+        );
+        require!(
+            !game.ledger_enabled || ctx.accounts.ledger.is_some(),
+            CubeGameError::LedgerRequired
+        );
+        let record = &mut ctx.accounts.cube_record;
+        require!(
+            record.is_removed
+                && record.credited_remover() == game.last_remover
+                && record.removed_at == game.last_removed_at,
+            CubeGameError::LastRemovalMismatch
+        );
+
+        let record_key = record.key();
+        let system_program = ctx.accounts.system_program.to_account_info();
+        let route = RefundRoute {
+            treasury_bump: game.treasury_bump,
+            refund_pool: ctx.accounts.refund_pool.as_mut(),
+            ledger: ctx.accounts.ledger.as_deref_mut(),
+            treasury: &ctx.accounts.treasury,
+            system_program: &system_program,
+        };
+        let refunded = restore_cube(
+            game,
+            record,
+            record_key,
+            &mut ctx.accounts.remover_stats,
+            None,
+            &mut ctx.remaining_accounts.iter(),
+            Some(route),
+        )?;
+        if let Some(analytics) = ctx.accounts.analytics.as_mut() {
+            analytics.record_refund(refunded)?;
+        }
+
+        let remover = game.last_remover;
+        game.last_cube_id.clear();
+        game.last_region_id.clear();
+        game.last_removed_at = 0;
+
+        emit!(UndoRemovalEvent {
+            cube_id: record.cube_id.clone(),
+            remover,
+            refunded,
+        });
+        Ok(())
+    }
+
+    /// Restore the passed removed cubes of a zone for a new round (owner
+    /// only)
+    ///
+    /// `remaining_accounts` is laid out as for `rollback_removals`, without
+    /// the zone. With `refund` set, each payment is returned to its payer
+    /// from the refund pool (falling back to the treasury). Large zones are
+    /// reset over several calls.
+    pub fn reset_zone<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ResetZone<'info>>,
+        zone_id: u32,
+        refund: bool,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.zone.zone_id == zone_id,
+            CubeGameError::InvalidZone
+        );
+        require!(
+            !ctx.accounts.game_state.analytics_enabled || ctx.accounts.analytics.is_some(),
+            CubeGameError::AnalyticsRequired
+        );
+        require!(
+            !ctx.accounts.game_state.ledger_enabled || ctx.accounts.ledger.is_some(),
+            CubeGameError::LedgerRequired
+        );
+
+        let now = Clock::get()?.unix_timestamp;
+        let authority = ctx.accounts.authority.key();
+        let system_program = ctx.accounts.system_program.to_account_info();
+        let mut accounts = ctx.remaining_accounts.iter();
+        let mut records = 0;
+        let mut restored = 0u32;
+        let mut refunded = 0u64;
+
+        while let Some(record_info) = accounts.next() {
+            records += 1;
+            require!(
+                records <= MAX_ROLLBACK_BATCH,
+                CubeGameError::TooManyAccounts
+            );
+            let mut record = Account::<CubeRecord>::try_from(record_info)?;
+            require!(
+                record
+                    .cube_id
+                    .starts_with(ctx.accounts.zone.prefix.as_str()),
+                CubeGameError::CubeNotInZone
+            );
+            if !record.is_removed {
+                continue;
+            }
+            let mut stats = Account::<PlayerStats>::try_from(next_account(&mut accounts)?)?;
+
+            let game = &mut ctx.accounts.game_state;
+            let route = RefundRoute {
+                treasury_bump: game.treasury_bump,
+                refund_pool: ctx.accounts.refund_pool.as_mut(),
+                ledger: ctx.accounts.ledger.as_deref_mut(),
+                treasury: &ctx.accounts.treasury,
+                system_program: &system_program,
+            };
+            refunded += restore_cube(
+                game,
+                &mut record,
+                record_info.key(),
+                &mut stats,
+                Some(&mut ctx.accounts.zone),
+                &mut accounts,
+                refund.then_some(route),
+            )?;
+            record.exit(&crate::ID)?;
+            stats.exit(&crate::ID)?;
+            restored += 1;
+
+            log_activity(
+                game,
+                ctx.accounts.activity_feed.as_mut(),
+                ACTIVITY_RESTORE,
+                authority,
+                &record.cube_id,
+                now,
+            )?;
+        }
+        if let Some(analytics) = ctx.accounts.analytics.as_mut() {
+            analytics.record_refund(refunded)?;
+        }
+
+        emit!(ZoneResetEvent {
+            zone_id,
+            restored,
+            refunded,
+        });
+        Ok(())
+    }
+
+    /// Refund removers from the treasury (owner only). `remaining_accounts`
+    /// holds a removed cube's record followed by one of its payers for each
+    /// entry of `refunds`. Each refund is taken off that payer's entry in the
+    /// record's payments, so nobody is refunded more than they paid, and the
+    /// total must fit in the treasury's balance above rent without touching
+    /// what refund-all mode still owes other removers.
+    pub fn batch_refund<'info>(
+        ctx: Context<'_, '_, 'info, 'info, BatchRefund<'info>>,
+        refunds: Vec<u64>,
+    ) -> Result<()> {
+        let treasury = &ctx.accounts.treasury;
+        let spendable = treasury_spendable(treasury)?;
+        let total = batch_refund_total(&refunds, ctx.remaining_accounts.len(), spendable)?;
+        let game = &mut ctx.accounts.game_state;
+        require!(
+            !game.analytics_enabled || ctx.accounts.analytics.is_some(),
+            CubeGameError::AnalyticsRequired
+        );
+        require!(
+            !game.ledger_enabled || ctx.accounts.ledger.is_some(),
+            CubeGameError::LedgerRequired
+        );
+
+        let system_program = ctx.accounts.system_program.to_account_info();
+        for (pair, amount) in ctx.remaining_accounts.chunks(2).zip(refunds.iter()) {
+            let (record_info, recipient) = (&pair[0], &pair[1]);
+            let mut record = Account::<CubeRecord>::try_from(record_info)?;
+            record.take_refund(&recipient.key(), *amount)?;
+            record.exit(&crate::ID)?;
+            game.release_paid(*amount);
+            transfer_from_treasury(
+                treasury,
+                recipient,
+                &system_program,
+                game.treasury_bump,
+                *amount,
+            )?;
+        }
+        game.require_liabilities_covered(total, spendable)?;
+
+        if let Some(analytics) = ctx.accounts.analytics.as_mut() {
+            analytics.record_refund(total)?;
+        }
+        if let Some(ledger) = ctx.accounts.ledger.as_mut() {
+            ledger.record_refund(total, 0)?;
+        }
+
+        emit!(BatchRefundEvent {
+            count: refunds.len() as u32,
+            total,
+        });
+        Ok(())
+    }
+
+    /// Keep at least `reserve` lamports liquid in the treasury on top of its
+    /// rent when staking (owner only)
+    pub fn set_stake_liquid_reserve(ctx: Context<SetConfig>, reserve: u64) -> Result<()> {
+        ctx.accounts.game_state.stake_liquid_reserve = reserve;
+        Ok(())
+    }
+
+    /// Delegate `amount` of idle treasury SOL to `vote_account` through a new
+    /// stake account controlled by the treasury PDA (owner only). Staked
+    /// lamports cannot pay refunds, so in refund-all mode what is owed to
+    /// removers stays liquid.
+    pub fn stake_treasury(ctx: Context<StakeTreasury>, amount: u64) -> Result<()> {
+        let game = &mut ctx.accounts.game_state;
+        let treasury = &ctx.accounts.treasury;
+
+        game.require_stakeable(amount, treasury_spendable(treasury)?)?;
+        require_ledger(game, ctx.accounts.ledger.as_deref())?;
+        if let Some(ledger) = ctx.accounts.ledger.as_mut() {
+            ledger.record_stake(amount)?;
+        }
+        game.treasury_staked = game
+            .treasury_staked
+            .checked_add(amount)
+            .ok_or(CubeGameError::MathOverflow)?;
+
+        let treasury_seeds: &[&[u8]] = &[b"treasury", &[game.treasury_bump]];
+        let accounts = [
+            treasury.to_account_info(),
+            ctx.accounts.stake_account.to_account_info(),
+            ctx.accounts.vote_account.to_account_info(),
+            ctx.accounts.rent.to_account_info(),
+            ctx.accounts.clock.to_account_info(),
+            ctx.accounts.stake_history.to_account_info(),
+            ctx.accounts.stake_config.to_account_info(),
+            ctx.accounts.system_program.to_account_info(),
+            ctx.accounts.stake_program.to_account_info(),
+        ];
+        for ix in stake_instructions(
+            &treasury.key(),
+            &ctx.accounts.stake_account.key(),
+            &ctx.accounts.vote_account.key(),
+            amount,
+        ) {
+            anchor_lang::solana_program::program::invoke_signed(&ix, &accounts, &[treasury_seeds])?;
+        }
+
+        emit!(TreasuryStakedEvent {
+            stake_account: ctx.accounts.stake_account.key(),
+            vote_account: ctx.accounts.vote_account.key(),
+            amount,
+        });
+        Ok(())
+    }
+
+    /// Begin the cooldown of a treasury-controlled stake account (owner only)
+    pub fn deactivate_stake(ctx: Context<DeactivateStake>) -> Result<()> {
+        let treasury = &ctx.accounts.treasury;
+        anchor_lang::solana_program::program::invoke_signed(
+            &stake::instruction::deactivate_stake(
+                &ctx.accounts.stake_account.key(),
+                &treasury.key(),
+            ),
+            &[
+                ctx.accounts.stake_account.to_account_info(),
+                ctx.accounts.clock.to_account_info(),
+                treasury.to_account_info(),
+                ctx.accounts.stake_program.to_account_info(),
+            ],
+            &[&[b"treasury", &[ctx.accounts.game_state.treasury_bump]]],
+        )?;
+        Ok(())
+    }
+
+    /// Return `amount` lamports of a deactivated treasury stake account to
+    /// the treasury (owner only). Rewards above the staked principal arrive
+    /// as ordinary treasury balance.
+    pub fn withdraw_stake(ctx: Context<WithdrawStake>, amount: u64) -> Result<()> {
+        let game = &mut ctx.accounts.game_state;
+        let treasury = &ctx.accounts.treasury;
+        require_ledger(game, ctx.accounts.ledger.as_deref())?;
+        anchor_lang::solana_program::program::invoke_signed(
+            &stake::instruction::withdraw(
+                &ctx.accounts.stake_account.key(),
+                &treasury.key(),
+                &treasury.key(),
+                amount,
+                None,
+            ),
+            &[
+                ctx.accounts.stake_account.to_account_info(),
+                treasury.to_account_info(),
+                ctx.accounts.clock.to_account_info(),
+                ctx.accounts.stake_history.to_account_info(),
+                ctx.accounts.stake_program.to_account_info(),
+            ],
+            &[&[b"treasury", &[game.treasury_bump]]],
+        )?;
+        game.treasury_staked = game.treasury_staked.saturating_sub(amount);
+        if let Some(ledger) = ctx.accounts.ledger.as_mut() {
+            ledger.record_unstake(amount)?;
+        }
+
+        emit!(StakeWithdrawnEvent {
+            stake_account: ctx.accounts.stake_account.key(),
+            amount,
+            treasury_staked: game.treasury_staked,
+        });
+        Ok(())
+    }
+
+    /// Emit an event each time the treasury reaches `first`, `first + step`,
+    /// and so on (owner only, a step of 0 disables)
+    pub fn set_treasury_thresholds(ctx: Context<SetConfig>, first: u64, step: u64) -> Result<()> {
+        let game = &mut ctx.accounts.game_state;
+        game.next_threshold = first;
+        game.threshold_step = step;
+        Ok(())
+    }
+
+    /// Rank players by the rarity score of the cubes they removed rather
+    /// than their decayed removal count (owner only)
+    pub fn set_ranked_mode(ctx: Context<SetConfig>, ranked_mode: bool) -> Result<()> {
+        ctx.accounts.game_state.ranked_mode = ranked_mode;
+        Ok(())
+    }
+
+    /// Decay ranking scores by `rate_bps_per_day` of a player's removals for
+    /// every day since their last removal (owner only, 0 disables decay)
+    pub fn set_score_decay(ctx: Context<SetConfig>, rate_bps_per_day: u16) -> Result<()> {
+        require!(
+            rate_bps_per_day as u64 <= BPS_DENOMINATOR,
+            CubeGameError::InvalidBps
+        );
+        ctx.accounts.game_state.decay_rate_bps_per_day = rate_bps_per_day;
+        Ok(())
+    }
+
+    /// Make `rate_bps` of cube ids bombs that clear their neighbours when
+    /// removed (owner only, 0 disables)
+    pub fn set_bomb_rate(ctx: Context<SetConfig>, rate_bps: u16) -> Result<()> {
+        require!(
+            rate_bps as u64 <= BPS_DENOMINATOR,
+            CubeGameError::InvalidBps
+        );
+        ctx.accounts.game_state.bomb_rate_bps = rate_bps;
+        Ok(())
+    }
+
+    /// Pay `bonus` for removing a cube with at least `min_neighbors`
+    /// neighbours removed in the last `window` seconds (owner only,
+    /// 0 neighbours disables combos)
+    pub fn set_combo(
+        ctx: Context<SetConfig>,
+        bonus: u64,
+        window: i64,
+        min_neighbors: u8,
+    ) -> Result<()> {
+        require!(window >= 0, CubeGameError::InvalidDuration);
+        require!(
+            min_neighbors as usize <= MAX_COMBO_NEIGHBORS,
+            CubeGameError::InvalidComboThreshold
+        );
+        let game = &mut ctx.accounts.game_state;
+        game.combo_bonus = bonus;
+        game.combo_window = window;
+        game.combo_min_neighbors = min_neighbors;
+        Ok(())
+    }
+
+    /// Build a timed combo from removals at most `window` seconds apart
+    /// (owner only, 0 disables it). The n-th removal of a combo pays
+    /// `(n - 1) * step_bonus`, capped at `max_bonus` (0 = no cap).
+    pub fn set_combo_timer(
+        ctx: Context<SetConfig>,
+        window: i64,
+        step_bonus: u64,
+        max_bonus: u64,
+    ) -> Result<()> {
+        require!(window >= 0, CubeGameError::InvalidDuration);
+        let game = &mut ctx.accounts.game_state;
+        game.combo_timer_seconds = window;
+        game.combo_step_bonus = step_bonus;
+        game.combo_max_bonus = max_bonus;
+        Ok(())
+    }
+
+    /// Allow or stop `import_removal` (owner only). Fails once imports are
+    /// locked.
+    pub fn set_import_mode(ctx: Context<SetConfig>, enabled: bool) -> Result<()> {
+        let game = &mut ctx.accounts.game_state;
+        require!(!game.import_locked, CubeGameError::ImportLocked);
+        game.import_mode = enabled;
+        Ok(())
+    }
+
+    /// Permanently end the migration window (owner only)
+    pub fn lock_imports(ctx: Context<SetConfig>) -> Result<()> {
+        let game = &mut ctx.accounts.game_state;
+        game.import_mode = false;
+        game.import_locked = true;
+        Ok(())
+    }
+
+    /// Seed a `CubeRecord` from a prior deployment's history while import
+    /// mode is on (owner only)
+    pub fn import_removal(
+        ctx: Context<ImportRemoval>,
+        cube_id: String,
+        region_id: String,
+        removed_by: Pubkey,
+        removed_at: i64,
+        price_paid: u64,
+    ) -> Result<()> {
+        let game = &mut ctx.accounts.game_state;
+        require!(game.import_mode, CubeGameError::ImportModeOff);
+        require!(!game.ephemeral_records, CubeGameError::EphemeralBoard);
+        game.validate_cube_id(&cube_id)?;
+        game.note_record_created()?;
+
+        let record = &mut ctx.accounts.cube_record;
+        record.is_removed = true;
+        record.removed_by = removed_by;
+        record.original_remover = removed_by;
+        record.removed_at = removed_at;
+        record.cube_id = cube_id.clone();
+        // Imported history was paid to the prior deployment, so it is shown
+        // as the price paid but never enters this treasury's payments or
+        // refund liability
+        record.price_paid = price_paid;
+
+        game.count_removal()?;
+        require!(
+            region_id.len() <= MAX_REGION_LEN,
+            CubeGameError::InvalidRegion
+        );
+        let mut region = load_region(ctx.accounts.region.as_ref(), &region_id)?;
+        credit_region(region.as_mut(), ctx.accounts.region.as_ref(), 1)?;
+        Ok(())
+    }
+
+    /// Freeze the game's key counters into a new `Snapshot` under `label`
+    /// (owner only). A label can only be used once.
+    pub fn take_snapshot(ctx: Context<TakeSnapshot>, label: String) -> Result<()> {
+        validate_snapshot_label(&label)?;
+        let game = &ctx.accounts.game_state;
+        let clock = Clock::get()?;
+
+        let snapshot = &mut ctx.accounts.snapshot;
+        snapshot.label = label;
+        snapshot.total_cubes_removed = game.total_cubes_removed;
+        snapshot.price_per_cube = game.base_price()?;
+        snapshot.treasury_balance = ctx.accounts.treasury.lamports();
+        snapshot.timestamp = clock.unix_timestamp;
+        snapshot.slot = clock.slot;
+        snapshot.bump = ctx.bumps.snapshot;
+        Ok(())
+    }
+
+    /// Emit the game's key counters as of `as_of` in an `AttestationEvent`.
+    /// Simulate it to get the payload for the attestor to sign. Sent with an
+    /// Ed25519 program instruction signing that payload just before it, the
+    /// event is marked as signed by `attestor`.
+    pub fn attest_stats(ctx: Context<AttestStats>, as_of: i64) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        require!(
+            as_of <= now && now - as_of <= MAX_ATTESTATION_AGE,
+            CubeGameError::StaleAttestation
+        );
+        let game = &ctx.accounts.game_state;
+        let payload = StatsAttestation {
+            game: game.key(),
+            total_cubes_removed: game.total_cubes_removed,
+            active_cube_records: game.active_cube_records,
+            price_per_cube: game.base_price()?,
+            price_epoch: game.price_epoch,
+            treasury_balance: ctx.accounts.treasury.lamports(),
+            as_of,
+        }
+        .try_to_vec()?;
+
+        let signed = match ctx.accounts.instructions.as_ref() {
+            Some(instructions) if game.attestor != Pubkey::default() => {
+                verify_ed25519_signature(instructions, &game.attestor, &payload, None)?;
+                true
+            }
+            _ => false,
+        };
+        emit!(AttestationEvent {
+            payload,
+            attestor: game.attestor,
+            signed,
+        });
+        Ok(())
+    }
+
+    /// Require removals to carry a ticket signed by `ticket_signer` (owner
+    /// only, the default key turns tickets off)
+    pub fn set_ticket_signer(ctx: Context<SetConfig>, ticket_signer: Pubkey) -> Result<()> {
+        ctx.accounts.game_state.ticket_signer = ticket_signer;
+        Ok(())
+    }
+
+    /// Set the key whose signatures `attest_stats` accepts (owner only,
+    /// the default key disables signing)
+    pub fn set_attestor(ctx: Context<SetConfig>, attestor: Pubkey) -> Result<()> {
+        ctx.accounts.game_state.attestor = attestor;
+        Ok(())
+    }
+
+    /// Grow a game state allocated before newer fields existed to the current
+    /// layout, the authority paying the extra rent (owner only). New fields
+    /// start zeroed.
+    pub fn migrate_game_state(ctx: Context<MigrateGameState>) -> Result<()> {
+        use anchor_lang::Discriminator;
+
+        let info = ctx.accounts.game_state.to_account_info();
+        require_keys_eq!(*info.owner, crate::ID, CubeGameError::GameStatePdaMismatch);
+        let authority = {
+            let data = info.try_borrow_data()?;
+            require!(
+                data.len() >= 40 && data[..8] == GameState::DISCRIMINATOR,
+                CubeGameError::GameStatePdaMismatch
+            );
+            Pubkey::try_from(&data[8..40]).map_err(|_| CubeGameError::GameStatePdaMismatch)?
+        };
+        require_keys_eq!(
+            authority,
+            ctx.accounts.authority.key(),
+            CubeGameError::Unauthorized
+        );
+
+        let old_len = info.data_len();
+        let new_len = 8 + GameState::INIT_SPACE;
+        if old_len >= new_len {
+            return Ok(());
+        }
+        let top_up = Rent::get()?
+            .minimum_balance(new_len)
+            .saturating_sub(info.lamports());
+        transfer_lamports(
+            &ctx.accounts.authority.to_account_info(),
+            &info,
+            &ctx.accounts.system_program.to_account_info(),
+            top_up,
+        )?;
+        info.realloc(new_len, true)?;
+
+        emit!(GameStateMigratedEvent {
+            old_len: old_len as u32,
+            new_len: new_len as u32,
+        });
+        Ok(())
+    }
+
+    /// Move an existing global cube record into `region_id`, closing the
+    /// global record (owner only)
+    pub fn migrate_cube_to_region(
+        ctx: Context<MigrateCubeToRegion>,
+        cube_id: String,
+        region_id: String,
+    ) -> Result<()> {
+        require!(
+            !region_id.is_empty() && region_id.len() <= MAX_REGION_LEN,
+            CubeGameError::InvalidRegion
+        );
+        let old = &ctx.accounts.global_record;
+        old.validate(&cube_id)?;
+
+        // Every field moves, payments, tips and reveals included
+        let removed = old.is_removed;
+        ctx.accounts.region_record.set_inner((**old).clone());
+        if removed {
+            let mut region = load_region(ctx.accounts.region.as_ref(), &region_id)?;
+            credit_region(region.as_mut(), ctx.accounts.region.as_ref(), 1)?;
+        }
+        Ok(())
+    }
+
+    /// Close a cube record that is not currently removed, freeing its slot
+    /// under `max_cube_records` (owner only)
+    pub fn close_cube_record(
+        ctx: Context<CloseCubeRecord>,
+        _cube_id: String,
+        _region_id: String,
+    ) -> Result<()> {
+        require!(
+            !ctx.accounts.cube_record.is_removed,
+            CubeGameError::CubeAlreadyRemoved
+        );
+        require!(
+            ctx.accounts.cube_record.tip_owed == 0,
+            CubeGameError::TipUnclaimed
+        );
+        ctx.accounts.game_state.note_record_closed();
+        Ok(())
+    }
+
+    /// Set the removal and spend thresholds for the Silver, Gold and
+    /// Platinum tiers (owner only). Both lists must be non-decreasing; a
+    /// tier with both thresholds 0 is disabled along with those above it.
+    pub fn set_tier_thresholds(
+        ctx: Context<SetConfig>,
+        cube_thresholds: [u64; 3],
+        spend_thresholds: [u64; 3],
+    ) -> Result<()> {
+        require!(
+            cube_thresholds.windows(2).all(|w| w[0] <= w[1])
+                && spend_thresholds.windows(2).all(|w| w[0] <= w[1]),
+            CubeGameError::InvalidTierThresholds
+        );
+        let game = &mut ctx.accounts.game_state;
+        game.tier_cube_thresholds = cube_thresholds;
+        game.tier_spend_thresholds = spend_thresholds;
+        Ok(())
+    }
+
+    /// Require removals to attest a priority fee of at least
+    /// `min_priority_fee` micro-lamports per compute unit (owner only, 0
+    /// disables). The attestation is client-supplied, see `RemovalArgs`.
+    pub fn set_min_priority_fee(ctx: Context<SetConfig>, min_priority_fee: u64) -> Result<()> {
+        ctx.accounts.game_state.min_priority_fee = min_priority_fee;
+        Ok(())
+    }
+
+    /// Require `hits` hits to remove a cube, each paying its share of the
+    /// price (owner only). Applies to cubes from their next first hit;
+    /// batch, delegated and crowdfunded removals are unavailable while it is
+    /// above 1.
+    pub fn set_hits_required(ctx: Context<SetConfig>, hits: u8) -> Result<()> {
+        // Every hitter's payment has to fit on the cube's record
+        require!(
+            hits as usize <= MAX_CUBE_CONTRIBUTORS,
+            CubeGameError::InvalidConfiguration
+        );
+        let game = &mut ctx.accounts.game_state;
+        require!(
+            hits <= 1 || game.max_removers <= 1,
+            CubeGameError::InvalidConfiguration
+        );
+        require!(
+            hits <= 1 || !game.ephemeral_records,
+            CubeGameError::EphemeralBoard
+        );
+        game.hits_required = hits;
+        Ok(())
+    }
+
+    /// Let `max_removers` different players each remove a cube at full
+    /// price, the cube only disappearing on the last one (owner only, 0 or 1
+    /// = one remover). Applies to cubes from their next first removal, and
+    /// cannot be combined with multi-hit cubes.
+    pub fn set_max_removers(ctx: Context<SetConfig>, max_removers: u8) -> Result<()> {
+        require!(
+            max_removers as usize <= MAX_SHARED_REMOVERS,
+            CubeGameError::TooManySharedRemovers
+        );
+        let game = &mut ctx.accounts.game_state;
+        require!(
+            max_removers <= 1 || game.hits_required <= 1,
+            CubeGameError::InvalidConfiguration
+        );
+        require!(
+            max_removers <= 1 || !game.ephemeral_records,
+            CubeGameError::EphemeralBoard
+        );
+        game.max_removers = max_removers;
+        Ok(())
+    }
+
+    /// Set the lamports `claim_daily` pays (owner only, 0 disables it)
+    pub fn set_daily_reward(ctx: Context<SetConfig>, reward: u64) -> Result<()> {
+        ctx.accounts.game_state.daily_reward = reward;
+        Ok(())
+    }
+
+    /// Set the scale clients apply to displayed player stats (owner only)
+    pub fn set_stats_display_multiplier(ctx: Context<SetConfig>, bps: u16) -> Result<()> {
+        ctx.accounts.game_state.stats_display_multiplier_bps = bps;
+        Ok(())
+    }
+
+    /// Mint `amount` of `reward_mint` to a player every `every` cubes they
+    /// remove (owner only). The mint's authority must already be
+    /// `reward_mint_authority_pda`. The default mint or 0 disables rewards.
+    pub fn set_milestone_reward(
+        ctx: Context<SetConfig>,
+        reward_mint: Pubkey,
+        amount: u64,
+        every: u64,
+    ) -> Result<()> {
+        let game = &mut ctx.accounts.game_state;
+        game.reward_mint = reward_mint;
+        game.milestone_reward_amount = amount;
+        game.milestone_every = every;
+        Ok(())
+    }
+
+    /// Accept `payment_mint` tokens, paid into `token_treasury`, for part of
+    /// a removal's price at `rate` lamports per base unit times
+    /// `SPLIT_RATE_SCALE` (owner only, the default mint disables it)
+    pub fn set_split_payment(
+        ctx: Context<SetConfig>,
+        payment_mint: Pubkey,
+        token_treasury: Pubkey,
+        rate: u64,
+    ) -> Result<()> {
+        let game = &mut ctx.accounts.game_state;
+        game.payment_mint = payment_mint;
+        game.token_treasury = token_treasury;
+        game.split_rate = rate;
+        Ok(())
+    }
+
+    /// Price `remove_cube_usd` at `usd_price`, converted with the Pyth
+    /// `price_feed` while its quote is at most `max_price_staleness` seconds
+    /// old (owner only, the default feed disables it)
+    pub fn set_usd_pricing(
+        ctx: Context<SetConfig>,
+        price_feed: Pubkey,
+        max_price_staleness: i64,
+        usd_price: u64,
+    ) -> Result<()> {
+        require!(
+            max_price_staleness > 0 || price_feed == Pubkey::default(),
+            CubeGameError::InvalidPriceStaleness
+        );
+        let game = &mut ctx.accounts.game_state;
+        game.price_feed = price_feed;
+        game.max_price_staleness = max_price_staleness;
+        game.usd_price = usd_price;
+        Ok(())
+    }
+
+    /// Burn `burn_bps` of the tokens in every split payment instead of
+    /// banking them in the token treasury (owner only, 0 disables)
+    pub fn set_burn_bps(ctx: Context<SetConfig>, burn_bps: u16) -> Result<()> {
+        require!(
+            burn_bps as u64 <= BPS_DENOMINATOR,
+            CubeGameError::InvalidBps
+        );
+        ctx.accounts.game_state.burn_bps = burn_bps;
+        Ok(())
+    }
+
+    /// Choose what a removal does with a full inventory: drop the oldest
+    /// item (`ring`) or fail with `InventoryFull` (owner only)
+    pub fn set_inventory_policy(ctx: Context<SetConfig>, ring: bool) -> Result<()> {
+        ctx.accounts.game_state.inventory_ring = ring;
+        Ok(())
+    }
+
+    /// Accrue `bps` of every referred single removal's price to the
+    /// referrer, claimable from the treasury (owner only)
+    pub fn set_referral_bps(ctx: Context<SetConfig>, bps: u16) -> Result<()> {
+        require!(bps as u64 <= BPS_DENOMINATOR, CubeGameError::InvalidBps);
+        ctx.accounts.game_state.referral_bps = bps;
+        Ok(())
+    }
+
+    /// Give the front of each cube's removal queue `turn_seconds` to remove
+    /// it, charging `deposit` lamports to join a queue (owner only, 0 turns
+    /// queueing off)
+    pub fn set_queue_turn(ctx: Context<SetConfig>, turn_seconds: i64, deposit: u64) -> Result<()> {
+        require!(turn_seconds >= 0, CubeGameError::InvalidDuration);
+        let game = &mut ctx.accounts.game_state;
+        game.queue_turn_seconds = turn_seconds;
+        game.queue_deposit = deposit;
+        Ok(())
+    }
+
+    /// Allow at most one removal per `interval_seconds` across the whole
+    /// board (owner only, 0 disables pacing)
+    pub fn set_global_pace(ctx: Context<SetConfig>, interval_seconds: i64) -> Result<()> {
+        require!(interval_seconds >= 0, CubeGameError::InvalidDuration);
+        ctx.accounts.game_state.min_global_interval_seconds = interval_seconds;
+        Ok(())
+    }
+
+    /// Cap removals per slot across all players (owner only, 0 removes the
+    /// cap)
+    pub fn set_slot_throttle(ctx: Context<SetConfig>, max_removals_per_slot: u32) -> Result<()> {
+        ctx.accounts.game_state.max_removals_per_slot = max_removals_per_slot;
+        Ok(())
+    }
+
+    /// Cap how many cube records may exist at once (owner only, 0 removes
+    /// the cap)
+    pub fn set_max_cube_records(ctx: Context<SetConfig>, max_records: u64) -> Result<()> {
+        ctx.accounts.game_state.max_cube_records = max_records;
+        Ok(())
+    }
+
+    /// Commit the completion seed of a cleared board: the hash of the first
+    /// slot after the completing removal (anyone). Once that hash has left
+    /// the SlotHashes window the commitment moves to the current slot, to be
+    /// committed by a later call.
+    pub fn commit_completion_seed(ctx: Context<CommitCompletionSeed>) -> Result<()> {
+        use anchor_lang::solana_program::slot_hashes;
+
+        let game = &mut ctx.accounts.game_state;
+        require!(game.completion_slot > 0, CubeGameError::BoardNotComplete);
+        require!(
+            game.completion_seed == [0; 32],
+            CubeGameError::CompletionSeedCommitted
+        );
+
+        let slot = Clock::get()?.slot;
+        let data = ctx.accounts.slot_hashes.try_borrow_data()?;
+        match slot_hash_after(&data, game.completion_slot) {
+            Some(hash) => game.completion_seed = hash,
+            None => {
+                require!(
+                    slot.saturating_sub(game.completion_slot) > slot_hashes::MAX_ENTRIES as u64,
+                    CubeGameError::SlotHashUnavailable
+                );
+                game.completion_slot = slot;
+            }
+        }
+        Ok(())
+    }
+
+    /// Draw the grand-prize winner of a completed board and pay them (owner
+    /// only)
+    ///
+    /// `remaining_accounts` holds participants' `PlayerStats`, sorted by
+    /// address and continuing after those counted by earlier calls. Each is
+    /// weighted by `cubes_removed` and the ticket comes from the committed
+    /// completion seed, so anyone can recompute the result. The prize is
+    /// paid to `winner` once the counted weight reaches `entrant_weight`,
+    /// which proves no player was left out.
+    pub fn finalize_game<'info>(
+        ctx: Context<'_, '_, 'info, 'info, FinalizeGame<'info>>,
+    ) -> Result<()> {
+        let game = &mut ctx.accounts.game_state;
+        require!(game.board_cleared(), CubeGameError::BoardNotComplete);
+        require!(game.winner.is_none(), CubeGameError::GameAlreadyFinalized);
+        require!(
+            game.completion_seed != [0; 32],
+            CubeGameError::CompletionSeedRequired
+        );
+        require!(game.entrant_weight > 0, CubeGameError::NoEntrants);
+        require!(
+            ctx.remaining_accounts.len() <= MAX_FINALIZE_PLAYERS,
+            CubeGameError::TooManyAccounts
+        );
+
+        let mut entrants = Vec::with_capacity(ctx.remaining_accounts.len());
+        let mut batch_weight = 0u64;
+        for info in ctx.remaining_accounts {
+            // Strictly ascending addresses, across calls too, rule out
+            // counting a player twice
+            require!(
+                game.finalize_cursor < info.key(),
+                CubeGameError::UnsortedAccounts
+            );
+            game.finalize_cursor = info.key();
+
+            let stats = Account::<PlayerStats>::try_from(info)?;
+            batch_weight = batch_weight
+                .checked_add(stats.cubes_removed)
+                .ok_or(CubeGameError::MathOverflow)?;
+            entrants.push((stats.player, stats.cubes_removed));
+        }
+
+        let ticket = draw_ticket(&game.completion_seed, game.entrant_weight);
+        if game.draw_winner == Pubkey::default() {
+            if let Some(player) = pick_entrant(&entrants, game.finalize_weight, ticket) {
+                game.draw_winner = player;
+            }
+        }
+        game.finalize_weight = game
+            .finalize_weight
+            .checked_add(batch_weight)
+            .ok_or(CubeGameError::MathOverflow)?;
+        require!(
+            game.finalize_weight <= game.entrant_weight,
+            CubeGameError::EntrantWeightMismatch
+        );
+        if game.finalize_weight < game.entrant_weight {
+            return Ok(());
+        }
+
+        let winner = game.draw_winner;
+        let winner_info = ctx
+            .accounts
+            .winner
+            .as_ref()
+            .ok_or(CubeGameError::WinnerMismatch)?;
+        require_keys_eq!(winner_info.key(), winner, CubeGameError::WinnerMismatch);
+
+        let prize = game.grand_prize;
+        let treasury = &ctx.accounts.treasury;
+        require_treasury_covers(game, treasury, prize)?;
+        pay_reward(
+            game,
+            treasury,
+            &winner_info.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+            ctx.accounts.ledger.as_deref_mut(),
+            prize,
+        )?;
+
+        game.winner = Some(winner);
+        emit!(WinnerSelectedEvent {
+            winner,
+            prize,
+            total_weight: game.entrant_weight,
+        });
+        Ok(())
+    }
+
+    /// Pay the player `daily_reward` lamports from the treasury, once per
+    /// UTC day. Only unfrozen players old enough for `min_player_age` who
+    /// have removed at least one cube may claim.
+    pub fn claim_daily(ctx: Context<ClaimDaily>) -> Result<()> {
+        let game = &ctx.accounts.game_state;
+        let reward = game.daily_reward;
+        require!(reward > 0, CubeGameError::DailyRewardDisabled);
+
+        let now = Clock::get()?.unix_timestamp;
+        let stats = &mut ctx.accounts.player_stats;
+        stats.claim_daily(game, now)?;
+
+        let treasury = &ctx.accounts.treasury;
+        require_treasury_covers(game, treasury, reward)?;
+        pay_reward(
+            game,
+            treasury,
+            &ctx.accounts.player.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+            ctx.accounts.ledger.as_deref_mut(),
+            reward,
+        )?;
+
+        emit!(DailyRewardClaimedEvent {
+            player: if stats.private {
+                Pubkey::default()
+            } else {
+                stats.player
+            },
+            reward,
+        });
+        Ok(())
+    }
+
+    /// Open a community goal of `target` removals before `ends_at` (0 = no
+    /// deadline), paying each participant `reward` lamports from the treasury
+    /// once it is reached (owner only). Only paid removals enroll a player.
+    /// A new goal replaces the last one, so it may only start once that goal
+    /// was reached or has run out.
+    pub fn start_community_goal(
+        ctx: Context<StartCommunityGoal>,
+        target: u64,
+        ends_at: i64,
+        reward: u64,
+    ) -> Result<()> {
+        require!(target > 0, CubeGameError::InvalidConfiguration);
+        let now = Clock::get()?.unix_timestamp;
+        let game = &mut ctx.accounts.game_state;
+        let goal = &mut ctx.accounts.community_goal;
+        require!(
+            !game.community_goal_open || !goal.is_running(now),
+            CubeGameError::GoalInProgress
+        );
+
+        goal.goal_id += 1;
+        goal.target = target;
+        goal.progress = 0;
+        goal.participants = 0;
+        goal.claimed = 0;
+        goal.ends_at = ends_at;
+        goal.reward = reward;
+        goal.reached = false;
+        goal.bump = ctx.bumps.community_goal;
+        game.community_goal_open = true;
+        Ok(())
+    }
+
+    /// Pay a participant of the reached community goal their reward, once.
+    /// The treasury must hold every unclaimed reward, so early claimers
+    /// cannot leave later ones unpaid.
+    pub fn claim_community_reward(ctx: Context<ClaimCommunityReward>) -> Result<()> {
+        let goal = &mut ctx.accounts.community_goal;
+        let treasury = &ctx.accounts.treasury;
+        require_treasury_covers(
+            &ctx.accounts.game_state,
+            treasury,
+            goal.outstanding_rewards()?,
+        )?;
+        goal.claim(&mut ctx.accounts.player_stats)?;
+        pay_reward(
+            &ctx.accounts.game_state,
+            treasury,
+            &ctx.accounts.player.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+            ctx.accounts.ledger.as_deref_mut(),
+            goal.reward,
+        )?;
+
+        emit!(CommunityRewardClaimedEvent {
+            goal_id: goal.goal_id,
+            player: ctx.accounts.player.key(),
+            reward: goal.reward,
+        });
+        Ok(())
+    }
+
+    /// Withdraw funds (withdraw authority only)
+    pub fn withdraw(ctx: Context<Withdraw>, amount: u64) -> Result<()> {
+        require!(
+            ctx.accounts.game_state.withdraw_delay_slots == 0,
+            CubeGameError::WithdrawTimelockRequired
+        );
+        let now = Clock::get()?.unix_timestamp;
+        ctx.accounts.game_state.require_withdraw_cooled(now)?;
+        ctx.accounts.game_state.verify_pda_bumps()?;
+        ctx.accounts.game_state.require_withdraw_threshold()?;
+        // Every check runs before any lamports move, so an over-withdrawal
+        // fails cleanly and leaves both balances untouched
+        let treasury = &ctx.accounts.treasury;
+        let spendable = treasury_spendable(treasury)?;
+        ctx.accounts
+            .game_state
+            .authorize_withdrawal(amount, spendable, now)?;
+        require!(
+            !ctx.accounts.game_state.ledger_enabled || ctx.accounts.ledger.is_some(),
+            CubeGameError::LedgerRequired
+        );
+
+        transfer_from_treasury(
+            treasury,
+            &ctx.accounts.authority.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+            ctx.accounts.game_state.treasury_bump,
+            amount,
+        )?;
+        if let Some(ledger) = ctx.accounts.ledger.as_mut() {
+            ledger.record_withdrawal(amount)?;
+        }
+
+        refill_hot_treasury(
+            &ctx.accounts.game_state,
+            treasury,
+            ctx.accounts.cold_reserve.as_mut(),
+            ctx.accounts.ledger.as_deref_mut(),
+        )
+    }
+
+    /// Shut the game down for good and let every remover reclaim what they
+    /// paid with `claim_full_refund` (owner only). Removals stop, and
+    /// withdrawals may not dip into the outstanding `paid_liability`.
+    pub fn enable_refund_all_mode(ctx: Context<SetConfig>) -> Result<()> {
+        let game = &mut ctx.accounts.game_state;
+        game.refund_all_mode = true;
+        emit!(RefundAllModeEvent {
+            paid_liability: game.paid_liability,
+        });
+        Ok(())
+    }
+
+    /// Reclaim what the caller paid toward a cube, once refund-all mode is on.
+    /// The refund comes from the refund pool, topped up from the treasury,
+    /// and the cube's record is cleared once its last payer has reclaimed.
+    pub fn claim_full_refund(
+        ctx: Context<ClaimFullRefund>,
+        cube_id: String,
+        _region_id: String,
+    ) -> Result<()> {
+        let game = &mut ctx.accounts.game_state;
+        require!(game.refund_all_mode, CubeGameError::RefundModeInactive);
+        require!(
+            !game.analytics_enabled || ctx.accounts.analytics.is_some(),
+            CubeGameError::AnalyticsRequired
+        );
+        require!(
+            !game.ledger_enabled || ctx.accounts.ledger.is_some(),
+            CubeGameError::LedgerRequired
+        );
+        let record = &mut ctx.accounts.cube_record;
+        record.validate(&cube_id)?;
+        let position = record
+            .payments
+            .iter()
+            .position(|p| p.contributor == ctx.accounts.player.key())
+            .ok_or(CubeGameError::NotCubeOwner)?;
+        let amount = record.payments.remove(position).amount;
+        require!(amount > 0, CubeGameError::NothingToRefund);
+        game.release_paid(amount);
+
+        if record.payments.is_empty() {
+            if record.is_removed {
+                game.total_cubes_removed = game.total_cubes_removed.saturating_sub(1);
+            }
+            record.is_removed = false;
+            record.removed_by = Pubkey::default();
+            record.original_remover = Pubkey::default();
+            record.price_paid = 0;
+            record.hits_taken = 0;
+            record.remover_count = 0;
+            record.memo.clear();
+        }
+
+        pay_refund(
+            game.treasury_bump,
+            ctx.accounts.refund_pool.as_mut(),
+            ctx.accounts.ledger.as_deref_mut(),
+            &ctx.accounts.treasury,
+            &ctx.accounts.player.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+            amount,
+        )?;
+        if let Some(analytics) = ctx.accounts.analytics.as_mut() {
+            analytics.record_refund(amount)?;
+        }
+
+        emit!(FullRefundEvent {
+            cube_id,
+            player: ctx.accounts.player.key(),
+            amount,
+            paid_liability: game.paid_liability,
+        });
+        Ok(())
+    }
+
+    /// Block withdrawals until `min_cubes` cubes have been removed (owner
+    /// only). The threshold can only be raised, so players can rely on it.
+    pub fn set_min_cubes_before_withdraw(ctx: Context<SetConfig>, min_cubes: u64) -> Result<()> {
+        let game = &mut ctx.accounts.game_state;
+        require!(
+            min_cubes >= game.min_cubes_before_withdraw,
+            CubeGameError::InvalidConfiguration
+        );
+        game.min_cubes_before_withdraw = min_cubes;
+        Ok(())
+    }
+
+    /// Block withdrawals for `cooldown_seconds` after each deposit (owner
+    /// only, 0 disables). The cooldown can only be raised.
+    pub fn set_withdraw_cooldown(ctx: Context<SetConfig>, cooldown_seconds: i64) -> Result<()> {
+        require!(cooldown_seconds >= 0, CubeGameError::InvalidDuration);
+        let game = &mut ctx.accounts.game_state;
+        require!(
+            cooldown_seconds >= game.withdraw_cooldown_seconds,
+            CubeGameError::InvalidConfiguration
+        );
+        game.withdraw_cooldown_seconds = cooldown_seconds;
+        Ok(())
+    }
+
+    /// Require withdrawals to wait `delay_slots` after being proposed
+    /// (owner only, 0 allows immediate `withdraw`). The delay can only be
+    /// raised, so it cannot be lifted right before a withdrawal.
+    pub fn set_withdraw_delay(ctx: Context<SetConfig>, delay_slots: u64) -> Result<()> {
+        let game = &mut ctx.accounts.game_state;
+        require!(
+            delay_slots >= game.withdraw_delay_slots,
+            CubeGameError::InvalidConfiguration
+        );
+        game.withdraw_delay_slots = delay_slots;
+        Ok(())
+    }
+
+    /// Cap withdrawals at `limit` lamports per `window_seconds` (owner only,
+    /// a limit of 0 removes the cap). Once set, the cap can only be
+    /// tightened: a lower limit over a window at least as long. The current
+    /// window's withdrawals still count against the new cap.
+    pub fn set_withdraw_limit(
+        ctx: Context<SetConfig>,
+        limit: u64,
+        window_seconds: i64,
+    ) -> Result<()> {
+        require!(
+            limit == 0 || window_seconds > 0,
+            CubeGameError::InvalidDuration
+        );
+        let game = &mut ctx.accounts.game_state;
+        if game.withdraw_limit_per_window == 0 {
+            game.window_withdrawn = 0;
+            game.window_start = 0;
+        } else {
+            require!(
+                limit > 0
+                    && limit <= game.withdraw_limit_per_window
+                    && window_seconds >= game.withdraw_window_seconds,
+                CubeGameError::InvalidConfiguration
+            );
+        }
+        game.withdraw_limit_per_window = limit;
+        game.withdraw_window_seconds = window_seconds;
+        Ok(())
+    }
+
+    /// Announce a withdrawal that becomes executable after the delay
+    /// (withdraw authority only)
+    pub fn propose_withdraw(ctx: Context<ProposeWithdraw>, amount: u64) -> Result<()> {
+        let eligible_slot = Clock::get()?
+            .slot
+            .checked_add(ctx.accounts.game_state.withdraw_delay_slots)
+            .ok_or(CubeGameError::MathOverflow)?;
+
+        let timelock = &mut ctx.accounts.withdraw_timelock;
+        timelock.amount = amount;
+        timelock.eligible_slot = eligible_slot;
+
+        emit!(WithdrawProposedEvent {
+            amount,
+            eligible_slot,
+        });
+        Ok(())
+    }
+
+    /// Execute a proposed withdrawal once its delay has passed (withdraw
+    /// authority only)
+    pub fn execute_withdraw(ctx: Context<ExecuteWithdraw>) -> Result<()> {
+        let timelock = &ctx.accounts.withdraw_timelock;
+        let clock = Clock::get()?;
+        require!(
+            clock.slot >= timelock.eligible_slot,
+            CubeGameError::WithdrawTimelockActive
+        );
+        ctx.accounts
+            .game_state
+            .require_withdraw_cooled(clock.unix_timestamp)?;
+        ctx.accounts.game_state.verify_pda_bumps()?;
+        ctx.accounts.game_state.require_withdraw_threshold()?;
+        let spendable = treasury_spendable(&ctx.accounts.treasury)?;
+        require!(
+            timelock.amount <= spendable,
+            CubeGameError::InsufficientTreasuryBalance
+        );
+        ctx.accounts
+            .game_state
+            .require_liabilities_covered(timelock.amount, spendable)?;
+        ctx.accounts
+            .game_state
+            .consume_withdraw_allowance(timelock.amount, clock.unix_timestamp)?;
+        require!(
+            !ctx.accounts.game_state.ledger_enabled || ctx.accounts.ledger.is_some(),
+            CubeGameError::LedgerRequired
+        );
+
+        transfer_from_treasury(
+            &ctx.accounts.treasury,
+            &ctx.accounts.authority.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+            ctx.accounts.game_state.treasury_bump,
+            timelock.amount,
+        )?;
+        if let Some(ledger) = ctx.accounts.ledger.as_mut() {
+            ledger.record_withdrawal(timelock.amount)?;
+        }
+
+        refill_hot_treasury(
+            &ctx.accounts.game_state,
+            &ctx.accounts.treasury,
+            ctx.accounts.cold_reserve.as_mut(),
+            ctx.accounts.ledger.as_deref_mut(),
+        )
+    }
+}
+
+/// How a `remove_cube` variant differs from the plain flow
+#[derive(Default)]
+struct RemovalFlags {
+    /// The caller proved they hold an NFT from the required collection
+    holds_required_nft: bool,
+    /// Emit `CubeRemovedCompact` instead of `CubeRemovedEvent`
+    compact_event: bool,
+    /// Lamports and payment-mint tokens offered by `remove_cube_split`
+    split_payment: Option<(u64, u64)>,
+    /// Most payment-mint tokens `remove_cube_usd` may charge
+    usd_payment: Option<u64>,
+    /// The caller presented a valid ticket from `ticket_signer`
+    ticketed: bool,
+    /// The caller revealed an aged `RemovalCommitment` for this cube
+    revealed: bool,
+    /// The caller proved they hold a membership NFT
+    member: bool,
+}
+
+/// Shared removal flow behind `remove_cube` and its variants
+fn process_removal<'info>(
+    ctx: Context<'_, '_, 'info, 'info, RemoveCube<'info>>,
+    cube_id: String,
+    args: RemovalArgs,
+    flags: RemovalFlags,
+) -> Result<()> {
+    let RemovalArgs {
+        memo,
+        nonce,
+        board_proof,
+        region_id,
+        priority_fee,
+        expected_price_epoch,
+        deadline,
+        action_nonce,
+        message,
+        expected_total,
+    } = args;
+
+    // A retry of the player's last action already applied; succeed quietly
+    // instead of charging again
+    if action_nonce != 0 {
+        let player_stats = &mut ctx.accounts.player_stats;
+        if player_stats.is_replay(&cube_id, action_nonce) {
+            return Ok(());
+        }
+        player_stats.last_action = action_key(&cube_id, action_nonce);
+    }
+    let game = &mut ctx.accounts.game_state;
+    let cube_record = &mut ctx.accounts.cube_record;
+    let player = &ctx.accounts.player;
+    let now = Clock::get()?.unix_timestamp;
+    require_before_deadline(deadline, now)?;
+
+    game.validate_cube_id(&cube_id)?;
+    validate_memo(&memo)?;
+    if !message.is_empty() {
+        validate_message(&message)?;
+        require!(
+            ctx.accounts.message_wall.is_some(),
+            CubeGameError::MessageWallRequired
+        );
+    }
+    game.require_priority_fee(priority_fee)?;
+    game.require_price_epoch(expected_price_epoch)?;
+    game.require_expected_total(expected_total)?;
+    enforce_tx_removal_limit(game, ctx.accounts.instructions.as_ref())?;
+    enforce_cpi_allowlist(game, ctx.accounts.instructions.as_ref())?;
+    game.verify_proof_of_work(&cube_id, &player.key(), nonce)?;
+    game.require_open_and_extend(now)?;
+    game.require_board_open()?;
+    game.throttle_slot()?;
+
+    if game.required_collection.is_some() {
+        require!(flags.holds_required_nft, CubeGameError::MissingRequiredNft);
+    }
+    if game.ticket_signer != Pubkey::default() {
+        require!(flags.ticketed, CubeGameError::TicketRequired);
+    }
+    if game.min_reveal_delay_slots > 0 {
+        require!(flags.revealed, CubeGameError::CommitRequired);
+    }
+
+    // init_if_needed hands us either a zeroed record or an existing one
+    game.materialize(cube_record, &cube_id, &board_proof)?;
+
+    // Sequenced cubes wait for their predecessor in the same region
+    cube_record.require_predecessor_removed(
+        &region_id,
+        ctx.accounts
+            .predecessor
+            .as_ref()
+            .map(|predecessor| (predecessor.key(), &***predecessor)),
+    )?;
+
+    ctx.accounts.player_stats.require_can_remove(game, now)?;
+    require_zone_open(game, ctx.accounts.zone.as_deref(), &cube_id)?;
+    require!(
+        !game.analytics_enabled || ctx.accounts.analytics.is_some(),
+        CubeGameError::AnalyticsRequired
+    );
+    require!(
+        !game.ledger_enabled || ctx.accounts.ledger.is_some(),
+        CubeGameError::LedgerRequired
+    );
+
+    // Contended global-board cubes go to the front of their queue
+    if game.queue_turn_seconds > 0 && region_id.is_empty() {
+        let queue = ctx
+            .accounts
+            .removal_queue
+            .as_ref()
+            .ok_or(CubeGameError::QueueRequired)?;
+        take_queue_turn(
+            queue,
+            &player.to_account_info(),
+            now,
+            game.queue_turn_seconds,
+        )?;
+    }
+
+    // Regions with their own curve replace the board's base price
+    let (mut region, region_price) =
+        load_priced_region(game, ctx.accounts.region.as_ref(), &region_id)?;
+    let mut price = cube_record.removal_price(
+        game,
+        region_price,
+        ctx.accounts.fee_exemption.is_some(),
+        now,
+    )?;
+    price = game.member_price(price, flags.member)?;
+
+    // High-value cubes need an intent recorded in an earlier slot
+    if game.needs_confirmation(price) {
+        let intent = ctx
+            .accounts
+            .removal_intent
+            .as_ref()
+            .ok_or(CubeGameError::ConfirmationRequired)?;
+        intent.require_confirmed(Clock::get()?.slot)?;
+    }
+
+    // An active subscription covers the removal while its quota lasts;
+    // an expired or used-up one falls back to paying
+    if price > 0 {
+        if let Some(subscription) = ctx.accounts.subscription.as_mut() {
+            if subscription.use_removal(now) {
+                price = 0;
+            }
+        }
+    }
+
+    // A prepaid credit covers the removal up to the price it was bought
+    // at, and the player pays any surge or curve above that
+    if price > 0 {
+        if let Some(credits) = ctx.accounts.credits.as_mut() {
+            if credits.balance > 0 {
+                price = credits.spend(price)?;
+            }
+        }
+    }
+
+    // A live price lock caps the price at the locked one
+    if let Some(voucher) = ctx.accounts.voucher.as_mut() {
+        price = voucher.apply(price, now);
+    }
+
+    // Multi-hit cubes split their price across the hits, the final hit
+    // paying any rounding remainder
+    let (hit_price, final_hit) = cube_record.hit_price(game.hits_required, price);
+    price = hit_price;
+
+    // Shared cubes take one full-price removal from each of several players
+    if cube_record.remover_count == 0 {
+        cube_record.max_removers = game.max_removers.max(1);
+    }
+    let shared = cube_record.max_removers > 1;
+    if shared {
+        require!(
+            !cube_record
+                .payments
+                .iter()
+                .any(|p| p.contributor == player.key()),
+            CubeGameError::AlreadySharedRemover
+        );
+    }
+    let final_removal = final_hit && cube_record.remover_count + 1 >= cube_record.max_removers;
+
+    // Respect the player's self-imposed spend cap
+    let player_stats = &mut ctx.accounts.player_stats;
+    player_stats.apply_pending_spend_cap(now);
+    let new_player = player_stats.register(
+        game,
+        ctx.accounts
+            .wallet_mark
+            .as_deref_mut()
+            .map(|mark| &mut **mark),
+    )?;
+    require!(
+        player_stats.spend_cap == 0
+            || player_stats.total_spent.saturating_add(price) <= player_stats.spend_cap,
+        CubeGameError::SpendCapReached
+    );
+
+    if shared {
+        cube_record.remover_count += 1;
+    } else {
+        cube_record.hits_taken += 1;
+    }
+    if !final_removal {
+        cube_record.cube_id = cube_id.clone();
+        credit_share(&mut cube_record.payments, player.key(), price)?;
+        game.note_paid(price);
+        player_stats.total_spent += price;
+        player_stats.player = player.key();
+        let public_player = if player_stats.private {
+            Pubkey::default()
+        } else {
+            player.key()
+        };
+        if shared {
+            emit!(SharedRemovalEvent {
+                cube_id,
+                player: public_player,
+                remover_count: cube_record.remover_count,
+                max_removers: cube_record.max_removers,
+            });
+        } else {
+            emit!(CubeHitEvent {
+                cube_id,
+                player: public_player,
+                hits_taken: cube_record.hits_taken,
+                hits_required: cube_record.hits_required,
+            });
+        }
+        collect_payment(
+            game,
+            PaymentRoute {
+                payer: &player.to_account_info(),
+                treasury: &ctx.accounts.treasury,
+                system_program: &ctx.accounts.system_program.to_account_info(),
+                refund_pool: ctx.accounts.refund_pool.as_deref_mut(),
+                insurance_vault: ctx.accounts.insurance_vault.as_deref_mut(),
+                incinerator: ctx.accounts.incinerator.as_deref(),
+                dao_treasury: ctx.accounts.dao_treasury.as_deref(),
+                ledger: ctx.accounts.ledger.as_deref_mut(),
+            },
+            price,
+        )?;
+        game.note_deposit(price, ctx.accounts.treasury.lamports(), now);
+        if let Some(analytics) = ctx.accounts.analytics.as_mut() {
+            analytics.record_removal(price, price, new_player)?;
+        }
+        return Ok(());
+    }
+    game.require_global_pace(now)?;
+
+    // A split payment settles the price partly in tokens. Only the tokens
+    // and SOL the price needs are taken; SOL offered beyond that counts as
+    // dust left with the player. A USD payment settles it wholly in tokens
+    // at the oracle's quote.
+    let split = match (flags.split_payment, flags.usd_payment) {
+        (Some((sol_amount, token_amount)), _) => {
+            Some(game.settle_split(price, sol_amount, token_amount)?)
+        }
+        (None, Some(max_tokens)) => {
+            let tokens = if price == 0 {
+                0
+            } else {
+                let feed = ctx
+                    .accounts
+                    .price_feed
+                    .as_ref()
+                    .ok_or(CubeGameError::PriceFeedRequired)?;
+                let mint = ctx
+                    .accounts
+                    .payment_mint
+                    .as_deref()
+                    .ok_or(CubeGameError::SplitAccountsRequired)?;
+                OraclePrice::from_pyth(&feed.try_borrow_data()?)?.tokens_for_usd(
+                    game.usd_price,
+                    mint.decimals,
+                    now,
+                    game.max_price_staleness,
+                )?
+            };
+            require!(tokens <= max_tokens, CubeGameError::UsdTokensExceeded);
+            Some(SplitSettlement {
+                tokens,
+                sol_due: 0,
+                dust: 0,
+            })
+        }
+        (None, None) => None,
+    };
+    let (sol_due, mut dust) = split.map_or((price, 0), |split| (split.sol_due, split.dust));
+
+    // Tip whoever removed the previous cube. The tip waits in the treasury,
+    // kept on this record until they claim it; a record still holding an
+    // earlier unclaimed tip takes no new one.
+    let previous_remover = game.last_remover;
+    let previous_private = game.last_remover_private;
+    let tip = if cube_record.tip_owed == 0 {
+        game.tip_for(&player.key(), sol_due)?
+    } else {
+        0
+    };
+    game.last_remover = player.key();
+    game.last_remover_private = player_stats.private;
+
+    // Stakers earn their share only while something is staked
+    let staker_reward = match ctx.accounts.staking_pool.as_ref() {
+        Some(pool) if pool.total_staked > 0 => apply_bps(sol_due, game.staker_reward_bps)?,
+        Some(_) => 0,
+        None => {
+            require!(
+                game.staker_reward_bps == 0,
+                CubeGameError::StakingPoolRequired
+            );
+            0
+        }
+    };
+    // The cube's creator takes a royalty ahead of the treasury
+    let (royalty, to_treasury) =
+        royalty_split(sol_due, cube_record.royalty_bps, tip + staker_reward)?;
+    if royalty > 0 {
+        require!(
+            ctx.accounts.creator.is_some(),
+            CubeGameError::CreatorRequired
+        );
+    }
+
+    // Every check and state write happens before lamports move, so the
+    // transfers below are the last steps that can fail
+    let is_fresh = cube_record.is_fresh();
+
+    // Mark cube as removed
+    cube_record.is_removed = true;
+    cube_record.removed_by = player.key();
+    cube_record.original_remover = player.key();
+    cube_record.stamp_removed_at(now);
+    cube_record.cube_id = cube_id.clone();
+    cube_record.price_paid = price;
+    // Only what the treasury keeps is refundable
+    credit_share(&mut cube_record.payments, player.key(), to_treasury)?;
+    game.note_paid(to_treasury);
+    if tip > 0 {
+        cube_record.tip_to = previous_remover;
+        cube_record.tip_owed = tip;
+    }
+    cube_record.priority_fee = priority_fee;
+    cube_record.memo = memo.clone();
+    cube_record.color = cube_record.removal_color(game, &cube_id, 0);
+    let ranked = cube_record.ranked_score(&cube_id);
+
+    // Update game stats
+    game.count_removal()?;
+    game.note_last_removal(&cube_id, &region_id, cube_record.removed_at);
+    credit_region(region.as_mut(), ctx.accounts.region.as_ref(), 1)?;
+    credit_zone(
+        game,
+        ctx.accounts.zone.as_deref_mut(),
+        ctx.accounts.next_zone.as_deref_mut(),
+    )?;
+
+    // Update player stats
+    let cubes_before = player_stats.cubes_removed;
+    player_stats.cubes_removed += 1;
+    game.entrant_weight += 1;
+    player_stats.score = player_stats.score.saturating_add(ranked);
+    player_stats.total_spent += price;
+    player_stats.player = player.key();
+    player_stats.last_removal_at = now;
+
+    if let Some(inventory) = ctx.accounts.inventory.as_mut() {
+        inventory.add(&cube_id, game.inventory_ring)?;
+    }
+
+    // Accrue the referrer's cut, paid out when they claim
+    if let Some(referrer_stats) = ctx.accounts.referrer_stats.as_mut() {
+        referrer_stats.accrue_referral(price, game.referral_bps)?;
+    }
+
+    // Credit the player's team, if any
+    if !player_stats.team_id.is_empty() {
+        let team = ctx
+            .accounts
+            .team
+            .as_mut()
+            .ok_or(CubeGameError::TeamAccountRequired)?;
+        team.total_cubes_removed += 1;
+    }
+
+    count_toward_goal(
+        game,
+        ctx.accounts.community_goal.as_deref_mut(),
+        player_stats,
+        price,
+        now,
+    )?;
+
+    // Reward clearing a cube next to recently removed ones
+    let mut combo_bonus = 0;
+    if game.combo_min_neighbors > 0 {
+        let neighbors =
+            count_recent_neighbors(game, &region_id, &cube_id, ctx.remaining_accounts, now)?;
+        if neighbors >= game.combo_min_neighbors as usize {
+            player_stats.combos += 1;
+            emit!(ComboEvent {
+                cube_id: cube_id.clone(),
+                player: if player_stats.private {
+                    Pubkey::default()
+                } else {
+                    player.key()
+                },
+                neighbors: neighbors as u8,
+            });
+
+            combo_bonus = game.combo_bonus;
+        }
+    }
+
+    // Rapid sequential removals build a timed combo, a gap resets it
+    if game.combo_timer_seconds > 0 {
+        let combo_count = player_stats.extend_combo(game.combo_timer_seconds, now);
+        let bonus = game.combo_timer_bonus(combo_count);
+        if bonus > 0 {
+            combo_bonus += bonus;
+            emit!(ComboTimerEvent {
+                player: if player_stats.private {
+                    Pubkey::default()
+                } else {
+                    player.key()
+                },
+                combo_count,
+                bonus,
+            });
+        }
+    }
+
+    // A bomb clears its neighbours for free, each flip counting as a removal
+    // for the player, region and team
+    let mut bomb_flips = Vec::new();
+    if game.bombs_armed(&region_id) && is_bomb(&cube_id, game.bomb_rate_bps) {
+        bomb_flips = detonate_bomb(
+            game,
+            &region_id,
+            &cube_id,
+            ctx.remaining_accounts,
+            &player.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+            now,
+        )?;
+        // Flips are not priced, so they stay out of the analytics average
+        let flipped = bomb_flips.len() as u64;
+        player_stats.cubes_removed += flipped;
+        game.entrant_weight += flipped;
+        for flip in &bomb_flips {
+            player_stats.score = player_stats.score.saturating_add(flip.score);
+        }
+        credit_region(region.as_mut(), ctx.accounts.region.as_ref(), flipped)?;
+        if let Some(team) = ctx
+            .accounts
+            .team
+            .as_mut()
+            .filter(|_| !player_stats.team_id.is_empty())
+        {
+            team.total_cubes_removed += flipped;
+        }
+        player_stats.bombs_triggered += 1;
+        emit!(BombEvent {
+            cube_id: cube_id.clone(),
+            player: if player_stats.private {
+                Pubkey::default()
+            } else {
+                player.key()
+            },
+            flipped,
+        });
+    }
+
+    // Private players are masked in logs, the record keeps the real key
+    let public_player = if player_stats.private {
+        Pubkey::default()
+    } else {
+        player.key()
+    };
+    player_stats.promote_tier(game, public_player);
+
+    log_activity(
+        game,
+        ctx.accounts.activity_feed.as_deref_mut(),
+        ACTIVITY_REMOVAL,
+        public_player,
+        &cube_id,
+        now,
+    )?;
+    record_history(
+        player_stats,
+        ctx.accounts.player_history.as_deref_mut().map(|h| &mut **h),
+        &cube_id,
+        price,
+        now,
+    )?;
+    if let Some(wall) = ctx
+        .accounts
+        .message_wall
+        .as_mut()
+        .filter(|_| !message.is_empty())
+    {
+        wall.push(WallEntry {
+            player: public_player,
+            message: message.clone(),
+            timestamp: now,
+        });
+    }
+
+    // Transfer payment to treasury. Free removals (a zero price during a
+    // promotion, an exemption or a credit) skip the transfer entirely.
+    if tip > 0 || staker_reward > 0 || royalty > 0 {
+        require_ledger(game, ctx.accounts.ledger.as_deref())?;
+    }
+    if tip > 0 {
+        transfer_lamports(
+            &player.to_account_info(),
+            &ctx.accounts.treasury,
+            &ctx.accounts.system_program.to_account_info(),
+            tip,
+        )?;
+        if let Some(ledger) = ctx.accounts.ledger.as_mut() {
+            ledger.record_inflow(tip, 0, 0, 0)?;
+        }
+        emit!(TipPaidEvent {
+            cube_id: cube_id.clone(),
+            from: public_player,
+            to: if previous_private {
+                Pubkey::default()
+            } else {
+                previous_remover
+            },
+            amount: tip,
+        });
+    }
+    if let Some(pool) = ctx
+        .accounts
+        .staking_pool
+        .as_mut()
+        .filter(|_| staker_reward > 0)
+    {
+        transfer_lamports(
+            &player.to_account_info(),
+            &pool.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+            staker_reward,
+        )?;
+        pool.accrue(staker_reward)?;
+        if let Some(ledger) = ctx.accounts.ledger.as_mut() {
+            ledger.record_shared(staker_reward)?;
+        }
+    }
+    if let Some(creator) = ctx.accounts.creator.as_ref().filter(|_| royalty > 0) {
+        transfer_lamports(
+            &player.to_account_info(),
+            creator,
+            &ctx.accounts.system_program.to_account_info(),
+            royalty,
+        )?;
+        if let Some(ledger) = ctx.accounts.ledger.as_mut() {
+            ledger.record_shared(royalty)?;
+        }
+        emit!(RoyaltyPaidEvent {
+            cube_id: cube_id.clone(),
+            from: public_player,
+            to: creator.key(),
+            amount: royalty,
+        });
+    }
+    let mut collected = 0;
+    if to_treasury > 0 {
+        collected = collect_payment(
+            game,
+            PaymentRoute {
+                payer: &player.to_account_info(),
+                treasury: &ctx.accounts.treasury,
+                system_program: &ctx.accounts.system_program.to_account_info(),
+                refund_pool: ctx.accounts.refund_pool.as_deref_mut(),
+                insurance_vault: ctx.accounts.insurance_vault.as_deref_mut(),
+                incinerator: ctx.accounts.incinerator.as_deref(),
+                dao_treasury: ctx.accounts.dao_treasury.as_deref(),
+                ledger: ctx.accounts.ledger.as_deref_mut(),
+            },
+            to_treasury,
+        )?;
+        game.note_deposit(to_treasury, ctx.accounts.treasury.lamports(), now);
+    }
+    if let Some(split) = split.filter(|split| split.tokens > 0) {
+        let (Some(from), Some(to), Some(token_program)) = (
+            ctx.accounts.payment_token_account.as_deref(),
+            ctx.accounts.token_treasury.as_deref(),
+            ctx.accounts.token_program.as_ref(),
+        ) else {
+            return err!(CubeGameError::SplitAccountsRequired);
+        };
+        let (burned, banked) = split.burn_split(game.burn_bps)?;
+        if burned > 0 {
+            let mint = ctx
+                .accounts
+                .payment_mint
+                .as_deref()
+                .ok_or(CubeGameError::SplitAccountsRequired)?;
+            anchor_spl::token::burn(
+                CpiContext::new(
+                    token_program.to_account_info(),
+                    anchor_spl::token::Burn {
+                        mint: mint.to_account_info(),
+                        from: from.to_account_info(),
+                        authority: player.to_account_info(),
+                    },
+                ),
+                burned,
+            )?;
+        }
+        if banked > 0 {
+            anchor_spl::token::transfer(
+                CpiContext::new(
+                    token_program.to_account_info(),
+                    anchor_spl::token::Transfer {
+                        from: from.to_account_info(),
+                        to: to.to_account_info(),
+                        authority: player.to_account_info(),
+                    },
+                ),
+                banked,
+            )?;
+        }
+    }
+
+    // Reconcile what the legs above routed against the SOL the removal was
+    // due, handing any rounding overpayment back from the treasury
+    let overpaid = overpayment(sol_due, &[tip, staker_reward, royalty, collected])?;
+    if overpaid > 0 {
+        transfer_from_treasury(
+            &ctx.accounts.treasury,
+            &player.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+            game.treasury_bump,
+            overpaid,
+        )?;
+        if let Some(ledger) = ctx.accounts.ledger.as_mut() {
+            ledger.record_refund(overpaid, 0)?;
+        }
+        dust += overpaid;
+    }
+    if let Some(analytics) = ctx.accounts.analytics.as_mut() {
+        analytics.record_removal(to_treasury, price, new_player)?;
+        analytics.record_dust(dust)?;
+    }
+
+    // Rebate the discoverer of a never-touched cube when the treasury can
+    // cover it. Ephemeral records are always fresh, so they earn none.
+    let rebate = game.first_removal_rebate;
+    if rebate > 0
+        && is_fresh
+        && !game.ephemeral_records
+        && game.can_pay(rebate, treasury_spendable(&ctx.accounts.treasury)?)
+    {
+        pay_reward(
+            game,
+            &ctx.accounts.treasury,
+            &player.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+            ctx.accounts.ledger.as_deref_mut(),
+            rebate,
+        )?;
+    }
+
+    // Cover a new wallet's first transaction fee while the treasury can,
+    // once per wallet ever
+    if let Some(mark) = ctx.accounts.wallet_mark.as_mut() {
+        let onboarding = mark.onboarding_due(game.onboarding_refund, sol_due);
+        if onboarding > 0
+            && new_player
+            && game.can_pay(onboarding, treasury_spendable(&ctx.accounts.treasury)?)
+        {
+            mark.onboarded = true;
+            pay_reward(
+                game,
+                &ctx.accounts.treasury,
+                &player.to_account_info(),
+                &ctx.accounts.system_program.to_account_info(),
+                ctx.accounts.ledger.as_deref_mut(),
+                onboarding,
+            )?;
+        }
+    }
+
+    if combo_bonus > 0 && game.can_pay(combo_bonus, treasury_spendable(&ctx.accounts.treasury)?) {
+        pay_reward(
+            game,
+            &ctx.accounts.treasury,
+            &player.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+            ctx.accounts.ledger.as_deref_mut(),
+            combo_bonus,
+        )?;
+    }
+
+    // Mint the reward token for every milestone this removal crossed
+    let amount = game.milestone_reward(cubes_before, player_stats.cubes_removed)?;
+    if amount > 0 {
+        mint_milestone_reward(
+            ctx.accounts.reward_mint.as_deref(),
+            ctx.accounts.reward_token_account.as_deref(),
+            ctx.accounts.reward_mint_authority.as_ref(),
+            ctx.accounts.token_program.as_ref(),
+            amount,
+        )?;
+        emit!(MilestoneRewardEvent {
+            player: public_player,
+            cubes_removed: player_stats.cubes_removed,
+            amount,
+        });
+    }
+
+    // Bounties are keyed by cube id alone, so they only pay on the global
+    // board
+    if let Some(bounty) = ctx.accounts.bounty.as_mut() {
+        if region_id.is_empty() && bounty.total > 0 {
+            let amount = bounty.claim();
+            require_ledger(game, ctx.accounts.ledger.as_deref())?;
+            **bounty.to_account_info().try_borrow_mut_lamports()? -= amount;
+            **player.to_account_info().try_borrow_mut_lamports()? += amount;
+            if let Some(ledger) = ctx.accounts.ledger.as_mut() {
+                ledger.record_reward(amount, amount)?;
+            }
+            emit!(BountyPaidEvent {
+                cube_id: cube_id.clone(),
+                player: public_player,
+                amount,
+            });
+        }
+    }
+
+    forward_treasury_overflow(
+        game,
+        &ctx.accounts.treasury,
+        ctx.accounts.cold_wallet.as_ref(),
+        ctx.accounts.ledger.as_deref_mut(),
+        &ctx.accounts.system_program.to_account_info(),
+    )?;
+
+    // Mirror the removal into the external analytics program, handing it the
+    // game state and the cube's record read-only; its failure fails the
+    // whole removal
+    if game.analytics_program != Pubkey::default() {
+        let program = ctx
+            .accounts
+            .analytics_program
+            .as_ref()
+            .ok_or(CubeGameError::AnalyticsProgramRequired)?;
+        let cube_record_info = cube_record.to_account_info();
+        let export = RemovalExport {
+            game: game.key(),
+            cube_id: cube_id.clone(),
+            player: public_player,
+            price,
+            timestamp: now,
+        };
+        anchor_lang::solana_program::program::invoke(
+            &anchor_lang::solana_program::instruction::Instruction {
+                program_id: program.key(),
+                accounts: vec![
+                    AccountMeta::new_readonly(game.key(), false),
+                    AccountMeta::new_readonly(cube_record_info.key(), false),
+                ],
+                data: export.try_to_vec()?,
+            },
+            &[
+                game.to_account_info(),
+                cube_record_info,
+                program.to_account_info(),
+            ],
+        )?;
+    }
+
+    // Warn, without failing, when paying left the player nearly broke
+    if let Some(warning) = game.low_balance_warning(public_player, player.lamports()) {
+        emit!(warning);
+    }
+
+    if flags.compact_event {
+        emit!(CubeRemovedCompact::new(
+            &cube_id,
+            public_player,
+            game.total_cubes_removed
+        ));
+    } else {
+        emit!(CubeRemovedEvent {
+            cube_id,
+            player: public_player,
+            total_removed: game.total_cubes_removed,
+            completion_bps: game.completion_bps(),
+            timestamp: now,
+            memo,
+            message,
+            color: cube_record.color,
+            tier: player_stats.tier,
+            is_founder: player_stats.is_founder,
+            score: player_stats.score,
+        });
+    }
+    for flip in bomb_flips {
+        log_activity(
+            game,
+            ctx.accounts.activity_feed.as_deref_mut(),
+            ACTIVITY_REMOVAL,
+            public_player,
+            &flip.cube_id,
+            now,
+        )?;
+        emit!(CubeRemovedEvent {
+            cube_id: flip.cube_id,
+            player: public_player,
+            total_removed: flip.total_removed,
+            completion_bps: flip.completion_bps,
+            timestamp: now,
+            memo: String::new(),
+            message: String::new(),
+            color: flip.color,
+            tier: player_stats.tier,
+            is_founder: player_stats.is_founder,
+            score: player_stats.score,
+        });
+    }
+
+    // Ephemeral boards keep only the aggregates: the record's rent goes back
+    // to the player and the cube can be removed again
+    if game.ephemeral_records && cube_record.is_removed {
+        game.forget_record(cube_record.sol_paid());
+        game.last_cube_id.clear();
+        cube_record.close(player.to_account_info())?;
+    }
+
+    Ok(())
+}
+
+/// Check that `owner` holds an NFT whose metadata lists `collection` as its
+/// verified collection
+pub fn verify_collection_nft(
+    owner: &Pubkey,
+    token_account: Option<&Account<'_, TokenAccount>>,
+    metadata: Option<&Account<'_, MetadataAccount>>,
+    collection: &Pubkey,
+) -> Result<()> {
+    let (Some(token_account), Some(metadata)) = (token_account, metadata) else {
+        return err!(CubeGameError::MissingRequiredNft);
+    };
+    require!(
+        token_account.owner == *owner && token_account.amount >= 1,
+        CubeGameError::MissingRequiredNft
+    );
+
+    let (expected_metadata, _) = Pubkey::find_program_address(
+        &[
+            b"metadata",
+            anchor_spl::metadata::ID.as_ref(),
+            token_account.mint.as_ref(),
+        ],
+        &anchor_spl::metadata::ID,
+    );
+    require_keys_eq!(
+        metadata.key(),
+        expected_metadata,
+        CubeGameError::MissingRequiredNft
+    );
+    require_keys_eq!(
+        metadata.mint,
+        token_account.mint,
+        CubeGameError::MissingRequiredNft
+    );
+
+    match &metadata.collection {
+        Some(c) if c.verified && c.key == *collection => Ok(()),
+        _ => err!(CubeGameError::MissingRequiredNft),
+    }
+}
+
+/// Price a player pays for their next removal, with every pricing modifier
+/// applied. Shared by `remove_cube` and `quote_next_price` so quotes always
+/// match real charges. `base_price` overrides `price_per_cube`, e.g. with a
+/// region's curve price.
+pub fn effective_price(
+    game: &GameState,
+    base_price: Option<u64>,
+    fee_exempt: bool,
+    now: i64,
+) -> Result<u64> {
+    // Partner wallets with a fee exemption remove for free
+    if fee_exempt {
+        return Ok(0);
+    }
+    let mut price = match base_price {
+        Some(price) => price,
+        None => game.base_price()?,
+    };
+
+    // Quadratic mode: base + k * total_removed^2
+    if game.quadratic_coeff > 0 {
+        price = game
+            .total_cubes_removed
+            .checked_mul(game.total_cubes_removed)
+            .and_then(|n2| n2.checked_mul(game.quadratic_coeff))
+            .and_then(|term| term.checked_add(price))
+            .ok_or(CubeGameError::MathOverflow)?;
+    }
+
+    // Rush pricing: every recent removal adds `rush_bps_per_removal`, capped
+    let rush_bps = game
+        .recent_removals(now)
+        .saturating_mul(game.rush_bps_per_removal as u64)
+        .min(game.rush_max_bps as u64);
+    if rush_bps > 0 {
+        price = apply_bps(
+            price,
+            (BPS_DENOMINATOR + rush_bps).min(u16::MAX as u64) as u16,
+        )?;
+    }
+
+    // Surge is applied last, on top of every other modifier
+    if now < game.surge_until {
+        price = apply_bps(price, game.surge_multiplier_bps)?;
+    }
+    Ok(price)
+}
+
+/// Display color (0xRRGGBB) of `cube_id` under `palette_seed`, taken from
+/// `keccak(seed || cube_id)`
+pub fn cube_color(cube_id: &str, palette_seed: u64) -> u32 {
+    let hash = anchor_lang::solana_program::keccak::hashv(&[
+        &palette_seed.to_le_bytes(),
+        cube_id.as_bytes(),
+    ])
+    .0;
+    u32::from_be_bytes([0, hash[0], hash[1], hash[2]])
+}
+
+/// The creator's `royalty_bps` cut of `sol_due` and what the treasury
+/// keeps once `carved_out` (tip and staker reward) is also taken
+pub fn royalty_split(sol_due: u64, royalty_bps: u16, carved_out: u64) -> Result<(u64, u64)> {
+    let royalty = apply_bps(sol_due, royalty_bps)?;
+    let to_treasury = carved_out
+        .checked_add(royalty)
+        .and_then(|taken| sol_due.checked_sub(taken))
+        .ok_or(CubeGameError::InvalidBps)?;
+    Ok((royalty, to_treasury))
+}
+
+/// Lamports the `routed` payment legs took beyond `due`, owed back to the
+/// payer
+pub fn overpayment(due: u64, routed: &[u64]) -> Result<u64> {
+    let total = routed
+        .iter()
+        .try_fold(0u64, |sum, leg| sum.checked_add(*leg))
+        .ok_or(CubeGameError::MathOverflow)?;
+    Ok(total.saturating_sub(due))
+}
+
+/// `amount * bps / 10000`, rounded down
+pub fn apply_bps(amount: u64, bps: u16) -> Result<u64> {
+    let scaled = (amount as u128) * (bps as u128) / (BPS_DENOMINATOR as u128);
+    u64::try_from(scaled).map_err(|_| error!(CubeGameError::MathOverflow))
+}
+
+/// Estimated largest batch that fits the compute budget when
+/// `account_creation_count` of its records must be created, capped at
+/// `MAX_BATCH_REMOVALS`
+pub fn max_safe_batch(account_creation_count: usize) -> usize {
+    let creation_cost = (account_creation_count as u64).saturating_mul(CU_PER_RECORD_CREATION);
+    let available = BATCH_COMPUTE_BUDGET
+        .saturating_sub(BATCH_BASE_CU)
+        .saturating_sub(creation_cost);
+    ((available / CU_PER_BATCH_REMOVAL) as usize).min(MAX_BATCH_REMOVALS)
+}
+
+/// Ids of the six face-adjacent cubes of an `"x:y:z"` cube id, keeping
+/// anything before the coordinates as a shared prefix. Ids that do not end
+/// in three integer coordinates have no neighbours.
+pub fn neighbor_ids(cube_id: &str) -> Vec<String> {
+    let mut parts = cube_id.rsplitn(4, ':');
+    let (Some(z), Some(y), Some(x)) = (parts.next(), parts.next(), parts.next()) else {
+        return Vec::new();
+    };
+    let prefix = parts.next().map(|p| format!("{p}:")).unwrap_or_default();
+    let (Ok(x), Ok(y), Ok(z)) = (x.parse::<i64>(), y.parse::<i64>(), z.parse::<i64>()) else {
+        return Vec::new();
+    };
+
+    // Steps past either end of the i64 range have no neighbour
+    [
+        (x.checked_sub(1), Some(y), Some(z)),
+        (x.checked_add(1), Some(y), Some(z)),
+        (Some(x), y.checked_sub(1), Some(z)),
+        (Some(x), y.checked_add(1), Some(z)),
+        (Some(x), Some(y), z.checked_sub(1)),
+        (Some(x), Some(y), z.checked_add(1)),
+    ]
+    .iter()
+    .filter_map(|coords| match *coords {
+        (Some(x), Some(y), Some(z)) => Some(format!("{prefix}{x}:{y}:{z}")),
+        _ => None,
+    })
+    .collect()
+}
+
+/// Number of distinct neighbours of `cube_id` in `region_id` among
+/// `records` that were removed within the combo window. Neighbours are
+/// matched by their record PDA, so a same-named cube in another region
+/// does not count. Accounts that are not neighbour records are ignored.
+pub fn count_recent_neighbors<'info>(
+    game: &GameState,
+    region_id: &str,
+    cube_id: &str,
+    records: &'info [AccountInfo<'info>],
+    now: i64,
+) -> Result<usize> {
+    require!(
+        records.len() <= MAX_COMBO_NEIGHBORS,
+        CubeGameError::TooManyAccounts
+    );
+    let mut neighbors: Vec<Pubkey> = neighbor_ids(cube_id)
+        .iter()
+        .map(|id| scoped_cube_record_pda(region_id, id))
+        .collect();
+    let mut count = 0;
+    for info in records {
+        // Drop matches so a record passed twice only counts once
+        let Some(pos) = neighbors.iter().position(|pda| *pda == info.key()) else {
+            continue;
+        };
+        let Ok(record) = Account::<CubeRecord>::try_from(info) else {
+            continue;
+        };
+        if !record.is_removed || elapsed(now, record.removed_at) > game.combo_window {
+            continue;
+        }
+        neighbors.swap_remove(pos);
+        count += 1;
+    }
+    Ok(count)
+}
+
+/// Whether `cube_id` is a bomb: `keccak(b"bomb" || cube_id)` falls under
+/// `bomb_rate_bps` of the hash space
+pub fn is_bomb(cube_id: &str, bomb_rate_bps: u16) -> bool {
+    if bomb_rate_bps == 0 {
+        return false;
+    }
+    let hash = anchor_lang::solana_program::keccak::hashv(&[b"bomb", cube_id.as_bytes()]).0;
+    (u16::from_le_bytes([hash[0], hash[1]]) as u64 % BPS_DENOMINATOR) < bomb_rate_bps as u64
+}
+
+/// Ranked score of `cube_id`, fixed by `keccak(b"rarity" || cube_id)`:
+/// 1 in 256 cubes is legendary (25), 7 in 256 rare (10), 40 in 256
+/// uncommon (3) and the rest common (1)
+pub fn cube_score(cube_id: &str) -> u64 {
+    rarity_score(anchor_lang::solana_program::keccak::hashv(&[b"rarity", cube_id.as_bytes()]).0[0])
+}
+
+/// Score for a rarity `roll` byte, with the odds `cube_score` describes
+pub fn rarity_score(roll: u8) -> u64 {
+    match roll {
+        0 => 25,
+        1..=7 => 10,
+        8..=47 => 3,
+        _ => 1,
+    }
+}
+
+/// `keccak(cube_id || action_nonce)`, binding a removal's replay nonce to
+/// the cube it removed
+pub fn action_key(cube_id: &str, action_nonce: u64) -> [u8; 32] {
+    anchor_lang::solana_program::keccak::hashv(&[cube_id.as_bytes(), &action_nonce.to_le_bytes()]).0
+}
+
+/// Rarity score and colour `reveal_cube` rolls for `cube_id` from
+/// `slot_hash`, with `cube_score`'s odds for the rarity
+pub fn reveal_attributes(slot_hash: &[u8; 32], cube_id: &str) -> (u64, u32) {
+    let hash =
+        anchor_lang::solana_program::keccak::hashv(&[b"reveal", slot_hash, cube_id.as_bytes()]).0;
+    (
+        rarity_score(hash[0]),
+        u32::from_be_bytes([0, hash[1], hash[2], hash[3]]),
+    )
+}
+
+/// Hash of the first slot after `slot` in SlotHashes sysvar data (a `u64`
+/// count followed by `(slot, hash)` pairs, newest first). None until that
+/// slot has a hash, or once it has left the window.
+pub fn slot_hash_after(data: &[u8], slot: u64) -> Option<[u8; 32]> {
+    let count = u64::from_le_bytes(data.get(..8)?.try_into().ok()?) as usize;
+    let mut after = None;
+    for i in 0..count {
+        let entry = data.get(8 + i * 40..8 + (i + 1) * 40)?;
+        let entry_slot = u64::from_le_bytes(entry[..8].try_into().ok()?);
+        if entry_slot <= slot {
+            return after;
+        }
+        after = Some(entry[8..].try_into().ok()?);
+    }
+    None
+}
+
+/// Winning ticket in `[0, total_weight)` drawn from the completion seed
+pub fn draw_ticket(seed: &[u8; 32], total_weight: u64) -> u64 {
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&seed[..8]);
+    u64::from_le_bytes(bytes) % total_weight.max(1)
+}
+
+/// Entrant whose share of the weight holds `ticket`, given that earlier
+/// batches already counted `counted` of it
+pub fn pick_entrant(entrants: &[(Pubkey, u64)], counted: u64, ticket: u64) -> Option<Pubkey> {
+    let mut start = counted;
+    for (player, weight) in entrants {
+        let end = start.saturating_add(*weight);
+        if ticket >= start && ticket < end {
+            return Some(*player);
+        }
+        start = end;
+    }
+    None
+}
+
+/// Add `amount` to `payer`'s entry in `shares`, opening one while fewer than
+/// `MAX_CUBE_CONTRIBUTORS` wallets hold a share. Crowdfunding shares and the
+/// payments recorded on a cube both go through here.
+pub fn credit_share(shares: &mut Vec<CubeContribution>, payer: Pubkey, amount: u64) -> Result<()> {
+    match shares.iter_mut().find(|c| c.contributor == payer) {
+        Some(existing) => {
+            existing.amount = existing
+                .amount
+                .checked_add(amount)
+                .ok_or(CubeGameError::MathOverflow)?;
+        }
+        None => {
+            require!(
+                shares.len() < MAX_CUBE_CONTRIBUTORS,
+                CubeGameError::TooManyContributors
+            );
+            shares.push(CubeContribution {
+                contributor: payer,
+                amount,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// A neighbour cleared by a bomb, with the board's totals right after it
+pub struct BombFlip {
+    pub cube_id: String,
+    pub color: u32,
+    /// Ranked score the flip earns the bomb's remover
+    pub score: u64,
+    pub total_removed: u64,
+    pub completion_bps: u16,
+}
+
+/// Remove, free of charge, every neighbour of the bomb `cube_id` passed in
+/// `records`, creating records that do not exist yet. Every record must be
+/// a neighbour's PDA in `region_id`. Cubes placed behind a predecessor,
+/// carrying a creator royalty, not yet expired or part-way through hits or
+/// shared removals are left standing, since a free flip can honour none of
+/// them. Returns the cubes flipped, stopping early once a bounded board is
+/// complete.
+pub fn detonate_bomb<'info>(
+    game: &mut GameState,
+    region_id: &str,
+    cube_id: &str,
+    records: &'info [AccountInfo<'info>],
+    player: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    now: i64,
+) -> Result<Vec<BombFlip>> {
+    require!(
+        records.len() <= MAX_COMBO_NEIGHBORS,
+        CubeGameError::TooManyAccounts
+    );
+    let mut neighbors: Vec<(String, Pubkey)> = neighbor_ids(cube_id)
+        .into_iter()
+        .filter(|id| game.validate_cube_id(id).is_ok())
+        .map(|id| {
+            let pda = scoped_cube_record_pda(region_id, &id);
+            (id, pda)
+        })
+        .collect();
+
+    let mut flipped = Vec::new();
+    for info in records {
+        // Drop matches so a record passed twice is only flipped once
+        let pos = neighbors
+            .iter()
+            .position(|(_, pda)| *pda == info.key())
+            .ok_or(CubeGameError::NotANeighbor)?;
+        let (neighbor_id, _) = neighbors.swap_remove(pos);
+        if game.board_cleared() {
+            break;
+        }
+
+        let mut record =
+            load_or_create_cube_record(info, region_id, &neighbor_id, player, system_program)?;
+        if record.cube_id.is_empty() {
+            game.note_record_created()?;
+        }
+        if record.is_removed
+            || record.prev_cube_id.is_some()
+            || record.royalty_bps > 0
+            || (record.expires_at > 0 && !record.is_expired(now))
+            || record.hits_taken > 0
+            || record.remover_count > 0
+        {
+            continue;
+        }
+        record.is_removed = true;
+        record.removed_by = player.key();
+        record.original_remover = player.key();
+        record.stamp_removed_at(now);
+        record.cube_id = neighbor_id.clone();
+        record.price_paid = 0;
+        record.priority_fee = 0;
+        record.memo.clear();
+        record.color = record.removal_color(game, &neighbor_id, 0);
+        let score = record.ranked_score(&neighbor_id);
+        if game.ephemeral_records {
+            game.forget_record(0);
+            record.close(player.clone())?;
+        } else {
+            record.exit(&crate::ID)?;
+        }
+
+        game.count_removal()?;
+        flipped.push(BombFlip {
+            cube_id: neighbor_id,
+            color: record.color,
+            score,
+            total_removed: game.total_cubes_removed,
+            completion_bps: game.completion_bps(),
+        });
+    }
+    Ok(flipped)
+}
+
+/// Require the zone a cube is removed under to contain it and be unlocked,
+/// once the board is split into zones
+pub fn require_zone_open(
+    game: &GameState,
+    zone: Option<&Account<Zone>>,
+    cube_id: &str,
+) -> Result<()> {
+    if game.zone_count == 0 {
+        return Ok(());
+    }
+    let zone = zone.ok_or(CubeGameError::ZoneAccountRequired)?;
+    require!(
+        cube_id.starts_with(zone.prefix.as_str()),
+        CubeGameError::CubeNotInZone
+    );
+    require!(
+        zone.unlocked && zone.cubes_removed < zone.cubes_total,
+        CubeGameError::ZoneLocked
+    );
+    Ok(())
+}
+
+/// Count a removal in its zone, unlocking the next zone once it is cleared
+pub fn credit_zone(
+    game: &GameState,
+    zone: Option<&mut Account<Zone>>,
+    next_zone: Option<&mut Account<Zone>>,
+) -> Result<()> {
+    if game.zone_count == 0 {
+        return Ok(());
+    }
+    let zone = zone.ok_or(CubeGameError::ZoneAccountRequired)?;
+    zone.cubes_removed += 1;
+
+    if zone.cubes_removed == zone.cubes_total && zone.zone_id + 1 < game.zone_count {
+        let next = next_zone.ok_or(CubeGameError::ZoneAccountRequired)?;
+        require!(next.zone_id == zone.zone_id + 1, CubeGameError::InvalidZone);
+        next.unlocked = true;
+        emit!(ZoneUnlockedEvent {
+            zone_id: next.zone_id,
+            prefix: next.prefix.clone(),
+        });
+    }
+    Ok(())
+}
+
+/// Reject a removal landing after its client-set `deadline` (0 = none),
+/// before anything is charged
+pub fn require_before_deadline(deadline: i64, now: i64) -> Result<()> {
+    require!(
+        deadline == 0 || now <= deadline,
+        CubeGameError::DeadlineExceeded
+    );
+    Ok(())
+}
+
+/// Seconds from `since` to `now`. The cluster clock can step backwards
+/// slightly between validators, so a negative span counts as zero.
+pub fn elapsed(now: i64, since: i64) -> i64 {
+    now.saturating_sub(since).max(0)
+}
+
+/// Accept at most `MAX_MESSAGE_LEN` bytes of printable ASCII for the wall
+pub fn validate_message(message: &str) -> Result<()> {
+    require!(
+        message.len() <= MAX_MESSAGE_LEN,
+        CubeGameError::MessageTooLong
+    );
+    require!(
+        message.bytes().all(|b| (0x20..=0x7e).contains(&b)),
+        CubeGameError::InvalidMessage
+    );
+    Ok(())
+}
+
+/// Require one color, one memo and one record account per cube id of a
+/// non-empty batch
+pub fn require_batch_aligned(
+    cube_ids: &[String],
+    colors: &[u32],
+    memos: &[String],
+    accounts: usize,
+) -> Result<()> {
+    let count = cube_ids.len();
+    require!(
+        count > 0 && colors.len() == count && memos.len() == count && accounts == count,
+        CubeGameError::BatchArgMismatch
+    );
+    Ok(())
+}
+
+/// Total of a `batch_refund`, requiring a record and a recipient account
+/// per amount, at most `MAX_BATCH_REFUNDS` of them, and a total the
+/// treasury's `spendable` lamports cover
+pub fn batch_refund_total(refunds: &[u64], accounts: usize, spendable: u64) -> Result<u64> {
+    require!(
+        refunds.len() * 2 == accounts,
+        CubeGameError::BatchArgMismatch
+    );
+    require!(
+        refunds.len() <= MAX_BATCH_REFUNDS,
+        CubeGameError::TooManyAccounts
+    );
+    let total = refunds
+        .iter()
+        .try_fold(0u64, |sum, amount| sum.checked_add(*amount))
+        .ok_or(CubeGameError::MathOverflow)?;
+    require!(
+        total <= spendable,
+        CubeGameError::InsufficientTreasuryBalance
+    );
+    Ok(total)
+}
+
+/// Reject a reconciliation memo longer than `MAX_MEMO_LEN` bytes; an empty
+/// memo is stored as empty
+pub fn validate_memo(memo: &str) -> Result<()> {
+    require!(memo.len() <= MAX_MEMO_LEN, CubeGameError::MemoTooLong);
+    Ok(())
+}
+
+/// Commitment `commit_removal` expects for `player` later revealing a
+/// removal of `cube_id` with `salt`
+pub fn removal_commitment_hash(player: &Pubkey, cube_id: &str, salt: &[u8; 32]) -> [u8; 32] {
+    anchor_lang::solana_program::keccak::hashv(&[player.as_ref(), cube_id.as_bytes(), salt]).0
+}
+
+/// Append an entry to the activity feed when it has been created
+pub fn log_activity(
+    game: &GameState,
+    feed: Option<&mut Account<'_, ActivityFeed>>,
+    action_type: u8,
+    actor: Pubkey,
+    cube_id: &str,
+    timestamp: i64,
+) -> Result<()> {
+    if !game.activity_feed_enabled {
+        return Ok(());
+    }
+    let feed = feed.ok_or(CubeGameError::ActivityFeedRequired)?;
+    feed.push(ActivityEntry::new(action_type, actor, cube_id, timestamp));
+    Ok(())
+}
+
+/// Where an incoming payment is routed
+pub struct PaymentRoute<'a, 'info> {
+    pub payer: &'a AccountInfo<'info>,
+    pub treasury: &'a AccountInfo<'info>,
+    pub system_program: &'a AccountInfo<'info>,
+    pub refund_pool: Option<&'a mut Account<'info, RefundPool>>,
+    pub insurance_vault: Option<&'a mut Account<'info, InsuranceVault>>,
+    pub incinerator: Option<&'a AccountInfo<'info>>,
+    pub dao_treasury: Option<&'a AccountInfo<'info>>,
+    pub ledger: Option<&'a mut Account<'info, Ledger>>,
+}
+
+/// Collect `amount` from the payer, reserving the configured shares in the
+/// refund pool and the insurance vault, burning the configured share, paying
+/// the DAO tax and banking the rest in the treasury. Shares round down, so
+/// the treasury also keeps any rounding remainder.
+pub fn collect_payment(game: &GameState, route: PaymentRoute<'_, '_>, amount: u64) -> Result<u64> {
+    if amount == 0 {
+        return Ok(0);
+    }
+    require!(
+        !game.ledger_enabled || route.ledger.is_some(),
+        CubeGameError::LedgerRequired
+    );
+
+    let PaymentShares {
+        reserve,
+        insurance,
+        burn,
+        dao_tax,
+        to_treasury,
+    } = game.payment_shares(amount)?;
+    // Every share's destination is checked, and the ledger entry written,
+    // before any lamports move, so a missing account cannot leave a
+    // payment half made
+    require!(
+        reserve == 0 || route.refund_pool.is_some(),
+        CubeGameError::RefundPoolRequired
+    );
+    require!(
+        insurance == 0 || route.insurance_vault.is_some(),
+        CubeGameError::InsuranceVaultRequired
+    );
+    require!(
+        burn == 0 || route.incinerator.is_some(),
+        CubeGameError::IncineratorRequired
+    );
+    require!(
+        dao_tax == 0 || route.dao_treasury.is_some(),
+        CubeGameError::DaoTreasuryRequired
+    );
+    if let Some(ledger) = route.ledger {
+        ledger.record_inflow(amount, reserve + insurance, burn, dao_tax)?;
+    }
+
+    if reserve > 0 {
+        let refund_pool = route.refund_pool.ok_or(CubeGameError::RefundPoolRequired)?;
+        transfer_lamports(
+            route.payer,
+            &refund_pool.to_account_info(),
+            route.system_program,
+            reserve,
+        )?;
+        refund_pool.total_reserved += reserve;
+    }
+
+    if insurance > 0 {
+        let vault = route
+            .insurance_vault
+            .ok_or(CubeGameError::InsuranceVaultRequired)?;
+        transfer_lamports(
+            route.payer,
+            &vault.to_account_info(),
+            route.system_program,
+            insurance,
+        )?;
+        vault.total_insured += insurance;
+    }
+
+    if burn > 0 {
+        let incinerator = route
+            .incinerator
+            .ok_or(CubeGameError::IncineratorRequired)?;
+        transfer_lamports(route.payer, incinerator, route.system_program, burn)?;
+    }
+
+    if dao_tax > 0 {
+        let dao_treasury = route
+            .dao_treasury
+            .ok_or(CubeGameError::DaoTreasuryRequired)?;
+        transfer_lamports(route.payer, dao_treasury, route.system_program, dao_tax)?;
+    }
+
+    transfer_lamports(
+        route.payer,
+        route.treasury,
+        route.system_program,
+        to_treasury,
+    )?;
+    Ok(reserve + insurance + burn + dao_tax + to_treasury)
+}
+
+/// Require the insurance vault to hold `amount` above its rent exemption
+pub fn release_insurance(vault: &AccountInfo, amount: u64) -> Result<()> {
+    let available = vault
+        .lamports()
+        .saturating_sub(Rent::get()?.minimum_balance(vault.data_len()));
+    require!(
+        amount <= available,
+        CubeGameError::InsufficientInsuranceFunds
+    );
+    Ok(())
+}
+
+/// Emit a `TreasuryUnfundedWarning` while the treasury holds less than its
+/// rent-exempt minimum
+pub fn warn_if_treasury_unfunded(treasury: &AccountInfo) -> Result<()> {
+    let minimum = Rent::get()?.minimum_balance(treasury.data_len());
+    if let Some(warning) = treasury_unfunded(treasury.lamports(), minimum) {
+        emit!(warning);
+    }
+    Ok(())
+}
+
+/// Warning due while a treasury holding `balance` is short of its
+/// rent-exempt `minimum`
+pub fn treasury_unfunded(balance: u64, minimum: u64) -> Option<TreasuryUnfundedWarning> {
+    (balance < minimum).then_some(TreasuryUnfundedWarning { balance, minimum })
+}
+
+/// Top the treasury back up to `target_hot_balance` from the cold reserve,
+/// as far as the reserve's balance above rent allows
+pub fn refill_hot_treasury(
+    game: &GameState,
+    treasury: &AccountInfo,
+    cold_reserve: Option<&mut Account<ColdReserve>>,
+    ledger: Option<&mut Account<Ledger>>,
+) -> Result<()> {
+    if game.target_hot_balance == 0 {
+        return Ok(());
+    }
+    let reserve = cold_reserve.ok_or(CubeGameError::ColdReserveRequired)?;
+    let reserve_info = reserve.to_account_info();
+    let available = reserve_info
+        .lamports()
+        .saturating_sub(Rent::get()?.minimum_balance(reserve_info.data_len()));
+    let refill = game.hot_refill(treasury.lamports(), available);
+    if refill > 0 {
+        **reserve_info.try_borrow_mut_lamports()? -= refill;
+        **treasury.try_borrow_mut_lamports()? += refill;
+        reserve.total_released += refill;
+        if let Some(ledger) = ledger {
+            ledger.record_refill(refill);
+        }
+        emit!(TreasuryRefilledEvent {
+            amount: refill,
+            treasury_balance: treasury.lamports(),
+        });
+    }
+    Ok(())
+}
+
+/// Lamports of an `amount` refund drawn from a pool with `pool_available`
+/// spendable, and the rest the treasury covers
+pub fn refund_sources(amount: u64, pool_available: u64) -> (u64, u64) {
+    let from_pool = amount.min(pool_available);
+    (from_pool, amount - from_pool)
+}
+
+/// Pay a refund from the refund pool, topping up from the treasury when the
+/// pool alone cannot cover it. Neither account drops below rent exemption.
+pub fn pay_refund<'info>(
+    treasury_bump: u8,
+    refund_pool: Option<&mut Account<'info, RefundPool>>,
+    ledger: Option<&mut Account<'info, Ledger>>,
+    treasury: &AccountInfo<'info>,
+    recipient: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    amount: u64,
+) -> Result<()> {
+    let rent = Rent::get()?;
+    let mut from_pool = 0;
+
+    if let Some(pool) = refund_pool {
+        let pool_info = pool.to_account_info();
+        let available = pool_info
+            .lamports()
+            .saturating_sub(rent.minimum_balance(pool_info.data_len()));
+        from_pool = refund_sources(amount, available).0;
+        if from_pool > 0 {
+            // The pool is owned by this program, so it can be debited directly
+            **pool_info.try_borrow_mut_lamports()? -= from_pool;
+            **recipient.try_borrow_mut_lamports()? += from_pool;
+            pool.total_refunded += from_pool;
+        }
+    }
+
+    let from_treasury = amount - from_pool;
+    if from_treasury > 0 {
+        require!(
+            from_treasury <= treasury_spendable(treasury)?,
+            CubeGameError::InsufficientRefundFunds
+        );
+        transfer_from_treasury(
+            treasury,
+            recipient,
+            system_program,
+            treasury_bump,
+            from_treasury,
+        )?;
+    }
+    if let Some(ledger) = ledger {
+        ledger.record_refund(amount, from_pool)?;
+    }
+    Ok(())
+}
+
+/// Where `restore_cube` pays refunds from, as for `pay_refund`
+pub struct RefundRoute<'a, 'info> {
+    pub treasury_bump: u8,
+    pub refund_pool: Option<&'a mut Account<'info, RefundPool>>,
+    pub ledger: Option<&'a mut Account<'info, Ledger>>,
+    pub treasury: &'a AccountInfo<'info>,
+    pub system_program: &'a AccountInfo<'info>,
+}
+
+/// Next of a handler's `remaining_accounts`, failing when they run out
+pub fn next_account<'a, 'info>(
+    accounts: &mut std::slice::Iter<'a, AccountInfo<'info>>,
+) -> Result<&'a AccountInfo<'info>> {
+    accounts
+        .next()
+        .ok_or(error!(CubeGameError::BatchArgMismatch))
+}
+
+/// Put a removed cube back and take back everything its removal counted:
+/// the game's and the original remover's totals, even after a transfer, the region, team and zone counters
+/// and its payments. `accounts` yields, in order, the cube's `Region` when
+/// its record is regional, the remover's `Team` when they are on one, its
+/// `Zone` on a zoned board unless `zone` is given, then with `refund` one
+/// wallet per payment, each refunded what it paid. Returns the lamports
+/// refunded.
+pub fn restore_cube<'info>(
+    game: &mut GameState,
+    record: &mut CubeRecord,
+    record_key: Pubkey,
+    stats: &mut PlayerStats,
+    zone: Option<&mut Zone>,
+    accounts: &mut std::slice::Iter<'info, AccountInfo<'info>>,
+    refund: Option<RefundRoute<'_, 'info>>,
+) -> Result<u64> {
+    require_keys_eq!(
+        stats.player,
+        record.credited_remover(),
+        CubeGameError::RemoverStatsMismatch
+    );
+    let cube_id = record.cube_id.clone();
+
+    if record_key != scoped_cube_record_pda("", &cube_id) {
+        let info = next_account(accounts)?;
+        let mut region = Account::<Region>::try_from(info)?;
+        require_keys_eq!(
+            scoped_cube_record_pda(&region.region_id, &cube_id),
+            record_key,
+            CubeGameError::RegionRequired
+        );
+        region.cubes_removed = region.cubes_removed.saturating_sub(1);
+        region.exit(&crate::ID)?;
+    }
+    if !stats.team_id.is_empty() {
+        let mut team = Account::<Team>::try_from(next_account(accounts)?)?;
+        require!(
+            team.team_id == stats.team_id,
+            CubeGameError::TeamAccountRequired
+        );
+        team.total_cubes_removed = team.total_cubes_removed.saturating_sub(1);
+        team.exit(&crate::ID)?;
+    }
+    if game.zone_count > 0 {
+        match zone {
+            Some(zone) => zone.cubes_removed = zone.cubes_removed.saturating_sub(1),
+            None => {
+                let mut zone = Account::<Zone>::try_from(next_account(accounts)?)?;
+                require!(
+                    cube_id.starts_with(zone.prefix.as_str()),
+                    CubeGameError::CubeNotInZone
+                );
+                zone.cubes_removed = zone.cubes_removed.saturating_sub(1);
+                zone.exit(&crate::ID)?;
+            }
+        }
+    }
+
+    let mut refunded = 0;
+    if let Some(mut route) = refund {
+        for payment in record.payments.iter() {
+            let payer = next_account(accounts)?;
+            require_keys_eq!(
+                payer.key(),
+                payment.contributor,
+                CubeGameError::RefundRecipientMismatch
+            );
+            if payment.amount == 0 {
+                continue;
+            }
+            pay_refund(
+                route.treasury_bump,
+                route.refund_pool.as_deref_mut(),
+                route.ledger.as_deref_mut(),
+                route.treasury,
+                payer,
+                route.system_program,
+                payment.amount,
+            )?;
+            refunded += payment.amount;
+        }
+    }
+    game.release_paid(record.sol_paid());
+
+    record.is_removed = false;
+    record.removed_by = Pubkey::default();
+    record.original_remover = Pubkey::default();
+    record.price_paid = 0;
+    record.hits_taken = 0;
+    record.remover_count = 0;
+    record.payments.clear();
+    record.memo.clear();
+
+    game.total_cubes_removed = game.total_cubes_removed.saturating_sub(1);
+    if stats.cubes_removed > 0 {
+        stats.cubes_removed -= 1;
+        game.entrant_weight = game.entrant_weight.saturating_sub(1);
+    }
+    stats.score = stats.score.saturating_sub(record.ranked_score(&cube_id));
+    Ok(refunded)
+}
+
+/// System transfer from a signer
+pub fn transfer_lamports<'info>(
+    from: &AccountInfo<'info>,
+    to: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    amount: u64,
+) -> Result<()> {
+    if amount == 0 {
+        return Ok(());
+    }
+    let transfer_ix =
+        anchor_lang::solana_program::system_instruction::transfer(from.key, to.key, amount);
+    anchor_lang::solana_program::program::invoke(
+        &transfer_ix,
+        &[from.clone(), to.clone(), system_program.clone()],
+    )?;
+    Ok(())
+}
+
+/// Reject the removal when the transaction bundles more removals than the
+/// configured per-transaction limit. The instructions sysvar only lists
+/// top-level instructions, so removals made through CPI are refused
+/// while the limit is on rather than slipping past the count.
+pub fn enforce_tx_removal_limit(
+    game: &GameState,
+    instructions: Option<&UncheckedAccount>,
+) -> Result<()> {
+    use anchor_lang::solana_program::instruction::{
+        get_stack_height, TRANSACTION_LEVEL_STACK_HEIGHT,
+    };
+
+    if game.max_removals_per_tx == 0 {
+        return Ok(());
+    }
+    require!(
+        get_stack_height() <= TRANSACTION_LEVEL_STACK_HEIGHT,
+        CubeGameError::RemovalViaCpi
+    );
+    let instructions = instructions.ok_or(CubeGameError::InstructionsSysvarRequired)?;
+    require!(
+        count_removals_in_tx(instructions)? <= game.max_removals_per_tx as usize,
+        CubeGameError::TooManyRemovalsPerTx
+    );
+    Ok(())
+}
+
+/// Forward anything above the hot cap to the cold wallet
+pub fn forward_treasury_overflow<'info>(
+    game: &GameState,
+    treasury: &AccountInfo<'info>,
+    cold_wallet: Option<&UncheckedAccount<'info>>,
+    ledger: Option<&mut Account<'info, Ledger>>,
+    system_program: &AccountInfo<'info>,
+) -> Result<()> {
+    if game.treasury_hot_cap == 0 {
+        return Ok(());
+    }
+    let floor = game
+        .treasury_hot_cap
+        .max(Rent::get()?.minimum_balance(treasury.data_len()));
+    let overflow = treasury.lamports().saturating_sub(floor);
+    if overflow > 0 && game.can_pay(overflow, treasury_spendable(treasury)?) {
+        require!(
+            !game.ledger_enabled || ledger.is_some(),
+            CubeGameError::LedgerRequired
+        );
+        let cold_wallet = cold_wallet.ok_or(CubeGameError::ColdWalletRequired)?;
+        transfer_from_treasury(
+            treasury,
+            &cold_wallet.to_account_info(),
+            system_program,
+            game.treasury_bump,
+            overflow,
+        )?;
+        if let Some(ledger) = ledger {
+            ledger.record_to_dev(overflow)?;
+        }
+    }
+    Ok(())
+}
+
+/// Whether the cube record at `info` exists and is removed
+pub fn cube_is_removed(info: &AccountInfo) -> Result<bool> {
+    if info.owner != &crate::ID {
+        return Ok(false);
+    }
+    let record = CubeRecord::try_deserialize(&mut &info.try_borrow_data()?[..])?;
+    Ok(record.is_removed)
+}
+
+/// Let `player` through the cube's removal queue at `info`: expired turns are
+/// dropped, then the player must be at the front, and is served and given
+/// back their deposit. A queue that was never created lets everyone through.
+pub fn take_queue_turn(
+    info: &AccountInfo,
+    player: &AccountInfo,
+    now: i64,
+    turn_seconds: i64,
+) -> Result<()> {
+    if info.data_is_empty() {
+        return Ok(());
+    }
+    require_keys_eq!(*info.owner, crate::ID, CubeGameError::QueueRequired);
+    let mut queue = RemovalQueue::try_deserialize(&mut &info.try_borrow_data()?[..])?;
+    queue.expire_turns(now, turn_seconds);
+    let deposit = queue.serve(player.key, now)?;
+    queue.try_serialize(&mut &mut info.try_borrow_mut_data()?[..])?;
+    **info.try_borrow_mut_lamports()? -= deposit;
+    **player.try_borrow_mut_lamports()? += deposit;
+    Ok(())
+}
+
+/// Load the `CubeRecord` of `cube_id` in `region_id` at `info`, creating it
+/// at its PDA when it does not exist yet. A freshly created record comes back
+/// zeroed, like one from `init_if_needed`.
+pub fn load_or_create_cube_record<'info>(
+    info: &'info AccountInfo<'info>,
+    region_id: &str,
+    cube_id: &str,
+    payer: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+) -> Result<Account<'info, CubeRecord>> {
+    use anchor_lang::solana_program::{program::invoke_signed, system_instruction};
+
+    require!(cube_id.len() <= 32, CubeGameError::InvalidCubeId);
+    let region_seed = cube_record_seed(region_id);
+    let (pda, bump) = Pubkey::find_program_address(&[&region_seed, cube_id.as_bytes()], &crate::ID);
+    require_keys_eq!(info.key(), pda, CubeGameError::CubeRecordMismatch);
+    if info.owner == &crate::ID {
+        return Account::try_from(info);
+    }
+
+    let space = 8 + CubeRecord::INIT_SPACE;
+    let rent = Rent::get()?.minimum_balance(space);
+    let seeds: &[&[u8]] = &[&region_seed, cube_id.as_bytes(), &[bump]];
+    if info.lamports() == 0 {
+        invoke_signed(
+            &system_instruction::create_account(
+                payer.key,
+                info.key,
+                rent,
+                space as u64,
+                &crate::ID,
+            ),
+            &[payer.clone(), info.clone(), system_program.clone()],
+            &[seeds],
+        )?;
+    } else {
+        // Someone pre-funded the address, so create_account would fail
+        transfer_lamports(
+            payer,
+            info,
+            system_program,
+            rent.saturating_sub(info.lamports()),
+        )?;
+        invoke_signed(
+            &system_instruction::allocate(info.key, space as u64),
+            &[info.clone(), system_program.clone()],
+            &[seeds],
+        )?;
+        invoke_signed(
+            &system_instruction::assign(info.key, &crate::ID),
+            &[info.clone(), system_program.clone()],
+            &[seeds],
+        )?;
+    }
+    Account::try_from_unchecked(info)
+}
+
+/// Reject a removal reached through CPI unless its direct caller is an
+/// allowlisted program. Direct calls, and every call while the allowlist is
+/// empty, pass.
+pub fn enforce_cpi_allowlist(
+    game: &GameState,
+    instructions: Option<&UncheckedAccount>,
+) -> Result<()> {
+    use anchor_lang::solana_program::instruction::{
+        get_stack_height, TRANSACTION_LEVEL_STACK_HEIGHT,
+    };
+    use anchor_lang::solana_program::sysvar::instructions::{
+        load_current_index_checked, load_instruction_at_checked,
+    };
+
+    if game.allowed_cpi_callers.is_empty() || get_stack_height() <= TRANSACTION_LEVEL_STACK_HEIGHT {
+        return Ok(());
+    }
+    let instructions = instructions.ok_or(CubeGameError::InstructionsSysvarRequired)?;
+    let current = load_current_index_checked(instructions)?;
+    let top_level = load_instruction_at_checked(current as usize, instructions)?.program_id;
+    let caller = direct_cpi_caller(get_stack_height(), top_level);
+    require!(
+        caller.is_some_and(|caller| game.allowed_cpi_callers.contains(&caller)),
+        CubeGameError::UnauthorizedCpiCaller
+    );
+    Ok(())
+}
+
+/// Program that invoked this one at `stack_height`, given the program of the
+/// transaction's current top-level instruction. Only a call one level below
+/// the top-level instruction has a caller the runtime lets us name; deeper
+/// calls give None, so an allowlisted top-level program cannot relay a
+/// removal through an arbitrary intermediary.
+pub fn direct_cpi_caller(stack_height: usize, top_level: Pubkey) -> Option<Pubkey> {
+    use anchor_lang::solana_program::instruction::TRANSACTION_LEVEL_STACK_HEIGHT;
+
+    (stack_height == TRANSACTION_LEVEL_STACK_HEIGHT + 1).then_some(top_level)
+}
+
+/// Whether a game state account of `data_len` bytes holds the current
+/// layout. A smaller one cannot even deserialize, so `MigrationCheck`
+/// tests the raw length before `game_state` is loaded.
+pub fn is_migrated(data_len: usize) -> bool {
+    data_len >= 8 + GameState::INIT_SPACE
+}
+
+/// Check that the instruction just before the current one is an Ed25519
+/// program instruction verifying `signer`'s signature over exactly
+/// `message`, with its key, signature and message all inline. When
+/// `signature` is given, the instruction must verify that very signature.
+pub fn verify_ed25519_signature(
+    instructions: &AccountInfo,
+    signer: &Pubkey,
+    message: &[u8],
+    signature: Option<&[u8; 64]>,
+) -> Result<()> {
+    use anchor_lang::solana_program::ed25519_program;
+    use anchor_lang::solana_program::sysvar::instructions::{
+        load_current_index_checked, load_instruction_at_checked,
+    };
+
+    let current = load_current_index_checked(instructions)? as usize;
+    require!(current > 0, CubeGameError::InvalidSignature);
+    let ix = load_instruction_at_checked(current - 1, instructions)?;
+    require_keys_eq!(
+        ix.program_id,
+        ed25519_program::ID,
+        CubeGameError::InvalidSignature
+    );
+    check_ed25519_data(&ix.data, signer, message, signature)
+}
+
+/// Check Ed25519 program instruction `data` for a single inline signature
+/// by `signer` over exactly `message`, and over `signature` when given
+pub fn check_ed25519_data(
+    data: &[u8],
+    signer: &Pubkey,
+    message: &[u8],
+    signature: Option<&[u8; 64]>,
+) -> Result<()> {
+    // One signature: count and padding, then seven u16 offsets
+    require!(
+        data.len() >= 16 && data[0] == 1,
+        CubeGameError::InvalidSignature
+    );
+    let read = |at: usize| u16::from_le_bytes([data[at], data[at + 1]]) as usize;
+    let inline = u16::MAX as usize;
+    require!(
+        read(4) == inline && read(8) == inline && read(14) == inline,
+        CubeGameError::InvalidSignature
+    );
+    let (signature_at, key_at) = (read(2), read(6));
+    let (message_at, message_len) = (read(10), read(12));
+    if let Some(signature) = signature {
+        require!(
+            data.get(signature_at..signature_at + 64) == Some(&signature[..]),
+            CubeGameError::InvalidSignature
+        );
+    }
+    require!(
+        data.get(key_at..key_at + 32) == Some(signer.as_ref())
+            && data.get(message_at..message_at + message_len) == Some(message),
+        CubeGameError::InvalidSignature
+    );
+    Ok(())
+}
+
+/// Number of removal instructions targeting this program in the current
+/// transaction, read from the instructions sysvar. A batch counts once per
+/// cube it removes, a contribution once whether or not it completes the
+/// cube.
+pub fn count_removals_in_tx(instructions: &AccountInfo) -> Result<usize> {
+    use anchor_lang::solana_program::sysvar::instructions::load_instruction_at_checked;
+    use anchor_lang::Discriminator;
+
+    let mut count = 0;
+    let mut index = 0;
+    while let Ok(ix) = load_instruction_at_checked(index, instructions) {
+        if ix.program_id == crate::ID
+            && (ix.data.starts_with(&instruction::RemoveCube::DISCRIMINATOR)
+                || ix
+                    .data
+                    .starts_with(&instruction::RemoveCubeIdempotent::DISCRIMINATOR)
+                || ix
+                    .data
+                    .starts_with(&instruction::RemoveCubeCompact::DISCRIMINATOR)
+                || ix
+                    .data
+                    .starts_with(&instruction::RemoveCubeInRegion::DISCRIMINATOR)
+                || ix
+                    .data
+                    .starts_with(&instruction::RemoveCubeGated::DISCRIMINATOR)
+                || ix
+                    .data
+                    .starts_with(&instruction::RemoveCubeDelegated::DISCRIMINATOR)
+                || ix.data.starts_with(&instruction::HitCube::DISCRIMINATOR)
+                || ix
+                    .data
+                    .starts_with(&instruction::RemoveCubeSplit::DISCRIMINATOR)
+                || ix
+                    .data
+                    .starts_with(&instruction::RemoveCubeTicketed::DISCRIMINATOR)
+                || ix
+                    .data
+                    .starts_with(&instruction::RevealRemove::DISCRIMINATOR)
+                || ix
+                    .data
+                    .starts_with(&instruction::RemoveCubeMember::DISCRIMINATOR)
+                || ix
+                    .data
+                    .starts_with(&instruction::ContributeToCube::DISCRIMINATOR))
+        {
+            count += 1;
+        } else if ix.program_id == crate::ID
+            && ix
+                .data
+                .starts_with(&instruction::RemoveCubesBatch::DISCRIMINATOR)
+        {
+            // The first argument is `cube_ids`, prefixed by its u32 length
+            let len = ix
+                .data
+                .get(8..12)
+                .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+                .unwrap_or(0);
+            count += len as usize;
+        }
+        index += 1;
+    }
+    Ok(count)
+}
+
+/// The configured `Region` behind a removal's region PDA, or `None` on the
+/// global board and in regions `configure_region` never set up. Regional
+/// removals must always pass the PDA, so a pause cannot be skipped by
+/// leaving the account out.
+pub fn load_region(info: Option<&UncheckedAccount>, region_id: &str) -> Result<Option<Region>> {
+    if region_id.is_empty() {
+        return Ok(None);
+    }
+    let info = info.ok_or(CubeGameError::RegionRequired)?;
+    if info.owner != &crate::ID {
+        return Ok(None);
+    }
+    let data = info.try_borrow_data()?;
+    Ok(Some(Region::try_deserialize(&mut &data[..])?))
+}
+
+/// First seed of a cube record: `b"cube"` on the global board, otherwise
+/// `b"rc"`, the region length and the region. Seeds are hashed without
+/// separators, so the length byte keeps `("ab", "c")` and `("a", "bc")`
+/// apart, and the differing tag keeps regions clear of global ids.
+pub fn cube_record_seed(region_id: &str) -> Vec<u8> {
+    region_scoped_seed(b"cube", region_id)
+}
+
+/// First seed of a per-cube account whose global seed is `global`: `global`
+/// itself on the global board, otherwise `b"r"`, the first byte of
+/// `global`, the region length and the region, as for cube records
+pub fn region_scoped_seed(global: &[u8], region_id: &str) -> Vec<u8> {
+    if region_id.is_empty() {
+        return global.to_vec();
+    }
+    let mut seed = Vec::with_capacity(3 + region_id.len());
+    seed.push(b'r');
+    seed.push(global[0]);
+    seed.push(region_id.len() as u8);
+    seed.extend_from_slice(region_id.as_bytes());
+    seed
+}
+
+/// Status of the cube record in `info`, `None` for any other account or a
+/// record no removal has touched
+pub fn cube_status<'info>(info: &'info AccountInfo<'info>) -> Option<CubeStatus> {
+    let record = Account::<CubeRecord>::try_from(info).ok()?;
+    (!record.cube_id.is_empty()).then(|| CubeStatus {
+        cube_id: record.cube_id.clone(),
+        is_removed: record.is_removed,
+    })
+}
+
+/// Rent a removal pays for the cube record and player stats it would
+/// create, 0 for each that already exists
+pub fn creation_rent(rent: &Rent, record_exists: bool, stats_exists: bool) -> (u64, u64) {
+    let record_rent = if record_exists {
+        0
+    } else {
+        rent.minimum_balance(8 + CubeRecord::INIT_SPACE)
+    };
+    let stats_rent = if stats_exists {
+        0
+    } else {
+        rent.minimum_balance(8 + PlayerStats::INIT_SPACE)
+    };
+    (record_rent, stats_rent)
+}
+
+/// `load_region` for a removal: a configured region must not be paused and
+/// replaces the base price with its curve price; an unconfigured one is
+/// refused while regions are priced
+pub fn load_priced_region(
+    game: &GameState,
+    info: Option<&UncheckedAccount>,
+    region_id: &str,
+) -> Result<(Option<Region>, Option<u64>)> {
+    require!(
+        region_id.len() <= MAX_REGION_LEN,
+        CubeGameError::InvalidRegion
+    );
+    let region = load_region(info, region_id)?;
+    let price = match region.as_ref() {
+        Some(region) => {
+            require!(!region.paused, CubeGameError::RegionPaused);
+            Some(region.curve_price()?)
+        }
+        None => {
+            require!(
+                region_id.is_empty() || !game.region_pricing,
+                CubeGameError::RegionRequired
+            );
+            None
+        }
+    };
+    Ok((region, price))
+}
+
+/// Count `removed` more removals in a region loaded by `load_region`
+pub fn credit_region(
+    region: Option<&mut Region>,
+    info: Option<&UncheckedAccount>,
+    removed: u64,
+) -> Result<()> {
+    if let (Some(region), Some(info)) = (region, info) {
+        region.cubes_removed += removed;
+        region.try_serialize(&mut &mut info.try_borrow_mut_data()?[..])?;
+    }
+    Ok(())
+}
+
+/// Address of the `CubeRecord` for `cube_id` within `region_id`
+pub fn scoped_cube_record_pda(region_id: &str, cube_id: &str) -> Pubkey {
+    Pubkey::find_program_address(
+        &[&cube_record_seed(region_id), cube_id.as_bytes()],
+        &crate::ID,
+    )
+    .0
+}
+
+/// Require a payout destination to be neither the treasury, which would pay
+/// itself, nor the `authority`, who would take the cut back unaccounted
+pub fn require_payout_key(key: &Pubkey, authority: &Pubkey) -> Result<()> {
+    require!(
+        *key != treasury_pda() && key != authority,
+        CubeGameError::InvalidConfiguration
+    );
+    Ok(())
+}
+
+/// Require the treasury PDA to be a plain system account without data
+pub fn require_system_treasury(owner: &Pubkey, data_len: usize) -> Result<()> {
+    require!(
+        *owner == anchor_lang::system_program::ID && data_len == 0,
+        CubeGameError::InvalidTreasury
+    );
+    Ok(())
+}
+
+/// Canonical treasury address. Handlers verify against it explicitly so a
+/// substituted account fails with `InvalidTreasury` rather than an opaque
+/// seeds or transfer error.
+pub fn treasury_pda() -> Pubkey {
+    Pubkey::find_program_address(&[b"treasury"], &crate::ID).0
+}
+
+/// Canonical game state address, derived without trusting the stored bump
+pub fn game_state_pda() -> Pubkey {
+    Pubkey::find_program_address(&[b"game_state"], &crate::ID).0
+}
+
+/// Address of the removal queue of a global-board cube
+pub fn removal_queue_pda(cube_id: &str) -> Pubkey {
+    Pubkey::find_program_address(&[b"queue", cube_id.as_bytes()], &crate::ID).0
+}
+
+/// Address of the `Like` recording that `liker` liked `cube_id` within
+/// `region_id`. There is one per liker, so `like_cube` cannot count a
+/// second like.
+pub fn like_pda(region_id: &str, cube_id: &str, liker: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[
+            &region_scoped_seed(b"like", region_id),
+            cube_id.as_bytes(),
+            liker.as_ref(),
+        ],
+        &crate::ID,
+    )
+    .0
+}
+
+/// Address of the `Snapshot` taken under `label`. Each label has exactly
+/// one, so `take_snapshot` cannot reuse a label.
+pub fn snapshot_pda(label: &str) -> Pubkey {
+    Pubkey::find_program_address(&[b"snapshot", label.as_bytes()], &crate::ID).0
+}
+
+/// A snapshot label is 1 to 32 bytes, the longest its seed and record hold
+pub fn validate_snapshot_label(label: &str) -> Result<()> {
+    require!(
+        !label.is_empty() && label.len() <= 32,
+        CubeGameError::InvalidSnapshotLabel
+    );
+    Ok(())
+}
+
+/// Address of the PDA that must hold the reward mint's mint authority
+pub fn reward_mint_authority_pda() -> Pubkey {
+    Pubkey::find_program_address(&[b"reward_mint_authority"], &crate::ID).0
+}
+
+/// Mint `amount` reward tokens to the player, signing as the mint authority
+/// PDA. Every account is required once a reward mint is configured.
+pub fn mint_milestone_reward<'info>(
+    mint: Option<&Account<'info, Mint>>,
+    to: Option<&Account<'info, TokenAccount>>,
+    authority: Option<&UncheckedAccount<'info>>,
+    token_program: Option<&Program<'info, Token>>,
+    amount: u64,
+) -> Result<()> {
+    let (Some(mint), Some(to), Some(authority), Some(token_program)) =
+        (mint, to, authority, token_program)
+    else {
+        return err!(CubeGameError::RewardAccountsRequired);
+    };
+    let bump = Pubkey::find_program_address(&[b"reward_mint_authority"], &crate::ID).1;
+    anchor_spl::token::mint_to(
+        CpiContext::new_with_signer(
+            token_program.to_account_info(),
+            anchor_spl::token::MintTo {
+                mint: mint.to_account_info(),
+                to: to.to_account_info(),
+                authority: authority.to_account_info(),
+            },
+            &[&[b"reward_mint_authority", &[bump]]],
+        ),
+        amount,
+    )
+}
+
+/// Lamports the treasury can pay out without dropping below rent exemption
+pub fn treasury_spendable(treasury: &AccountInfo) -> Result<u64> {
+    Ok(treasury
+        .lamports()
+        .saturating_sub(Rent::get()?.minimum_balance(treasury.data_len())))
+}
+
+/// Instructions funding `stake_account` with `amount` treasury lamports,
+/// with the treasury as staker and withdrawer, and delegating it to
+/// `vote_account`
+pub fn stake_instructions(
+    treasury: &Pubkey,
+    stake_account: &Pubkey,
+    vote_account: &Pubkey,
+    amount: u64,
+) -> Vec<anchor_lang::solana_program::instruction::Instruction> {
+    let authorized = stake::state::Authorized {
+        staker: *treasury,
+        withdrawer: *treasury,
+    };
+    let mut ixs = stake::instruction::create_account(
+        treasury,
+        stake_account,
+        &authorized,
+        &stake::state::Lockup::default(),
+        amount,
+    );
+    ixs.push(stake::instruction::delegate_stake(
+        stake_account,
+        treasury,
+        vote_account,
+    ));
+    ixs
+}
+
+/// Require the treasury to cover a payout of `amount` that is not a
+/// refund, leaving what refund-all mode still owes removers in place
+pub fn require_treasury_covers(
+    game: &GameState,
+    treasury: &AccountInfo,
+    amount: u64,
+) -> Result<()> {
+    let spendable = treasury_spendable(treasury)?;
+    require!(
+        amount <= spendable,
+        CubeGameError::InsufficientTreasuryBalance
+    );
+    game.require_liabilities_covered(amount, spendable)
+}
+
+/// Count a removal priced at `price` towards the open community goal,
+/// enrolling the player in it when they paid
+pub fn count_toward_goal(
+    game: &mut GameState,
+    goal: Option<&mut Account<CommunityGoal>>,
+    stats: &mut PlayerStats,
+    price: u64,
+    now: i64,
+) -> Result<()> {
+    if !game.community_goal_open {
+        return Ok(());
+    }
+    let goal = goal.ok_or(CubeGameError::CommunityGoalRequired)?;
+    if !goal.is_running(now) {
+        // The deadline passed short of the target
+        game.community_goal_open = false;
+    } else if goal.count_removal(stats, price) {
+        game.community_goal_open = false;
+        emit!(CommunityGoalReachedEvent {
+            goal_id: goal.goal_id,
+            progress: goal.progress,
+            participants: goal.participants,
+        });
+    }
+    Ok(())
+}
+
+/// Require the ledger once it is enabled
+pub fn require_ledger(game: &GameState, ledger: Option<&Account<Ledger>>) -> Result<()> {
+    require!(
+        !game.ledger_enabled || ledger.is_some(),
+        CubeGameError::LedgerRequired
+    );
+    Ok(())
+}
+
+/// Pay `amount` of a prize, reward, rebate, bonus or claimed tip out of the
+/// treasury, booking it in the ledger
+pub fn pay_reward<'info>(
+    game: &GameState,
+    treasury: &AccountInfo<'info>,
+    to: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    ledger: Option<&mut Account<'info, Ledger>>,
+    amount: u64,
+) -> Result<()> {
+    require_ledger(game, ledger.as_deref())?;
+    transfer_from_treasury(treasury, to, system_program, game.treasury_bump, amount)?;
+    if let Some(ledger) = ledger {
+        ledger.record_reward(amount, 0)?;
+    }
+    Ok(())
+}
+
+/// Move lamports out of the system-owned treasury PDA
+pub fn transfer_from_treasury<'info>(
+    treasury: &AccountInfo<'info>,
+    to: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    treasury_bump: u8,
+    amount: u64,
+) -> Result<()> {
+    let transfer_ix =
+        anchor_lang::solana_program::system_instruction::transfer(treasury.key, to.key, amount);
+    anchor_lang::solana_program::program::invoke_signed(
+        &transfer_ix,
+        &[treasury.clone(), to.clone(), system_program.clone()],
+        &[&[b"treasury", &[treasury_bump]]],
+    )?;
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct Initialize<'info> {
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + GameState::INIT_SPACE,
+        seeds = [b"game_state"],
+        bump
+    )]
+    pub game_state: Account<'info, GameState>,
+
+    /// CHECK: Treasury PDA to hold funds
+    #[account(
+        seeds = [b"treasury"],
+        bump
+    )]
+    pub treasury: AccountInfo<'info>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(cube_id: String, args: RemovalArgs)]
+pub struct RemoveCube<'info> {
+    pub migration_check: MigrationCheck<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"game_state"],
+        bump = game_state.bump
+    )]
+    pub game_state: Box<Account<'info, GameState>>,
+
+    #[account(
+        init_if_needed,
+        payer = player,
+        space = 8 + CubeRecord::INIT_SPACE,
+        seeds = [cube_record_seed(&args.region_id).as_slice(), cube_id.as_bytes()],
+        bump
+    )]
+    pub cube_record: Box<Account<'info, CubeRecord>>,
+
+    /// Record of the cube's predecessor, required when it has one
+    pub predecessor: Option<Box<Account<'info, CubeRecord>>>,
+
+    #[account(
+        init_if_needed,
+        payer = player,
+        space = 8 + PlayerStats::INIT_SPACE,
+        seeds = [b"player", player.key().as_ref()],
+        bump
+    )]
+    pub player_stats: Box<Account<'info, PlayerStats>>,
+
+    #[account(
+        seeds = [b"exempt", player.key().as_ref()],
+        bump = fee_exemption.bump
+    )]
+    pub fee_exemption: Option<Box<Account<'info, FeeExemption>>>,
+
+    #[account(
+        mut,
+        seeds = [b"credits", player.key().as_ref()],
+        bump = credits.bump
+    )]
+    pub credits: Option<Box<Account<'info, Credits>>>,
+
+    #[account(
+        mut,
+        seeds = [b"subscription", player.key().as_ref()],
+        bump = subscription.bump
+    )]
+    pub subscription: Option<Box<Account<'info, Subscription>>>,
+
+    #[account(
+        mut,
+        seeds = [b"voucher", player.key().as_ref()],
+        bump = voucher.bump
+    )]
+    pub voucher: Option<Box<Account<'info, PriceVoucher>>>,
+
+    /// Consumed by the removal it confirms
+    #[account(
+        mut,
+        close = player,
+        seeds = [b"intent", player.key().as_ref(), cube_id.as_bytes()],
+        bump = removal_intent.bump
+    )]
+    pub removal_intent: Option<Box<Account<'info, RemovalIntent>>>,
+
+    /// CHECK: Region PDA of `args.region_id`, checked by its seeds and read
+    /// by `load_region`. Required for every regional removal.
+    #[account(
+        mut,
+        seeds = [b"region", args.region_id.as_bytes()],
+        bump
+    )]
+    pub region: Option<UncheckedAccount<'info>>,
+
+    #[account(
+        mut,
+        seeds = [b"bounty", cube_id.as_bytes()],
+        bump = bounty.bump
+    )]
+    pub bounty: Option<Box<Account<'info, Bounty>>>,
+
+    /// CHECK: Removal queue of the cube, which may not exist yet; required
+    /// while queueing is on
+    #[account(mut, address = removal_queue_pda(&cube_id) @ CubeGameError::QueueRequired)]
+    pub removal_queue: Option<UncheckedAccount<'info>>,
+
+    #[account(
+        mut,
+        seeds = [b"inventory", player.key().as_ref()],
+        bump = inventory.bump
+    )]
+    pub inventory: Option<Box<Account<'info, Inventory>>>,
+
+    #[account(mut, seeds = [b"analytics"], bump = analytics.bump)]
+    pub analytics: Option<Box<Account<'info, Analytics>>>,
+
+    /// Stats of the player's referrer, credited with the referral cut
+    #[account(
+        mut,
+        seeds = [b"player", player_stats.referrer.as_ref()],
+        bump
+    )]
+    pub referrer_stats: Option<Box<Account<'info, PlayerStats>>>,
+
+    #[account(
+        mut,
+        seeds = [b"team", player_stats.team_id.as_bytes()],
+        bump = team.bump
+    )]
+    pub team: Option<Box<Account<'info, Team>>>,
+
+    #[account(
+        mut,
+        seeds = [b"refund_pool"],
+        bump = refund_pool.bump
+    )]
+    pub refund_pool: Option<Box<Account<'info, RefundPool>>>,
+
+    #[account(
+        mut,
+        seeds = [b"insurance"],
+        bump = insurance_vault.bump
+    )]
+    pub insurance_vault: Option<Box<Account<'info, InsuranceVault>>>,
+
+    /// CHECK: Only receives burned lamports, checked by address
+    #[account(
+        mut,
+        address = anchor_lang::solana_program::incinerator::ID
+    )]
+    pub incinerator: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: Only receives the DAO tax, must match the configured DAO treasury
+    #[account(
+        mut,
+        address = game_state.dao_treasury @ CubeGameError::DaoTreasuryMismatch
+    )]
+    pub dao_treasury: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: Only receives the royalty, must be the cube's creator
+    #[account(
+        mut,
+        address = cube_record.creator @ CubeGameError::CreatorMismatch
+    )]
+    pub creator: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: External program removals are mirrored into, checked by address
+    #[account(
+        address = game_state.analytics_program @ CubeGameError::AnalyticsProgramRequired
+    )]
+    pub analytics_program: Option<UncheckedAccount<'info>>,
+
+    #[account(mut, seeds = [b"staking_pool"], bump = staking_pool.bump)]
+    pub staking_pool: Option<Box<Account<'info, StakingPool>>>,
+
+    #[account(
+        mut,
+        seeds = [b"history", player.key().as_ref()],
+        bump = player_history.bump
+    )]
+    pub player_history: Option<Box<Account<'info, PlayerHistory>>>,
+
+    #[account(mut, seeds = [b"message_wall"], bump)]
+    pub message_wall: Option<Box<Account<'info, MessageWall>>>,
+
+    #[account(mut, seeds = [b"community_goal"], bump = community_goal.bump)]
+    pub community_goal: Option<Box<Account<'info, CommunityGoal>>>,
+
+    #[account(
+        mut,
+        seeds = [b"commitment", player.key().as_ref()],
+        bump = removal_commitment.bump
+    )]
+    pub removal_commitment: Option<Box<Account<'info, RemovalCommitment>>>,
+
+    /// CHECK: Treasury PDA, checked against the canonical derivation
+    #[account(
+        mut,
+        address = treasury_pda() @ CubeGameError::InvalidTreasury
+    )]
+    pub treasury: AccountInfo<'info>,
+
+    #[account(mut, seeds = [b"ledger"], bump = ledger.bump)]
+    pub ledger: Option<Box<Account<'info, Ledger>>>,
+
+    #[account(
+        mut,
+        seeds = [b"activity_feed"],
+        bump
+    )]
+    pub activity_feed: Option<Box<Account<'info, ActivityFeed>>>,
+
+    #[account(
+        mut,
+        seeds = [b"zone", zone.prefix.as_bytes()],
+        bump = zone.bump
+    )]
+    pub zone: Option<Box<Account<'info, Zone>>>,
+
+    /// Zone after `zone`, unlocked when this removal clears it
+    #[account(
+        mut,
+        seeds = [b"zone", next_zone.prefix.as_bytes()],
+        bump = next_zone.bump
+    )]
+    pub next_zone: Option<Box<Account<'info, Zone>>>,
+
+    /// Player's token account for the gating NFT
+    pub nft_token_account: Option<Box<Account<'info, TokenAccount>>>,
+
+    /// Token Metadata account of the gating NFT
+    pub nft_metadata: Option<Box<Account<'info, MetadataAccount>>>,
+
+    #[account(
+        mut,
+        address = game_state.reward_mint @ CubeGameError::RewardMintMismatch
+    )]
+    pub reward_mint: Option<Box<Account<'info, Mint>>>,
+
+    /// Player's token account receiving milestone rewards
+    #[account(
+        mut,
+        token::mint = game_state.reward_mint,
+        token::authority = player
+    )]
+    pub reward_token_account: Option<Box<Account<'info, TokenAccount>>>,
+
+    /// CHECK: PDA holding the reward mint's authority, checked by address
+    #[account(address = reward_mint_authority_pda())]
+    pub reward_mint_authority: Option<UncheckedAccount<'info>>,
+
+    /// Player's token account paying the token part of a split payment
+    #[account(
+        mut,
+        token::mint = game_state.payment_mint,
+        token::authority = player
+    )]
+    pub payment_token_account: Option<Box<Account<'info, TokenAccount>>>,
+
+    #[account(
+        mut,
+        address = game_state.token_treasury @ CubeGameError::TokenTreasuryMismatch
+    )]
+    pub token_treasury: Option<Box<Account<'info, TokenAccount>>>,
+
+    /// Payment mint, required to burn part of a split payment
+    #[account(
+        mut,
+        address = game_state.payment_mint @ CubeGameError::PaymentMintMismatch
+    )]
+    pub payment_mint: Option<Box<Account<'info, Mint>>>,
+
+    /// CHECK: Pyth price account, checked by address and parsed by
+    /// `OraclePrice::from_pyth`
+    #[account(address = game_state.price_feed @ CubeGameError::PriceFeedMismatch)]
+    pub price_feed: Option<UncheckedAccount<'info>>,
+
+    pub token_program: Option<Program<'info, Token>>,
+
+    /// CHECK: Only receives lamports, must match the configured cold wallet
+    #[account(
+        mut,
+        address = game_state.cold_wallet @ CubeGameError::ColdWalletMismatch
+    )]
+    pub cold_wallet: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: Instructions sysvar, checked by address
+    #[account(address = anchor_lang::solana_program::sysvar::instructions::ID)]
+    pub instructions: Option<UncheckedAccount<'info>>,
+
+    #[account(mut)]
+    pub player: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+
+    /// The player's permanent mark, required on their first removal and
+    /// for the onboarding refund
+    #[account(
+        init_if_needed,
+        payer = player,
+        space = 8 + WalletMark::INIT_SPACE,
+        seeds = [b"wallet_mark", player.key().as_ref()],
+        bump
+    )]
+    pub wallet_mark: Option<Box<Account<'info, WalletMark>>>,
+}
+
+#[derive(Accounts)]
+pub struct RemoveCubesBatch<'info> {
+    pub migration_check: MigrationCheck<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"game_state"],
+        bump = game_state.bump
+    )]
+    pub game_state: Account<'info, GameState>,
+
+    #[account(
+        init_if_needed,
+        payer = player,
+        space = 8 + PlayerStats::INIT_SPACE,
+        seeds = [b"player", player.key().as_ref()],
+        bump
+    )]
+    pub player_stats: Account<'info, PlayerStats>,
+
+    #[account(
+        seeds = [b"exempt", player.key().as_ref()],
+        bump = fee_exemption.bump
+    )]
+    pub fee_exemption: Option<Account<'info, FeeExemption>>,
+
+    #[account(
+        mut,
+        seeds = [b"team", player_stats.team_id.as_bytes()],
+        bump = team.bump
+    )]
+    pub team: Option<Account<'info, Team>>,
+
+    #[account(
+        mut,
+        seeds = [b"refund_pool"],
+        bump = refund_pool.bump
+    )]
+    pub refund_pool: Option<Account<'info, RefundPool>>,
+
+    #[account(
+        mut,
+        seeds = [b"insurance"],
+        bump = insurance_vault.bump
+    )]
+    pub insurance_vault: Option<Account<'info, InsuranceVault>>,
+
+    /// CHECK: Only receives burned lamports, checked by address
+    #[account(
+        mut,
+        address = anchor_lang::solana_program::incinerator::ID
+    )]
+    pub incinerator: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: Only receives the DAO tax, must match the configured DAO treasury
+    #[account(
+        mut,
+        address = game_state.dao_treasury @ CubeGameError::DaoTreasuryMismatch
+    )]
+    pub dao_treasury: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: Treasury PDA, checked against the canonical derivation
+    #[account(
+        mut,
+        address = treasury_pda() @ CubeGameError::InvalidTreasury
+    )]
+    pub treasury: AccountInfo<'info>,
+
+    #[account(mut, seeds = [b"ledger"], bump = ledger.bump)]
+    pub ledger: Option<Box<Account<'info, Ledger>>>,
+
+    #[account(mut, seeds = [b"analytics"], bump = analytics.bump)]
+    pub analytics: Option<Account<'info, Analytics>>,
+
+    #[account(
+        mut,
+        seeds = [b"activity_feed"],
+        bump
+    )]
+    pub activity_feed: Option<Account<'info, ActivityFeed>>,
+
+    #[account(
+        mut,
+        seeds = [b"zone", zone.prefix.as_bytes()],
+        bump = zone.bump
+    )]
+    pub zone: Option<Account<'info, Zone>>,
+
+    /// Zone after `zone`, unlocked when this removal clears it
+    #[account(
+        mut,
+        seeds = [b"zone", next_zone.prefix.as_bytes()],
+        bump = next_zone.bump
+    )]
+    pub next_zone: Option<Account<'info, Zone>>,
+
+    /// CHECK: Only receives lamports, must match the configured cold wallet
+    #[account(
+        mut,
+        address = game_state.cold_wallet @ CubeGameError::ColdWalletMismatch
+    )]
+    pub cold_wallet: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: Instructions sysvar, checked by address
+    #[account(address = anchor_lang::solana_program::sysvar::instructions::ID)]
+    pub instructions: Option<UncheckedAccount<'info>>,
+
+    #[account(mut)]
+    pub player: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+
+    #[account(mut, seeds = [b"community_goal"], bump = community_goal.bump)]
+    pub community_goal: Option<Box<Account<'info, CommunityGoal>>>,
+
+    #[account(
+        mut,
+        seeds = [b"history", player.key().as_ref()],
+        bump = player_history.bump
+    )]
+    pub player_history: Option<Box<Account<'info, PlayerHistory>>>,
+
+    /// The player's permanent mark, required on their first removal
+    #[account(
+        init_if_needed,
+        payer = player,
+        space = 8 + WalletMark::INIT_SPACE,
+        seeds = [b"wallet_mark", player.key().as_ref()],
+        bump
+    )]
+    pub wallet_mark: Option<Box<Account<'info, WalletMark>>>,
+}
+
+#[derive(Accounts)]
+#[instruction(cube_id: String, region_id: String)]
+pub struct ContributeToCube<'info> {
+    pub migration_check: MigrationCheck<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"game_state"],
+        bump = game_state.bump
+    )]
+    pub game_state: Account<'info, GameState>,
+
+    #[account(
+        init_if_needed,
+        payer = contributor,
+        space = 8 + CubeRecord::INIT_SPACE,
+        seeds = [cube_record_seed(&region_id).as_slice(), cube_id.as_bytes()],
+        bump
+    )]
+    pub cube_record: Account<'info, CubeRecord>,
+
+    #[account(
+        init_if_needed,
+        payer = contributor,
+        space = 8 + CubeShares::INIT_SPACE,
+        seeds = [region_scoped_seed(b"shares", &region_id).as_slice(), cube_id.as_bytes()],
+        bump
+    )]
+    pub cube_shares: Account<'info, CubeShares>,
+
+    /// CHECK: Region PDA of `region_id`, checked by its seeds and read by
+    /// `load_region`. Required for every regional removal.
+    #[account(
+        mut,
+        seeds = [b"region", region_id.as_bytes()],
+        bump
+    )]
+    pub region: Option<UncheckedAccount<'info>>,
+
+    /// Stats of the contributor, checked for a freeze and the minimum age
+    #[account(
+        init_if_needed,
+        payer = contributor,
+        space = 8 + PlayerStats::INIT_SPACE,
+        seeds = [b"player", contributor.key().as_ref()],
+        bump
+    )]
+    pub player_stats: Account<'info, PlayerStats>,
+
+    /// Stats of the largest contributor, credited with the removal when
+    /// this contribution completes a cube owned by someone else
+    #[account(mut)]
+    pub owner_stats: Option<Account<'info, PlayerStats>>,
+
+    /// History of the largest contributor, required when they keep one and
+    /// this contribution completes the cube
+    #[account(mut)]
+    pub owner_history: Option<Box<Account<'info, PlayerHistory>>>,
+
+    #[account(
+        mut,
+        seeds = [b"activity_feed"],
+        bump
+    )]
+    pub activity_feed: Option<Account<'info, ActivityFeed>>,
+
+    #[account(
+        mut,
+        seeds = [b"zone", zone.prefix.as_bytes()],
+        bump = zone.bump
+    )]
+    pub zone: Option<Account<'info, Zone>>,
+
+    /// Zone after `zone`, unlocked when this removal clears it
+    #[account(
+        mut,
+        seeds = [b"zone", next_zone.prefix.as_bytes()],
+        bump = next_zone.bump
+    )]
+    pub next_zone: Option<Account<'info, Zone>>,
+
+    #[account(
+        mut,
+        seeds = [b"refund_pool"],
+        bump = refund_pool.bump
+    )]
+    pub refund_pool: Option<Account<'info, RefundPool>>,
+
+    #[account(
+        mut,
+        seeds = [b"insurance"],
+        bump = insurance_vault.bump
+    )]
+    pub insurance_vault: Option<Account<'info, InsuranceVault>>,
+
+    /// CHECK: Only receives burned lamports, checked by address
+    #[account(
+        mut,
+        address = anchor_lang::solana_program::incinerator::ID
+    )]
+    pub incinerator: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: Only receives the DAO tax, must match the configured DAO treasury
+    #[account(
+        mut,
+        address = game_state.dao_treasury @ CubeGameError::DaoTreasuryMismatch
+    )]
+    pub dao_treasury: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: Treasury PDA, checked against the canonical derivation
+    #[account(
+        mut,
+        address = treasury_pda() @ CubeGameError::InvalidTreasury
+    )]
+    pub treasury: AccountInfo<'info>,
+
+    #[account(mut, seeds = [b"ledger"], bump = ledger.bump)]
+    pub ledger: Option<Box<Account<'info, Ledger>>>,
+
+    #[account(mut, seeds = [b"analytics"], bump = analytics.bump)]
+    pub analytics: Option<Account<'info, Analytics>>,
+
+    /// CHECK: Instructions sysvar, checked by address
+    #[account(address = anchor_lang::solana_program::sysvar::instructions::ID)]
+    pub instructions: Option<UncheckedAccount<'info>>,
+
+    #[account(mut)]
+    pub contributor: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(cube_id: String, region_id: String)]
+pub struct TransferCube<'info> {
+    #[account(
+        seeds = [b"game_state"],
+        bump = game_state.bump
+    )]
+    pub game_state: Account<'info, GameState>,
+
+    #[account(
+        mut,
+        seeds = [cube_record_seed(&region_id).as_slice(), cube_id.as_bytes()],
+        bump,
+        constraint = cube_record.removed_by == owner.key() @ CubeGameError::NotCubeOwner
+    )]
+    pub cube_record: Account<'info, CubeRecord>,
+
+    #[account(
+        mut,
+        seeds = [b"activity_feed"],
+        bump
+    )]
+    pub activity_feed: Option<Account<'info, ActivityFeed>>,
+
+    pub owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct GetGameInfo<'info> {
+    #[account(
+        seeds = [b"game_state"],
+        bump = game_state.bump
+    )]
+    pub game_state: Account<'info, GameState>,
+}
+
+#[derive(Accounts)]
+#[instruction(player: Pubkey)]
+pub struct QuoteNextPrice<'info> {
+    #[account(
+        seeds = [b"game_state"],
+        bump = game_state.bump
+    )]
+    pub game_state: Account<'info, GameState>,
+
+    #[account(
+        seeds = [b"exempt", player.as_ref()],
+        bump = fee_exemption.bump
+    )]
+    pub fee_exemption: Option<Account<'info, FeeExemption>>,
+}
+
+#[derive(Accounts)]
+#[instruction(cube_id: String, region_id: String, player: Pubkey)]
+pub struct QuoteRemovalCost<'info> {
+    #[account(
+        seeds = [b"game_state"],
+        bump = game_state.bump
+    )]
+    pub game_state: Account<'info, GameState>,
+
+    #[account(
+        seeds = [b"exempt", player.as_ref()],
+        bump = fee_exemption.bump
+    )]
+    pub fee_exemption: Option<Account<'info, FeeExemption>>,
+
+    /// CHECK: Only probed for existence, derived from the region and cube id
+    #[account(seeds = [cube_record_seed(&region_id).as_slice(), cube_id.as_bytes()], bump)]
+    pub cube_record: UncheckedAccount<'info>,
+
+    /// CHECK: Only probed for existence, derived from the player
+    #[account(seeds = [b"player", player.as_ref()], bump)]
+    pub player_stats: UncheckedAccount<'info>,
+
+    /// CHECK: Region PDA of `region_id`, checked by its seeds and read by
+    /// `load_region`. Required to quote a regional removal.
+    #[account(seeds = [b"region", region_id.as_bytes()], bump)]
+    pub region: Option<UncheckedAccount<'info>>,
+}
+
+#[derive(Accounts)]
+#[instruction(player: Pubkey)]
+pub struct ScaledCubesRemoved<'info> {
+    #[account(
+        seeds = [b"game_state"],
+        bump = game_state.bump
+    )]
+    pub game_state: Account<'info, GameState>,
+
+    #[account(seeds = [b"player", player.as_ref()], bump)]
+    pub player_stats: Account<'info, PlayerStats>,
+}
+
+#[derive(Accounts)]
+pub struct AttestStats<'info> {
+    #[account(
+        seeds = [b"game_state"],
+        bump = game_state.bump
+    )]
+    pub game_state: Account<'info, GameState>,
+
+    /// CHECK: Treasury PDA, only its balance is read
+    #[account(address = treasury_pda() @ CubeGameError::InvalidTreasury)]
+    pub treasury: AccountInfo<'info>,
+
+    /// CHECK: Instructions sysvar, checked by address
+    #[account(address = anchor_lang::solana_program::sysvar::instructions::ID)]
+    pub instructions: Option<UncheckedAccount<'info>>,
+}
+
+#[derive(Accounts)]
+pub struct CheckCubes {}
+
+#[derive(Accounts)]
+pub struct RankPlayers<'info> {
+    #[account(
+        seeds = [b"game_state"],
+        bump = game_state.bump
+    )]
+    pub game_state: Account<'info, GameState>,
+}
+
+#[derive(Accounts)]
+pub struct AddLeaderboardPage<'info> {
+    #[account(
+        seeds = [b"game_state"],
+        bump = game_state.bump,
+        has_one = authority @ CubeGameError::Unauthorized
+    )]
+    pub game_state: Account<'info, GameState>,
+
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = 8 + LeaderboardMeta::INIT_SPACE,
+        seeds = [b"leaderboard_meta"],
+        bump
+    )]
+    pub leaderboard_meta: Account<'info, LeaderboardMeta>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + LeaderboardPage::INIT_SPACE,
+        seeds = [b"leaderboard", leaderboard_meta.page_count.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub leaderboard_page: Account<'info, LeaderboardPage>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(player: Pubkey)]
+pub struct UpdateLeaderboard<'info> {
+    #[account(seeds = [b"game_state"], bump = game_state.bump)]
+    pub game_state: Account<'info, GameState>,
+
+    #[account(mut, seeds = [b"leaderboard_meta"], bump = leaderboard_meta.bump)]
+    pub leaderboard_meta: Account<'info, LeaderboardMeta>,
+
+    #[account(mut, seeds = [b"player", player.as_ref()], bump)]
+    pub player_stats: Account<'info, PlayerStats>,
+}
+
+#[derive(Accounts)]
+pub struct GetErrorCatalog {}
+
+#[derive(Accounts)]
+pub struct QuoteBatchLimit {}
+
+#[derive(Accounts)]
+pub struct PlayerSettings<'info> {
+    #[account(
+        init_if_needed,
+        payer = player,
+        space = 8 + PlayerStats::INIT_SPACE,
+        seeds = [b"player", player.key().as_ref()],
+        bump
+    )]
+    pub player_stats: Account<'info, PlayerStats>,
+
+    #[account(mut)]
+    pub player: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(cube_id: String, region_id: String)]
+pub struct ClaimFullRefund<'info> {
+    #[account(
+        mut,
+        seeds = [b"game_state"],
+        bump = game_state.bump
+    )]
+    pub game_state: Account<'info, GameState>,
+
+    #[account(
+        mut,
+        seeds = [cube_record_seed(&region_id).as_slice(), cube_id.as_bytes()],
+        bump
+    )]
+    pub cube_record: Account<'info, CubeRecord>,
+
+    #[account(
+        mut,
+        seeds = [b"refund_pool"],
+        bump = refund_pool.bump
+    )]
+    pub refund_pool: Option<Account<'info, RefundPool>>,
+
+    #[account(mut, seeds = [b"analytics"], bump = analytics.bump)]
+    pub analytics: Option<Account<'info, Analytics>>,
+
+    /// CHECK: Treasury PDA, checked against the canonical derivation
+    #[account(
+        mut,
+        address = treasury_pda() @ CubeGameError::InvalidTreasury
+    )]
+    pub treasury: AccountInfo<'info>,
+
+    #[account(mut, seeds = [b"ledger"], bump = ledger.bump)]
+    pub ledger: Option<Box<Account<'info, Ledger>>>,
+
+    #[account(mut)]
+    pub player: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(cube_id: String)]
+pub struct RemoveCubePractice<'info> {
+    #[account(seeds = [b"game_state"], bump = game_state.bump)]
+    pub game_state: Account<'info, GameState>,
+
+    #[account(mut, seeds = [b"player", player.key().as_ref()], bump)]
+    pub player_stats: Account<'info, PlayerStats>,
+
+    #[account(
+        init_if_needed,
+        payer = player,
+        space = 8 + PracticeRecord::INIT_SPACE,
+        seeds = [b"practice", player.key().as_ref(), cube_id.as_bytes()],
+        bump
+    )]
+    pub practice_record: Account<'info, PracticeRecord>,
+
+    #[account(mut)]
+    pub player: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(referrer: Pubkey)]
+pub struct SetReferrer<'info> {
+    #[account(
+        init_if_needed,
+        payer = player,
+        space = 8 + PlayerStats::INIT_SPACE,
+        seeds = [b"player", player.key().as_ref()],
+        bump
+    )]
+    pub player_stats: Account<'info, PlayerStats>,
+
+    #[account(seeds = [b"player", referrer.as_ref()], bump)]
+    pub referrer_stats: Account<'info, PlayerStats>,
+
+    #[account(mut)]
+    pub player: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimReferralEarnings<'info> {
+    #[account(seeds = [b"game_state"], bump = game_state.bump)]
+    pub game_state: Account<'info, GameState>,
+
+    #[account(
+        mut,
+        seeds = [b"player", player.key().as_ref()],
+        bump
+    )]
+    pub player_stats: Account<'info, PlayerStats>,
+
+    /// CHECK: Treasury PDA, checked against the canonical derivation
+    #[account(mut, address = treasury_pda() @ CubeGameError::InvalidTreasury)]
+    pub treasury: AccountInfo<'info>,
+
+    #[account(mut)]
+    pub player: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+
+    #[account(mut, seeds = [b"ledger"], bump = ledger.bump)]
+    pub ledger: Option<Box<Account<'info, Ledger>>>,
+}
+
+#[derive(Accounts)]
+#[instruction(cube_id: String, region_id: String)]
+pub struct ClaimTip<'info> {
+    #[account(seeds = [b"game_state"], bump = game_state.bump)]
+    pub game_state: Account<'info, GameState>,
+
+    #[account(
+        mut,
+        seeds = [cube_record_seed(&region_id).as_slice(), cube_id.as_bytes()],
+        bump
+    )]
+    pub cube_record: Account<'info, CubeRecord>,
+
+    /// CHECK: Treasury PDA, checked against the canonical derivation
+    #[account(mut, address = treasury_pda() @ CubeGameError::InvalidTreasury)]
+    pub treasury: AccountInfo<'info>,
+
+    #[account(mut)]
+    pub player: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+
+    #[account(mut, seeds = [b"ledger"], bump = ledger.bump)]
+    pub ledger: Option<Box<Account<'info, Ledger>>>,
+}
+
+#[derive(Accounts)]
+#[instruction(team_id: String)]
+pub struct JoinTeam<'info> {
+    #[account(
+        init_if_needed,
+        payer = player,
+        space = 8 + PlayerStats::INIT_SPACE,
+        seeds = [b"player", player.key().as_ref()],
+        bump
+    )]
+    pub player_stats: Account<'info, PlayerStats>,
+
+    #[account(
+        init_if_needed,
+        payer = player,
+        space = 8 + Team::INIT_SPACE,
+        seeds = [b"team", team_id.as_bytes()],
+        bump
+    )]
+    pub team: Account<'info, Team>,
+
+    #[account(mut)]
+    pub player: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(delegate: Pubkey)]
+pub struct AuthorizeDelegate<'info> {
+    #[account(
+        init_if_needed,
+        payer = player,
+        space = 8 + Delegation::INIT_SPACE,
+        seeds = [b"delegate", player.key().as_ref(), delegate.as_ref()],
+        bump
+    )]
+    pub delegation: Account<'info, Delegation>,
+
+    #[account(mut)]
+    pub player: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(delegate: Pubkey)]
+pub struct RevokeDelegate<'info> {
+    #[account(
+        mut,
+        close = player,
+        seeds = [b"delegate", player.key().as_ref(), delegate.as_ref()],
+        bump = delegation.bump
+    )]
+    pub delegation: Account<'info, Delegation>,
+
+    #[account(mut)]
+    pub player: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(cube_id: String, region_id: String)]
+pub struct RemoveCubeDelegated<'info> {
+    pub migration_check: MigrationCheck<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"game_state"],
+        bump = game_state.bump
+    )]
+    pub game_state: Account<'info, GameState>,
+
+    #[account(
+        init_if_needed,
+        payer = delegate,
+        space = 8 + CubeRecord::INIT_SPACE,
+        seeds = [cube_record_seed(&region_id).as_slice(), cube_id.as_bytes()],
+        bump
+    )]
+    pub cube_record: Account<'info, CubeRecord>,
+
+    /// CHECK: Region PDA of `region_id`, checked by its seeds and read by
+    /// `load_region`. Required for every regional removal.
+    #[account(
+        mut,
+        seeds = [b"region", region_id.as_bytes()],
+        bump
+    )]
+    pub region: Option<UncheckedAccount<'info>>,
+
+    #[account(
+        init_if_needed,
+        payer = delegate,
+        space = 8 + PlayerStats::INIT_SPACE,
+        seeds = [b"player", player.key().as_ref()],
+        bump
+    )]
+    pub player_stats: Account<'info, PlayerStats>,
+
+    #[account(
+        mut,
+        seeds = [b"credits", player.key().as_ref()],
+        bump = credits.bump
+    )]
+    pub credits: Account<'info, Credits>,
+
+    #[account(
+        mut,
+        seeds = [b"delegate", player.key().as_ref(), delegate.key().as_ref()],
+        bump = delegation.bump
+    )]
+    pub delegation: Account<'info, Delegation>,
+
+    #[account(
+        mut,
+        seeds = [b"team", player_stats.team_id.as_bytes()],
+        bump
+    )]
+    pub team: Option<Account<'info, Team>>,
+
+    #[account(
+        mut,
+        seeds = [b"activity_feed"],
+        bump
+    )]
+    pub activity_feed: Option<Account<'info, ActivityFeed>>,
+
+    #[account(mut, seeds = [b"analytics"], bump = analytics.bump)]
+    pub analytics: Option<Account<'info, Analytics>>,
+
+    #[account(
+        mut,
+        seeds = [b"zone", zone.prefix.as_bytes()],
+        bump = zone.bump
+    )]
+    pub zone: Option<Account<'info, Zone>>,
+
+    #[account(
+        mut,
+        seeds = [b"zone", next_zone.prefix.as_bytes()],
+        bump = next_zone.bump
+    )]
+    pub next_zone: Option<Account<'info, Zone>>,
+
+    /// CHECK: Instructions sysvar, checked by address
+    #[account(address = anchor_lang::solana_program::sysvar::instructions::ID)]
+    pub instructions: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: The player whose credits fund the removal, bound by the
+    /// delegation seeds
+    pub player: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub delegate: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+
+    #[account(
+        mut,
+        seeds = [b"history", player.key().as_ref()],
+        bump = player_history.bump
+    )]
+    pub player_history: Option<Box<Account<'info, PlayerHistory>>>,
+
+    /// The player's permanent mark, required on their first removal
+    #[account(
+        init_if_needed,
+        payer = delegate,
+        space = 8 + WalletMark::INIT_SPACE,
+        seeds = [b"wallet_mark", player.key().as_ref()],
+        bump
+    )]
+    pub wallet_mark: Option<Box<Account<'info, WalletMark>>>,
+}
+
+#[derive(Accounts)]
+pub struct BuyCredits<'info> {
+    #[account(
+        mut,
+        seeds = [b"game_state"],
+        bump = game_state.bump
+    )]
+    pub game_state: Account<'info, GameState>,
+
+    #[account(
+        init_if_needed,
+        payer = player,
+        space = 8 + Credits::INIT_SPACE,
+        seeds = [b"credits", player.key().as_ref()],
+        bump
+    )]
+    pub credits: Account<'info, Credits>,
+
+    #[account(
+        mut,
+        seeds = [b"refund_pool"],
+        bump = refund_pool.bump
+    )]
+    pub refund_pool: Option<Account<'info, RefundPool>>,
+
+    #[account(
+        mut,
+        seeds = [b"insurance"],
+        bump = insurance_vault.bump
+    )]
+    pub insurance_vault: Option<Account<'info, InsuranceVault>>,
+
+    /// CHECK: Only receives burned lamports, checked by address
+    #[account(
+        mut,
+        address = anchor_lang::solana_program::incinerator::ID
+    )]
+    pub incinerator: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: Only receives the DAO tax, must match the configured DAO treasury
+    #[account(
+        mut,
+        address = game_state.dao_treasury @ CubeGameError::DaoTreasuryMismatch
+    )]
+    pub dao_treasury: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: Treasury PDA, checked against the canonical derivation
+    #[account(
+        mut,
+        address = treasury_pda() @ CubeGameError::InvalidTreasury
+    )]
+    pub treasury: AccountInfo<'info>,
+
+    #[account(mut, seeds = [b"ledger"], bump = ledger.bump)]
+    pub ledger: Option<Box<Account<'info, Ledger>>>,
+
+    #[account(mut)]
+    pub player: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct InitStakingPool<'info> {
+    #[account(
+        seeds = [b"game_state"],
+        bump = game_state.bump,
+        has_one = authority @ CubeGameError::Unauthorized
+    )]
+    pub game_state: Account<'info, GameState>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + StakingPool::INIT_SPACE,
+        seeds = [b"staking_pool"],
+        bump
+    )]
+    pub staking_pool: Account<'info, StakingPool>,
+
+    /// Game token that is staked
+    pub mint: Account<'info, Mint>,
+
+    #[account(
+        init,
+        payer = authority,
+        token::mint = mint,
+        token::authority = staking_pool,
+        seeds = [b"stake_vault"],
+        bump
+    )]
+    pub vault: Account<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub token_program: Program<'info, Token>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct StakeTokens<'info> {
+    #[account(mut, seeds = [b"staking_pool"], bump = staking_pool.bump)]
+    pub staking_pool: Account<'info, StakingPool>,
+
+    #[account(
+        init_if_needed,
+        payer = staker,
+        space = 8 + Stake::INIT_SPACE,
+        seeds = [b"stake", staker.key().as_ref()],
+        bump
+    )]
+    pub stake: Account<'info, Stake>,
+
+    #[account(
+        mut,
+        address = staking_pool.vault @ CubeGameError::StakeVaultMismatch
+    )]
+    pub vault: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        token::mint = staking_pool.mint,
+        token::authority = staker
+    )]
+    pub staker_token_account: Account<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub staker: Signer<'info>,
+
+    pub token_program: Program<'info, Token>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimStakingRewards<'info> {
+    #[account(mut, seeds = [b"staking_pool"], bump = staking_pool.bump)]
+    pub staking_pool: Account<'info, StakingPool>,
+
+    #[account(
+        mut,
+        seeds = [b"stake", staker.key().as_ref()],
+        bump = stake.bump
+    )]
+    pub stake: Account<'info, Stake>,
+
+    #[account(mut)]
+    pub staker: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(cube_id: String, region_id: String)]
+pub struct UnlockCube<'info> {
+    pub migration_check: MigrationCheck<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"game_state"],
+        bump = game_state.bump
+    )]
+    pub game_state: Account<'info, GameState>,
+
+    #[account(
+        init_if_needed,
+        payer = player,
+        space = 8 + CubeRecord::INIT_SPACE,
+        seeds = [cube_record_seed(&region_id).as_slice(), cube_id.as_bytes()],
+        bump
+    )]
+    pub cube_record: Account<'info, CubeRecord>,
+
+    #[account(
+        mut,
+        seeds = [b"refund_pool"],
+        bump = refund_pool.bump
+    )]
+    pub refund_pool: Option<Account<'info, RefundPool>>,
+
+    #[account(
+        mut,
+        seeds = [b"insurance"],
+        bump = insurance_vault.bump
+    )]
+    pub insurance_vault: Option<Account<'info, InsuranceVault>>,
+
+    /// CHECK: Only receives burned lamports, checked by address
+    #[account(
+        mut,
+        address = anchor_lang::solana_program::incinerator::ID
+    )]
+    pub incinerator: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: Only receives the DAO tax, must match the configured DAO treasury
+    #[account(
+        mut,
+        address = game_state.dao_treasury @ CubeGameError::DaoTreasuryMismatch
+    )]
+    pub dao_treasury: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: Treasury PDA, checked against the canonical derivation
+    #[account(
+        mut,
+        address = treasury_pda() @ CubeGameError::InvalidTreasury
+    )]
+    pub treasury: AccountInfo<'info>,
+
+    #[account(mut, seeds = [b"ledger"], bump = ledger.bump)]
+    pub ledger: Option<Box<Account<'info, Ledger>>>,
+
+    #[account(mut)]
+    pub player: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(cube_id: String, region_id: String)]
+pub struct RevealCube<'info> {
+    #[account(
+        mut,
+        seeds = [cube_record_seed(&region_id).as_slice(), cube_id.as_bytes()],
+        bump
+    )]
+    pub cube_record: Account<'info, CubeRecord>,
+
+    /// CHECK: SlotHashes sysvar, checked by address and parsed by hand
+    #[account(address = anchor_lang::solana_program::sysvar::slot_hashes::ID)]
+    pub slot_hashes: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct BuySubscription<'info> {
+    #[account(
+        mut,
+        seeds = [b"game_state"],
+        bump = game_state.bump
+    )]
+    pub game_state: Account<'info, GameState>,
+
+    #[account(
+        init_if_needed,
+        payer = player,
+        space = 8 + Subscription::INIT_SPACE,
+        seeds = [b"subscription", player.key().as_ref()],
+        bump
+    )]
+    pub subscription: Account<'info, Subscription>,
+
+    #[account(
+        mut,
+        seeds = [b"refund_pool"],
+        bump = refund_pool.bump
+    )]
+    pub refund_pool: Option<Account<'info, RefundPool>>,
+
+    #[account(
+        mut,
+        seeds = [b"insurance"],
+        bump = insurance_vault.bump
+    )]
+    pub insurance_vault: Option<Account<'info, InsuranceVault>>,
+
+    /// CHECK: Only receives burned lamports, checked by address
+    #[account(
+        mut,
+        address = anchor_lang::solana_program::incinerator::ID
+    )]
+    pub incinerator: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: Only receives the DAO tax, must match the configured DAO treasury
+    #[account(
+        mut,
+        address = game_state.dao_treasury @ CubeGameError::DaoTreasuryMismatch
+    )]
+    pub dao_treasury: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: Treasury PDA, checked against the canonical derivation
+    #[account(
+        mut,
+        address = treasury_pda() @ CubeGameError::InvalidTreasury
+    )]
+    pub treasury: AccountInfo<'info>,
+
+    #[account(mut, seeds = [b"ledger"], bump = ledger.bump)]
+    pub ledger: Option<Box<Account<'info, Ledger>>>,
+
+    #[account(mut)]
+    pub player: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(cube_id: String)]
+pub struct IntendRemoval<'info> {
+    #[account(
+        seeds = [b"game_state"],
+        bump = game_state.bump
+    )]
+    pub game_state: Account<'info, GameState>,
+
+    #[account(
+        init,
+        payer = player,
+        space = 8 + RemovalIntent::INIT_SPACE,
+        seeds = [b"intent", player.key().as_ref(), cube_id.as_bytes()],
+        bump
+    )]
+    pub removal_intent: Account<'info, RemovalIntent>,
+
+    #[account(mut)]
+    pub player: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct BuyPriceLock<'info> {
+    #[account(
+        mut,
+        seeds = [b"game_state"],
+        bump = game_state.bump
+    )]
+    pub game_state: Account<'info, GameState>,
+
+    #[account(
+        init_if_needed,
+        payer = player,
+        space = 8 + PriceVoucher::INIT_SPACE,
+        seeds = [b"voucher", player.key().as_ref()],
+        bump
+    )]
+    pub voucher: Account<'info, PriceVoucher>,
+
+    #[account(
+        mut,
+        seeds = [b"refund_pool"],
+        bump = refund_pool.bump
+    )]
+    pub refund_pool: Option<Account<'info, RefundPool>>,
+
+    #[account(
+        mut,
+        seeds = [b"insurance"],
+        bump = insurance_vault.bump
+    )]
+    pub insurance_vault: Option<Account<'info, InsuranceVault>>,
+
+    /// CHECK: Only receives burned lamports, checked by address
+    #[account(
+        mut,
+        address = anchor_lang::solana_program::incinerator::ID
+    )]
+    pub incinerator: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: Only receives the DAO tax, must match the configured DAO treasury
+    #[account(
+        mut,
+        address = game_state.dao_treasury @ CubeGameError::DaoTreasuryMismatch
+    )]
+    pub dao_treasury: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: Treasury PDA, checked against the canonical derivation
+    #[account(
+        mut,
+        address = treasury_pda() @ CubeGameError::InvalidTreasury
+    )]
+    pub treasury: AccountInfo<'info>,
+
+    #[account(mut, seeds = [b"ledger"], bump = ledger.bump)]
+    pub ledger: Option<Box<Account<'info, Ledger>>>,
+
+    #[account(mut)]
+    pub player: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ExitGame<'info> {
+    #[account(
+        mut,
+        seeds = [b"game_state"],
+        bump = game_state.bump
+    )]
+    pub game_state: Account<'info, GameState>,
+
+    #[account(
+        mut,
+        seeds = [b"player", player.key().as_ref()],
+        bump
+    )]
+    pub player_stats: Account<'info, PlayerStats>,
+
+    #[account(
+        mut,
+        seeds = [b"team", player_stats.team_id.as_bytes()],
+        bump = team.bump
+    )]
+    pub team: Option<Account<'info, Team>>,
+
+    /// CHECK: Treasury PDA, checked against the canonical derivation
+    #[account(mut, address = treasury_pda() @ CubeGameError::InvalidTreasury)]
+    pub treasury: AccountInfo<'info>,
+
+    #[account(mut)]
+    pub player: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+
+    #[account(mut, seeds = [b"ledger"], bump = ledger.bump)]
+    pub ledger: Option<Box<Account<'info, Ledger>>>,
+}
+
+#[derive(Accounts)]
+pub struct ForgetPlayer<'info> {
+    #[account(
+        mut,
+        seeds = [b"game_state"],
+        bump = game_state.bump
+    )]
+    pub game_state: Account<'info, GameState>,
+
+    #[account(
+        mut,
+        seeds = [b"player", player.key().as_ref()],
+        bump
+    )]
+    pub player_stats: Account<'info, PlayerStats>,
+
+    #[account(
+        mut,
+        seeds = [b"team", player_stats.team_id.as_bytes()],
+        bump = team.bump
+    )]
+    pub team: Option<Account<'info, Team>>,
+
+    #[account(mut)]
+    pub player: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetPrice<'info> {
+    #[account(
+        mut,
+        seeds = [b"game_state"],
+        bump = game_state.bump,
+        constraint = game_state.role(game_state.price_authority) == authority.key()
+            @ CubeGameError::Unauthorized
+    )]
+    pub game_state: Account<'info, GameState>,
+
+    /// The price authority
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetPaused<'info> {
+    #[account(
+        mut,
+        seeds = [b"game_state"],
+        bump = game_state.bump,
+        constraint = game_state.role(game_state.pause_authority) == authority.key()
+            @ CubeGameError::Unauthorized
+    )]
+    pub game_state: Account<'info, GameState>,
+
+    /// The pause authority
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimDaily<'info> {
+    #[account(seeds = [b"game_state"], bump = game_state.bump)]
+    pub game_state: Account<'info, GameState>,
+
+    #[account(
+        mut,
+        seeds = [b"player", player.key().as_ref()],
+        bump
+    )]
+    pub player_stats: Account<'info, PlayerStats>,
+
+    /// CHECK: Treasury PDA, checked against the canonical derivation
+    #[account(mut, address = treasury_pda() @ CubeGameError::InvalidTreasury)]
+    pub treasury: AccountInfo<'info>,
+
+    #[account(mut)]
+    pub player: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+
+    #[account(mut, seeds = [b"ledger"], bump = ledger.bump)]
+    pub ledger: Option<Box<Account<'info, Ledger>>>,
+}
+
+#[derive(Accounts)]
+pub struct StartCommunityGoal<'info> {
+    #[account(
+        mut,
+        seeds = [b"game_state"],
+        bump = game_state.bump,
+        has_one = authority @ CubeGameError::Unauthorized
+    )]
+    pub game_state: Account<'info, GameState>,
+
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = 8 + CommunityGoal::INIT_SPACE,
+        seeds = [b"community_goal"],
+        bump
+    )]
+    pub community_goal: Account<'info, CommunityGoal>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimCommunityReward<'info> {
+    #[account(seeds = [b"game_state"], bump = game_state.bump)]
+    pub game_state: Account<'info, GameState>,
+
+    #[account(mut, seeds = [b"community_goal"], bump = community_goal.bump)]
+    pub community_goal: Account<'info, CommunityGoal>,
+
+    #[account(
+        mut,
+        seeds = [b"player", player.key().as_ref()],
+        bump
+    )]
+    pub player_stats: Account<'info, PlayerStats>,
+
+    /// CHECK: Treasury PDA, checked against the canonical derivation
+    #[account(mut, address = treasury_pda() @ CubeGameError::InvalidTreasury)]
+    pub treasury: AccountInfo<'info>,
+
+    #[account(mut)]
+    pub player: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+
+    #[account(mut, seeds = [b"ledger"], bump = ledger.bump)]
+    pub ledger: Option<Box<Account<'info, Ledger>>>,
+}
+
+#[derive(Accounts)]
+pub struct SetConfig<'info> {
+    #[account(
+        mut,
+        seeds = [b"game_state"],
+        bump = game_state.bump,
+        has_one = authority @ CubeGameError::Unauthorized
+    )]
+    pub game_state: Account<'info, GameState>,
+
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateConfig<'info> {
+    #[account(
+        mut,
+        seeds = [b"game_state"],
+        bump = game_state.bump,
+        has_one = authority @ CubeGameError::Unauthorized
+    )]
+    pub game_state: Account<'info, GameState>,
+
+    /// Required to reserve a share of payments for refunds
+    #[account(
+        seeds = [b"refund_pool"],
+        bump = refund_pool.bump
+    )]
+    pub refund_pool: Option<Account<'info, RefundPool>>,
+
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(prefix: String)]
+pub struct CreateZone<'info> {
+    #[account(
+        mut,
+        seeds = [b"game_state"],
+        bump = game_state.bump,
+        has_one = authority @ CubeGameError::Unauthorized
+    )]
+    pub game_state: Account<'info, GameState>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + Zone::INIT_SPACE,
+        seeds = [b"zone", prefix.as_bytes()],
+        bump
+    )]
+    pub zone: Account<'info, Zone>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(cube_id: String)]
+pub struct FundBounty<'info> {
+    #[account(seeds = [b"game_state"], bump = game_state.bump)]
+    pub game_state: Account<'info, GameState>,
+
+    #[account(
+        init_if_needed,
+        payer = funder,
+        space = 8 + Bounty::INIT_SPACE,
+        seeds = [b"bounty", cube_id.as_bytes()],
+        bump
+    )]
+    pub bounty: Account<'info, Bounty>,
+
+    /// CHECK: Global-board record of the cube, which may not exist yet
+    #[account(address = scoped_cube_record_pda("", &cube_id) @ CubeGameError::CubeRecordMismatch)]
+    pub cube_record: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub funder: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+
+    #[account(mut, seeds = [b"ledger"], bump = ledger.bump)]
+    pub ledger: Option<Box<Account<'info, Ledger>>>,
+}
+
+#[derive(Accounts)]
+#[instruction(cube_id: String)]
+pub struct LeaveQueue<'info> {
+    #[account(seeds = [b"game_state"], bump = game_state.bump)]
+    pub game_state: Account<'info, GameState>,
+
+    #[account(mut, seeds = [b"queue", cube_id.as_bytes()], bump = removal_queue.bump)]
+    pub removal_queue: Account<'info, RemovalQueue>,
+
+    #[account(mut)]
+    pub player: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(cube_id: String)]
+pub struct CloseQueue<'info> {
+    #[account(seeds = [b"game_state"], bump = game_state.bump)]
+    pub game_state: Account<'info, GameState>,
+
+    #[account(
+        mut,
+        seeds = [b"queue", cube_id.as_bytes()],
+        bump = removal_queue.bump,
+        close = opener
+    )]
+    pub removal_queue: Account<'info, RemovalQueue>,
+
+    /// CHECK: Receives the queue's rent, must be the wallet that opened it
+    #[account(mut, address = removal_queue.opener @ CubeGameError::Unauthorized)]
+    pub opener: UncheckedAccount<'info>,
+
+    /// CHECK: Treasury PDA, checked against the canonical derivation
+    #[account(mut, address = treasury_pda() @ CubeGameError::InvalidTreasury)]
+    pub treasury: AccountInfo<'info>,
+
+    #[account(mut, seeds = [b"ledger"], bump = ledger.bump)]
+    pub ledger: Option<Box<Account<'info, Ledger>>>,
+}
+
+#[derive(Accounts)]
+#[instruction(cube_id: String)]
+pub struct EnqueueForCube<'info> {
+    #[account(seeds = [b"game_state"], bump = game_state.bump)]
+    pub game_state: Account<'info, GameState>,
+
+    #[account(
+        init_if_needed,
+        payer = player,
+        space = 8 + RemovalQueue::INIT_SPACE,
+        seeds = [b"queue", cube_id.as_bytes()],
+        bump
+    )]
+    pub removal_queue: Account<'info, RemovalQueue>,
+
+    /// CHECK: Global-board record of the cube, which may not exist yet
+    #[account(address = scoped_cube_record_pda("", &cube_id) @ CubeGameError::CubeRecordMismatch)]
+    pub cube_record: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub player: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(cube_id: String)]
+pub struct RefundBounty<'info> {
+    #[account(seeds = [b"game_state"], bump = game_state.bump)]
+    pub game_state: Account<'info, GameState>,
+
+    #[account(
+        mut,
+        seeds = [b"bounty", cube_id.as_bytes()],
+        bump = bounty.bump
+    )]
+    pub bounty: Account<'info, Bounty>,
+
+    /// CHECK: Global-board record of the cube, which may not exist yet
+    #[account(address = scoped_cube_record_pda("", &cube_id) @ CubeGameError::CubeRecordMismatch)]
+    pub cube_record: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub funder: Signer<'info>,
+
+    #[account(mut, seeds = [b"ledger"], bump = ledger.bump)]
+    pub ledger: Option<Box<Account<'info, Ledger>>>,
+}
+
+#[derive(Accounts)]
+pub struct InitInventory<'info> {
+    #[account(
+        init,
+        payer = player,
+        space = 8 + Inventory::INIT_SPACE,
+        seeds = [b"inventory", player.key().as_ref()],
+        bump
+    )]
+    pub inventory: Account<'info, Inventory>,
+
+    #[account(mut)]
+    pub player: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(player: Pubkey)]
+pub struct ViewInventory<'info> {
+    #[account(seeds = [b"inventory", player.as_ref()], bump = inventory.bump)]
+    pub inventory: Account<'info, Inventory>,
+}
+
+#[derive(Accounts)]
+#[instruction(cube_id: String, region_id: String)]
+pub struct LikeCube<'info> {
+    #[account(mut, seeds = [cube_record_seed(&region_id).as_slice(), cube_id.as_bytes()], bump)]
+    pub cube_record: Account<'info, CubeRecord>,
+
+    /// Exists once `liker` has liked the cube, blocking a second like
+    #[account(
+        init,
+        payer = liker,
+        space = 8 + Like::INIT_SPACE,
+        seeds = [
+            region_scoped_seed(b"like", &region_id).as_slice(),
+            cube_id.as_bytes(),
+            liker.key().as_ref()
+        ],
+        bump
+    )]
+    pub like: Account<'info, Like>,
+
+    #[account(mut)]
+    pub liker: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct PlaceCubes<'info> {
+    #[account(
+        mut,
+        seeds = [b"game_state"],
+        bump = game_state.bump,
+        has_one = authority @ CubeGameError::Unauthorized
+    )]
+    pub game_state: Account<'info, GameState>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(region_id: String)]
+pub struct ConfigureRegion<'info> {
+    #[account(
+        seeds = [b"game_state"],
+        bump = game_state.bump,
+        has_one = authority @ CubeGameError::Unauthorized
+    )]
+    pub game_state: Account<'info, GameState>,
+
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = 8 + Region::INIT_SPACE,
+        seeds = [b"region", region_id.as_bytes()],
+        bump
+    )]
+    pub region: Account<'info, Region>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(region_id: String)]
+pub struct SetRegionAuthority<'info> {
+    #[account(
+        seeds = [b"game_state"],
+        bump = game_state.bump,
+        has_one = authority @ CubeGameError::Unauthorized
+    )]
+    pub game_state: Account<'info, GameState>,
+
+    #[account(
+        mut,
+        seeds = [b"region", region_id.as_bytes()],
+        bump = region.bump
+    )]
+    pub region: Account<'info, Region>,
+
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(region_id: String)]
+pub struct RegionAdmin<'info> {
+    #[account(seeds = [b"game_state"], bump = game_state.bump)]
+    pub game_state: Account<'info, GameState>,
+
+    #[account(
+        mut,
+        seeds = [b"region", region_id.as_bytes()],
+        bump = region.bump,
+        constraint = authority.key() == game_state.authority
+            || (region.region_authority != Pubkey::default()
+                && authority.key() == region.region_authority)
+            @ CubeGameError::Unauthorized
+    )]
+    pub region: Account<'info, Region>,
+
+    /// The region authority, or the owner overriding it
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct InitRefundPool<'info> {
+    #[account(
+        seeds = [b"game_state"],
+        bump = game_state.bump,
+        has_one = authority @ CubeGameError::Unauthorized
+    )]
+    pub game_state: Account<'info, GameState>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + RefundPool::INIT_SPACE,
+        seeds = [b"refund_pool"],
+        bump
+    )]
+    pub refund_pool: Account<'info, RefundPool>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/// Leads a removal's accounts so an undersized game state fails with
+/// `AccountNeedsMigration` instead of an opaque deserialization error.
+/// Clients pass the game state PDA here as well as in `game_state`.
+#[derive(Accounts)]
+pub struct MigrationCheck<'info> {
+    /// CHECK: Only its length is read, before `game_state` is deserialized
+    #[account(
+        address = game_state_pda() @ CubeGameError::GameStatePdaMismatch,
+        constraint = is_migrated(game_state.data_len()) @ CubeGameError::AccountNeedsMigration
+    )]
+    pub game_state: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateGameState<'info> {
+    /// CHECK: Possibly too small to deserialize; the handler checks its owner,
+    /// discriminator and authority by hand
+    #[account(mut, address = game_state_pda() @ CubeGameError::GameStatePdaMismatch)]
+    pub game_state: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct InitAnalytics<'info> {
+    #[account(
+        mut,
+        seeds = [b"game_state"],
+        bump = game_state.bump,
+        has_one = authority @ CubeGameError::Unauthorized
+    )]
+    pub game_state: Account<'info, GameState>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + Analytics::INIT_SPACE,
+        seeds = [b"analytics"],
+        bump
+    )]
+    pub analytics: Account<'info, Analytics>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct InitLedger<'info> {
+    #[account(
+        mut,
+        seeds = [b"game_state"],
+        bump = game_state.bump,
+        has_one = authority @ CubeGameError::Unauthorized
+    )]
+    pub game_state: Account<'info, GameState>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + Ledger::INIT_SPACE,
+        seeds = [b"ledger"],
+        bump
+    )]
+    pub ledger: Account<'info, Ledger>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct InitColdReserve<'info> {
+    #[account(
+        seeds = [b"game_state"],
+        bump = game_state.bump,
+        has_one = authority @ CubeGameError::Unauthorized
+    )]
+    pub game_state: Account<'info, GameState>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + ColdReserve::INIT_SPACE,
+        seeds = [b"cold_reserve"],
+        bump
+    )]
+    pub cold_reserve: Account<'info, ColdReserve>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct FundTreasury<'info> {
+    #[account(
+        seeds = [b"game_state"],
+        bump = game_state.bump,
+        has_one = authority @ CubeGameError::Unauthorized
+    )]
+    pub game_state: Account<'info, GameState>,
+
+    /// CHECK: Treasury PDA, checked against the canonical derivation
+    #[account(
+        mut,
+        address = treasury_pda() @ CubeGameError::InvalidTreasury
+    )]
+    pub treasury: AccountInfo<'info>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+
+    #[account(mut, seeds = [b"ledger"], bump = ledger.bump)]
+    pub ledger: Option<Box<Account<'info, Ledger>>>,
+}
+
+#[derive(Accounts)]
+pub struct FundColdReserve<'info> {
+    #[account(
+        seeds = [b"game_state"],
+        bump = game_state.bump,
+        has_one = authority @ CubeGameError::Unauthorized
+    )]
+    pub game_state: Account<'info, GameState>,
+
+    #[account(
+        mut,
+        seeds = [b"cold_reserve"],
+        bump = cold_reserve.bump
+    )]
+    pub cold_reserve: Account<'info, ColdReserve>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+
+    #[account(mut, seeds = [b"ledger"], bump = ledger.bump)]
+    pub ledger: Option<Box<Account<'info, Ledger>>>,
+}
+
+#[derive(Accounts)]
+pub struct InitInsuranceVault<'info> {
+    #[account(
+        seeds = [b"game_state"],
+        bump = game_state.bump,
+        has_one = authority @ CubeGameError::Unauthorized
+    )]
+    pub game_state: Account<'info, GameState>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + InsuranceVault::INIT_SPACE,
+        seeds = [b"insurance"],
+        bump
+    )]
+    pub insurance_vault: Account<'info, InsuranceVault>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SetInsurance<'info> {
+    #[account(
+        mut,
+        seeds = [b"game_state"],
+        bump = game_state.bump,
+        has_one = authority @ CubeGameError::Unauthorized
+    )]
+    pub game_state: Account<'info, GameState>,
+
+    /// Must exist before any share of payments can be insured
+    #[account(
+        seeds = [b"insurance"],
+        bump = insurance_vault.bump
+    )]
+    pub insurance_vault: Account<'info, InsuranceVault>,
+
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ManageInsurance<'info> {
+    #[account(
+        seeds = [b"game_state"],
+        bump = game_state.bump,
+        has_one = authority @ CubeGameError::Unauthorized
+    )]
+    pub game_state: Account<'info, GameState>,
+
+    #[account(
+        mut,
+        seeds = [b"insurance"],
+        bump = insurance_vault.bump
+    )]
+    pub insurance_vault: Account<'info, InsuranceVault>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(mut, seeds = [b"ledger"], bump = ledger.bump)]
+    pub ledger: Option<Box<Account<'info, Ledger>>>,
+}
+
+#[derive(Accounts)]
+pub struct FundRefundsFromInsurance<'info> {
+    #[account(
+        seeds = [b"game_state"],
+        bump = game_state.bump,
+        has_one = authority @ CubeGameError::Unauthorized
+    )]
+    pub game_state: Account<'info, GameState>,
+
+    #[account(
+        mut,
+        seeds = [b"insurance"],
+        bump = insurance_vault.bump
+    )]
+    pub insurance_vault: Account<'info, InsuranceVault>,
+
+    #[account(
+        mut,
+        seeds = [b"refund_pool"],
+        bump = refund_pool.bump
+    )]
+    pub refund_pool: Account<'info, RefundPool>,
+
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetRefundReserve<'info> {
+    #[account(
+        mut,
+        seeds = [b"game_state"],
+        bump = game_state.bump,
+        has_one = authority @ CubeGameError::Unauthorized
+    )]
+    pub game_state: Account<'info, GameState>,
+
+    /// Must exist before any share of payments can be reserved
+    #[account(
+        seeds = [b"refund_pool"],
+        bump = refund_pool.bump
+    )]
+    pub refund_pool: Account<'info, RefundPool>,
+
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct InitMessageWall<'info> {
+    #[account(
+        seeds = [b"game_state"],
+        bump = game_state.bump,
+        has_one = authority @ CubeGameError::Unauthorized
+    )]
+    pub game_state: Account<'info, GameState>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + MessageWall::INIT_SPACE,
+        seeds = [b"message_wall"],
+        bump
+    )]
+    pub message_wall: Account<'info, MessageWall>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CommitRemoval<'info> {
+    #[account(
+        init,
+        payer = player,
+        space = 8 + RemovalCommitment::INIT_SPACE,
+        seeds = [b"commitment", player.key().as_ref()],
+        bump
+    )]
+    pub removal_commitment: Account<'info, RemovalCommitment>,
+
+    #[account(mut)]
+    pub player: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct InitPlayerHistory<'info> {
+    #[account(
+        init_if_needed,
+        payer = player,
+        space = 8 + PlayerStats::INIT_SPACE,
+        seeds = [b"player", player.key().as_ref()],
+        bump
+    )]
+    pub player_stats: Account<'info, PlayerStats>,
+
+    #[account(
+        init,
+        payer = player,
+        space = 8 + PlayerHistory::INIT_SPACE,
+        seeds = [b"history", player.key().as_ref()],
+        bump
+    )]
+    pub player_history: Account<'info, PlayerHistory>,
+
+    #[account(mut)]
+    pub player: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct InitActivityFeed<'info> {
+    #[account(
+        mut,
+        seeds = [b"game_state"],
+        bump = game_state.bump,
+        has_one = authority @ CubeGameError::Unauthorized
+    )]
+    pub game_state: Account<'info, GameState>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + ActivityFeed::INIT_SPACE,
+        seeds = [b"activity_feed"],
+        bump
+    )]
+    pub activity_feed: Account<'info, ActivityFeed>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(player: Pubkey)]
+pub struct SetPlayerFrozen<'info> {
+    #[account(
+        seeds = [b"game_state"],
+        bump = game_state.bump,
+        has_one = authority @ CubeGameError::Unauthorized
+    )]
+    pub game_state: Account<'info, GameState>,
+
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = 8 + PlayerStats::INIT_SPACE,
+        seeds = [b"player", player.as_ref()],
+        bump
+    )]
+    pub player_stats: Account<'info, PlayerStats>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(player: Pubkey)]
+pub struct GrantExemption<'info> {
+    #[account(
+        seeds = [b"game_state"],
+        bump = game_state.bump,
+        has_one = authority @ CubeGameError::Unauthorized
+    )]
+    pub game_state: Account<'info, GameState>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + FeeExemption::INIT_SPACE,
+        seeds = [b"exempt", player.as_ref()],
+        bump
+    )]
+    pub fee_exemption: Account<'info, FeeExemption>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(player: Pubkey)]
+pub struct RevokeExemption<'info> {
+    #[account(
+        seeds = [b"game_state"],
+        bump = game_state.bump,
+        has_one = authority @ CubeGameError::Unauthorized
+    )]
+    pub game_state: Account<'info, GameState>,
+
+    #[account(
+        mut,
+        close = authority,
+        seeds = [b"exempt", player.as_ref()],
+        bump = fee_exemption.bump
+    )]
+    pub fee_exemption: Account<'info, FeeExemption>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct RollbackRemovals<'info> {
+    #[account(
+        mut,
+        seeds = [b"game_state"],
+        bump = game_state.bump,
+        has_one = authority @ CubeGameError::Unauthorized
+    )]
+    pub game_state: Account<'info, GameState>,
+
+    #[account(
+        mut,
+        seeds = [b"refund_pool"],
+        bump = refund_pool.bump
+    )]
+    pub refund_pool: Option<Account<'info, RefundPool>>,
+
+    #[account(
+        mut,
+        seeds = [b"activity_feed"],
+        bump
+    )]
+    pub activity_feed: Option<Account<'info, ActivityFeed>>,
+
+    #[account(mut, seeds = [b"analytics"], bump = analytics.bump)]
+    pub analytics: Option<Account<'info, Analytics>>,
+
+    /// CHECK: Treasury PDA, checked against the canonical derivation
+    #[account(
+        mut,
+        address = treasury_pda() @ CubeGameError::InvalidTreasury
+    )]
+    pub treasury: AccountInfo<'info>,
+
+    #[account(mut, seeds = [b"ledger"], bump = ledger.bump)]
+    pub ledger: Option<Box<Account<'info, Ledger>>>,
+
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UndoLastRemoval<'info> {
+    #[account(
+        mut,
+        seeds = [b"game_state"],
+        bump = game_state.bump,
+        has_one = authority @ CubeGameError::Unauthorized
+    )]
+    pub game_state: Account<'info, GameState>,
+
+    #[account(
+        mut,
+        address = scoped_cube_record_pda(&game_state.last_region_id, &game_state.last_cube_id)
+            @ CubeGameError::LastRemovalMismatch
+    )]
+    pub cube_record: Account<'info, CubeRecord>,
+
+    #[account(
+        mut,
+        seeds = [b"player", game_state.last_remover.as_ref()],
+        bump
+    )]
+    pub remover_stats: Account<'info, PlayerStats>,
+
+    #[account(
+        mut,
+        seeds = [b"refund_pool"],
+        bump = refund_pool.bump
+    )]
+    pub refund_pool: Option<Account<'info, RefundPool>>,
+
+    #[account(mut, seeds = [b"analytics"], bump = analytics.bump)]
+    pub analytics: Option<Account<'info, Analytics>>,
+
+    /// CHECK: Treasury PDA, checked against the canonical derivation
+    #[account(
+        mut,
+        address = treasury_pda() @ CubeGameError::InvalidTreasury
+    )]
+    pub treasury: AccountInfo<'info>,
+
+    #[account(mut, seeds = [b"ledger"], bump = ledger.bump)]
+    pub ledger: Option<Box<Account<'info, Ledger>>>,
+
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(cube_id: String, region_id: String)]
+pub struct CloseCubeRecord<'info> {
+    #[account(
+        mut,
+        seeds = [b"game_state"],
+        bump = game_state.bump,
+        has_one = authority @ CubeGameError::Unauthorized
+    )]
+    pub game_state: Account<'info, GameState>,
+
+    #[account(
+        mut,
+        close = authority,
+        seeds = [cube_record_seed(&region_id).as_slice(), cube_id.as_bytes()],
+        bump
+    )]
+    pub cube_record: Account<'info, CubeRecord>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct BatchRefund<'info> {
+    #[account(
+        mut,
+        seeds = [b"game_state"],
+        bump = game_state.bump,
+        has_one = authority @ CubeGameError::Unauthorized
+    )]
+    pub game_state: Account<'info, GameState>,
+
+    /// CHECK: Treasury PDA, checked against the canonical derivation
+    #[account(
+        mut,
+        address = treasury_pda() @ CubeGameError::InvalidTreasury
+    )]
+    pub treasury: AccountInfo<'info>,
+
+    #[account(mut, seeds = [b"ledger"], bump = ledger.bump)]
+    pub ledger: Option<Box<Account<'info, Ledger>>>,
+
+    #[account(mut, seeds = [b"analytics"], bump = analytics.bump)]
+    pub analytics: Option<Account<'info, Analytics>>,
+
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ResetZone<'info> {
+    #[account(
+        mut,
+        seeds = [b"game_state"],
+        bump = game_state.bump,
+        has_one = authority @ CubeGameError::Unauthorized
+    )]
+    pub game_state: Account<'info, GameState>,
+
+    #[account(
+        mut,
+        seeds = [b"zone", zone.prefix.as_bytes()],
+        bump = zone.bump
+    )]
+    pub zone: Account<'info, Zone>,
+
+    #[account(
+        mut,
+        seeds = [b"refund_pool"],
+        bump = refund_pool.bump
+    )]
+    pub refund_pool: Option<Account<'info, RefundPool>>,
+
+    #[account(
+        mut,
+        seeds = [b"activity_feed"],
+        bump
+    )]
+    pub activity_feed: Option<Account<'info, ActivityFeed>>,
+
+    #[account(mut, seeds = [b"analytics"], bump = analytics.bump)]
+    pub analytics: Option<Account<'info, Analytics>>,
+
+    /// CHECK: Treasury PDA, checked against the canonical derivation
+    #[account(
+        mut,
+        address = treasury_pda() @ CubeGameError::InvalidTreasury
+    )]
+    pub treasury: AccountInfo<'info>,
+
+    #[account(mut, seeds = [b"ledger"], bump = ledger.bump)]
+    pub ledger: Option<Box<Account<'info, Ledger>>>,
+
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(cube_id: String, region_id: String)]
+pub struct ImportRemoval<'info> {
+    #[account(
+        mut,
+        seeds = [b"game_state"],
+        bump = game_state.bump,
+        has_one = authority @ CubeGameError::Unauthorized
+    )]
+    pub game_state: Account<'info, GameState>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + CubeRecord::INIT_SPACE,
+        seeds = [cube_record_seed(&region_id).as_slice(), cube_id.as_bytes()],
+        bump
+    )]
+    pub cube_record: Account<'info, CubeRecord>,
+
+    /// CHECK: Region PDA of `region_id`, checked by its seeds and read by
+    /// `load_region`. Credited with imported regional removals.
+    #[account(
+        mut,
+        seeds = [b"region", region_id.as_bytes()],
+        bump
+    )]
+    pub region: Option<UncheckedAccount<'info>>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(label: String)]
+pub struct TakeSnapshot<'info> {
+    #[account(
+        seeds = [b"game_state"],
+        bump = game_state.bump,
+        has_one = authority @ CubeGameError::Unauthorized
+    )]
+    pub game_state: Account<'info, GameState>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + Snapshot::INIT_SPACE,
+        seeds = [b"snapshot", label.as_bytes()],
+        bump
+    )]
+    pub snapshot: Account<'info, Snapshot>,
+
+    /// CHECK: Treasury PDA, only its balance is read
+    #[account(address = treasury_pda() @ CubeGameError::InvalidTreasury)]
+    pub treasury: AccountInfo<'info>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(cube_id: String, region_id: String)]
+pub struct MigrateCubeToRegion<'info> {
+    #[account(
+        seeds = [b"game_state"],
+        bump = game_state.bump,
+        has_one = authority @ CubeGameError::Unauthorized
+    )]
+    pub game_state: Account<'info, GameState>,
+
+    #[account(
+        mut,
+        close = authority,
+        seeds = [b"cube", cube_id.as_bytes()],
+        bump
+    )]
+    pub global_record: Account<'info, CubeRecord>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + CubeRecord::INIT_SPACE,
+        seeds = [cube_record_seed(&region_id).as_slice(), cube_id.as_bytes()],
+        bump
+    )]
+    pub region_record: Account<'info, CubeRecord>,
+
+    /// CHECK: Region PDA of `region_id`, checked by its seeds and read by
+    /// `load_region`. Credited when the migrated cube is removed.
+    #[account(
+        mut,
+        seeds = [b"region", region_id.as_bytes()],
+        bump
+    )]
+    pub region: Option<UncheckedAccount<'info>>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CommitCompletionSeed<'info> {
+    #[account(mut, seeds = [b"game_state"], bump = game_state.bump)]
+    pub game_state: Account<'info, GameState>,
+
+    /// CHECK: SlotHashes sysvar, checked by address and parsed by hand
+    #[account(address = anchor_lang::solana_program::sysvar::slot_hashes::ID)]
+    pub slot_hashes: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct FinalizeGame<'info> {
+    #[account(
+        mut,
+        seeds = [b"game_state"],
+        bump = game_state.bump,
+        has_one = authority @ CubeGameError::Unauthorized
+    )]
+    pub game_state: Account<'info, GameState>,
+
+    /// CHECK: Treasury PDA, checked against the canonical derivation
+    #[account(
+        mut,
+        address = treasury_pda() @ CubeGameError::InvalidTreasury
+    )]
+    pub treasury: AccountInfo<'info>,
+
+    /// CHECK: Only receives the prize, must be the drawn player; needed by
+    /// the call that counts the last entrants
+    #[account(mut)]
+    pub winner: Option<UncheckedAccount<'info>>,
+
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+
+    #[account(mut, seeds = [b"ledger"], bump = ledger.bump)]
+    pub ledger: Option<Box<Account<'info, Ledger>>>,
+}
+
+#[derive(Accounts)]
+pub struct StakeTreasury<'info> {
+    #[account(
+        mut,
+        seeds = [b"game_state"],
+        bump = game_state.bump,
+        has_one = authority @ CubeGameError::Unauthorized
+    )]
+    pub game_state: Account<'info, GameState>,
+
+    /// CHECK: Treasury PDA, checked against the canonical derivation
+    #[account(
+        mut,
+        address = treasury_pda() @ CubeGameError::InvalidTreasury
+    )]
+    pub treasury: AccountInfo<'info>,
+
+    /// Fresh keypair the stake account is created at
+    #[account(mut)]
+    pub stake_account: Signer<'info>,
+
+    /// CHECK: Validated by the stake program during delegation
+    pub vote_account: UncheckedAccount<'info>,
+
+    /// CHECK: Stake config account, checked by address
+    #[account(address = stake::config::ID)]
+    pub stake_config: UncheckedAccount<'info>,
+
+    /// CHECK: Stake history sysvar, checked by address
+    #[account(address = anchor_lang::solana_program::sysvar::stake_history::ID)]
+    pub stake_history: UncheckedAccount<'info>,
+
+    /// CHECK: Stake program, checked by address
+    #[account(address = stake::program::ID)]
+    pub stake_program: UncheckedAccount<'info>,
+
+    pub authority: Signer<'info>,
+
+    pub clock: Sysvar<'info, Clock>,
+    pub rent: Sysvar<'info, Rent>,
+    pub system_program: Program<'info, System>,
+
+    #[account(mut, seeds = [b"ledger"], bump = ledger.bump)]
+    pub ledger: Option<Box<Account<'info, Ledger>>>,
+}
+
+#[derive(Accounts)]
+pub struct DeactivateStake<'info> {
+    #[account(
+        seeds = [b"game_state"],
+        bump = game_state.bump,
+        has_one = authority @ CubeGameError::Unauthorized
+    )]
+    pub game_state: Account<'info, GameState>,
+
+    /// CHECK: Treasury PDA, checked against the canonical derivation
+    #[account(address = treasury_pda() @ CubeGameError::InvalidTreasury)]
+    pub treasury: AccountInfo<'info>,
+
+    /// CHECK: Must have the treasury as staker, enforced by the stake program
+    #[account(mut)]
+    pub stake_account: UncheckedAccount<'info>,
+
+    /// CHECK: Stake program, checked by address
+    #[account(address = stake::program::ID)]
+    pub stake_program: UncheckedAccount<'info>,
+
+    pub authority: Signer<'info>,
+
+    pub clock: Sysvar<'info, Clock>,
+}
+
+#[derive(Accounts)]
+pub struct WithdrawStake<'info> {
+    #[account(
+        mut,
+        seeds = [b"game_state"],
+        bump = game_state.bump,
+        has_one = authority @ CubeGameError::Unauthorized
+    )]
+    pub game_state: Account<'info, GameState>,
+
+    /// CHECK: Treasury PDA, checked against the canonical derivation
+    #[account(
+        mut,
+        address = treasury_pda() @ CubeGameError::InvalidTreasury
+    )]
+    pub treasury: AccountInfo<'info>,
+
+    /// CHECK: Must have the treasury as withdrawer, enforced by the stake
+    /// program
+    #[account(mut)]
+    pub stake_account: UncheckedAccount<'info>,
+
+    /// CHECK: Stake history sysvar, checked by address
+    #[account(address = anchor_lang::solana_program::sysvar::stake_history::ID)]
+    pub stake_history: UncheckedAccount<'info>,
+
+    /// CHECK: Stake program, checked by address
+    #[account(address = stake::program::ID)]
+    pub stake_program: UncheckedAccount<'info>,
+
+    pub authority: Signer<'info>,
+
+    pub clock: Sysvar<'info, Clock>,
+
+    #[account(mut, seeds = [b"ledger"], bump = ledger.bump)]
+    pub ledger: Option<Box<Account<'info, Ledger>>>,
+}
+
+#[derive(Accounts)]
+pub struct Withdraw<'info> {
+    /// Checked against the canonical address rather than the stored bump so
+    /// a corrupted bump surfaces as `GameStatePdaMismatch`
+    #[account(
+        mut,
+        address = game_state_pda() @ CubeGameError::GameStatePdaMismatch,
+        constraint = game_state.role(game_state.withdraw_authority) == authority.key()
+            @ CubeGameError::Unauthorized
+    )]
+    pub game_state: Account<'info, GameState>,
+
+    /// CHECK: Treasury PDA, checked against the canonical derivation
+    #[account(
+        mut,
+        address = treasury_pda() @ CubeGameError::InvalidTreasury
+    )]
+    pub treasury: AccountInfo<'info>,
+
+    #[account(mut, seeds = [b"ledger"], bump = ledger.bump)]
+    pub ledger: Option<Box<Account<'info, Ledger>>>,
+
+    #[account(
+        mut,
+        seeds = [b"cold_reserve"],
+        bump = cold_reserve.bump
+    )]
+    pub cold_reserve: Option<Account<'info, ColdReserve>>,
+
+    #[account(mut)]
+    /// The withdraw authority, which receives the lamports
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ProposeWithdraw<'info> {
+    #[account(
+        seeds = [b"game_state"],
+        bump = game_state.bump,
+        constraint = game_state.role(game_state.withdraw_authority) == authority.key()
+            @ CubeGameError::Unauthorized
+    )]
+    pub game_state: Account<'info, GameState>,
+
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = 8 + WithdrawTimelock::INIT_SPACE,
+        seeds = [b"withdraw_timelock"],
+        bump
+    )]
+    pub withdraw_timelock: Account<'info, WithdrawTimelock>,
+
+    #[account(mut)]
+    /// The withdraw authority, which receives the lamports
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ExecuteWithdraw<'info> {
+    /// Checked against the canonical address rather than the stored bump so
+    /// a corrupted bump surfaces as `GameStatePdaMismatch`
+    #[account(
+        mut,
+        address = game_state_pda() @ CubeGameError::GameStatePdaMismatch,
+        constraint = game_state.role(game_state.withdraw_authority) == authority.key()
+            @ CubeGameError::Unauthorized
+    )]
+    pub game_state: Account<'info, GameState>,
+
+    #[account(
+        mut,
+        close = authority,
+        seeds = [b"withdraw_timelock"],
+        bump
+    )]
+    pub withdraw_timelock: Account<'info, WithdrawTimelock>,
+
+    /// CHECK: Treasury PDA, checked against the canonical derivation
+    #[account(
+        mut,
+        address = treasury_pda() @ CubeGameError::InvalidTreasury
+    )]
+    pub treasury: AccountInfo<'info>,
+
+    #[account(mut, seeds = [b"ledger"], bump = ledger.bump)]
+    pub ledger: Option<Box<Account<'info, Ledger>>>,
+
+    #[account(
+        mut,
+        seeds = [b"cold_reserve"],
+        bump = cold_reserve.bump
+    )]
+    pub cold_reserve: Option<Account<'info, ColdReserve>>,
+
+    #[account(mut)]
+    /// The withdraw authority, which receives the lamports
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct GameState {
+    pub authority: Pubkey,
+    pub price_per_cube: u64,
+    pub total_cubes_removed: u64,
+    pub bump: u8,
+    pub treasury_bump: u8,
+    /// Seconds after removal during which a cube cannot be transferred (0 = no lock)
+    pub transfer_lock_seconds: i64,
+    /// Treasury balance above which removals forward the excess (0 = disabled)
+    pub treasury_hot_cap: u64,
+    pub cold_wallet: Pubkey,
+    /// Final price multiplier while `surge_until` is in the future (10000 = 1x)
+    pub surge_multiplier_bps: u16,
+    pub surge_until: i64,
+    /// Slots a proposed withdrawal must wait (0 = plain `withdraw` allowed)
+    pub withdraw_delay_slots: u64,
+    /// Prefix every cube_id must start with, e.g. the current season (empty = any)
+    #[max_len(16)]
+    pub required_prefix: String,
+    pub activity_feed_enabled: bool,
+    /// When the game closes to removals (0 = untimed)
+    pub ends_at: i64,
+    pub anti_snipe_window: i64,
+    pub anti_snipe_extension: i64,
+    pub max_extensions: u32,
+    pub extensions_used: u32,
+    /// Collection whose NFT holders may remove cubes (None = open board)
+    pub required_collection: Option<Pubkey>,
+    /// Share of every payment held in the refund pool
+    pub refund_reserve_bps: u16,
+    /// k in `price_per_cube + k * total_cubes_removed^2` (0 = flat)
+    pub quadratic_coeff: u64,
+    /// Lamports `stake_treasury` leaves liquid in the treasury above rent
+    pub stake_liquid_reserve: u64,
+    pub max_removals_per_tx: u8,
+    /// Board size at which the game completes (0 = unbounded)
+    pub max_cubes: u64,
+    /// Hash of the first slot after the board was cleared, set by
+    /// `commit_completion_seed`
+    pub completion_seed: [u8; 32],
+    pub grand_prize: u64,
+    pub winner: Option<Pubkey>,
+    /// Paid back to whoever removes a cube for the very first time
+    pub first_removal_rebate: u64,
+    /// Leading zero bits a removal's proof of work needs (0 = disabled)
+    pub pow_difficulty: u8,
+    /// Display unit prices are quoted in, e.g. "SOL"
+    #[max_len(16)]
+    pub currency_label: String,
+    pub currency_decimals: u8,
+    /// Merkle root of every cube_id on the board (zero = uncommitted)
+    pub board_root: [u8; 32],
+    /// Most the authority may withdraw per window (0 = unlimited)
+    pub withdraw_limit_per_window: u64,
+    pub withdraw_window_seconds: i64,
+    pub window_withdrawn: u64,
+    pub window_start: i64,
+    /// Share of each payment sent to the incinerator
+    pub sol_burn_bps: u16,
+    /// Discount on the base price when buying removal credits
+    pub credit_discount_bps: u16,
+    /// `import_removal` is allowed while set
+    pub import_mode: bool,
+    /// Set by `lock_imports`, after which import mode can never return
+    pub import_locked: bool,
+    /// Treasury balance that triggers the next milestone event
+    pub next_threshold: u64,
+    /// Spacing between milestones (0 = no milestone events)
+    pub threshold_step: u64,
+    /// Paid for removing a cube with enough recently removed neighbours
+    pub combo_bonus: u64,
+    /// How recent a neighbour's removal must be to count
+    pub combo_window: i64,
+    /// Neighbours needed for a combo (0 = combos disabled)
+    pub combo_min_neighbors: u8,
+    /// Zones created so far (0 = no zones, the whole board is open)
+    pub zone_count: u32,
+    /// Receives `dao_tax_bps` of every payment
+    pub dao_treasury: Pubkey,
+    pub dao_tax_bps: u16,
+    /// Up-front premium on a price lock's locked cost
+    pub voucher_premium_bps: u16,
+    /// How long a price lock lasts (0 = price locks disabled)
+    pub voucher_duration: i64,
+    /// Programs allowed to CPI into the removal instructions (empty = any)
+    #[max_len(MAX_CPI_CALLERS)]
+    pub allowed_cpi_callers: Vec<Pubkey>,
+    /// Ranking decay per idle day (0 = rank by raw removals)
+    pub decay_rate_bps_per_day: u16,
+    /// Prices above this need a prior `intend_removal` (0 = never)
+    pub high_value_threshold: u64,
+    /// Themes removed cubes via `cube_color` (0 = players pick colors)
+    pub palette_seed: u64,
+    pub last_deposit_at: i64,
+    /// Withdrawals wait this long after the latest deposit (0 = no wait)
+    pub withdraw_cooldown_seconds: i64,
+    /// Share of cube ids that are bombs, by hash (0 = no bombs)
+    pub bomb_rate_bps: u16,
+    /// Length of the rush pricing window (0 = rush pricing off)
+    pub rush_window_seconds: i64,
+    /// Price increase per removal in the current window
+    pub rush_bps_per_removal: u16,
+    /// Cap on the total rush increase
+    pub rush_max_bps: u16,
+    pub rush_window_start: i64,
+    pub recent_removal_count: u64,
+    /// Share of every payment locked in the insurance vault
+    pub insurance_bps: u16,
+    /// Most cube records that may exist at once (0 = unbounded)
+    pub max_cube_records: u64,
+    pub active_cube_records: u64,
+    /// Removals needed for Silver, Gold and Platinum
+    pub tier_cube_thresholds: [u64; 3],
+    /// Total spend needed for Silver, Gold and Platinum
+    pub tier_spend_thresholds: [u64; 3],
+    /// Lowest attested priority fee a removal may claim (0 = not enforced)
+    pub min_priority_fee: u64,
+    /// Hits a cube takes before it is removed (0 or 1 = a single hit)
+    pub hits_required: u8,
+    /// Lamports `claim_daily` pays once per UTC day (0 = disabled)
+    pub daily_reward: u64,
+    /// Regional removals must pass their `Region` account
+    pub region_pricing: bool,
+    /// Scale clients apply to displayed player stats, for comparing seasons
+    /// (10000 = as recorded)
+    pub stats_display_multiplier_bps: u16,
+    /// Bumped by every change to `price_per_cube`
+    pub price_epoch: u64,
+    /// Longest gap between removals that keeps a timed combo going
+    /// (0 = disabled)
+    pub combo_timer_seconds: i64,
+    pub combo_step_bonus: u64,
+    /// Cap on one removal's timed combo bonus (0 = no cap)
+    pub combo_max_bonus: u64,
+    /// Key that signs `attest_stats` payloads (default = unsigned only)
+    pub attestor: Pubkey,
+    /// Treasury balance withdrawals refill to from the cold reserve
+    /// (0 = no refill)
+    pub target_hot_balance: u64,
+    /// Players who have made a removal, never decremented
+    pub total_players: u64,
+    /// How many of the first players earn the founder badge
+    pub founder_cap: u64,
+    /// Removals accepted per slot across all players (0 = unlimited)
+    pub max_removals_per_slot: u32,
+    pub removals_this_slot: u32,
+    /// Slot `removals_this_slot` counts for
+    pub current_slot: u64,
+    /// Share of a referred removal's price accrued to the referrer
+    pub referral_bps: u16,
+    /// Shortest gap between any two removals (0 = unpaced)
+    pub min_global_interval_seconds: i64,
+    pub last_global_removal_at: i64,
+    /// SPL mint of the milestone reward token (default = no rewards)
+    pub reward_mint: Pubkey,
+    pub milestone_reward_amount: u64,
+    /// A milestone is reached every `milestone_every` cubes a player removes
+    pub milestone_every: u64,
+    /// Players who each remove a cube before it disappears (0 or 1 = one)
+    pub max_removers: u8,
+    /// A full inventory drops its oldest item instead of rejecting removals
+    pub inventory_ring: bool,
+    /// SPL mint accepted by `remove_cube_split` (default = disabled)
+    pub payment_mint: Pubkey,
+    /// Token account receiving split payments
+    pub token_treasury: Pubkey,
+    /// Lamports per payment-mint base unit, times `SPLIT_RATE_SCALE`
+    pub split_rate: u64,
+    /// Removals required before the authority can withdraw
+    pub min_cubes_before_withdraw: u64,
+    /// How long the front of a removal queue keeps its turn (0 = no queues)
+    pub queue_turn_seconds: i64,
+    /// Lamports a player leaves in a removal queue while waiting in it
+    pub queue_deposit: u64,
+    /// Removals and refunds must pass the analytics rollup
+    pub analytics_enabled: bool,
+    /// Server key whose tickets removals need (default = no tickets)
+    pub ticket_signer: Pubkey,
+    /// `rank_players` orders by score instead of active score
+    pub ranked_mode: bool,
+    /// Keys allowed to price, pause and withdraw (default = the owner)
+    pub price_authority: Pubkey,
+    pub pause_authority: Pubkey,
+    pub withdraw_authority: Pubkey,
+    /// Removals are stopped by the pause authority
+    pub paused: bool,
+    /// Share of each removal's payment tipped to `last_remover`
+    pub tip_bps: u16,
+    /// Wallet that removed the most recent cube
+    pub last_remover: Pubkey,
+    /// Removals are over and removers can reclaim what they paid
+    pub refund_all_mode: bool,
+    /// `price_paid` summed over removed cubes, owed back in refund-all mode
+    pub paid_liability: u64,
+    /// Prices of the quota and unlimited subscription tiers
+    pub subscription_prices: [u64; 2],
+    /// Length of a subscription (0 = not on sale)
+    pub subscription_duration: i64,
+    /// Removals covered by a quota-tier subscription
+    pub subscription_quota: u64,
+    /// Player balance below which removals emit a low-balance warning
+    pub low_balance_threshold: u64,
+    /// Program every removal is mirrored into (default = none)
+    pub analytics_program: Pubkey,
+    /// Cube, region and time of the removal `undo_last_removal` reverses
+    /// (empty once undone or when the last removal cannot be undone)
+    #[max_len(32)]
+    pub last_cube_id: String,
+    #[max_len(16)]
+    pub last_region_id: String,
+    pub last_removed_at: i64,
+    /// Price of revealing a cube with `unlock_cube` (0 = unlocking off)
+    pub unlock_fee: u64,
+    /// Share of each removal's payment paid to game token stakers
+    pub staker_reward_bps: u16,
+    /// Paid back to a wallet on its first removal to cover the network fee
+    pub onboarding_refund: u64,
+    /// Seconds a player must have been registered before removing cubes
+    pub min_player_age: i64,
+    /// Slots between `commit_removal` and `reveal_remove` (0 = removals need
+    /// no commitment)
+    pub min_reveal_delay_slots: u64,
+    /// Payments and treasury outflows must be recorded in the `Ledger`
+    pub ledger_enabled: bool,
+    /// Collection whose holders get `member_discount_bps` off (default =
+    /// no membership program)
+    pub membership_collection: Pubkey,
+    pub member_discount_bps: u16,
+    /// A `CommunityGoal` is collecting removals
+    pub community_goal_open: bool,
+    /// Cube records are closed right after their removal
+    pub ephemeral_records: bool,
+    /// Base price in points, replacing `price_per_cube` when set
+    pub price_in_points: u64,
+    /// Lamports one point is charged at
+    pub point_value_lamports: u64,
+    /// Treasury lamports delegated by `stake_treasury` and not yet returned
+    /// by `withdraw_stake`
+    pub treasury_staked: u64,
+    /// Share of the tokens in a split payment that is burned
+    pub burn_bps: u16,
+    /// Sum of every player's `cubes_removed`, the weight `finalize_game`
+    /// must account for
+    pub entrant_weight: u64,
+    /// Slot of the removal that cleared the board (0 = not cleared)
+    pub completion_slot: u64,
+    /// Weight `finalize_game` has counted so far and the last `PlayerStats`
+    /// it counted
+    pub finalize_weight: u64,
+    pub finalize_cursor: Pubkey,
+    /// Player the draw landed on (default = not reached yet)
+    pub draw_winner: Pubkey,
+    /// `last_remover` had `private` set, so tips to them are masked
+    pub last_remover_private: bool,
+    /// Pyth price account quoting the payment mint in USD (default = USD
+    /// pricing off)
+    pub price_feed: Pubkey,
+    /// Oldest `price_feed` publish time `remove_cube_usd` accepts, in
+    /// seconds before now
+    pub max_price_staleness: i64,
+    /// Price of a cube on `remove_cube_usd`, see `USD_PRICE_DECIMALS`
+    pub usd_price: u64,
+    /// Leaves under `board_root`; the game completes once that many cubes
+    /// are removed (0 = not sized)
+    pub board_size: u64,
+}
+
+impl GameState {
+    /// Apply the fields an `update_config` call sets, validating each. An
+    /// invalid field fails the instruction, so none of the batch is kept.
+    pub fn apply_config(&mut self, update: &GameConfigUpdate, has_refund_pool: bool) -> Result<()> {
+        if let Some(price) = update.price_per_cube {
+            self.set_price(price);
+        }
+        if let Some(seconds) = update.transfer_lock_seconds {
+            require!(seconds >= 0, CubeGameError::InvalidDuration);
+            self.transfer_lock_seconds = seconds;
+        }
+        if let Some(max) = update.max_removals_per_tx {
+            self.max_removals_per_tx = max;
+        }
+        if let Some(max_cubes) = update.max_cubes {
+            require!(
+                max_cubes == 0 || !self.ephemeral_records,
+                CubeGameError::EphemeralBoard
+            );
+            self.max_cubes = max_cubes;
+        }
+        if let Some(ends_at) = update.ends_at {
+            self.ends_at = ends_at;
+            self.extensions_used = 0;
+        }
+        if let Some(coeff) = update.quadratic_coeff {
+            self.quadratic_coeff = coeff;
+        }
+        if let Some(prize) = update.grand_prize {
+            self.grand_prize = prize;
+        }
+        if let Some(rebate) = update.first_removal_rebate {
+            self.first_removal_rebate = rebate;
+        }
+        if let Some(discount_bps) = update.credit_discount_bps {
+            require!(
+                discount_bps as u64 <= BPS_DENOMINATOR,
+                CubeGameError::InvalidBps
+            );
+            self.credit_discount_bps = discount_bps;
+        }
+        if let Some(reserve_bps) = update.refund_reserve_bps {
+            require!(
+                reserve_bps == 0 || has_refund_pool,
+                CubeGameError::RefundPoolRequired
+            );
+            self.refund_reserve_bps = reserve_bps;
+        }
+        if let Some(burn_bps) = update.sol_burn_bps {
+            self.sol_burn_bps = burn_bps;
+        }
+        self.require_valid_split()
+    }
+
+    /// Require an attested `priority_fee` of at least `min_priority_fee`. The
+    /// client supplies the figure, so this is a courtesy check rather than
+    /// proof of what the transaction paid.
+    pub fn require_priority_fee(&self, priority_fee: u64) -> Result<()> {
+        require!(
+            priority_fee >= self.min_priority_fee,
+            CubeGameError::PriorityFeeTooLow
+        );
+        Ok(())
+    }
+
+    /// Whether a removal at `price` is high-value and needs a `RemovalIntent`
+    pub fn needs_confirmation(&self, price: u64) -> bool {
+        self.high_value_threshold > 0 && price > self.high_value_threshold
+    }
+
+    /// Warning for a player left with `balance` lamports after paying, when
+    /// that is under `low_balance_threshold`
+    pub fn low_balance_warning(
+        &self,
+        player: Pubkey,
+        balance: u64,
+    ) -> Option<LowBalanceWarningEvent> {
+        (balance < self.low_balance_threshold).then_some(LowBalanceWarningEvent {
+            player,
+            balance,
+            threshold: self.low_balance_threshold,
+        })
+    }
+
+    /// Key holding `role`: the role's own key, or the owner while it is unset
+    pub fn role(&self, role: Pubkey) -> Pubkey {
+        if role == Pubkey::default() {
+            self.authority
+        } else {
+            role
+        }
+    }
+
+    /// Reject removals while paused or after the game ended, extending a
+    /// timed game when a removal lands inside the anti-snipe window
+    pub fn require_open_and_extend(&mut self, now: i64) -> Result<()> {
+        require!(!self.paused, CubeGameError::GamePaused);
+        require!(!self.refund_all_mode, CubeGameError::RefundModeActive);
+        if self.ends_at == 0 {
+            return Ok(());
+        }
+        require!(now < self.ends_at, CubeGameError::GameEnded);
+
+        if self.anti_snipe_extension > 0
+            && self.ends_at - now <= self.anti_snipe_window
+            && self.extensions_used < self.max_extensions
+        {
+            self.ends_at += self.anti_snipe_extension;
+            self.extensions_used += 1;
+            emit!(GameExtendedEvent {
+                ends_at: self.ends_at,
+                extensions_used: self.extensions_used,
+            });
+        }
+        Ok(())
+    }
+
+    /// Count a removal against this slot's `max_removals_per_slot`,
+    /// rejecting it once the slot is full
+    pub fn throttle_slot(&mut self) -> Result<()> {
+        if self.max_removals_per_slot == 0 {
+            return Ok(());
+        }
+        self.throttle_slot_at(Clock::get()?.slot)
+    }
+
+    /// `throttle_slot` for a removal landing in `slot`
+    pub fn throttle_slot_at(&mut self, slot: u64) -> Result<()> {
+        if self.max_removals_per_slot == 0 {
+            return Ok(());
+        }
+        if slot != self.current_slot {
+            self.current_slot = slot;
+            self.removals_this_slot = 0;
+        }
+        require!(
+            self.removals_this_slot < self.max_removals_per_slot,
+            CubeGameError::SlotThrottled
+        );
+        self.removals_this_slot += 1;
+        Ok(())
+    }
+
+    /// Reject a removal arriving within `min_global_interval_seconds` of the
+    /// previous one from any player, recording it otherwise
+    pub fn require_global_pace(&mut self, now: i64) -> Result<()> {
+        if self.min_global_interval_seconds == 0 {
+            return Ok(());
+        }
+        require!(
+            self.last_global_removal_at == 0
+                || elapsed(now, self.last_global_removal_at) >= self.min_global_interval_seconds,
+            CubeGameError::GlobalPaceLimited
+        );
+        self.last_global_removal_at = now;
+        Ok(())
+    }
+
+    /// Label and decimals clients show prices in, the label at most 16 bytes
+    pub fn set_currency(&mut self, label: String, decimals: u8) -> Result<()> {
+        require!(label.len() <= 16, CubeGameError::CurrencyLabelTooLong);
+        self.currency_label = label;
+        self.currency_decimals = decimals;
+        Ok(())
+    }
+
+    /// Configuration and progress reported by `get_game_info`
+    pub fn info(&self) -> Result<GameInfoEvent> {
+        Ok(GameInfoEvent {
+            authority: self.authority,
+            price_per_cube: self.base_price()?,
+            total_cubes_removed: self.total_cubes_removed,
+            max_cubes: self.max_cubes,
+            board_size: self.board_size,
+            completion_bps: self.completion_bps(),
+            ends_at: self.ends_at,
+            currency_label: self.currency_label.clone(),
+            currency_decimals: self.currency_decimals,
+            stats_display_multiplier_bps: self.stats_display_multiplier_bps,
+        })
+    }
+
+    /// Removals that clear the board: the smaller of `max_cubes` and
+    /// `board_size` when either is set, 0 for an unbounded board
+    pub fn cube_limit(&self) -> u64 {
+        match (self.max_cubes, self.board_size) {
+            (0, size) => size,
+            (max, 0) => max,
+            (max, size) => max.min(size),
+        }
+    }
+
+    /// True once a bounded board has been cleared
+    pub fn board_cleared(&self) -> bool {
+        let limit = self.cube_limit();
+        limit > 0 && self.total_cubes_removed >= limit
+    }
+
+    /// Reject removals once a bounded board has been cleared
+    pub fn require_board_open(&self) -> Result<()> {
+        require!(!self.board_cleared(), CubeGameError::BoardComplete);
+        Ok(())
+    }
+
+    /// Reward tokens to mint for the milestones a player crossed going from
+    /// `before` to `after` removals, 0 without a reward mint
+    pub fn milestone_reward(&self, before: u64, after: u64) -> Result<u64> {
+        if self.reward_mint == Pubkey::default() || self.milestone_every == 0 {
+            return Ok(0);
+        }
+        let crossed = after / self.milestone_every - before / self.milestone_every;
+        self.milestone_reward_amount
+            .checked_mul(crossed)
+            .ok_or(error!(CubeGameError::MathOverflow))
+    }
+
+    /// Lamports the cold reserve, with `available` to spare, moves into a
+    /// treasury holding `treasury_balance` to restore `target_hot_balance`
+    pub fn hot_refill(&self, treasury_balance: u64, available: u64) -> u64 {
+        self.target_hot_balance
+            .saturating_sub(treasury_balance)
+            .min(available)
+    }
+
+    /// Bonus paid for the `combo_count`-th removal of a timed combo
+    pub fn combo_timer_bonus(&self, combo_count: u32) -> u64 {
+        let bonus = self
+            .combo_step_bonus
+            .saturating_mul(combo_count.saturating_sub(1) as u64);
+        if self.combo_max_bonus > 0 {
+            bonus.min(self.combo_max_bonus)
+        } else {
+            bonus
+        }
+    }
+
+    /// Set `price_per_cube`, starting a new `price_epoch`
+    pub fn set_price(&mut self, price: u64) {
+        self.price_per_cube = price;
+        self.price_epoch += 1;
+    }
+
+    /// Return to a flat `base_price`, clearing every board-wide modifier
+    pub fn reset_pricing(&mut self, base_price: u64) {
+        self.set_price(base_price);
+        self.price_in_points = 0;
+        self.surge_multiplier_bps = BPS_DENOMINATOR as u16;
+        self.surge_until = 0;
+        self.quadratic_coeff = 0;
+        self.rush_window_seconds = 0;
+        self.rush_bps_per_removal = 0;
+        self.rush_max_bps = 0;
+        self.rush_window_start = 0;
+        self.recent_removal_count = 0;
+        self.credit_discount_bps = 0;
+    }
+
+    /// Require `expected` (when given) to be the current `price_epoch`, so a
+    /// removal priced before a price change fails instead of paying the new
+    /// price
+    pub fn require_price_epoch(&self, expected: Option<u64>) -> Result<()> {
+        if let Some(epoch) = expected {
+            require!(epoch == self.price_epoch, CubeGameError::PriceEpochMismatch);
+        }
+        Ok(())
+    }
+
+    /// Require `expected` (when given) to equal the removals made so far, so
+    /// a client claiming a specific removal number fails on a stale view
+    pub fn require_expected_total(&self, expected: Option<u64>) -> Result<()> {
+        if let Some(total) = expected {
+            require!(
+                total == self.total_cubes_removed,
+                CubeGameError::ConcurrencyConflict
+            );
+        }
+        Ok(())
+    }
+
+    /// `price` less `member_discount_bps` for a proven `member`
+    pub fn member_price(&self, price: u64, member: bool) -> Result<u64> {
+        if !member {
+            return Ok(price);
+        }
+        Ok(price - apply_bps(price, self.member_discount_bps)?)
+    }
+
+    /// Shares of an `amount` payment, each rounded down; the treasury takes
+    /// what is left
+    pub fn payment_shares(&self, amount: u64) -> Result<PaymentShares> {
+        let reserve = apply_bps(amount, self.refund_reserve_bps)?;
+        let insurance = apply_bps(amount, self.insurance_bps)?;
+        let burn = apply_bps(amount, self.sol_burn_bps)?;
+        let dao_tax = apply_bps(amount, self.dao_tax_bps)?;
+        Ok(PaymentShares {
+            reserve,
+            insurance,
+            burn,
+            dao_tax,
+            to_treasury: amount - reserve - insurance - burn - dao_tax,
+        })
+    }
+
+    /// Lamport value of `token_amount` base units of the payment mint
+    pub fn token_value(&self, token_amount: u64) -> Result<u64> {
+        let value = token_amount as u128 * self.split_rate as u128 / SPLIT_RATE_SCALE as u128;
+        u64::try_from(value).map_err(|_| error!(CubeGameError::MathOverflow))
+    }
+
+    /// How a split payment offering `sol_amount` lamports and up to
+    /// `token_amount` tokens settles `price`: the fewest tokens worth at
+    /// most the price, then the SOL still needed. A free removal takes
+    /// neither.
+    pub fn settle_split(
+        &self,
+        price: u64,
+        sol_amount: u64,
+        token_amount: u64,
+    ) -> Result<SplitSettlement> {
+        if price == 0 || self.split_rate == 0 {
+            require!(sol_amount >= price, CubeGameError::SplitUnderpaid);
+            return Ok(SplitSettlement {
+                tokens: 0,
+                sol_due: price,
+                dust: sol_amount - price,
+            });
+        }
+        let needed = (price as u128 * SPLIT_RATE_SCALE as u128).div_ceil(self.split_rate as u128);
+        let tokens = u64::try_from(needed.min(token_amount as u128))
+            .map_err(|_| error!(CubeGameError::MathOverflow))?;
+        let token_value = self.token_value(tokens)?.min(price);
+        let sol_due = price - token_value;
+        require!(sol_amount >= sol_due, CubeGameError::SplitUnderpaid);
+        Ok(SplitSettlement {
+            tokens,
+            sol_due,
+            dust: sol_amount - sol_due,
+        })
+    }
+
+    /// `value` scaled by `stats_display_multiplier_bps`
+    pub fn scaled_stat(&self, value: u64) -> Result<u64> {
+        apply_bps(value, self.stats_display_multiplier_bps)
+    }
+
+    /// Count a removal, noting the slot that clears the board. The completion
+    /// seed is the hash of a later slot, which the completing remover cannot
+    /// know or grind.
+    pub fn count_removal(&mut self) -> Result<()> {
+        self.total_cubes_removed += 1;
+        // Only `note_last_removal` makes a removal undoable; any other path
+        // leaves nothing to undo
+        self.last_cube_id.clear();
+        if self.rush_window_seconds > 0 {
+            self.note_rush(Clock::get()?.unix_timestamp);
+        }
+        if self.cube_limit() > 0 && self.total_cubes_removed == self.cube_limit() {
+            self.completion_slot = Clock::get()?.slot;
+        }
+        Ok(())
+    }
+
+    /// Require a Merkle proof that `cube_id` is on the committed board, when
+    /// one is committed. Leaves are `keccak(0x00 || cube_id)` and nodes
+    /// `keccak(0x01 || min(a, b) || max(a, b))`.
+    pub fn verify_on_board(&self, cube_id: &str, proof: &[[u8; 32]]) -> Result<()> {
+        use anchor_lang::solana_program::keccak::hashv;
+
+        if self.board_root == [0; 32] {
+            return Ok(());
+        }
+        let mut node = hashv(&[&[0x00], cube_id.as_bytes()]).0;
+        for sibling in proof {
+            let (a, b) = if node <= *sibling {
+                (node, *sibling)
+            } else {
+                (*sibling, node)
+            };
+            node = hashv(&[&[0x01], &a, &b]).0;
+        }
+        require!(node == self.board_root, CubeGameError::NotOnBoard);
+        Ok(())
+    }
+
+    /// Commit to `board_hash` over `board_size` leaves, refused once a cube
+    /// has been removed
+    pub fn commit_board_root(&mut self, board_hash: [u8; 32], board_size: u64) -> Result<()> {
+        require!(self.total_cubes_removed == 0, CubeGameError::BoardLocked);
+        require!(
+            board_size == 0 || !self.ephemeral_records,
+            CubeGameError::EphemeralBoard
+        );
+        self.board_root = board_hash;
+        self.board_size = board_size;
+        Ok(())
+    }
+
+    /// Admit `record` for a removal of `cube_id`, proving it is on the
+    /// committed board. A fresh record is materialized here and counted
+    /// against `max_cube_records`, so no record exists before its proof.
+    pub fn materialize(
+        &mut self,
+        record: &CubeRecord,
+        cube_id: &str,
+        proof: &[[u8; 32]],
+    ) -> Result<()> {
+        self.verify_on_board(cube_id, proof)?;
+        record.validate(cube_id)?;
+        require!(!record.is_removed, CubeGameError::CubeAlreadyRemoved);
+        if record.cube_id.is_empty() {
+            self.note_record_created()?;
+        }
+        Ok(())
+    }
+
+    /// Require `keccak(cube_id || player || nonce)` to start with
+    /// `pow_difficulty` zero bits
+    pub fn verify_proof_of_work(&self, cube_id: &str, player: &Pubkey, nonce: u64) -> Result<()> {
+        if self.pow_difficulty == 0 {
+            return Ok(());
+        }
+        let hash = anchor_lang::solana_program::keccak::hashv(&[
+            cube_id.as_bytes(),
+            player.as_ref(),
+            &nonce.to_le_bytes(),
+        ]);
+
+        let mut zero_bits = 0u32;
+        for byte in hash.0 {
+            zero_bits += byte.leading_zeros();
+            if byte != 0 {
+                break;
+            }
+        }
+        require!(
+            zero_bits >= self.pow_difficulty as u32,
+            CubeGameError::InvalidProofOfWork
+        );
+        Ok(())
+    }
+
+    /// Reject withdrawals until the cooling period since the latest deposit
+    /// has passed
+    pub fn require_withdraw_cooled(&self, now: i64) -> Result<()> {
+        require!(
+            elapsed(now, self.last_deposit_at) >= self.withdraw_cooldown_seconds,
+            CubeGameError::WithdrawCoolingPeriod
+        );
+        Ok(())
+    }
+
+    /// Admit a withdrawal of `amount` from a treasury with `spendable`
+    /// lamports above its rent floor: within the balance, clear of what is
+    /// owed to players, and within the rate limit, which it then uses up.
+    /// Nothing is recorded unless every check passes.
+    pub fn authorize_withdrawal(&mut self, amount: u64, spendable: u64, now: i64) -> Result<()> {
+        require!(
+            amount <= spendable,
+            CubeGameError::InsufficientTreasuryBalance
+        );
+        self.require_liabilities_covered(amount, spendable)?;
+        self.consume_withdraw_allowance(amount, now)
+    }
+
+    /// Count `amount` as paid for a cube that is now removed
+    pub fn note_paid(&mut self, amount: u64) {
+        self.paid_liability = self.paid_liability.saturating_add(amount);
+    }
+
+    /// Remember the removal `undo_last_removal` would reverse
+    pub fn note_last_removal(&mut self, cube_id: &str, region_id: &str, removed_at: i64) {
+        self.last_cube_id = cube_id.to_string();
+        self.last_region_id = region_id.to_string();
+        self.last_removed_at = removed_at;
+    }
+
+    /// Stop counting `amount` once its cube is restored or refunded
+    pub fn release_paid(&mut self, amount: u64) {
+        self.paid_liability = self.paid_liability.saturating_sub(amount);
+    }
+
+    /// In refund-all mode, keep what is still owed to removers in the
+    /// treasury after withdrawing `amount` of its `spendable` lamports
+    pub fn require_liabilities_covered(&self, amount: u64, spendable: u64) -> Result<()> {
+        if self.refund_all_mode {
+            require!(
+                spendable.saturating_sub(amount) >= self.paid_liability,
+                CubeGameError::RefundLiabilityOutstanding
+            );
+        }
+        Ok(())
+    }
+
+    /// Require `amount` to be stakeable from the treasury's `spendable`
+    /// lamports, keeping `stake_liquid_reserve` and what refund-all mode
+    /// owes liquid
+    pub fn require_stakeable(&self, amount: u64, spendable: u64) -> Result<()> {
+        let required = self
+            .stake_liquid_reserve
+            .checked_add(amount)
+            .ok_or(CubeGameError::MathOverflow)?;
+        require!(spendable >= required, CubeGameError::InsufficientLiquidity);
+        self.require_liabilities_covered(amount, spendable)
+    }
+
+    /// Whether an optional payout of `amount` fits in the treasury's
+    /// `spendable` lamports without touching what refund-all mode owes
+    pub fn can_pay(&self, amount: u64, spendable: u64) -> bool {
+        amount <= spendable && self.require_liabilities_covered(amount, spendable).is_ok()
+    }
+
+    /// Reject withdrawals until `min_cubes_before_withdraw` cubes have been
+    /// removed
+    pub fn require_withdraw_threshold(&self) -> Result<()> {
+        require!(
+            self.total_cubes_removed >= self.min_cubes_before_withdraw,
+            CubeGameError::WithdrawThresholdNotMet
+        );
+        Ok(())
+    }
+
+    /// Make sure the stored bumps still produce the canonical game state and
+    /// treasury addresses before signing with them
+    pub fn verify_pda_bumps(&self) -> Result<()> {
+        let (_, game_bump) = Pubkey::find_program_address(&[b"game_state"], &crate::ID);
+        require!(self.bump == game_bump, CubeGameError::GameStatePdaMismatch);
+        let (_, treasury_bump) = Pubkey::find_program_address(&[b"treasury"], &crate::ID);
+        require!(
+            self.treasury_bump == treasury_bump,
+            CubeGameError::TreasuryPdaMismatch
+        );
+        Ok(())
+    }
+
+    /// Count `amount` against the current withdrawal window, starting a new
+    /// window once the previous one has elapsed
+    pub fn consume_withdraw_allowance(&mut self, amount: u64, now: i64) -> Result<()> {
+        if self.withdraw_limit_per_window == 0 {
+            return Ok(());
+        }
+        if elapsed(now, self.window_start) >= self.withdraw_window_seconds {
+            self.window_start = now;
+            self.window_withdrawn = 0;
+        }
+        let withdrawn = self
+            .window_withdrawn
+            .checked_add(amount)
+            .ok_or(CubeGameError::MathOverflow)?;
+        require!(
+            withdrawn <= self.withdraw_limit_per_window,
+            CubeGameError::WithdrawRateLimited
+        );
+        self.window_withdrawn = withdrawn;
+        Ok(())
+    }
+
+    /// Record a deposit of `amount` that left the treasury at `balance`:
+    /// start the withdraw cooling period and emit a `TreasuryThresholdEvent`
+    /// for every milestone reached, advancing `next_threshold` past them (at
+    /// most `MAX_THRESHOLD_EVENTS` per call)
+    pub fn note_deposit(&mut self, amount: u64, balance: u64, now: i64) {
+        if amount == 0 {
+            return;
+        }
+        self.last_deposit_at = now;
+        for threshold in self.cross_thresholds(balance) {
+            emit!(TreasuryThresholdEvent { balance, threshold });
+        }
+    }
+
+    /// Milestones a treasury at `balance` has reached, advancing
+    /// `next_threshold` past them (at most `MAX_THRESHOLD_EVENTS`)
+    pub fn cross_thresholds(&mut self, balance: u64) -> Vec<u64> {
+        let mut crossed = Vec::new();
+        if self.threshold_step == 0 {
+            return crossed;
+        }
+        while crossed.len() < MAX_THRESHOLD_EVENTS && balance >= self.next_threshold {
+            crossed.push(self.next_threshold);
+            self.next_threshold = self.next_threshold.saturating_add(self.threshold_step);
+        }
+        crossed
+    }
+
+    /// Tier earned by `cubes_removed` and `total_spent`: each configured tier
+    /// above Bronze needs both of its thresholds, and tiers are earned in
+    /// order
+    pub fn tier_for(&self, cubes_removed: u64, total_spent: u64) -> u8 {
+        let mut tier = 0;
+        for (cubes, spent) in self
+            .tier_cube_thresholds
+            .iter()
+            .zip(self.tier_spend_thresholds.iter())
+        {
+            let configured = *cubes > 0 || *spent > 0;
+            if !configured || cubes_removed < *cubes || total_spent < *spent {
+                break;
+            }
+            tier += 1;
+        }
+        tier
+    }
+
+    /// Count a newly created cube record against `max_cube_records`
+    pub fn note_record_created(&mut self) -> Result<()> {
+        require!(
+            self.max_cube_records == 0 || self.active_cube_records < self.max_cube_records,
+            CubeGameError::RecordLimitReached
+        );
+        self.active_cube_records += 1;
+        Ok(())
+    }
+
+    /// Count a removal at `now` in the rush window, opening a new window
+    /// once the current one has lapsed
+    pub fn note_rush(&mut self, now: i64) {
+        if elapsed(now, self.rush_window_start) >= self.rush_window_seconds {
+            self.rush_window_start = now;
+            self.recent_removal_count = 0;
+        }
+        self.recent_removal_count += 1;
+    }
+
+    /// Removals counted in the current rush window, 0 once it has lapsed
+    pub fn recent_removals(&self, now: i64) -> u64 {
+        if self.rush_window_seconds == 0
+            || elapsed(now, self.rush_window_start) >= self.rush_window_seconds
+        {
+            return 0;
+        }
+        self.recent_removal_count
+    }
+
+    /// Tip owed to `last_remover` out of a removal by `player` paying
+    /// `sol_due`. The very first removal has nobody to tip, players do not
+    /// tip themselves, and boards whose records close on removal have
+    /// nowhere to keep the tip until it is claimed.
+    pub fn tip_for(&self, player: &Pubkey, sol_due: u64) -> Result<u64> {
+        if self.last_remover == Pubkey::default()
+            || self.last_remover == *player
+            || self.ephemeral_records
+        {
+            return Ok(0);
+        }
+        apply_bps(sol_due, self.tip_bps)
+    }
+
+    /// Reject payment splits that would hand out more than the payment
+    pub fn require_valid_split(&self) -> Result<()> {
+        let split = self.refund_reserve_bps as u64
+            + self.insurance_bps as u64
+            + self.sol_burn_bps as u64
+            + self.dao_tax_bps as u64
+            + self.tip_bps as u64
+            + self.staker_reward_bps as u64;
+        require!(split <= BPS_DENOMINATOR, CubeGameError::InvalidBps);
+        Ok(())
+    }
+
+    /// Base price in lamports before any modifier: `price_in_points` at the
+    /// point peg when set, otherwise `price_per_cube`
+    pub fn base_price(&self) -> Result<u64> {
+        self.require_point_peg()?;
+        if self.price_in_points == 0 {
+            return Ok(self.price_per_cube);
+        }
+        self.price_in_points
+            .checked_mul(self.point_value_lamports)
+            .ok_or(error!(CubeGameError::MathOverflow))
+    }
+
+    /// A price in points must have a peg, or every removal would be free
+    pub fn require_point_peg(&self) -> Result<()> {
+        require!(
+            self.price_in_points == 0 || self.point_value_lamports > 0,
+            CubeGameError::PointValueUnset
+        );
+        Ok(())
+    }
+
+    /// Share of a bounded board removed so far, 10000 once cleared and 0 for
+    /// an unbounded board
+    pub fn completion_bps(&self) -> u16 {
+        let limit = self.cube_limit();
+        if limit == 0 {
+            return 0;
+        }
+        let removed = self.total_cubes_removed.min(limit) as u128;
+        (removed * BPS_DENOMINATOR as u128 / limit as u128) as u16
+    }
+
+    /// Whether bombs detonate on `region_id`. Boards whose neighbours'
+    /// zones, proofs, hits, shared removers or queue turns a free flip
+    /// cannot honour have no bombs.
+    pub fn bombs_armed(&self, region_id: &str) -> bool {
+        self.bomb_rate_bps > 0
+            && self.zone_count == 0
+            && self.board_root == [0; 32]
+            && self.hits_required <= 1
+            && self.max_removers <= 1
+            && !(self.queue_turn_seconds > 0 && region_id.is_empty())
+    }
+
+    /// Reject switching to ephemeral records while a record exists that
+    /// its remover did not pay for, or could be created that way
+    pub fn require_ephemeral_compatible(&self) -> Result<()> {
+        require!(
+            self.cube_limit() == 0
+                && self.zone_count == 0
+                && self.active_cube_records == 0
+                && self.hits_required <= 1
+                && self.max_removers <= 1,
+            CubeGameError::EphemeralBoard
+        );
+        Ok(())
+    }
+
+    /// Stop counting a closed record against `max_cube_records`
+    pub fn note_record_closed(&mut self) {
+        self.active_cube_records = self.active_cube_records.saturating_sub(1);
+    }
+
+    /// Stop counting a record closed on an ephemeral board, and what was
+    /// `paid` toward it
+    pub fn forget_record(&mut self, paid: u64) {
+        self.note_record_closed();
+        self.release_paid(paid);
+    }
+
+    /// Take a player's `weight` out of the grand-prize draw when their
+    /// `PlayerStats` close, refused while `finalize_game` is counting
+    pub fn drop_entrant(&mut self, weight: u64) -> Result<()> {
+        require!(
+            self.finalize_weight == 0 || self.winner.is_some(),
+            CubeGameError::FinalizeInProgress
+        );
+        self.entrant_weight = self.entrant_weight.saturating_sub(weight);
+        Ok(())
+    }
+
+    /// True once a timed game has ended or a bounded board has been cleared
+    pub fn is_over(&self, now: i64) -> bool {
+        (self.ends_at != 0 && now >= self.ends_at) || self.board_cleared()
+    }
+
+    /// Color stored for a removal of `cube_id`: the palette color when a
+    /// palette seed is set, otherwise the player's `chosen` color
+    pub fn removal_color(&self, cube_id: &str, chosen: u32) -> u32 {
+        if self.palette_seed == 0 {
+            chosen
+        } else {
+            cube_color(cube_id, self.palette_seed)
+        }
+    }
+
+    pub fn validate_cube_id(&self, cube_id: &str) -> Result<()> {
+        require!(
+            cube_id.starts_with(self.required_prefix.as_str()),
+            CubeGameError::InvalidCubeId
+        );
+        Ok(())
+    }
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct CubeRecord {
+    pub is_removed: bool,
+    pub removed_by: Pubkey,
+    pub removed_at: i64,
+    #[max_len(32)]
+    pub cube_id: String,
+    pub price_paid: u64,
+    #[max_len(MAX_MEMO_LEN)]
+    pub memo: String,
+    pub color: u32,
+    /// Client-attested priority fee of the removing transaction, for
+    /// analytics only
+    pub priority_fee: u64,
+    /// Hits needed to remove the cube, fixed by its first hit
+    pub hits_required: u8,
+    /// Hits dealt since the cube was last restored
+    pub hits_taken: u8,
+    /// When the cube becomes free to remove (0 = never), set by `place_cubes`
+    pub expires_at: i64,
+    /// Players who must remove the cube before it disappears, fixed by its
+    /// first removal
+    pub max_removers: u8,
+    pub remover_count: u8,
+    /// Every wallet that paid toward the cube since it was last restored,
+    /// with the lamports each paid: its contributors, shared removers or
+    /// hitters
+    #[max_len(MAX_CUBE_CONTRIBUTORS)]
+    pub payments: Vec<CubeContribution>,
+    /// Endorsements from other players via `like_cube`
+    pub likes: u64,
+    /// Cube in the same region that must be removed first, set by
+    /// `place_cubes`
+    #[max_len(32)]
+    pub prev_cube_id: Option<String>,
+    /// Set once `reveal_cube` has rolled the attributes below
+    pub revealed: bool,
+    pub rarity: u64,
+    pub revealed_color: u32,
+    /// Wallet credited with designing the cube, set by `place_cubes`
+    pub creator: Pubkey,
+    /// Share of each removal payment routed to `creator`
+    pub royalty_bps: u16,
+    /// Previous remover tipped by the cube's last removal, who claims
+    /// `tip_owed` from the treasury with `claim_tip`
+    pub tip_to: Pubkey,
+    pub tip_owed: u64,
+    /// Wallet whose stats the removal was counted in. Unlike `removed_by`,
+    /// `transfer_cube` leaves it alone.
+    pub original_remover: Pubkey,
+    /// Slot `unlock_cube` was paid in, whose successor's hash rolls the
+    /// revealed attributes (0 = never unlocked)
+    pub unlock_slot: u64,
+}
+
+impl CubeRecord {
+    /// Ranked score a removal of `cube_id` earns: the rarity `reveal_cube`
+    /// rolled, or `cube_score` for a cube never revealed
+    pub fn ranked_score(&self, cube_id: &str) -> u64 {
+        if self.revealed {
+            self.rarity
+        } else {
+            cube_score(cube_id)
+        }
+    }
+
+    /// Whether `player` already removed `cube_id`, so a retried removal can
+    /// succeed without charging again. A cube someone else removed is still
+    /// `CubeAlreadyRemoved`.
+    pub fn removed_by_caller(&self, cube_id: &str, player: &Pubkey) -> Result<bool> {
+        if !self.is_removed {
+            return Ok(false);
+        }
+        self.validate(cube_id)?;
+        require!(
+            self.removed_by == *player,
+            CubeGameError::CubeAlreadyRemoved
+        );
+        Ok(true)
+    }
+
+    /// Require the cube's predecessor in `region_id`, if it has one, to be
+    /// passed as `predecessor` (its address and record) and already removed
+    pub fn require_predecessor_removed(
+        &self,
+        region_id: &str,
+        predecessor: Option<(Pubkey, &CubeRecord)>,
+    ) -> Result<()> {
+        let Some(prev_id) = self.prev_cube_id.as_ref() else {
+            return Ok(());
+        };
+        let (key, predecessor) = predecessor.ok_or(CubeGameError::PredecessorNotRemoved)?;
+        require_keys_eq!(
+            key,
+            scoped_cube_record_pda(region_id, prev_id),
+            CubeGameError::CubeRecordMismatch
+        );
+        require!(predecessor.is_removed, CubeGameError::PredecessorNotRemoved);
+        Ok(())
+    }
+
+    /// Take `amount` of a batch refund out of what `contributor` paid toward
+    /// the removed cube
+    pub fn take_refund(&mut self, contributor: &Pubkey, amount: u64) -> Result<()> {
+        require!(self.is_removed, CubeGameError::CubeNotRemoved);
+        let payment = self
+            .payments
+            .iter_mut()
+            .find(|p| p.contributor == *contributor)
+            .ok_or(CubeGameError::NotCubeOwner)?;
+        payment.amount = payment
+            .amount
+            .checked_sub(amount)
+            .ok_or(CubeGameError::NothingToRefund)?;
+        Ok(())
+    }
+
+    /// What the next hit on a cube priced at `price` pays, and whether it is
+    /// the final one. The first hit fixes `hits_required` from the game's
+    /// `hits_required`; hits split the price evenly, the final hit paying
+    /// any rounding remainder.
+    pub fn hit_price(&mut self, hits_required: u8, price: u64) -> (u64, bool) {
+        if self.hits_taken == 0 {
+            self.hits_required = hits_required.max(1);
+        }
+        let hits_required = self.hits_required as u64;
+        let final_hit = self.hits_taken as u64 + 1 >= hits_required;
+        if hits_required == 1 {
+            return (price, final_hit);
+        }
+        let share = price / hits_required;
+        if final_hit {
+            (price - share * (hits_required - 1), true)
+        } else {
+            (share, false)
+        }
+    }
+
+    /// Count a like of the removed cube, returning the new total
+    pub fn add_like(&mut self) -> Result<u64> {
+        require!(self.is_removed, CubeGameError::CubeNotRemoved);
+        self.likes += 1;
+        Ok(self.likes)
+    }
+
+    /// Stamp a removal at `now`, never moving `removed_at` back past an
+    /// earlier removal when the clock regresses
+    pub fn stamp_removed_at(&mut self, now: i64) {
+        self.removed_at = now.max(self.removed_at);
+    }
+
+    /// Color stored for a removal of `cube_id`: the revealed colour once
+    /// `reveal_cube` has rolled one, otherwise `GameState::removal_color`
+    pub fn removal_color(&self, game: &GameState, cube_id: &str, chosen: u32) -> u32 {
+        if self.revealed {
+            self.revealed_color
+        } else {
+            game.removal_color(cube_id, chosen)
+        }
+    }
+
+    /// Wallet whose stats this removal counted in, falling back to
+    /// `removed_by` for records removed before `original_remover` existed
+    pub fn credited_remover(&self) -> Pubkey {
+        if self.original_remover == Pubkey::default() {
+            self.removed_by
+        } else {
+            self.original_remover
+        }
+    }
+
+    /// Lamports paid toward the cube since it was last restored, what
+    /// refunding it gives back
+    pub fn sol_paid(&self) -> u64 {
+        self.payments.iter().map(|p| p.amount).sum()
+    }
+
+    /// Lamports to release from `paid_liability` when `player` closes the
+    /// record on exit. Refused for a record held by someone else, still
+    /// owing a refund to another payer or holding an unclaimed tip.
+    pub fn exit_release(&self, player: &Pubkey) -> Result<u64> {
+        require_keys_eq!(self.removed_by, *player, CubeGameError::NotCubeOwner);
+        require!(
+            self.payments.iter().all(|p| p.contributor == *player),
+            CubeGameError::CubeHasOtherPayers
+        );
+        require!(self.tip_owed == 0, CubeGameError::TipUnclaimed);
+        Ok(self.sol_paid())
+    }
+
+    /// Hand the unclaimed tip to `claimer`, who must be the tipped remover
+    pub fn take_tip(&mut self, claimer: &Pubkey) -> Result<u64> {
+        require!(self.tip_owed > 0, CubeGameError::NoTipOwed);
+        require_keys_eq!(self.tip_to, *claimer, CubeGameError::TipRecipientMismatch);
+        let tip = self.tip_owed;
+        self.tip_owed = 0;
+        self.tip_to = Pubkey::default();
+        Ok(tip)
+    }
+
+    /// True once a placed cube's expiry has passed
+    pub fn is_expired(&self, now: i64) -> bool {
+        self.expires_at > 0 && now >= self.expires_at
+    }
+
+    /// Price of removing the cube at `now`: free once it has expired,
+    /// otherwise `effective_price`
+    pub fn removal_price(
+        &self,
+        game: &GameState,
+        region_price: Option<u64>,
+        fee_exempt: bool,
+        now: i64,
+    ) -> Result<u64> {
+        if self.is_expired(now) {
+            return Ok(0);
+        }
+        effective_price(game, region_price, fee_exempt, now)
+    }
+
+    /// True for a record that `init_if_needed` has just created
+    pub fn is_fresh(&self) -> bool {
+        self.cube_id.is_empty()
+            && !self.is_removed
+            && self.removed_by == Pubkey::default()
+            && self.removed_at == 0
+            && self.price_paid == 0
+            && self.memo.is_empty()
+            && self.color == 0
+    }
+
+    /// Reject record states no instruction can legitimately produce, so
+    /// `init_if_needed` never silently treats a damaged record as new
+    pub fn validate(&self, cube_id: &str) -> Result<()> {
+        if self.cube_id.is_empty() {
+            require!(self.is_fresh(), CubeGameError::CubeRecordCorrupt);
+        } else {
+            require!(self.cube_id == cube_id, CubeGameError::CubeRecordCorrupt);
+            require!(
+                !self.is_removed || self.removed_by != Pubkey::default(),
+                CubeGameError::CubeRecordCorrupt
+            );
+        }
+        Ok(())
+    }
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct PlayerStats {
+    pub player: Pubkey,
+    pub cubes_removed: u64,
+    pub total_spent: u64,
+    /// Self-imposed maximum total spend (0 = no cap)
+    pub spend_cap: u64,
+    pub pending_spend_cap: u64,
+    /// When `pending_spend_cap` replaces `spend_cap` (0 = nothing pending)
+    pub spend_cap_raise_at: i64,
+    /// Mask the player's key in emitted events
+    pub private: bool,
+    /// Team the player's removals are credited to (empty = none)
+    #[max_len(32)]
+    pub team_id: String,
+    /// Suspended by the authority, blocks removals
+    pub frozen: bool,
+    /// Removals that completed a neighbour combo
+    pub combos: u64,
+    pub last_removal_at: i64,
+    pub bombs_triggered: u64,
+    /// 0 = Bronze, 1 = Silver, 2 = Gold, 3 = Platinum. Never lowered.
+    pub tier: u8,
+    /// When `claim_daily` last paid out (0 = never)
+    pub last_daily_claim_at: i64,
+    /// Removals in the current timed combo
+    pub combo_count: u32,
+    /// The timed combo resets on a removal at or after this time
+    pub combo_expires_at: i64,
+    /// 1-based order of the player's first removal (0 = no removal yet)
+    pub player_index: u64,
+    /// Among the first `founder_cap` players. Never unset.
+    pub is_founder: bool,
+    /// Player who referred this one (default = none)
+    pub referrer: Pubkey,
+    /// Referral cut accrued and not yet claimed
+    pub referral_earnings: u64,
+    pub referral_claimed: u64,
+    /// Sum of `cube_score` over the cubes removed, for ranked play
+    pub score: u64,
+    /// Sandbox where removals are free and count only towards
+    /// `practice_removals`
+    pub practice_mode: bool,
+    pub practice_removals: u64,
+    /// `action_key` of the cube and `action_nonce` of the player's last
+    /// applied removal
+    pub last_action: [u8; 32],
+    /// Removals are recorded in the player's `PlayerHistory`
+    pub history_enabled: bool,
+    /// When `register_player` was first called (0 = never)
+    pub registered_at: i64,
+    /// Last community goal the player removed a cube towards, and the last
+    /// one whose reward they claimed
+    pub community_goal_id: u64,
+    pub community_reward_claimed: u64,
+    /// Score the player is listed with on the leaderboard (0 = not listed)
+    pub leaderboard_recorded: u64,
+}
+
+impl PlayerStats {
+    /// Whether removing `cube_id` with `action_nonce` repeats the player's
+    /// last applied removal. The same nonce on another cube is a new action.
+    pub fn is_replay(&self, cube_id: &str, action_nonce: u64) -> bool {
+        action_nonce != 0 && self.last_action == action_key(cube_id, action_nonce)
+    }
+
+    /// What the leaderboard ranks the player by: score in ranked mode,
+    /// otherwise removals
+    pub fn leaderboard_score(&self, ranked: bool) -> u64 {
+        if ranked {
+            self.score
+        } else {
+            self.cubes_removed
+        }
+    }
+
+    /// Stamp `player`'s registration at `now`. Registering again keeps the
+    /// first stamp, so it can't be used to reset the player's age.
+    pub fn stamp_registration(&mut self, player: Pubkey, now: i64) {
+        self.player = player;
+        if self.registered_at == 0 {
+            self.registered_at = now;
+        }
+    }
+
+    /// Require `min_player_age` seconds to have passed since `register_player`
+    pub fn require_aged(&self, game: &GameState, now: i64) -> Result<()> {
+        if game.min_player_age > 0 {
+            require!(
+                self.registered_at != 0 && elapsed(now, self.registered_at) >= game.min_player_age,
+                CubeGameError::PlayerTooNew
+            );
+        }
+        Ok(())
+    }
+
+    /// Suspend or reinstate `player`'s removals, leaving every other stat as
+    /// it is. On a freshly created account this makes a frozen stub.
+    pub fn set_frozen(&mut self, player: Pubkey, frozen: bool) {
+        self.player = player;
+        self.frozen = frozen;
+    }
+
+    /// Require the player to be free to make a real removal: not frozen, out
+    /// of the practice sandbox and old enough
+    pub fn require_can_remove(&self, game: &GameState, now: i64) -> Result<()> {
+        require!(!self.frozen, CubeGameError::PlayerFrozen);
+        require!(!self.practice_mode, CubeGameError::PracticeModeOn);
+        self.require_aged(game, now)
+    }
+
+    /// Mark a cube removed in the player's practice sandbox
+    pub fn practice_remove(&mut self, record: &mut PracticeRecord) -> Result<()> {
+        require!(self.practice_mode, CubeGameError::PracticeModeOff);
+        require!(!record.is_removed, CubeGameError::CubeAlreadyRemoved);
+        record.is_removed = true;
+        self.practice_removals += 1;
+        Ok(())
+    }
+
+    /// Mark today's daily reward as claimed, once the player is eligible
+    pub fn claim_daily(&mut self, game: &GameState, now: i64) -> Result<()> {
+        require!(!self.frozen, CubeGameError::PlayerFrozen);
+        require!(self.cubes_removed > 0, CubeGameError::DailyRewardIneligible);
+        self.require_aged(game, now)?;
+        require!(
+            self.last_daily_claim_at == 0
+                || self.last_daily_claim_at.div_euclid(86_400) < now.div_euclid(86_400),
+            CubeGameError::AlreadyClaimedToday
+        );
+        self.last_daily_claim_at = now;
+        Ok(())
+    }
+
+    /// Give the player their `player_index` on their first removal, with the
+    /// founder badge while it is within `founder_cap`. Both are kept on the
+    /// wallet's `mark`, which is required here: stats reopened after an exit
+    /// take them back from it instead of counting the wallet again. True only
+    /// for a wallet never registered before.
+    pub fn register(
+        &mut self,
+        game: &mut GameState,
+        mark: Option<&mut WalletMark>,
+    ) -> Result<bool> {
+        if self.player_index != 0 {
+            return Ok(false);
+        }
+        let mark = mark.ok_or(CubeGameError::WalletMarkRequired)?;
+        let new_wallet = mark.player_index == 0;
+        if new_wallet {
+            game.total_players += 1;
+            mark.player_index = game.total_players;
+            mark.is_founder = mark.player_index <= game.founder_cap;
+        }
+        self.player_index = mark.player_index;
+        self.is_founder = mark.is_founder;
+        Ok(new_wallet)
+    }
+
+    /// Raise the tier to what the player's totals now earn, emitting a
+    /// `TierUpgradedEvent` (with `public_player`) on an upgrade. Returns
+    /// whether the tier went up.
+    pub fn promote_tier(&mut self, game: &GameState, public_player: Pubkey) -> bool {
+        let earned = game.tier_for(self.cubes_removed, self.total_spent);
+        if earned <= self.tier {
+            return false;
+        }
+        self.tier = earned;
+        emit!(TierUpgradedEvent {
+            player: public_player,
+            tier: earned,
+        });
+        true
+    }
+
+    /// Accrue `referral_bps` of a referred removal's `price`
+    pub fn accrue_referral(&mut self, price: u64, referral_bps: u16) -> Result<()> {
+        self.referral_earnings += apply_bps(price, referral_bps)?;
+        Ok(())
+    }
+
+    /// Move the accrued referral earnings into `referral_claimed`, returning
+    /// what to pay
+    pub fn take_referral_earnings(&mut self) -> Result<u64> {
+        let amount = std::mem::take(&mut self.referral_earnings);
+        require!(amount > 0, CubeGameError::NoReferralEarnings);
+        self.referral_claimed += amount;
+        Ok(amount)
+    }
+
+    /// Count a removal at `now` in the player's timed combo, which lasts
+    /// `window` seconds from each removal; a gap starts a new combo.
+    /// Returns the combo's length.
+    pub fn extend_combo(&mut self, window: i64, now: i64) -> u32 {
+        self.combo_count = if now < self.combo_expires_at {
+            self.combo_count + 1
+        } else {
+            1
+        };
+        self.combo_expires_at = now + window;
+        self.combo_count
+    }
+
+    /// `cubes_removed` decayed by `rate_bps_per_day` for each day (pro rata)
+    /// since the last removal, used only for ranking
+    pub fn active_score(&self, rate_bps_per_day: u16, now: i64) -> u64 {
+        let idle = elapsed(now, self.last_removal_at) as u128;
+        let decay_bps = (rate_bps_per_day as u128 * idle / 86_400).min(BPS_DENOMINATOR as u128);
+        let kept = self.cubes_removed as u128 * (BPS_DENOMINATOR as u128 - decay_bps)
+            / BPS_DENOMINATOR as u128;
+        kept as u64
+    }
+
+    /// Clear what identifies the player beyond their key, which is masked
+    /// in events from now on. Removal counts stay for the global totals.
+    pub fn forget(&mut self) {
+        self.total_spent = 0;
+        self.spend_cap = 0;
+        self.pending_spend_cap = 0;
+        self.spend_cap_raise_at = 0;
+        self.private = true;
+        self.team_id.clear();
+    }
+
+    /// Promote a pending spend cap raise once its cooldown has elapsed
+    pub fn apply_pending_spend_cap(&mut self, now: i64) {
+        if self.spend_cap_raise_at != 0 && now >= self.spend_cap_raise_at {
+            self.spend_cap = self.pending_spend_cap;
+            self.pending_spend_cap = 0;
+            self.spend_cap_raise_at = 0;
+        }
+    }
+}
+
+/// Point-in-time copy of the game's counters, never modified after creation
+#[account]
+#[derive(InitSpace)]
+pub struct Snapshot {
+    #[max_len(32)]
+    pub label: String,
+    pub total_cubes_removed: u64,
+    pub price_per_cube: u64,
+    pub treasury_balance: u64,
+    pub timestamp: i64,
+    pub slot: u64,
+    pub bump: u8,
+}
+
+/// A section of the board, cleared in order with the other zones
+#[account]
+#[derive(InitSpace)]
+pub struct Zone {
+    pub zone_id: u32,
+    /// Cubes whose id starts with this prefix belong to the zone
+    #[max_len(16)]
+    pub prefix: String,
+    pub cubes_total: u64,
+    pub cubes_removed: u64,
+    pub unlocked: bool,
+    pub bump: u8,
+}
+
+/// A board region priced on its own demand curve
+#[account]
+#[derive(InitSpace)]
+pub struct Region {
+    #[max_len(16)]
+    pub region_id: String,
+    /// Price of the region's first removal (0 = the board's `price_per_cube`)
+    pub base_price: u64,
+    /// Added to the price for every cube removed in the region
+    pub increment: u64,
+    pub cubes_removed: u64,
+    pub bump: u8,
+    /// Sub-authority managing this region (default = owner only)
+    pub region_authority: Pubkey,
+    /// Removals passing this region are stopped
+    pub paused: bool,
+}
+
+impl Region {
+    /// Base price of the region's next removal, before the board's modifiers
+    pub fn curve_price(&self) -> Result<u64> {
+        self.increment
+            .checked_mul(self.cubes_removed)
+            .and_then(|step| step.checked_add(self.base_price))
+            .ok_or(error!(CubeGameError::MathOverflow))
+    }
+}
+
+/// A price locked in by a player for a limited number of removals
+#[account]
+#[derive(InitSpace)]
+pub struct PriceVoucher {
+    pub player: Pubkey,
+    pub locked_price: u64,
+    pub uses_remaining: u32,
+    pub expires_at: i64,
+    pub bump: u8,
+}
+
+impl PriceVoucher {
+    /// Price a removal at `now` pays under the voucher, using up one use
+    /// when the locked price beats `price`
+    pub fn apply(&mut self, price: u64, now: i64) -> u64 {
+        if self.uses_remaining > 0 && now < self.expires_at && self.locked_price < price {
+            self.uses_remaining -= 1;
+            return self.locked_price;
+        }
+        price
+    }
+}
+
+/// Dashboard aggregates kept up to date by removals and refunds, so clients
+/// read one account instead of scanning every record
+#[account]
+#[derive(InitSpace)]
+pub struct Analytics {
+    /// Lamports collected from removals
+    pub total_collected: u64,
+    /// Lamports returned by rollbacks, zone resets and batch refunds
+    pub total_refunded: u64,
+    /// Running sum and count of prices paid, for the average price
+    pub price_sum: u64,
+    pub price_count: u64,
+    pub unique_players: u64,
+    /// Lamports of split-payment overpayment left with players
+    pub total_dust_refunded: u64,
+    pub bump: u8,
+}
+
+impl Analytics {
+    /// Count one paid removal (or hit) collecting `collected` lamports of a
+    /// `price` price, by a player removing for the first time when
+    /// `new_player` is set
+    pub fn record_removal(&mut self, collected: u64, price: u64, new_player: bool) -> Result<()> {
+        self.total_collected = self
+            .total_collected
+            .checked_add(collected)
+            .ok_or(CubeGameError::MathOverflow)?;
+        self.price_sum = self
+            .price_sum
+            .checked_add(price)
+            .ok_or(CubeGameError::MathOverflow)?;
+        self.price_count = self
+            .price_count
+            .checked_add(1)
+            .ok_or(CubeGameError::MathOverflow)?;
+        if new_player {
+            self.unique_players = self
+                .unique_players
+                .checked_add(1)
+                .ok_or(CubeGameError::MathOverflow)?;
+        }
+        Ok(())
+    }
+
+    /// Count lamports collected ahead of the removal they pay for, such as a
+    /// crowdfunding contribution
+    pub fn record_collected(&mut self, collected: u64) -> Result<()> {
+        self.total_collected = self
+            .total_collected
+            .checked_add(collected)
+            .ok_or(CubeGameError::MathOverflow)?;
+        Ok(())
+    }
+
+    /// Count SOL a split payment offered beyond the price and was not charged
+    pub fn record_dust(&mut self, dust: u64) -> Result<()> {
+        self.total_dust_refunded = self
+            .total_dust_refunded
+            .checked_add(dust)
+            .ok_or(CubeGameError::MathOverflow)?;
+        Ok(())
+    }
+
+    pub fn record_refund(&mut self, amount: u64) -> Result<()> {
+        self.total_refunded = self
+            .total_refunded
+            .checked_add(amount)
+            .ok_or(CubeGameError::MathOverflow)?;
+        Ok(())
+    }
+
+    /// Average price paid so far, rounded down (0 before any removal)
+    pub fn average_price(&self) -> u64 {
+        self.price_sum.checked_div(self.price_count).unwrap_or(0)
+    }
+}
+
+/// Double-entry totals of lamports paid in and where they went, kept from
+/// `init_ledger` on. Every lamport of `total_inflow` and `total_funded`
+/// sits in exactly one bucket, so
+/// `inflow + funded == to_dev + to_dao + burned + shared + refunded +
+/// withdrawn + rewards + escrow + reserve + staked + retained`, where
+/// `retained` is the treasury's growth since the ledger was created.
+#[account]
+#[derive(InitSpace)]
+pub struct Ledger {
+    /// Lamports paid in through removals, purchases, contributions, tips,
+    /// bounties and staking rewards
+    pub total_inflow: u64,
+    /// Treasury overflow swept to the cold wallet
+    pub total_to_dev: u64,
+    pub total_to_dao: u64,
+    pub total_burned: u64,
+    /// Lamports returned to players, from the refund pool or the treasury
+    pub total_refunded: u64,
+    pub total_withdrawn: u64,
+    /// Held in the refund pool, insurance vault and bounties
+    pub total_in_escrow: u64,
+    pub bump: u8,
+    /// Put in by the owner through `fund_treasury` and `fund_cold_reserve`
+    pub total_funded: u64,
+    /// Prizes, rewards, rebates, bonuses, claimed tips and bounties paid to
+    /// players
+    pub total_rewards: u64,
+    /// Parts of removal payments routed straight to stakers and creators
+    pub total_shared: u64,
+    /// Held in the cold reserve
+    pub total_in_reserve: u64,
+    /// Treasury lamports delegated to stake accounts
+    pub total_staked: u64,
+}
+
+impl Ledger {
+    fn credit(bucket: &mut u64, amount: u64) -> Result<()> {
+        *bucket = bucket
+            .checked_add(amount)
+            .ok_or(CubeGameError::MathOverflow)?;
+        Ok(())
+    }
+
+    /// Book a payment of `amount`, of which `escrowed` went to the refund
+    /// pool and insurance vault, `burned` to the incinerator and `to_dao` to
+    /// the DAO treasury
+    pub fn record_inflow(
+        &mut self,
+        amount: u64,
+        escrowed: u64,
+        burned: u64,
+        to_dao: u64,
+    ) -> Result<()> {
+        Self::credit(&mut self.total_inflow, amount)?;
+        Self::credit(&mut self.total_in_escrow, escrowed)?;
+        Self::credit(&mut self.total_burned, burned)?;
+        Self::credit(&mut self.total_to_dao, to_dao)
+    }
+
+    /// Book a refund of `amount`, `from_escrow` of it paid by the refund pool
+    pub fn record_refund(&mut self, amount: u64, from_escrow: u64) -> Result<()> {
+        self.total_in_escrow = self.total_in_escrow.saturating_sub(from_escrow);
+        Self::credit(&mut self.total_refunded, amount)
+    }
+
+    pub fn record_withdrawal(&mut self, amount: u64) -> Result<()> {
+        Self::credit(&mut self.total_withdrawn, amount)
+    }
+
+    pub fn record_to_dev(&mut self, amount: u64) -> Result<()> {
+        Self::credit(&mut self.total_to_dev, amount)
+    }
+
+    /// Book owner funding of the treasury, or of the cold reserve when
+    /// `to_reserve`
+    pub fn record_funding(&mut self, amount: u64, to_reserve: bool) -> Result<()> {
+        if to_reserve {
+            Self::credit(&mut self.total_in_reserve, amount)?;
+        }
+        Self::credit(&mut self.total_funded, amount)
+    }
+
+    /// Book `amount` moved from the cold reserve into the treasury
+    pub fn record_refill(&mut self, amount: u64) {
+        self.total_in_reserve = self.total_in_reserve.saturating_sub(amount);
+    }
+
+    /// Book a payout of `amount` to a player, `from_escrow` of it from a
+    /// bounty rather than the treasury
+    pub fn record_reward(&mut self, amount: u64, from_escrow: u64) -> Result<()> {
+        self.total_in_escrow = self.total_in_escrow.saturating_sub(from_escrow);
+        Self::credit(&mut self.total_rewards, amount)
+    }
+
+    /// Book part of a payment sent straight to stakers or a creator
+    pub fn record_shared(&mut self, amount: u64) -> Result<()> {
+        Self::credit(&mut self.total_inflow, amount)?;
+        Self::credit(&mut self.total_shared, amount)
+    }
+
+    /// Book `amount` paid into escrow outside the treasury, such as a bounty
+    pub fn record_escrow(&mut self, amount: u64) -> Result<()> {
+        Self::credit(&mut self.total_inflow, amount)?;
+        Self::credit(&mut self.total_in_escrow, amount)
+    }
+
+    /// Book `amount` withdrawn by the owner from the insurance vault
+    pub fn record_escrow_withdrawal(&mut self, amount: u64) -> Result<()> {
+        self.total_in_escrow = self.total_in_escrow.saturating_sub(amount);
+        Self::credit(&mut self.total_withdrawn, amount)
+    }
+
+    pub fn record_stake(&mut self, amount: u64) -> Result<()> {
+        Self::credit(&mut self.total_staked, amount)
+    }
+
+    /// Book `amount` returned from stake accounts; anything above the staked
+    /// principal is a staking reward
+    pub fn record_unstake(&mut self, amount: u64) -> Result<()> {
+        let principal = amount.min(self.total_staked);
+        self.total_staked -= principal;
+        Self::credit(&mut self.total_inflow, amount - principal)
+    }
+
+    /// Lamports still attributed to the treasury: inflow and funding less
+    /// every other bucket
+    pub fn retained(&self) -> u64 {
+        self.total_inflow
+            .saturating_add(self.total_funded)
+            .saturating_sub(self.total_to_dev)
+            .saturating_sub(self.total_to_dao)
+            .saturating_sub(self.total_burned)
+            .saturating_sub(self.total_shared)
+            .saturating_sub(self.total_refunded)
+            .saturating_sub(self.total_withdrawn)
+            .saturating_sub(self.total_rewards)
+            .saturating_sub(self.total_in_escrow)
+            .saturating_sub(self.total_in_reserve)
+            .saturating_sub(self.total_staked)
+    }
+}
+
+/// A player waiting in a `RemovalQueue` and the deposit they left
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, InitSpace)]
+pub struct QueueEntry {
+    pub player: Pubkey,
+    pub deposit: u64,
+}
+
+/// Players waiting to remove a contended cube, front first
+#[account]
+#[derive(InitSpace)]
+pub struct RemovalQueue {
+    #[max_len(32)]
+    pub cube_id: String,
+    #[max_len(MAX_QUEUE_LEN)]
+    pub entries: Vec<QueueEntry>,
+    /// When the front player's turn started
+    pub front_since: i64,
+    /// Wallet that paid the queue's rent, refunded by `close_queue`
+    pub opener: Pubkey,
+    /// Deposits of expired turns, owed to the treasury
+    pub forfeited: u64,
+    pub bump: u8,
+}
+
+impl RemovalQueue {
+    /// Drop front players whose turn ran out, forfeiting their deposits. The
+    /// next turn starts when the previous one ended, so a long idle queue
+    /// expires several at once.
+    pub fn expire_turns(&mut self, now: i64, turn_seconds: i64) {
+        while !self.entries.is_empty() && elapsed(now, self.front_since) >= turn_seconds {
+            let expired = self.entries.remove(0);
+            self.forfeited = self.forfeited.saturating_add(expired.deposit);
+            self.front_since = self.front_since.saturating_add(turn_seconds);
+        }
+    }
+
+    /// Add `player` to the back with `deposit`, starting their turn at
+    /// `now` if nobody is ahead
+    pub fn join(&mut self, player: Pubkey, deposit: u64, now: i64) -> Result<()> {
+        require!(
+            !self.entries.iter().any(|e| e.player == player),
+            CubeGameError::AlreadyQueued
+        );
+        require!(self.entries.len() < MAX_QUEUE_LEN, CubeGameError::QueueFull);
+        if self.entries.is_empty() {
+            self.front_since = now;
+        }
+        self.entries.push(QueueEntry { player, deposit });
+        Ok(())
+    }
+
+    /// Take `player` out of the queue wherever they stand, returning their
+    /// deposit. Leaving the front hands the next player their turn at `now`.
+    pub fn leave(&mut self, player: &Pubkey, now: i64) -> Result<u64> {
+        let pos = self
+            .entries
+            .iter()
+            .position(|e| e.player == *player)
+            .ok_or(CubeGameError::NotQueued)?;
+        if pos == 0 {
+            self.front_since = now;
+        }
+        Ok(self.entries.remove(pos).deposit)
+    }
+
+    /// Serve `player`, who must be at the front of a non-empty queue,
+    /// returning their deposit
+    pub fn serve(&mut self, player: &Pubkey, now: i64) -> Result<u64> {
+        match self.entries.first() {
+            None => Ok(0),
+            Some(front) => {
+                require_keys_eq!(front.player, *player, CubeGameError::NotFrontOfQueue);
+                self.leave(player, now)
+            }
+        }
+    }
+}
+
+/// Ids of the cubes a player has removed, oldest first
+#[account]
+#[derive(InitSpace)]
+pub struct Inventory {
+    pub player: Pubkey,
+    #[max_len(MAX_INVENTORY_ITEMS, 32)]
+    pub items: Vec<String>,
+    pub bump: u8,
+}
+
+impl Inventory {
+    /// Append `cube_id`. A full inventory drops its oldest item when `ring`
+    /// is set and rejects the item otherwise.
+    pub fn add(&mut self, cube_id: &str, ring: bool) -> Result<()> {
+        if self.items.len() >= MAX_INVENTORY_ITEMS {
+            require!(ring, CubeGameError::InventoryFull);
+            self.items.remove(0);
+        }
+        self.items.push(cube_id.to_string());
+        Ok(())
+    }
+}
+
+/// A cube removed in one player's practice sandbox
+#[account]
+#[derive(InitSpace)]
+pub struct PracticeRecord {
+    pub is_removed: bool,
+    pub bump: u8,
+}
+
+/// Marks that a player has liked a cube
+#[account]
+#[derive(InitSpace)]
+pub struct Like {
+    pub bump: u8,
+}
+
+/// First step of a two-step high-value removal
+#[account]
+#[derive(InitSpace)]
+pub struct RemovalIntent {
+    pub player: Pubkey,
+    #[max_len(32)]
+    pub cube_id: String,
+    pub slot: u64,
+    pub bump: u8,
+}
+
+impl RemovalIntent {
+    /// Require the intent to predate `slot`, so intent and removal cannot
+    /// share a transaction
+    pub fn require_confirmed(&self, slot: u64) -> Result<()> {
+        require!(self.slot < slot, CubeGameError::ConfirmationRequired);
+        Ok(())
+    }
+}
+
+/// Facts about a wallet that outlive its `PlayerStats`. It is never
+/// closed, so a player cannot reset them by closing their stats.
+#[account]
+#[derive(InitSpace)]
+pub struct WalletMark {
+    /// The onboarding refund has been paid
+    pub onboarded: bool,
+    /// `PlayerStats::player_index` first given to the wallet (0 = never
+    /// registered)
+    pub player_index: u64,
+    /// The wallet earned the founder badge
+    pub is_founder: bool,
+}
+
+impl WalletMark {
+    /// Onboarding refund owed for a removal that paid `sol_due`: all of
+    /// `refund` once per wallet, and only when the removal paid at least
+    /// that much
+    pub fn onboarding_due(&self, refund: u64, sol_due: u64) -> u64 {
+        if self.onboarded || sol_due < refund {
+            0
+        } else {
+            refund
+        }
+    }
+}
+
+/// A wallet allowed to spend a player's credits on removals
+#[account]
+#[derive(InitSpace)]
+pub struct Delegation {
+    pub player: Pubkey,
+    pub delegate: Pubkey,
+    /// Remaining value, at effective prices, the delegate may remove
+    pub spend_limit: u64,
+    pub bump: u8,
+}
+
+impl Delegation {
+    /// Take a removal at `price` out of the remaining limit
+    pub fn spend(&mut self, price: u64) -> Result<()> {
+        self.spend_limit = self
+            .spend_limit
+            .checked_sub(price)
+            .ok_or(CubeGameError::DelegateLimitExceeded)?;
+        Ok(())
+    }
+}
+
+/// Prepaid removals, each covering one removal up to the base price it
+/// was bought at
+#[account]
+#[derive(InitSpace)]
+pub struct Credits {
+    pub player: Pubkey,
+    pub balance: u64,
+    pub bump: u8,
+    /// Base-price lamports the remaining credits cover between them
+    pub face_value: u64,
+}
+
+impl Credits {
+    /// Add `count` credits, each covering `unit_value` lamports
+    pub fn add(&mut self, count: u64, unit_value: u64) -> Result<()> {
+        self.face_value = count
+            .checked_mul(unit_value)
+            .and_then(|value| value.checked_add(self.face_value))
+            .ok_or(CubeGameError::MathOverflow)?;
+        self.balance = self
+            .balance
+            .checked_add(count)
+            .ok_or(CubeGameError::MathOverflow)?;
+        Ok(())
+    }
+
+    /// Spend one credit on a removal priced at `price`, returning what is
+    /// left to pay. Credits bought at different prices cover their average,
+    /// the last one taking any rounding remainder.
+    pub fn spend(&mut self, price: u64) -> Result<u64> {
+        require!(self.balance > 0, CubeGameError::NoCredits);
+        let cover = self.face_value / self.balance;
+        self.balance -= 1;
+        self.face_value -= cover;
+        Ok(price.saturating_sub(cover))
+    }
+}
+
+/// A time-boxed pass covering removals until `expires_at`
+#[account]
+#[derive(InitSpace)]
+pub struct Subscription {
+    pub player: Pubkey,
+    /// 0 = limited to `removals_remaining`, 1 = unlimited
+    pub tier: u8,
+    pub expires_at: i64,
+    pub removals_remaining: u64,
+    pub bump: u8,
+}
+
+impl Subscription {
+    /// Spend one covered removal, false when the pass has expired or its
+    /// quota is used up
+    pub fn use_removal(&mut self, now: i64) -> bool {
+        if now >= self.expires_at {
+            return false;
+        }
+        if self.tier == SUBSCRIPTION_UNLIMITED {
+            return true;
+        }
+        if self.removals_remaining == 0 {
+            return false;
+        }
+        self.removals_remaining -= 1;
+        true
+    }
+}
+
+/// Lamports owed to stakers of `mint`, shared out by stake size
+#[account]
+#[derive(InitSpace)]
+pub struct StakingPool {
+    pub mint: Pubkey,
+    /// Token account holding every staked token
+    pub vault: Pubkey,
+    pub total_staked: u64,
+    /// Rewards accrued per staked token, scaled by `REWARD_PER_SHARE_SCALE`
+    pub reward_per_share: u128,
+    pub total_claimed: u64,
+    pub bump: u8,
+}
+
+impl StakingPool {
+    /// Share `amount` just paid into the pool among the current stake
+    pub fn accrue(&mut self, amount: u64) -> Result<()> {
+        if self.total_staked == 0 {
+            return Ok(());
+        }
+        let per_share = amount as u128 * REWARD_PER_SHARE_SCALE / self.total_staked as u128;
+        self.reward_per_share = self
+            .reward_per_share
+            .checked_add(per_share)
+            .ok_or(CubeGameError::MathOverflow)?;
+        Ok(())
+    }
+}
+
+/// One wallet's staked tokens and the rewards they have earned
+#[account]
+#[derive(InitSpace)]
+pub struct Stake {
+    pub owner: Pubkey,
+    pub amount: u64,
+    /// Rewards already accounted for at the current `amount`, scaled like
+    /// `StakingPool::reward_per_share` times tokens
+    pub reward_debt: u128,
+    /// Settled rewards not yet claimed
+    pub pending_rewards: u64,
+    pub bump: u8,
+}
+
+impl Stake {
+    fn accrued(&self, pool: &StakingPool) -> Result<u128> {
+        (self.amount as u128)
+            .checked_mul(pool.reward_per_share)
+            .ok_or(error!(CubeGameError::MathOverflow))
+    }
+
+    /// Move rewards earned since the last change of stake into
+    /// `pending_rewards`
+    pub fn settle(&mut self, pool: &StakingPool) -> Result<()> {
+        let accrued = self.accrued(pool)?;
+        let earned = accrued.saturating_sub(self.reward_debt) / REWARD_PER_SHARE_SCALE;
+        self.pending_rewards = self
+            .pending_rewards
+            .checked_add(earned as u64)
+            .ok_or(CubeGameError::MathOverflow)?;
+        // Keep the unpaid fraction for the next settlement
+        self.reward_debt += earned * REWARD_PER_SHARE_SCALE;
+        Ok(())
+    }
+
+    /// Start earning afresh after `amount` changed
+    pub fn reset_debt(&mut self, pool: &StakingPool) -> Result<()> {
+        self.reward_debt = self.accrued(pool)?;
+        Ok(())
+    }
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct FeeExemption {
+    pub player: Pubkey,
+    pub bump: u8,
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct Team {
+    #[max_len(32)]
+    pub team_id: String,
+    pub total_cubes_removed: u64,
+    pub member_count: u32,
+    pub bump: u8,
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct WithdrawTimelock {
+    pub amount: u64,
+    pub eligible_slot: u64,
+}
+
+/// Program-owned lamport vault reserved for refunds; `withdraw` only ever
+/// draws from the treasury, so the authority cannot drain it
+#[account]
+#[derive(InitSpace)]
+pub struct RefundPool {
+    pub total_reserved: u64,
+    pub total_refunded: u64,
+    pub bump: u8,
+}
+
+/// Program-owned lamport vault insuring players. The authority can only
+/// draw from it once `locked_until` has passed, or to top up the refund pool.
+#[account]
+#[derive(InitSpace)]
+pub struct InsuranceVault {
+    pub locked_until: i64,
+    pub total_insured: u64,
+    pub total_released: u64,
+    pub bump: u8,
+}
+
+impl InsuranceVault {
+    /// Require the lock to have expired before the authority draws on it
+    pub fn require_unlocked(&self, now: i64) -> Result<()> {
+        require!(now >= self.locked_until, CubeGameError::InsuranceLocked);
+        Ok(())
+    }
+}
+
+/// Program-owned float that refills the treasury to `target_hot_balance`
+/// after withdrawals
+#[account]
+#[derive(InitSpace)]
+pub struct ColdReserve {
+    pub total_deposited: u64,
+    pub total_released: u64,
+    pub bump: u8,
+}
+
+/// Ring buffer of the most recent actions, newest entry at `head`
+#[account]
+#[derive(InitSpace)]
+pub struct ActivityFeed {
+    pub head: u8,
+    #[max_len(ACTIVITY_FEED_LEN)]
+    pub entries: Vec<ActivityEntry>,
+}
+
+impl ActivityFeed {
+    pub fn push(&mut self, entry: ActivityEntry) {
+        if self.entries.len() < ACTIVITY_FEED_LEN {
+            self.entries.push(entry);
+            self.head = (self.entries.len() - 1) as u8;
+        } else {
+            self.head = ((self.head as usize + 1) % ACTIVITY_FEED_LEN) as u8;
+            self.entries[self.head as usize] = entry;
+        }
+    }
+}
+
+/// Removals the whole community is working towards, unlocking a reward for
+/// every participant once `target` is reached
+#[account]
+#[derive(InitSpace)]
+pub struct CommunityGoal {
+    /// Increments with every new goal so participation never carries over
+    pub goal_id: u64,
+    pub target: u64,
+    pub progress: u64,
+    pub participants: u64,
+    /// Removals stop counting after this time (0 = no deadline)
+    pub ends_at: i64,
+    /// Lamports each participant may claim once the goal is reached
+    pub reward: u64,
+    pub reached: bool,
+    pub bump: u8,
+    /// Participants who have claimed their reward
+    pub claimed: u64,
+}
+
+impl CommunityGoal {
+    /// True while the goal is unreached and before its deadline
+    pub fn is_running(&self, now: i64) -> bool {
+        !self.reached && (self.ends_at == 0 || now < self.ends_at)
+    }
+
+    /// Count one removal by `stats`' player priced at `price`, true when it
+    /// reaches the target. Only a paid removal enrolls the player.
+    pub fn count_removal(&mut self, stats: &mut PlayerStats, price: u64) -> bool {
+        self.progress += 1;
+        if price > 0 && stats.community_goal_id != self.goal_id {
+            stats.community_goal_id = self.goal_id;
+            self.participants += 1;
+        }
+        self.reached = self.progress >= self.target;
+        self.reached
+    }
+
+    /// Lamports still owed to participants who have not claimed
+    pub fn outstanding_rewards(&self) -> Result<u64> {
+        self.participants
+            .saturating_sub(self.claimed)
+            .checked_mul(self.reward)
+            .ok_or(error!(CubeGameError::MathOverflow))
+    }
+
+    /// Mark `stats`' player as paid for the reached goal
+    pub fn claim(&mut self, stats: &mut PlayerStats) -> Result<()> {
+        require!(self.reached, CubeGameError::GoalNotReached);
+        require!(
+            stats.community_goal_id == self.goal_id,
+            CubeGameError::NotGoalContributor
+        );
+        require!(
+            stats.community_reward_claimed != self.goal_id,
+            CubeGameError::CommunityRewardClaimed
+        );
+        stats.community_reward_claimed = self.goal_id;
+        self.claimed += 1;
+        Ok(())
+    }
+}
+
+/// Page count and score bounds of every `LeaderboardPage`, enough to tell
+/// which page a score belongs on without loading the pages
+#[account]
+#[derive(InitSpace)]
+pub struct LeaderboardMeta {
+    pub page_count: u32,
+    #[max_len(MAX_LEADERBOARD_PAGES)]
+    pub pages: Vec<PageSummary>,
+    pub bump: u8,
+}
+
+/// One slice of the leaderboard, highest score first. Every entry on a
+/// page scores at least as much as every entry on the pages after it.
+#[account]
+#[derive(InitSpace)]
+pub struct LeaderboardPage {
+    pub page: u32,
+    #[max_len(LEADERBOARD_PAGE_SIZE)]
+    pub entries: Vec<LeaderboardEntry>,
+    pub bump: u8,
+}
+
+impl LeaderboardPage {
+    pub fn summary(&self) -> PageSummary {
+        PageSummary {
+            len: self.entries.len() as u8,
+            max_score: self.entries.first().map_or(0, |e| e.score),
+            min_score: self.entries.last().map_or(0, |e| e.score),
+        }
+    }
+}
+
+/// List `player` with `score` on the leaderboard, taking down the entry
+/// they were `recorded` with. `pages` must be consecutive and cover every
+/// page touched. An entry pushed off the last page leaves the board.
+/// Returns the score the player is now listed with, 0 when they did not
+/// make the board.
+pub fn rank_on_leaderboard(
+    meta: &mut LeaderboardMeta,
+    pages: &mut [LeaderboardPage],
+    player: Pubkey,
+    recorded: u64,
+    score: u64,
+) -> Result<u64> {
+    let first = pages
+        .first()
+        .ok_or(CubeGameError::LeaderboardPageMissing)?
+        .page as usize;
+    for (i, page) in pages.iter().enumerate() {
+        require!(
+            page.page as usize == first + i,
+            CubeGameError::LeaderboardPageMissing
+        );
+    }
+    let passed = pages.len();
+    let slot = |page: usize| -> Result<usize> {
+        require!(
+            page >= first && page < first + passed,
+            CubeGameError::LeaderboardPageMissing
+        );
+        Ok(page - first)
+    };
+
+    // Every page whose bounds hold the recorded score may list the player
+    if recorded > 0 {
+        for p in 0..meta.pages.len() {
+            let summary = &meta.pages[p];
+            if summary.len > 0 && summary.min_score <= recorded && recorded <= summary.max_score {
+                let i = slot(p)?;
+                pages[i].entries.retain(|e| e.player != player);
+                meta.pages[p] = pages[i].summary();
+            }
+        }
+    }
+    if score == 0 {
+        return Ok(0);
+    }
+
+    // The first page the score beats, or one with room above lower scores
+    let count = meta.pages.len();
+    let Some(target) = (0..count).find(|&p| {
+        let summary = &meta.pages[p];
+        (summary.len > 0 && score > summary.min_score)
+            || ((summary.len as usize) < LEADERBOARD_PAGE_SIZE
+                && (p + 1 == count
+                    || meta.pages[p + 1].len == 0
+                    || meta.pages[p + 1].max_score <= score))
+    }) else {
+        return Ok(0);
+    };
+
+    // Each full page hands its lowest entry to the top of the next one
+    let mut carry = Some(LeaderboardEntry { player, score });
+    let mut p = target;
+    while let Some(entry) = carry.take() {
+        if p == count {
+            break;
+        }
+        let i = slot(p)?;
+        let entries = &mut pages[i].entries;
+        let at = entries
+            .iter()
+            .position(|e| e.score < entry.score)
+            .unwrap_or(entries.len());
+        entries.insert(at, entry);
+        if entries.len() > LEADERBOARD_PAGE_SIZE {
+            carry = entries.pop();
+        }
+        meta.pages[p] = pages[i].summary();
+        p += 1;
+    }
+    Ok(score)
+}
+
+/// A player's hidden pending removal, revealed by `reveal_remove`
+#[account]
+#[derive(InitSpace)]
+pub struct RemovalCommitment {
+    pub player: Pubkey,
+    pub commitment: [u8; 32],
+    pub commit_slot: u64,
+    pub bump: u8,
+}
+
+impl RemovalCommitment {
+    /// Require `player`'s reveal of `cube_id` with `salt` to match the
+    /// commitment and to land at least `min_delay` slots after it, at `slot`
+    pub fn check_reveal(
+        &self,
+        player: &Pubkey,
+        cube_id: &str,
+        salt: &[u8; 32],
+        slot: u64,
+        min_delay: u64,
+    ) -> Result<()> {
+        require!(
+            self.commitment == removal_commitment_hash(player, cube_id, salt),
+            CubeGameError::CommitmentMismatch
+        );
+        require!(
+            slot.saturating_sub(self.commit_slot) >= min_delay,
+            CubeGameError::RevealTooSoon
+        );
+        Ok(())
+    }
+}
+
+/// One player's most recent removals, newest entry at `head`
+#[account]
+#[derive(InitSpace)]
+pub struct PlayerHistory {
+    pub player: Pubkey,
+    pub head: u8,
+    #[max_len(PLAYER_HISTORY_LEN)]
+    pub entries: Vec<HistoryEntry>,
+    pub bump: u8,
+}
+
+/// Record a removal of `cube_id` for `price` in `history`, which a player
+/// with `history_enabled` must pass
+pub fn record_history(
+    stats: &PlayerStats,
+    history: Option<&mut PlayerHistory>,
+    cube_id: &str,
+    price: u64,
+    now: i64,
+) -> Result<()> {
+    if !stats.history_enabled {
+        return Ok(());
+    }
+    let history = history.ok_or(CubeGameError::PlayerHistoryRequired)?;
+    history.push(HistoryEntry {
+        cube_id_hash: anchor_lang::solana_program::keccak::hash(cube_id.as_bytes()).0,
+        price_paid: price,
+        timestamp: now,
+    });
+    Ok(())
+}
+
+impl PlayerHistory {
+    pub fn push(&mut self, entry: HistoryEntry) {
+        if self.entries.len() < PLAYER_HISTORY_LEN {
+            self.entries.push(entry);
+            self.head = (self.entries.len() - 1) as u8;
+        } else {
+            self.head = ((self.head as usize + 1) % PLAYER_HISTORY_LEN) as u8;
+            self.entries[self.head as usize] = entry;
+        }
+    }
+}
+
+/// Ring of the most recent public messages, newest entry at `head`
+#[account]
+#[derive(InitSpace)]
+pub struct MessageWall {
+    pub head: u8,
+    #[max_len(MESSAGE_WALL_LEN)]
+    pub entries: Vec<WallEntry>,
+}
+
+impl MessageWall {
+    pub fn push(&mut self, entry: WallEntry) {
+        if self.entries.len() < MESSAGE_WALL_LEN {
+            self.entries.push(entry);
+            self.head = (self.entries.len() - 1) as u8;
+        } else {
+            self.head = ((self.head as usize + 1) % MESSAGE_WALL_LEN) as u8;
+            self.entries[self.head as usize] = entry;
+        }
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, InitSpace)]
+pub struct WallEntry {
+    /// Poster, the default key for private players
+    pub player: Pubkey,
+    #[max_len(MAX_MESSAGE_LEN)]
+    pub message: String,
+    pub timestamp: i64,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, InitSpace)]
+pub struct HistoryEntry {
+    /// keccak of the cube id
+    pub cube_id_hash: [u8; 32],
+    pub price_paid: u64,
+    pub timestamp: i64,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, InitSpace)]
+pub struct ActivityEntry {
+    pub action_type: u8,
+    pub actor: Pubkey,
+    /// First 16 bytes of the cube id, zero padded
+    pub cube_id: [u8; 16],
+    pub timestamp: i64,
+}
+
+impl ActivityEntry {
+    /// Entry for `cube_id`, cut to its first 16 bytes
+    pub fn new(action_type: u8, actor: Pubkey, cube_id: &str, timestamp: i64) -> Self {
+        let mut short_id = [0u8; 16];
+        let len = cube_id.len().min(short_id.len());
+        short_id[..len].copy_from_slice(&cube_id.as_bytes()[..len]);
+        Self {
+            action_type,
+            actor,
+            cube_id: short_id,
+            timestamp,
+        }
+    }
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct CubeShares {
+    #[max_len(32)]
+    pub cube_id: String,
+    pub total_contributed: u64,
+    #[max_len(MAX_CUBE_CONTRIBUTORS)]
+    pub contributions: Vec<CubeContribution>,
+}
+
+/// Lamports escrowed for whoever removes a cube
+#[account]
+#[derive(InitSpace)]
+pub struct Bounty {
+    #[max_len(32)]
+    pub cube_id: String,
+    pub total: u64,
+    #[max_len(MAX_BOUNTY_FUNDERS)]
+    pub funders: Vec<CubeContribution>,
+    pub bump: u8,
+}
+
+impl Bounty {
+    /// Add `amount` to the bounty, to `funder`'s existing share if they have
+    /// one
+    pub fn fund(&mut self, funder: Pubkey, amount: u64) -> Result<()> {
+        match self.funders.iter_mut().find(|f| f.contributor == funder) {
+            Some(existing) => existing.amount += amount,
+            None => {
+                require!(
+                    self.funders.len() < MAX_BOUNTY_FUNDERS,
+                    CubeGameError::TooManyBountyFunders
+                );
+                self.funders.push(CubeContribution {
+                    contributor: funder,
+                    amount,
+                });
+            }
+        }
+        self.total += amount;
+        Ok(())
+    }
+
+    /// Pay out the whole bounty to the remover, clearing every share
+    pub fn claim(&mut self) -> u64 {
+        self.funders.clear();
+        std::mem::take(&mut self.total)
+    }
+
+    /// Take `funder`'s share out of an unclaimed bounty
+    pub fn withdraw_share(&mut self, funder: &Pubkey) -> Result<u64> {
+        let index = self
+            .funders
+            .iter()
+            .position(|f| f.contributor == *funder)
+            .ok_or(CubeGameError::NoBountyShare)?;
+        let amount = self.funders.remove(index).amount;
+        self.total -= amount;
+        Ok(amount)
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, InitSpace)]
+pub struct CubeContribution {
+    pub contributor: Pubkey,
+    pub amount: u64,
+}
+
+/// Fields for `update_config`; `None` leaves the setting unchanged
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Default)]
+pub struct GameConfigUpdate {
+    pub price_per_cube: Option<u64>,
+    pub transfer_lock_seconds: Option<i64>,
+    pub max_removals_per_tx: Option<u8>,
+    pub max_cubes: Option<u64>,
+    pub ends_at: Option<i64>,
+    pub quadratic_coeff: Option<u64>,
+    pub grand_prize: Option<u64>,
+    pub first_removal_rebate: Option<u64>,
+    pub credit_discount_bps: Option<u16>,
+    pub refund_reserve_bps: Option<u16>,
+    pub sol_burn_bps: Option<u16>,
+}
+
+/// Creator royalty assigned to a cube by `place_cubes`
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct CubeRoyalty {
+    /// Wallet paid the royalty, the owner when left as the default key
+    pub creator: Pubkey,
+    pub royalty_bps: u16,
+}
+
+/// Per-removal arguments of `remove_cube` beyond the cube id
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct RemovalArgs {
+    /// Reconciliation memo, at most `MAX_MEMO_LEN` bytes
+    pub memo: String,
+    /// Proof-of-work nonce, ignored while `pow_difficulty` is 0
+    pub nonce: u64,
+    /// Merkle proof against `board_root`, ignored while it is uncommitted
+    pub board_proof: Vec<[u8; 32]>,
+    /// Region the cube belongs to, empty for the global board
+    pub region_id: String,
+    /// Compute-unit price the client says the transaction pays, in
+    /// micro-lamports. Self-reported: the program cannot see the fee, so this
+    /// keeps honest clients honest rather than proving anything.
+    pub priority_fee: u64,
+    /// Reject the removal if the price has changed since this `price_epoch`
+    pub expected_price_epoch: Option<u64>,
+    /// Reject the removal if it executes after this time (0 = no deadline)
+    pub deadline: i64,
+    /// Client-chosen id of this action; resending the player's last one for
+    /// the same cube is a successful no-op (0 = no replay protection)
+    pub action_nonce: u64,
+    /// Public message for the message wall, empty for none
+    pub message: String,
+    /// Reject the removal unless exactly this many cubes were removed before
+    /// it, so a client can claim a specific removal number
+    pub expected_total: Option<u64>,
+}
+
+/// Payload of an `AttestationEvent`, Borsh-encoded for signing
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct StatsAttestation {
+    pub game: Pubkey,
+    pub total_cubes_removed: u64,
+    pub active_cube_records: u64,
+    pub price_per_cube: u64,
+    pub price_epoch: u64,
+    pub treasury_balance: u64,
+    pub as_of: i64,
+}
+
+/// Tokens and lamports a split payment is charged, and the SOL offered
+/// beyond what it needed
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SplitSettlement {
+    pub tokens: u64,
+    pub sol_due: u64,
+    pub dust: u64,
+}
+
+/// A Pyth aggregate price: `price * 10^expo` dollars per whole token
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct OraclePrice {
+    pub price: i64,
+    pub expo: i32,
+    pub publish_time: i64,
+}
+
+impl OraclePrice {
+    /// Read the aggregate price out of a Pyth price account, rejecting any
+    /// other account and a price that is not trading
+    pub fn from_pyth(data: &[u8]) -> Result<Self> {
+        let u32_at = |at: usize| u32::from_le_bytes(data[at..at + 4].try_into().unwrap());
+        let i64_at = |at: usize| i64::from_le_bytes(data[at..at + 8].try_into().unwrap());
+        require!(data.len() >= 240, CubeGameError::InvalidPriceFeed);
+        require!(
+            u32_at(0) == PYTH_MAGIC && u32_at(8) == PYTH_PRICE_ACCOUNT,
+            CubeGameError::InvalidPriceFeed
+        );
+        require!(
+            u32_at(224) == PYTH_STATUS_TRADING,
+            CubeGameError::InvalidOraclePrice
+        );
+        Ok(Self {
+            price: i64_at(208),
+            expo: u32_at(20) as i32,
+            publish_time: i64_at(96),
+        })
+    }
+
+    /// Base units of a mint with `decimals` worth `usd_price`, rounded up.
+    /// A quote older than `max_staleness` seconds or not above zero is
+    /// rejected.
+    pub fn tokens_for_usd(
+        &self,
+        usd_price: u64,
+        decimals: u8,
+        now: i64,
+        max_staleness: i64,
+    ) -> Result<u64> {
+        require!(
+            now.saturating_sub(self.publish_time) <= max_staleness,
+            CubeGameError::StalePrice
+        );
+        require!(self.price > 0, CubeGameError::InvalidOraclePrice);
+        let pow = |exp: u32| 10u128.checked_pow(exp).ok_or(CubeGameError::MathOverflow);
+        let mut numerator = (usd_price as u128)
+            .checked_mul(pow(decimals as u32)?)
+            .ok_or(CubeGameError::MathOverflow)?;
+        let mut denominator = (self.price as u128) * pow(USD_PRICE_DECIMALS)?;
+        if self.expo < 0 {
+            numerator = numerator
+                .checked_mul(pow(self.expo.unsigned_abs())?)
+                .ok_or(CubeGameError::MathOverflow)?;
+        } else {
+            denominator = denominator
+                .checked_mul(pow(self.expo as u32)?)
+                .ok_or(CubeGameError::MathOverflow)?;
+        }
+        u64::try_from(numerator.div_ceil(denominator))
+            .map_err(|_| error!(CubeGameError::MathOverflow))
+    }
+}
+
+/// How `collect_payment` divides a payment between the refund pool, the
+/// insurance vault, the incinerator, the DAO treasury and the treasury
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PaymentShares {
+    pub reserve: u64,
+    pub insurance: u64,
+    pub burn: u64,
+    pub dao_tax: u64,
+    pub to_treasury: u64,
+}
+
+impl SplitSettlement {
+    /// The charged tokens `burn_bps` burns and the rest banked in the token
+    /// treasury
+    pub fn burn_split(&self, burn_bps: u16) -> Result<(u64, u64)> {
+        let burned = apply_bps(self.tokens, burn_bps)?;
+        Ok((burned, self.tokens - burned))
+    }
+}
+
+/// Instruction data of the CPI mirroring a removal into `analytics_program`
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct RemovalExport {
+    pub game: Pubkey,
+    pub cube_id: String,
+    /// Remover, or the default key for private players
+    pub player: Pubkey,
+    pub price: u64,
+    pub timestamp: i64,
+}
+
+/// What `ticket_signer` signs to let `player` remove `cube_id` until
+/// `expiry`. `program_id` and `game` pin the ticket to one deployment, so a
+/// signer shared between boards cannot have its tickets replayed elsewhere.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct RemovalTicket {
+    pub program_id: Pubkey,
+    pub game: Pubkey,
+    pub player: Pubkey,
+    pub cube_id: String,
+    pub expiry: i64,
+}
+
+impl RemovalTicket {
+    /// Ticket for `player` on this program's `game`
+    pub fn new(game: Pubkey, player: Pubkey, cube_id: &str, expiry: i64) -> Self {
+        Self {
+            program_id: crate::ID,
+            game,
+            player,
+            cube_id: cube_id.to_string(),
+            expiry,
+        }
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct ErrorCatalogEntry {
+    pub code: u32,
+    pub name: String,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, InitSpace)]
+pub struct LeaderboardEntry {
+    pub player: Pubkey,
+    pub score: u64,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Default, InitSpace, PartialEq, Debug)]
+pub struct PageSummary {
+    pub len: u8,
+    pub max_score: u64,
+    pub min_score: u64,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct PlayerScore {
+    pub player: Pubkey,
+    pub cubes_removed: u64,
+    pub active_score: u64,
+    pub score: u64,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct CubeStatus {
+    pub cube_id: String,
+    pub is_removed: bool,
+}
+
+/// Fixed-size removal event for log-constrained integrators
+#[event]
+pub struct CubeRemovedCompact {
+    /// keccak256 of the cube id
+    pub cube_hash: [u8; 32],
+    pub player: Pubkey,
+    /// `total_cubes_removed` after this removal
+    pub seq: u64,
+}
+
+impl CubeRemovedCompact {
+    pub fn new(cube_id: &str, player: Pubkey, seq: u64) -> Self {
+        Self {
+            cube_hash: anchor_lang::solana_program::keccak::hash(cube_id.as_bytes()).0,
+            player,
+            seq,
+        }
+    }
+}
+
+#[event]
+pub struct CubeRemovedEvent {
+    pub cube_id: String,
+    pub player: Pubkey,
+    pub total_removed: u64,
+    /// Share of a bounded board removed so far (0 when unbounded)
+    pub completion_bps: u16,
+    pub timestamp: i64,
+    pub memo: String,
+    /// Public message posted to the message wall, empty for none
+    pub message: String,
+    pub color: u32,
+    /// Remover's tier after the removal; for a crowdfunded cube, the owning
+    /// contributor's
+    pub tier: u8,
+    /// Remover holds the founder badge
+    pub is_founder: bool,
+    /// Remover's ranked score after the removal
+    pub score: u64,
+}
+
+#[event]
+pub struct TierUpgradedEvent {
+    pub player: Pubkey,
+    pub tier: u8,
+}
+
+#[event]
+pub struct CubeContributionEvent {
+    pub cube_id: String,
+    pub contributor: Pubkey,
+    pub amount: u64,
+    pub total_contributed: u64,
+}
+
+#[event]
+pub struct CubeTransferredEvent {
+    pub cube_id: String,
+    pub from: Pubkey,
+    pub to: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct GameInfoEvent {
+    pub authority: Pubkey,
+    pub price_per_cube: u64,
+    pub total_cubes_removed: u64,
+    pub max_cubes: u64,
+    pub board_size: u64,
+    pub completion_bps: u16,
+    pub ends_at: i64,
+    pub currency_label: String,
+    pub currency_decimals: u8,
+    pub stats_display_multiplier_bps: u16,
+}
+
+#[event]
+pub struct ScaledStatsEvent {
+    pub player: Pubkey,
+    pub cubes_removed: u64,
+    pub scaled_cubes_removed: u64,
+}
+
+#[event]
+pub struct PriceQuoteEvent {
+    pub player: Pubkey,
+    pub price: u64,
+}
+
+#[event]
+pub struct RemovalCostEvent {
+    pub cube_id: String,
+    pub player: Pubkey,
+    pub price: u64,
+    /// Rent of the cube record, 0 when it already exists
+    pub record_rent: u64,
+    /// Rent of the player's stats, 0 when they already exist
+    pub stats_rent: u64,
+    pub total: u64,
+}
+
+#[event]
+pub struct WithdrawProposedEvent {
+    pub amount: u64,
+    pub eligible_slot: u64,
+}
+
+#[event]
+pub struct CubeStatusBatchEvent {
+    pub statuses: Vec<CubeStatus>,
+}
+
+#[event]
+pub struct ConfigUpdatedEvent {
+    pub authority: Pubkey,
+    pub update: GameConfigUpdate,
+}
+
+#[event]
+pub struct PlayerRankingEvent {
+    pub scores: Vec<PlayerScore>,
+}
+
+#[event]
+pub struct BatchLimitEvent {
+    pub account_creation_count: u32,
+    pub max_batch: u32,
+}
+
+#[event]
+pub struct ErrorCatalogEvent {
+    pub entries: Vec<ErrorCatalogEntry>,
+}
+
+#[event]
+pub struct GameExtendedEvent {
+    pub ends_at: i64,
+    pub extensions_used: u32,
+}
+
+#[event]
+pub struct TreasuryStakedEvent {
+    pub stake_account: Pubkey,
+    pub vote_account: Pubkey,
+    pub amount: u64,
+}
+
+#[event]
+pub struct StakeWithdrawnEvent {
+    pub stake_account: Pubkey,
+    pub amount: u64,
+    pub treasury_staked: u64,
+}
+
+#[event]
+pub struct ZoneUnlockedEvent {
+    pub zone_id: u32,
+    pub prefix: String,
+}
+
+#[event]
+pub struct TreasuryRefilledEvent {
+    pub amount: u64,
+    pub treasury_balance: u64,
+}
+
+#[event]
+pub struct AttestationEvent {
+    /// Borsh-encoded `StatsAttestation`
+    pub payload: Vec<u8>,
+    pub attestor: Pubkey,
+    /// The attestor's signature over `payload` was verified in this
+    /// transaction
+    pub signed: bool,
+}
+
+#[event]
+pub struct MilestoneRewardEvent {
+    pub player: Pubkey,
+    pub cubes_removed: u64,
+    pub amount: u64,
+}
+
+#[event]
+pub struct ComboTimerEvent {
+    pub player: Pubkey,
+    pub combo_count: u32,
+    pub bonus: u64,
+}
+
+#[event]
+pub struct BountyFundedEvent {
+    pub cube_id: String,
+    pub funder: Pubkey,
+    pub amount: u64,
+    pub total: u64,
+}
+
+#[event]
+pub struct QueueJoinedEvent {
+    pub cube_id: String,
+    pub player: Pubkey,
+    /// 1-based place in the queue
+    pub position: u32,
+}
+
+#[event]
+pub struct BountyPaidEvent {
+    pub cube_id: String,
+    pub player: Pubkey,
+    pub amount: u64,
+}
+
+#[event]
+pub struct DailyRewardClaimedEvent {
+    pub player: Pubkey,
+    pub reward: u64,
+}
+
+#[event]
+pub struct InventoryEvent {
+    pub player: Pubkey,
+    pub items: Vec<String>,
+}
+
+#[event]
+pub struct LowBalanceWarningEvent {
+    pub player: Pubkey,
+    /// Player's lamports after paying
+    pub balance: u64,
+    pub threshold: u64,
+}
+
+#[event]
+pub struct SubscriptionPurchasedEvent {
+    pub player: Pubkey,
+    pub tier: u8,
+    pub expires_at: i64,
+}
+
+#[event]
+pub struct TreasuryUnfundedWarning {
+    pub balance: u64,
+    pub minimum: u64,
+}
+
+#[event]
+pub struct CommunityGoalReachedEvent {
+    pub goal_id: u64,
+    pub progress: u64,
+    pub participants: u64,
+}
+
+#[event]
+pub struct CommunityRewardClaimedEvent {
+    pub goal_id: u64,
+    pub player: Pubkey,
+    pub reward: u64,
+}
+
+#[event]
+pub struct StakeChangedEvent {
+    pub owner: Pubkey,
+    pub amount: u64,
+    pub total_staked: u64,
+}
+
+#[event]
+pub struct StakingRewardsClaimedEvent {
+    pub owner: Pubkey,
+    pub amount: u64,
+}
+
+#[event]
+pub struct RefundAllModeEvent {
+    pub paid_liability: u64,
+}
+
+#[event]
+pub struct FullRefundEvent {
+    pub cube_id: String,
+    pub player: Pubkey,
+    pub amount: u64,
+    /// Still owed to other removers
+    pub paid_liability: u64,
+}
+
+/// A tip accrued on `cube_id`'s record, claimable by `to` with `claim_tip`
+#[event]
+pub struct TipPaidEvent {
+    pub cube_id: String,
+    pub from: Pubkey,
+    pub to: Pubkey,
+    pub amount: u64,
+}
+
+#[event]
+pub struct RoyaltyPaidEvent {
+    pub cube_id: String,
+    pub from: Pubkey,
+    pub to: Pubkey,
+    pub amount: u64,
+}
+
+#[event]
+pub struct PracticeRemovalEvent {
+    pub cube_id: String,
+    pub player: Pubkey,
+    pub practice_removals: u64,
+}
+
+#[event]
+pub struct CubeUnlockedEvent {
+    pub cube_id: String,
+    pub player: Pubkey,
+    pub unlock_slot: u64,
+    pub fee: u64,
+}
+
+#[event]
+pub struct CubeRevealedEvent {
+    pub cube_id: String,
+    pub rarity: u64,
+    pub color: u32,
+}
+
+#[event]
+pub struct CubeLikedEvent {
+    pub cube_id: String,
+    pub liker: Pubkey,
+    pub likes: u64,
+}
+
+#[event]
+pub struct PricingResetEvent {
+    pub authority: Pubkey,
+    pub base_price: u64,
+}
+
+#[event]
+pub struct SharedRemovalEvent {
+    pub cube_id: String,
+    pub player: Pubkey,
+    pub remover_count: u8,
+    pub max_removers: u8,
+}
+
+#[event]
+pub struct CubeHitEvent {
+    pub cube_id: String,
+    pub player: Pubkey,
+    pub hits_taken: u8,
+    pub hits_required: u8,
+}
+
+#[event]
+pub struct BombEvent {
+    pub cube_id: String,
+    pub player: Pubkey,
+    pub flipped: u64,
+}
+
+#[event]
+pub struct ZoneResetEvent {
+    pub zone_id: u32,
+    pub restored: u32,
+    pub refunded: u64,
+}
+
+#[event]
+pub struct ComboEvent {
+    pub cube_id: String,
+    pub player: Pubkey,
+    pub neighbors: u8,
+}
+
+#[event]
+pub struct TreasuryThresholdEvent {
+    pub balance: u64,
+    pub threshold: u64,
+}
+
+#[event]
+pub struct WinnerSelectedEvent {
+    pub winner: Pubkey,
+    pub prize: u64,
+    pub total_weight: u64,
+}
+
+#[event]
+pub struct GameStateMigratedEvent {
+    pub old_len: u32,
+    pub new_len: u32,
+}
+
+#[event]
+pub struct BatchRefundEvent {
+    pub count: u32,
+    pub total: u64,
+}
+
+#[event]
+pub struct RollbackEvent {
+    pub since: i64,
+    pub restored: u32,
+    pub refunded: u64,
+}
+
+#[event]
+pub struct UndoRemovalEvent {
+    pub cube_id: String,
+    pub remover: Pubkey,
+    pub refunded: u64,
+}
+
+/// Program errors. Codes are 6000 + the explicit discriminant and are stable:
+/// append new variants at the end and never reorder or reuse a value.
+#[error_code]
+pub enum CubeGameError {
+    #[msg("This cube has already been removed")]
+    CubeAlreadyRemoved = 0,
+    #[msg("Contribution amount must be greater than zero")]
+    ZeroContribution = 1,
+    #[msg("This cube already has the maximum number of contributors")]
+    TooManyContributors = 2,
+    #[msg("This cube has not been removed yet")]
+    CubeNotRemoved = 3,
+    #[msg("Only the cube owner can do this")]
+    NotCubeOwner = 4,
+    #[msg("This cube is still locked after its removal")]
+    CubeLocked = 5,
+    #[msg("Duration must not be negative")]
+    InvalidDuration = 6,
+    #[msg("This removal would exceed your spend cap")]
+    SpendCapReached = 7,
+    #[msg("The cube record is in an unexpected state")]
+    CubeRecordCorrupt = 8,
+    #[msg("The cold wallet account is required to forward treasury overflow")]
+    ColdWalletRequired = 9,
+    #[msg("The cold wallet does not match the configured address")]
+    ColdWalletMismatch = 10,
+    #[msg("Basis points value is out of range")]
+    InvalidBps = 11,
+    #[msg("Arithmetic overflow")]
+    MathOverflow = 12,
+    #[msg("Team id must be between 1 and 32 bytes")]
+    InvalidTeamId = 13,
+    #[msg("Player is already on a team")]
+    AlreadyOnTeam = 14,
+    #[msg("The player's team account is required")]
+    TeamAccountRequired = 15,
+    #[msg("Withdrawals must go through propose_withdraw and execute_withdraw")]
+    WithdrawTimelockRequired = 16,
+    #[msg("The proposed withdrawal is not executable yet")]
+    WithdrawTimelockActive = 17,
+    #[msg("Too many accounts passed")]
+    TooManyAccounts = 18,
+    #[msg("The cube id does not match the required prefix")]
+    InvalidCubeId = 19,
+    #[msg("The cube id prefix must be at most 16 bytes")]
+    PrefixTooLong = 20,
+    #[msg("The treasury account is not the program's treasury PDA")]
+    InvalidTreasury = 21,
+    #[msg("The activity feed account is required")]
+    ActivityFeedRequired = 22,
+    #[msg("The game has ended")]
+    GameEnded = 23,
+    #[msg("A verified NFT from the required collection is needed")]
+    MissingRequiredNft = 24,
+    #[msg("This board does not require an NFT")]
+    CollectionNotRequired = 25,
+    #[msg("The refund pool account is required")]
+    RefundPoolRequired = 26,
+    #[msg("Batch arguments do not line up")]
+    BatchArgMismatch = 27,
+    #[msg("Refund recipient does not match the cube's remover")]
+    RefundRecipientMismatch = 28,
+    #[msg("Not enough funds in the refund pool and treasury")]
+    InsufficientRefundFunds = 29,
+    #[msg("The memo must be at most 64 bytes")]
+    MemoTooLong = 30,
+    #[msg("Staking would leave the treasury below its liquid reserve")]
+    InsufficientLiquidity = 31,
+    #[msg("The instructions sysvar is required to check the per-transaction limit")]
+    InstructionsSysvarRequired = 32,
+    #[msg("Too many cube removals in this transaction")]
+    TooManyRemovalsPerTx = 33,
+    #[msg("Every cube on the board has been removed")]
+    BoardComplete = 34,
+    #[msg("The board has not been completed yet")]
+    BoardNotComplete = 35,
+    #[msg("The game has already been finalized")]
+    GameAlreadyFinalized = 36,
+    #[msg("Accounts must be sorted by address without duplicates")]
+    UnsortedAccounts = 37,
+    #[msg("No player has any weight in the draw")]
+    NoEntrants = 38,
+    #[msg("The winner account does not match the selected player")]
+    WinnerMismatch = 39,
+    #[msg("Insufficient treasury balance")]
+    InsufficientTreasuryBalance = 40,
+    #[msg("The cube record account does not match the cube id")]
+    CubeRecordMismatch = 41,
+    #[msg("The proof-of-work nonce does not meet the difficulty")]
+    InvalidProofOfWork = 42,
+    #[msg("The currency label must be at most 16 bytes")]
+    CurrencyLabelTooLong = 43,
+    #[msg("This cube is not part of the committed board")]
+    NotOnBoard = 44,
+    #[msg("The board commitment is locked once play has started")]
+    BoardLocked = 45,
+    #[msg("This withdrawal would exceed the per-window limit")]
+    WithdrawRateLimited = 46,
+    #[msg("The incinerator account is required")]
+    IncineratorRequired = 47,
+    #[msg("The game is still running")]
+    GameNotOver = 48,
+    #[msg("Must buy at least one credit")]
+    ZeroCredits = 49,
+    #[msg("Import mode is not enabled")]
+    ImportModeOff = 50,
+    #[msg("Imports have been locked")]
+    ImportLocked = 51,
+    #[msg("The batch is too large for the compute budget")]
+    BatchExceedsComputeBudget = 52,
+    #[msg("This player has been frozen by the authority")]
+    PlayerFrozen = 53,
+    #[msg("A combo cannot need more than six neighbours")]
+    InvalidComboThreshold = 54,
+    #[msg("The zone account is required")]
+    ZoneAccountRequired = 55,
+    #[msg("This cube is not part of the given zone")]
+    CubeNotInZone = 56,
+    #[msg("This zone is locked until the previous zone is cleared")]
+    ZoneLocked = 57,
+    #[msg("Invalid zone")]
+    InvalidZone = 58,
+    #[msg("The DAO treasury account is required")]
+    DaoTreasuryRequired = 59,
+    #[msg("DAO treasury does not match the configured address")]
+    DaoTreasuryMismatch = 60,
+    #[msg("Snapshot labels must be 1 to 32 bytes")]
+    InvalidSnapshotLabel = 61,
+    #[msg("Region ids must be 1 to 16 bytes")]
+    InvalidRegion = 62,
+    #[msg("A price lock must cover at least one removal")]
+    ZeroVoucherUses = 63,
+    #[msg("Price locks are not being offered")]
+    VouchersDisabled = 64,
+    #[msg("The existing price lock is still active")]
+    VoucherActive = 65,
+    #[msg("Too many programs on the CPI allowlist")]
+    TooManyCpiCallers = 66,
+    #[msg("Calling program is not on the CPI allowlist")]
+    UnauthorizedCpiCaller = 67,
+    #[msg("High-value removal needs an intent from an earlier slot")]
+    ConfirmationRequired = 68,
+    #[msg("Withdrawals are cooling down after a recent deposit")]
+    WithdrawCoolingPeriod = 69,
+    #[msg("Account is not a neighbouring cube record")]
+    NotANeighbor = 70,
+    #[msg("Signer is not the game authority")]
+    Unauthorized = 71,
+    #[msg("Removal exceeds the delegate's spend limit")]
+    DelegateLimitExceeded = 72,
+    #[msg("No prepaid credits left")]
+    NoCredits = 73,
+    #[msg("Insurance vault account is required")]
+    InsuranceVaultRequired = 74,
+    #[msg("Insurance vault is still locked")]
+    InsuranceLocked = 75,
+    #[msg("Insurance vault cannot cover the amount")]
+    InsufficientInsuranceFunds = 76,
+    #[msg("No more cube records can be created")]
+    RecordLimitReached = 77,
+    #[msg("Tier thresholds must not decrease")]
+    InvalidTierThresholds = 78,
+    #[msg("Attested priority fee is below the minimum")]
+    PriorityFeeTooLow = 79,
+    #[msg("Cubes need several hits on this board")]
+    MultiHitBoard = 80,
+    #[msg("Daily rewards are disabled")]
+    DailyRewardDisabled = 81,
+    #[msg("Daily reward already claimed today")]
+    AlreadyClaimedToday = 82,
+    #[msg("Regional removals must pass the region account")]
+    RegionRequired = 83,
+    #[msg("The price changed since the expected price epoch")]
+    PriceEpochMismatch = 84,
+    #[msg("Bounties only apply to standing cubes in a running game")]
+    BountyUnavailable = 85,
+    #[msg("Too many wallets funding this bounty")]
+    TooManyBountyFunders = 86,
+    #[msg("Caller has no share in this bounty")]
+    NoBountyShare = 87,
+    #[msg("Missing or invalid Ed25519 signature instruction")]
+    InvalidSignature = 88,
+    #[msg("Attestation time is in the future or too old")]
+    StaleAttestation = 89,
+    #[msg("Cold reserve account required while a target hot balance is set")]
+    ColdReserveRequired = 90,
+    #[msg("The removal's deadline has passed")]
+    DeadlineExceeded = 91,
+    #[msg("Too many removals in this slot, retry in a later one")]
+    SlotThrottled = 92,
+    #[msg("Referrer already set")]
+    ReferrerAlreadySet = 93,
+    #[msg("Players cannot refer themselves")]
+    InvalidReferrer = 94,
+    #[msg("No referral earnings to claim")]
+    NoReferralEarnings = 95,
+    #[msg("The board is paced, wait before the next removal")]
+    GlobalPaceLimited = 96,
+    #[msg("Reward mint, token account, mint authority and token program required")]
+    RewardAccountsRequired = 97,
+    #[msg("Reward mint does not match the configured mint")]
+    RewardMintMismatch = 98,
+    #[msg("Configured keys must be distinct from the authority and treasury")]
+    InvalidConfiguration = 99,
+    #[msg("Player already removed this shared cube")]
+    AlreadySharedRemover = 100,
+    #[msg("Cubes are shared between several removers on this board")]
+    SharedCubeBoard = 101,
+    #[msg("Too many removers for a shared cube")]
+    TooManySharedRemovers = 102,
+    #[msg("Inventory is full")]
+    InventoryFull = 103,
+    #[msg("Split payments are disabled")]
+    SplitPaymentDisabled = 104,
+    #[msg("Lamports and token value together do not cover the price")]
+    SplitUnderpaid = 105,
+    #[msg("Payment token account, token treasury and token program required")]
+    SplitAccountsRequired = 106,
+    #[msg("Token treasury does not match the configured one")]
+    TokenTreasuryMismatch = 107,
+    #[msg("Not enough cubes removed yet to withdraw")]
+    WithdrawThresholdNotMet = 108,
+    #[msg("Stored game state bump does not match the canonical PDA")]
+    GameStatePdaMismatch = 109,
+    #[msg("Stored treasury bump does not match the canonical PDA")]
+    TreasuryPdaMismatch = 110,
+    #[msg("Removal queue account required while queueing is on")]
+    QueueRequired = 111,
+    #[msg("Another player is at the front of this cube's queue")]
+    NotFrontOfQueue = 112,
+    #[msg("Player is already queued for this cube")]
+    AlreadyQueued = 113,
+    #[msg("Removal queue is full")]
+    QueueFull = 114,
+    #[msg("Cubes are removed through queues on this board")]
+    QueuedBoard = 115,
+    #[msg("Removal queues are disabled")]
+    QueueDisabled = 116,
+    #[msg("Analytics account required once analytics are enabled")]
+    AnalyticsRequired = 117,
+    #[msg("Removals need a ticket from the ticket signer on this board")]
+    TicketRequired = 118,
+    #[msg("Removal tickets are disabled")]
+    TicketsDisabled = 119,
+    #[msg("Removal ticket has expired")]
+    TicketExpired = 120,
+    #[msg("Game state is smaller than the current layout; run migrate_game_state")]
+    AccountNeedsMigration = 121,
+    #[msg("Player is in practice mode")]
+    PracticeModeOn = 122,
+    #[msg("Player is not in practice mode")]
+    PracticeModeOff = 123,
+    #[msg("The game is paused")]
+    GamePaused = 124,
+    #[msg("Previous remover account required to pay the tip")]
+    TipRecipientRequired = 125,
+    #[msg("Tip recipient is not the previous remover")]
+    TipRecipientMismatch = 126,
+    #[msg("The cube's predecessor has not been removed yet")]
+    PredecessorNotRemoved = 127,
+    #[msg("Sequenced cubes can only be removed one at a time with remove_cube")]
+    SequencedCube = 128,
+    #[msg("Refund-all mode is not active")]
+    RefundModeInactive = 129,
+    #[msg("The game is in refund-all mode")]
+    RefundModeActive = 130,
+    #[msg("Nothing was paid for this cube")]
+    NothingToRefund = 131,
+    #[msg("Withdrawal would leave refunds owed to players unfunded")]
+    RefundLiabilityOutstanding = 132,
+    #[msg("This region is paused")]
+    RegionPaused = 133,
+    #[msg("Subscriptions are not on sale")]
+    SubscriptionsDisabled = 134,
+    #[msg("Unknown subscription tier")]
+    InvalidSubscriptionTier = 135,
+    #[msg("The configured analytics program account is required")]
+    AnalyticsProgramRequired = 136,
+    #[msg("There is no removal to undo")]
+    NoRemovalToUndo = 137,
+    #[msg("The passed cube record is not the last removal")]
+    LastRemovalMismatch = 138,
+    #[msg("Unlocking cubes is turned off")]
+    UnlockDisabled = 139,
+    #[msg("This cube has already been revealed")]
+    CubeAlreadyRevealed = 140,
+    #[msg("The staking pool account is required while staker rewards are on")]
+    StakingPoolRequired = 141,
+    #[msg("Stake amount must be greater than zero")]
+    ZeroStake = 142,
+    #[msg("Not enough tokens staked")]
+    InsufficientStake = 143,
+    #[msg("The staking pool cannot cover these rewards")]
+    InsufficientStakingRewards = 144,
+    #[msg("Vault does not match the staking pool")]
+    StakeVaultMismatch = 145,
+    #[msg("This stake has no rewards to claim")]
+    NoStakingRewards = 146,
+    #[msg("The player's history account is required")]
+    PlayerHistoryRequired = 147,
+    #[msg("Player registered too recently to remove cubes")]
+    PlayerTooNew = 148,
+    #[msg("Message exceeds the maximum length")]
+    MessageTooLong = 149,
+    #[msg("Messages may only contain printable ASCII")]
+    InvalidMessage = 150,
+    #[msg("The message wall account is required to post a message")]
+    MessageWallRequired = 151,
+    #[msg("Removals must be committed and revealed on this board")]
+    CommitRequired = 152,
+    #[msg("Reveal does not match the commitment")]
+    CommitmentMismatch = 153,
+    #[msg("Too few slots have passed since the commitment")]
+    RevealTooSoon = 154,
+    #[msg("The ledger account is required once the ledger is enabled")]
+    LedgerRequired = 155,
+    #[msg("No membership collection is configured")]
+    MembershipDisabled = 156,
+    #[msg("The community goal account is required while a goal is open")]
+    CommunityGoalRequired = 157,
+    #[msg("The current community goal is still running")]
+    GoalInProgress = 158,
+    #[msg("The community goal has not been reached")]
+    GoalNotReached = 159,
+    #[msg("Player did not contribute to the community goal")]
+    NotGoalContributor = 160,
+    #[msg("Community reward already claimed")]
+    CommunityRewardClaimed = 161,
+    #[msg("Another removal happened since the expected total was read")]
+    ConcurrencyConflict = 162,
+    #[msg("Funding amount must be greater than zero")]
+    ZeroFunding = 163,
+    #[msg("The creator account is required for a cube with a royalty")]
+    CreatorRequired = 164,
+    #[msg("Creator account does not match the cube's creator")]
+    CreatorMismatch = 165,
+    #[msg("Cubes with a creator royalty can only be removed by remove_cube")]
+    RoyaltyCube = 166,
+    #[msg("The cube owner's PlayerStats account is required to complete the cube")]
+    OwnerStatsRequired = 167,
+    #[msg("Mint does not match the configured payment mint")]
+    PaymentMintMismatch = 168,
+    #[msg("Ephemeral boards cannot have a size limit or zones")]
+    EphemeralBoard = 169,
+    #[msg("The completion seed has not been committed")]
+    CompletionSeedRequired = 170,
+    #[msg("The completion seed is already committed")]
+    CompletionSeedCommitted = 171,
+    #[msg("The slot hash after the board's completion is not available yet")]
+    SlotHashUnavailable = 172,
+    #[msg("Entrant weights exceed the board's recorded removals")]
+    EntrantWeightMismatch = 173,
+    #[msg("PlayerStats do not belong to the cube's remover")]
+    RemoverStatsMismatch = 174,
+    #[msg("The grand-prize draw is being counted")]
+    FinalizeInProgress = 175,
+    #[msg("Another payer is still owed a refund on this cube")]
+    CubeHasOtherPayers = 176,
+    #[msg("The cube's record holds a tip that has not been claimed")]
+    TipUnclaimed = 177,
+    #[msg("No tip is owed on this cube")]
+    NoTipOwed = 178,
+    #[msg("Pricing in points needs a nonzero point value")]
+    PointValueUnset = 179,
+    #[msg("The price is above what the credit was bought to cover")]
+    CreditValueExceeded = 180,
+    #[msg("Only players who have removed a cube can claim the daily reward")]
+    DailyRewardIneligible = 181,
+    #[msg("Removals must be top-level instructions while the per-transaction limit is on")]
+    RemovalViaCpi = 182,
+    #[msg("A leaderboard page this update touches was not passed")]
+    LeaderboardPageMissing = 183,
+    #[msg("The leaderboard already has its maximum number of pages")]
+    LeaderboardFull = 184,
+    #[msg("This cube has not been unlocked")]
+    CubeNotUnlocked = 185,
+    #[msg("A player's first removal needs their wallet mark")]
+    WalletMarkRequired = 186,
+    #[msg("The player is not in this removal queue")]
+    NotQueued = 187,
+    #[msg("Players are still waiting in this removal queue")]
+    QueueNotEmpty = 188,
+    #[msg("USD pricing is not configured")]
+    UsdPricingDisabled = 189,
+    #[msg("A price feed needs a positive max_price_staleness")]
+    InvalidPriceStaleness = 190,
+    #[msg("Price feed does not match the configured one")]
+    PriceFeedMismatch = 191,
+    #[msg("The price feed account is required for a USD payment")]
+    PriceFeedRequired = 192,
+    #[msg("Account is not a Pyth price account")]
+    InvalidPriceFeed = 193,
+    #[msg("Oracle price is not trading or not positive")]
+    InvalidOraclePrice = 194,
+    #[msg("Oracle price is older than max_price_staleness")]
+    StalePrice = 195,
+    #[msg("USD price converts to more than max_tokens")]
+    UsdTokensExceeded = 196,
+}
+
+impl CubeGameError {
+    /// Every variant in code order, for `get_error_catalog`
+    pub const ALL: &'static [CubeGameError] = &[
+        CubeGameError::CubeAlreadyRemoved,
+        CubeGameError::ZeroContribution,
+        CubeGameError::TooManyContributors,
+        CubeGameError::CubeNotRemoved,
+        CubeGameError::NotCubeOwner,
+        CubeGameError::CubeLocked,
+        CubeGameError::InvalidDuration,
+        CubeGameError::SpendCapReached,
+        CubeGameError::CubeRecordCorrupt,
+        CubeGameError::ColdWalletRequired,
+        CubeGameError::ColdWalletMismatch,
+        CubeGameError::InvalidBps,
+        CubeGameError::MathOverflow,
+        CubeGameError::InvalidTeamId,
+        CubeGameError::AlreadyOnTeam,
+        CubeGameError::TeamAccountRequired,
+        CubeGameError::WithdrawTimelockRequired,
+        CubeGameError::WithdrawTimelockActive,
+        CubeGameError::TooManyAccounts,
+        CubeGameError::InvalidCubeId,
+        CubeGameError::PrefixTooLong,
+        CubeGameError::InvalidTreasury,
+        CubeGameError::ActivityFeedRequired,
+        CubeGameError::GameEnded,
+        CubeGameError::MissingRequiredNft,
+        CubeGameError::CollectionNotRequired,
+        CubeGameError::RefundPoolRequired,
+        CubeGameError::BatchArgMismatch,
+        CubeGameError::RefundRecipientMismatch,
+        CubeGameError::InsufficientRefundFunds,
+        CubeGameError::MemoTooLong,
+        CubeGameError::InsufficientLiquidity,
+        CubeGameError::InstructionsSysvarRequired,
+        CubeGameError::TooManyRemovalsPerTx,
+        CubeGameError::BoardComplete,
+        CubeGameError::BoardNotComplete,
+        CubeGameError::GameAlreadyFinalized,
+        CubeGameError::UnsortedAccounts,
+        CubeGameError::NoEntrants,
+        CubeGameError::WinnerMismatch,
+        CubeGameError::InsufficientTreasuryBalance,
+        CubeGameError::CubeRecordMismatch,
+        CubeGameError::InvalidProofOfWork,
+        CubeGameError::CurrencyLabelTooLong,
+        CubeGameError::NotOnBoard,
+        CubeGameError::BoardLocked,
+        CubeGameError::WithdrawRateLimited,
+        CubeGameError::IncineratorRequired,
+        CubeGameError::GameNotOver,
+        CubeGameError::ZeroCredits,
+        CubeGameError::ImportModeOff,
+        CubeGameError::ImportLocked,
+        CubeGameError::BatchExceedsComputeBudget,
+        CubeGameError::PlayerFrozen,
+        CubeGameError::InvalidComboThreshold,
+        CubeGameError::ZoneAccountRequired,
+        CubeGameError::CubeNotInZone,
+        CubeGameError::ZoneLocked,
+        CubeGameError::InvalidZone,
+        CubeGameError::DaoTreasuryRequired,
+        CubeGameError::DaoTreasuryMismatch,
+        CubeGameError::InvalidSnapshotLabel,
+        CubeGameError::InvalidRegion,
+        CubeGameError::ZeroVoucherUses,
+        CubeGameError::VouchersDisabled,
+        CubeGameError::VoucherActive,
+        CubeGameError::TooManyCpiCallers,
+        CubeGameError::UnauthorizedCpiCaller,
+        CubeGameError::ConfirmationRequired,
+        CubeGameError::WithdrawCoolingPeriod,
+        CubeGameError::NotANeighbor,
+        CubeGameError::Unauthorized,
+        CubeGameError::DelegateLimitExceeded,
+        CubeGameError::NoCredits,
+        CubeGameError::InsuranceVaultRequired,
+        CubeGameError::InsuranceLocked,
+        CubeGameError::InsufficientInsuranceFunds,
+        CubeGameError::RecordLimitReached,
+        CubeGameError::InvalidTierThresholds,
+        CubeGameError::PriorityFeeTooLow,
+        CubeGameError::MultiHitBoard,
+        CubeGameError::DailyRewardDisabled,
+        CubeGameError::AlreadyClaimedToday,
+        CubeGameError::RegionRequired,
+        CubeGameError::PriceEpochMismatch,
+        CubeGameError::BountyUnavailable,
+        CubeGameError::TooManyBountyFunders,
+        CubeGameError::NoBountyShare,
+        CubeGameError::InvalidSignature,
+        CubeGameError::StaleAttestation,
+        CubeGameError::ColdReserveRequired,
+        CubeGameError::DeadlineExceeded,
+        CubeGameError::SlotThrottled,
+        CubeGameError::ReferrerAlreadySet,
+        CubeGameError::InvalidReferrer,
+        CubeGameError::NoReferralEarnings,
+        CubeGameError::GlobalPaceLimited,
+        CubeGameError::RewardAccountsRequired,
+        CubeGameError::RewardMintMismatch,
+        CubeGameError::InvalidConfiguration,
+        CubeGameError::AlreadySharedRemover,
+        CubeGameError::SharedCubeBoard,
+        CubeGameError::TooManySharedRemovers,
+        CubeGameError::InventoryFull,
+        CubeGameError::SplitPaymentDisabled,
+        CubeGameError::SplitUnderpaid,
+        CubeGameError::SplitAccountsRequired,
+        CubeGameError::TokenTreasuryMismatch,
+        CubeGameError::WithdrawThresholdNotMet,
+        CubeGameError::GameStatePdaMismatch,
+        CubeGameError::TreasuryPdaMismatch,
+        CubeGameError::QueueRequired,
+        CubeGameError::NotFrontOfQueue,
+        CubeGameError::AlreadyQueued,
+        CubeGameError::QueueFull,
+        CubeGameError::QueuedBoard,
+        CubeGameError::QueueDisabled,
+        CubeGameError::AnalyticsRequired,
+        CubeGameError::TicketRequired,
+        CubeGameError::TicketsDisabled,
+        CubeGameError::TicketExpired,
+        CubeGameError::AccountNeedsMigration,
+        CubeGameError::PracticeModeOn,
+        CubeGameError::PracticeModeOff,
+        CubeGameError::GamePaused,
+        CubeGameError::TipRecipientRequired,
+        CubeGameError::TipRecipientMismatch,
+        CubeGameError::PredecessorNotRemoved,
+        CubeGameError::SequencedCube,
+        CubeGameError::RefundModeInactive,
+        CubeGameError::RefundModeActive,
+        CubeGameError::NothingToRefund,
+        CubeGameError::RefundLiabilityOutstanding,
+        CubeGameError::RegionPaused,
+        CubeGameError::SubscriptionsDisabled,
+        CubeGameError::InvalidSubscriptionTier,
+        CubeGameError::AnalyticsProgramRequired,
+        CubeGameError::NoRemovalToUndo,
+        CubeGameError::LastRemovalMismatch,
+        CubeGameError::UnlockDisabled,
+        CubeGameError::CubeAlreadyRevealed,
+        CubeGameError::StakingPoolRequired,
+        CubeGameError::ZeroStake,
+        CubeGameError::InsufficientStake,
+        CubeGameError::InsufficientStakingRewards,
+        CubeGameError::StakeVaultMismatch,
+        CubeGameError::NoStakingRewards,
+        CubeGameError::PlayerHistoryRequired,
+        CubeGameError::PlayerTooNew,
+        CubeGameError::MessageTooLong,
+        CubeGameError::InvalidMessage,
+        CubeGameError::MessageWallRequired,
+        CubeGameError::CommitRequired,
+        CubeGameError::CommitmentMismatch,
+        CubeGameError::RevealTooSoon,
+        CubeGameError::LedgerRequired,
+        CubeGameError::MembershipDisabled,
+        CubeGameError::CommunityGoalRequired,
+        CubeGameError::GoalInProgress,
+        CubeGameError::GoalNotReached,
+        CubeGameError::NotGoalContributor,
+        CubeGameError::CommunityRewardClaimed,
+        CubeGameError::ConcurrencyConflict,
+        CubeGameError::ZeroFunding,
+        CubeGameError::CreatorRequired,
+        CubeGameError::CreatorMismatch,
+        CubeGameError::RoyaltyCube,
+        CubeGameError::OwnerStatsRequired,
+        CubeGameError::PaymentMintMismatch,
+        CubeGameError::EphemeralBoard,
+        CubeGameError::CompletionSeedRequired,
+        CubeGameError::CompletionSeedCommitted,
+        CubeGameError::SlotHashUnavailable,
+        CubeGameError::EntrantWeightMismatch,
+        CubeGameError::RemoverStatsMismatch,
+        CubeGameError::FinalizeInProgress,
+        CubeGameError::CubeHasOtherPayers,
+        CubeGameError::TipUnclaimed,
+        CubeGameError::NoTipOwed,
+        CubeGameError::PointValueUnset,
+        CubeGameError::CreditValueExceeded,
+        CubeGameError::DailyRewardIneligible,
+        CubeGameError::RemovalViaCpi,
+        CubeGameError::LeaderboardPageMissing,
+        CubeGameError::LeaderboardFull,
+        CubeGameError::CubeNotUnlocked,
+        CubeGameError::WalletMarkRequired,
+        CubeGameError::NotQueued,
+        CubeGameError::QueueNotEmpty,
+        CubeGameError::UsdPricingDisabled,
+        CubeGameError::InvalidPriceStaleness,
+        CubeGameError::PriceFeedMismatch,
+        CubeGameError::PriceFeedRequired,
+        CubeGameError::InvalidPriceFeed,
+        CubeGameError::InvalidOraclePrice,
+        CubeGameError::StalePrice,
+        CubeGameError::UsdTokensExceeded,
+    ];
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A game with every field zeroed, as `initialize` leaves most of them
+    fn zeroed_game() -> GameState {
+        let data = vec![0u8; GameState::INIT_SPACE];
+        GameState::deserialize(&mut data.as_slice()).unwrap()
+    }
+
+    #[test]
+    fn apply_bps_rounds_down_and_scales_past_the_denominator() {
+        assert_eq!(apply_bps(1_000, 2_500).unwrap(), 250);
+        assert_eq!(apply_bps(999, 1).unwrap(), 0);
+        assert_eq!(apply_bps(1_000, 15_000).unwrap(), 1_500);
+        assert_eq!(apply_bps(u64::MAX, 10_000).unwrap(), u64::MAX);
+        assert_eq!(
+            apply_bps(u64::MAX, 10_001).unwrap_err(),
+            error!(CubeGameError::MathOverflow)
+        );
+    }
+
+    #[test]
+    fn effective_price_applies_modifiers_in_order() {
+        let mut game = zeroed_game();
+        game.price_per_cube = 1_000;
+        assert_eq!(effective_price(&game, None, false, 0).unwrap(), 1_000);
+        assert_eq!(effective_price(&game, Some(400), false, 0).unwrap(), 400);
+        assert_eq!(effective_price(&game, None, true, 0).unwrap(), 0);
+
+        // base + k * total_removed^2
+        game.quadratic_coeff = 2;
+        game.total_cubes_removed = 3;
+        assert_eq!(effective_price(&game, None, false, 0).unwrap(), 1_018);
+
+        // Two recent removals at 5% each, capped at 8%
+        game.quadratic_coeff = 0;
+        game.rush_window_seconds = 60;
+        game.rush_window_start = 100;
+        game.recent_removal_count = 2;
+        game.rush_bps_per_removal = 500;
+        game.rush_max_bps = 800;
+        assert_eq!(effective_price(&game, None, false, 120).unwrap(), 1_080);
+        assert_eq!(effective_price(&game, None, false, 160).unwrap(), 1_000);
+
+        // Surge multiplies the rushed price and ends at `surge_until`
+        game.surge_until = 150;
+        game.surge_multiplier_bps = 20_000;
+        assert_eq!(effective_price(&game, None, false, 120).unwrap(), 2_160);
+        assert_eq!(effective_price(&game, None, false, 150).unwrap(), 1_080);
+    }
+
+    #[test]
+    fn effective_price_reports_quadratic_overflow() {
+        let mut game = zeroed_game();
+        game.quadratic_coeff = 1;
+        game.total_cubes_removed = u64::MAX;
+        assert_eq!(
+            effective_price(&game, None, false, 0).unwrap_err(),
+            error!(CubeGameError::MathOverflow)
+        );
+    }
+
+    #[test]
+    fn neighbor_ids_lists_the_six_faces_under_the_prefix() {
+        assert_eq!(
+            neighbor_ids("1:2:3"),
+            ["0:2:3", "2:2:3", "1:1:3", "1:3:3", "1:2:2", "1:2:4"]
+        );
+        assert_eq!(
+            neighbor_ids("a:b:0:0:-1"),
+            [
+                "a:b:-1:0:-1",
+                "a:b:1:0:-1",
+                "a:b:0:-1:-1",
+                "a:b:0:1:-1",
+                "a:b:0:0:-2",
+                "a:b:0:0:0"
+            ]
+        );
+        assert!(neighbor_ids("cube-7").is_empty());
+        assert!(neighbor_ids("1:x:3").is_empty());
+    }
+
+    #[test]
+    fn neighbor_ids_skips_steps_past_the_i64_range() {
+        let id = format!("{}:0:{}", i64::MIN, i64::MAX);
+        let ids = neighbor_ids(&id);
+        assert_eq!(ids.len(), 4);
+        assert!(ids.contains(&format!("{}:0:{}", i64::MIN + 1, i64::MAX)));
+        assert!(ids.contains(&format!("{}:0:{}", i64::MIN, i64::MAX - 1)));
+    }
+
+    #[test]
+    fn max_safe_batch_shrinks_with_record_creations() {
+        assert_eq!(max_safe_batch(0), MAX_BATCH_REMOVALS);
+        // 200k - 40k - 6 * 10k leaves room for eight removals
+        assert_eq!(max_safe_batch(6), 8);
+        assert_eq!(max_safe_batch(10), 5);
+        assert_eq!(max_safe_batch(16), 0);
+        assert_eq!(max_safe_batch(usize::MAX), 0);
+    }
+
+    #[test]
+    fn cube_record_seed_keeps_global_records_and_separates_regions() {
+        assert_eq!(cube_record_seed(""), b"cube");
+        assert_eq!(cube_record_seed("eu"), b"rc\x02eu");
+        // The length byte keeps the region/cube split unambiguous
+        assert_ne!(
+            scoped_cube_record_pda("ab", "c"),
+            scoped_cube_record_pda("a", "bc")
+        );
+        assert_ne!(
+            scoped_cube_record_pda("", "1:2:3"),
+            scoped_cube_record_pda("eu", "1:2:3")
+        );
+    }
+
+    #[test]
+    fn cube_color_is_a_stable_rgb_value_per_seed() {
+        let color = cube_color("1:2:3", 42);
+        assert_eq!(color, cube_color("1:2:3", 42));
+        assert!(color <= 0x00ff_ffff);
+
+        let hash = anchor_lang::solana_program::keccak::hashv(&[&42u64.to_le_bytes(), b"1:2:3"]).0;
+        assert_eq!(color, u32::from_be_bytes([0, hash[0], hash[1], hash[2]]));
+        assert_ne!(color, cube_color("1:2:3", 43));
+
+        // Without a palette the player's chosen color is kept
+        let mut game = zeroed_game();
+        assert_eq!(game.removal_color("1:2:3", 0x123456), 0x123456);
+        game.palette_seed = 42;
+        assert_eq!(game.removal_color("1:2:3", 0x123456), color);
+    }
+
+    #[test]
+    fn validate_message_accepts_short_printable_ascii() {
+        assert!(validate_message("").is_ok());
+        assert!(validate_message("gg, well played!").is_ok());
+        assert!(validate_message(&"~".repeat(MAX_MESSAGE_LEN)).is_ok());
+        assert_eq!(
+            validate_message(&"a".repeat(MAX_MESSAGE_LEN + 1)).unwrap_err(),
+            error!(CubeGameError::MessageTooLong)
+        );
+        for message in ["line\nbreak", "tab\there", "caf\u{e9}"] {
+            assert_eq!(
+                validate_message(message).unwrap_err(),
+                error!(CubeGameError::InvalidMessage)
+            );
+        }
+    }
+
+    #[test]
+    fn is_bomb_honours_the_rate_bounds() {
+        for i in 0..64 {
+            let cube_id = format!("cube-{i}");
+            assert!(!is_bomb(&cube_id, 0));
+            assert!(is_bomb(&cube_id, BPS_DENOMINATOR as u16));
+            if is_bomb(&cube_id, 1_000) {
+                assert!(is_bomb(&cube_id, 5_000));
+            }
+        }
+    }
+
+    #[test]
+    fn bombs_armed_only_on_plain_boards() {
+        let mut game = zeroed_game();
+        assert!(!game.bombs_armed("north"));
+        game.bomb_rate_bps = 500;
+        game.hits_required = 1;
+        game.max_removers = 1;
+        assert!(game.bombs_armed("north"));
+        game.queue_turn_seconds = 30;
+        assert!(game.bombs_armed("north"));
+        assert!(!game.bombs_armed(""));
+        game.queue_turn_seconds = 0;
+        game.hits_required = 2;
+        assert!(!game.bombs_armed("north"));
+        game.hits_required = 1;
+        game.zone_count = 1;
+        assert!(!game.bombs_armed("north"));
+        game.zone_count = 0;
+        game.ephemeral_records = true;
+        assert!(game.bombs_armed("north"));
+    }
+
+    #[test]
+    fn ephemeral_boards_hold_only_records_their_remover_paid_for() {
+        let mut game = zeroed_game();
+        assert!(game.require_ephemeral_compatible().is_ok());
+        for setup in [
+            |g: &mut GameState| g.active_cube_records = 1,
+            |g: &mut GameState| g.hits_required = 2,
+            |g: &mut GameState| g.max_removers = 2,
+            |g: &mut GameState| g.max_cubes = 100,
+            |g: &mut GameState| g.zone_count = 1,
+        ] {
+            let mut board = zeroed_game();
+            setup(&mut board);
+            assert_eq!(
+                board.require_ephemeral_compatible().unwrap_err(),
+                error!(CubeGameError::EphemeralBoard)
+            );
+        }
+
+        // A removal creates and closes its record, leaving nothing counted
+        game.ephemeral_records = true;
+        game.note_record_created().unwrap();
+        game.note_paid(900);
+        game.forget_record(900);
+        assert_eq!((game.active_cube_records, game.paid_liability), (0, 0));
+    }
+
+    #[test]
+    fn cube_score_follows_the_rarity_roll() {
+        for i in 0..256 {
+            let cube_id = format!("cube-{i}");
+            let roll =
+                anchor_lang::solana_program::keccak::hashv(&[b"rarity", cube_id.as_bytes()]).0[0];
+            let expected = match roll {
+                0 => 25,
+                1..=7 => 10,
+                8..=47 => 3,
+                _ => 1,
+            };
+            assert_eq!(cube_score(&cube_id), expected);
+        }
+    }
+
+    #[test]
+    fn require_liabilities_covered_keeps_paid_liability_in_refund_mode() {
+        let mut game = zeroed_game();
+        game.paid_liability = 400;
+        assert!(game.require_liabilities_covered(900, 1_000).is_ok());
+        game.refund_all_mode = true;
+        assert!(game.require_liabilities_covered(600, 1_000).is_ok());
+        assert_eq!(
+            game.require_liabilities_covered(601, 1_000).unwrap_err(),
+            error!(CubeGameError::RefundLiabilityOutstanding)
+        );
+        assert_eq!(
+            game.require_liabilities_covered(2_000, 1_000).unwrap_err(),
+            error!(CubeGameError::RefundLiabilityOutstanding)
+        );
+        assert!(game.can_pay(600, 1_000));
+        assert!(!game.can_pay(601, 1_000));
+        game.release_paid(400);
+        assert!(game.require_liabilities_covered(1_000, 1_000).is_ok());
+        assert!(game.can_pay(1_000, 1_000));
+        assert!(!game.can_pay(1_001, 1_000));
+    }
+
+    fn ed25519_data(signer: &Pubkey, signature: &[u8; 64], message: &[u8]) -> Vec<u8> {
+        let mut data = vec![1, 0];
+        for offset in [
+            48,
+            u16::MAX,
+            16,
+            u16::MAX,
+            112,
+            message.len() as u16,
+            u16::MAX,
+        ] {
+            data.extend_from_slice(&offset.to_le_bytes());
+        }
+        data.extend_from_slice(signer.as_ref());
+        data.extend_from_slice(signature);
+        data.extend_from_slice(message);
+        data
+    }
+
+    #[test]
+    fn check_ed25519_data_matches_signer_message_and_signature() {
+        let signer = Pubkey::new_unique();
+        let signature = [7u8; 64];
+        let data = ed25519_data(&signer, &signature, b"ticket");
+        assert!(check_ed25519_data(&data, &signer, b"ticket", None).is_ok());
+        assert!(check_ed25519_data(&data, &signer, b"ticket", Some(&signature)).is_ok());
+
+        let invalid = error!(CubeGameError::InvalidSignature);
+        let other = Pubkey::new_unique();
+        assert_eq!(
+            check_ed25519_data(&data, &other, b"ticket", None).unwrap_err(),
+            invalid
+        );
+        assert_eq!(
+            check_ed25519_data(&data, &signer, b"ticke", None).unwrap_err(),
+            invalid
+        );
+        assert_eq!(
+            check_ed25519_data(&data, &signer, b"ticket", Some(&[8u8; 64])).unwrap_err(),
+            invalid
+        );
+        assert_eq!(
+            check_ed25519_data(&data[..15], &signer, b"ticket", None).unwrap_err(),
+            invalid
+        );
+
+        // Two signatures, or a key read from another instruction
+        let mut two = data.clone();
+        two[0] = 2;
+        assert_eq!(
+            check_ed25519_data(&two, &signer, b"ticket", None).unwrap_err(),
+            invalid
+        );
+        let mut external = data.clone();
+        external[8..10].copy_from_slice(&0u16.to_le_bytes());
+        assert_eq!(
+            check_ed25519_data(&external, &signer, b"ticket", None).unwrap_err(),
+            invalid
+        );
+
+        // Offsets past the end of the data
+        let mut truncated = data;
+        truncated.truncate(115);
+        assert_eq!(
+            check_ed25519_data(&truncated, &signer, b"ticket", None).unwrap_err(),
+            invalid
+        );
+    }
+
+    fn zeroed_record() -> CubeRecord {
+        let data = vec![0u8; CubeRecord::INIT_SPACE];
+        CubeRecord::deserialize(&mut data.as_slice()).unwrap()
+    }
+
+    #[test]
+    fn exit_releases_own_payments_and_leaves_the_draw_settleable() {
+        let player = Pubkey::new_unique();
+        let mut record = zeroed_record();
+        record.removed_by = player;
+        credit_share(&mut record.payments, player, 700).unwrap();
+        assert_eq!(record.exit_release(&player).unwrap(), 700);
+        assert_eq!(
+            record.exit_release(&Pubkey::new_unique()).unwrap_err(),
+            error!(CubeGameError::NotCubeOwner)
+        );
+        credit_share(&mut record.payments, Pubkey::new_unique(), 300).unwrap();
+        assert_eq!(
+            record.exit_release(&player).unwrap_err(),
+            error!(CubeGameError::CubeHasOtherPayers)
+        );
+
+        // Two players with 3 and 2 removals; the first exits
+        let mut game = zeroed_game();
+        game.entrant_weight = 5;
+        game.drop_entrant(3).unwrap();
+        assert_eq!(game.entrant_weight, 2);
+        game.finalize_weight = 2;
+        assert_eq!(
+            game.drop_entrant(2).unwrap_err(),
+            error!(CubeGameError::FinalizeInProgress)
+        );
+        game.winner = Some(player);
+        assert!(game.drop_entrant(2).is_ok());
+    }
+
+    fn zeroed_stats() -> PlayerStats {
+        let data = vec![0u8; PlayerStats::INIT_SPACE];
+        PlayerStats::deserialize(&mut data.as_slice()).unwrap()
+    }
+
+    fn zeroed_mark() -> WalletMark {
+        let data = vec![0u8; WalletMark::INIT_SPACE];
+        WalletMark::deserialize(&mut data.as_slice()).unwrap()
+    }
+
+    #[test]
+    fn forget_clears_identifiable_fields_and_keeps_counts() {
+        let player = Pubkey::new_unique();
+        let mut stats = zeroed_stats();
+        stats.player = player;
+        stats.cubes_removed = 4;
+        stats.total_spent = 9_000;
+        stats.spend_cap = 20_000;
+        stats.pending_spend_cap = 30_000;
+        stats.spend_cap_raise_at = 77;
+        stats.team_id = "reds".to_string();
+        stats.forget();
+
+        assert!(stats.private);
+        assert!(stats.team_id.is_empty());
+        assert_eq!(
+            (stats.total_spent, stats.spend_cap, stats.pending_spend_cap),
+            (0, 0, 0)
+        );
+        assert_eq!(stats.spend_cap_raise_at, 0);
+        assert_eq!(stats.player, player);
+        assert_eq!(stats.cubes_removed, 4);
+    }
+
+    #[test]
+    fn tips_accrue_on_the_record_until_the_tipped_remover_claims() {
+        let (first, second) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let mut game = zeroed_game();
+        game.tip_bps = 500;
+        assert_eq!(game.tip_for(&first, 1_000).unwrap(), 0);
+        game.last_remover = first;
+        assert_eq!(game.tip_for(&first, 1_000).unwrap(), 0);
+        assert_eq!(game.tip_for(&second, 1_000).unwrap(), 50);
+        game.ephemeral_records = true;
+        assert_eq!(game.tip_for(&second, 1_000).unwrap(), 0);
+
+        let mut record = zeroed_record();
+        assert_eq!(
+            record.take_tip(&first).unwrap_err(),
+            error!(CubeGameError::NoTipOwed)
+        );
+        record.removed_by = second;
+        record.tip_to = first;
+        record.tip_owed = 50;
+        assert_eq!(
+            record.exit_release(&second).unwrap_err(),
+            error!(CubeGameError::TipUnclaimed)
+        );
+        assert_eq!(
+            record.take_tip(&second).unwrap_err(),
+            error!(CubeGameError::TipRecipientMismatch)
+        );
+        assert_eq!(record.take_tip(&first).unwrap(), 50);
+        assert_eq!(record.tip_owed, 0);
+        assert!(record.exit_release(&second).is_ok());
+    }
+
+    #[test]
+    fn staking_rewards_split_in_proportion_to_stake() {
+        let mut pool: StakingPool =
+            StakingPool::deserialize(&mut vec![0u8; StakingPool::INIT_SPACE].as_slice()).unwrap();
+        let stake = |amount: u64| Stake {
+            owner: Pubkey::new_unique(),
+            amount,
+            reward_debt: 0,
+            pending_rewards: 0,
+            bump: 0,
+        };
+        // Nothing staked: the reward is not shared
+        pool.accrue(1_000).unwrap();
+        assert_eq!(pool.reward_per_share, 0);
+
+        let (mut small, mut large) = (stake(100), stake(300));
+        pool.total_staked = 400;
+        pool.accrue(1_000).unwrap();
+        small.settle(&pool).unwrap();
+        large.settle(&pool).unwrap();
+        assert_eq!((small.pending_rewards, large.pending_rewards), (250, 750));
+
+        // Settling again pays nothing twice
+        small.settle(&pool).unwrap();
+        assert_eq!(small.pending_rewards, 250);
+
+        // A later staker earns only from rewards after they joined
+        let mut late = stake(100);
+        late.reset_debt(&pool).unwrap();
+        pool.total_staked = 500;
+        pool.accrue(500).unwrap();
+        late.settle(&pool).unwrap();
+        large.settle(&pool).unwrap();
+        assert_eq!(late.pending_rewards, 100);
+        assert_eq!(large.pending_rewards, 1_050);
+    }
+
+    #[test]
+    fn royalty_split_pays_the_creator_exactly_royalty_bps() {
+        assert_eq!(royalty_split(10_000, 750, 0).unwrap(), (750, 9_250));
+        assert_eq!(royalty_split(10_000, 750, 500).unwrap(), (750, 8_750));
+        assert_eq!(royalty_split(999, 1_000, 0).unwrap(), (99, 900));
+        assert_eq!(royalty_split(10_000, 0, 0).unwrap(), (0, 10_000));
+        assert_eq!(
+            royalty_split(10_000, 6_000, 5_000).unwrap_err(),
+            error!(CubeGameError::InvalidBps)
+        );
+    }
+
+    #[test]
+    fn ledger_buckets_account_for_every_treasury_lamport() {
+        let mut ledger =
+            Ledger::try_deserialize_unchecked(&mut &[0u8; 8 + Ledger::INIT_SPACE][..]).unwrap();
+        // Treasury balance as the sequence moves lamports in and out of it
+        let mut treasury: u64 = 0;
+
+        ledger.record_funding(5_000, false).unwrap();
+        treasury += 5_000;
+        ledger.record_funding(2_000, true).unwrap();
+        // Removal of 1_000: 100 to the dev, 50 burned, 200 escrowed for refunds
+        ledger.record_inflow(1_000, 200, 50, 0).unwrap();
+        ledger.record_to_dev(100).unwrap();
+        treasury += 1_000 - 100 - 50 - 200;
+        // A tip held for the previous remover, then claimed
+        ledger.record_inflow(30, 0, 0, 0).unwrap();
+        treasury += 30;
+        ledger.record_reward(30, 0).unwrap();
+        treasury -= 30;
+        // Staker reward and royalty never touch the treasury
+        ledger.record_shared(40).unwrap();
+        ledger.record_shared(25).unwrap();
+        // A bounty is funded, then paid out
+        ledger.record_escrow(300).unwrap();
+        ledger.record_reward(300, 300).unwrap();
+        // Prize, a refund out of the pool, and an owner withdrawal
+        ledger.record_reward(400, 0).unwrap();
+        treasury -= 400;
+        ledger.record_refund(150, 150).unwrap();
+        ledger.record_withdrawal(1_000).unwrap();
+        treasury -= 1_000;
+        // Refill half of the cold reserve, stake and unstake with a reward
+        ledger.record_refill(1_000);
+        treasury += 1_000;
+        ledger.record_stake(2_000).unwrap();
+        treasury -= 2_000;
+        ledger.record_unstake(2_010).unwrap();
+        treasury += 2_010;
+
+        assert_eq!(ledger.retained(), treasury);
+        assert_eq!(
+            ledger.total_inflow + ledger.total_funded,
+            ledger.total_withdrawn
+                + ledger.total_refunded
+                + ledger.total_in_escrow
+                + ledger.total_in_reserve
+                + ledger.total_staked
+                + ledger.total_rewards
+                + ledger.total_shared
+                + ledger.total_to_dev
+                + ledger.total_to_dao
+                + ledger.total_burned
+                + treasury
+        );
+        assert_eq!(ledger.total_in_escrow, 50);
+        assert_eq!(ledger.total_in_reserve, 1_000);
+        assert_eq!(ledger.total_staked, 0);
+    }
+
+    #[test]
+    fn point_prices_follow_the_peg_and_need_one() {
+        let mut game = zeroed_game();
+        game.price_per_cube = 1_000;
+        game.price_in_points = 5;
+        assert_eq!(
+            game.require_point_peg().unwrap_err(),
+            error!(CubeGameError::PointValueUnset)
+        );
+        assert!(effective_price(&game, None, false, 0).is_err());
+
+        game.point_value_lamports = 100;
+        assert_eq!(effective_price(&game, None, false, 0).unwrap(), 500);
+        game.point_value_lamports = 300;
+        assert_eq!(effective_price(&game, None, false, 0).unwrap(), 1_500);
+        assert_eq!(game.price_in_points, 5);
+
+        game.price_in_points = 0;
+        assert_eq!(effective_price(&game, None, false, 0).unwrap(), 1_000);
+    }
+
+    #[test]
+    fn only_paying_contributors_claim_a_reached_goal() {
+        let mut goal = CommunityGoal::try_deserialize_unchecked(
+            &mut &[0u8; 8 + CommunityGoal::INIT_SPACE][..],
+        )
+        .unwrap();
+        goal.goal_id = 1;
+        goal.target = 3;
+        goal.reward = 100;
+        goal.ends_at = 50;
+        let mut payer = zeroed_stats();
+        let mut free_rider = zeroed_stats();
+        let mut bystander = zeroed_stats();
+
+        assert!(goal.is_running(10));
+        assert!(!goal.count_removal(&mut payer, 500));
+        assert!(!goal.count_removal(&mut free_rider, 0));
+        assert_eq!(
+            goal.claim(&mut payer).unwrap_err(),
+            error!(CubeGameError::GoalNotReached)
+        );
+        assert!(goal.count_removal(&mut payer, 500));
+        assert!(!goal.is_running(10));
+        assert_eq!((goal.progress, goal.participants), (3, 1));
+        assert_eq!(goal.outstanding_rewards().unwrap(), 100);
+
+        for stats in [&mut free_rider, &mut bystander] {
+            assert_eq!(
+                goal.claim(stats).unwrap_err(),
+                error!(CubeGameError::NotGoalContributor)
+            );
+        }
+        goal.claim(&mut payer).unwrap();
+        assert_eq!(goal.outstanding_rewards().unwrap(), 0);
+        assert_eq!(
+            goal.claim(&mut payer).unwrap_err(),
+            error!(CubeGameError::CommunityRewardClaimed)
+        );
+
+        goal.reached = false;
+        assert!(!goal.is_running(50));
+    }
+
+    #[test]
+    fn credits_cover_only_the_price_they_were_bought_at() {
+        let mut credits =
+            Credits::try_deserialize_unchecked(&mut &[0u8; 8 + Credits::INIT_SPACE][..]).unwrap();
+        assert_eq!(
+            credits.spend(100).unwrap_err(),
+            error!(CubeGameError::NoCredits)
+        );
+        credits.add(2, 1_000).unwrap();
+        credits.add(1, 1_501).unwrap();
+
+        // A surge above the average face value is charged
+        assert_eq!(credits.spend(2_000).unwrap(), 833);
+        // A price within it is fully covered
+        assert_eq!(credits.spend(1_000).unwrap(), 0);
+        // The last credit carries the rounding remainder
+        assert_eq!(credits.face_value, 1_167);
+        assert_eq!(credits.spend(1_200).unwrap(), 33);
+        assert_eq!((credits.balance, credits.face_value), (0, 0));
+    }
+
+    #[test]
+    fn daily_reward_needs_an_aged_unfrozen_remover_once_a_day() {
+        let mut game = zeroed_game();
+        game.min_player_age = 3_600;
+        let mut stats = zeroed_stats();
+        stats.registered_at = 90_000;
+        let now = 2 * 86_400 + 100;
+
+        assert_eq!(
+            stats.claim_daily(&game, now).unwrap_err(),
+            error!(CubeGameError::DailyRewardIneligible)
+        );
+        stats.cubes_removed = 1;
+        stats.frozen = true;
+        assert_eq!(
+            stats.claim_daily(&game, now).unwrap_err(),
+            error!(CubeGameError::PlayerFrozen)
+        );
+        stats.frozen = false;
+        assert_eq!(
+            stats.claim_daily(&game, 91_000).unwrap_err(),
+            error!(CubeGameError::PlayerTooNew)
+        );
+
+        stats.claim_daily(&game, now).unwrap();
+        assert_eq!(
+            stats.claim_daily(&game, 3 * 86_400 - 1).unwrap_err(),
+            error!(CubeGameError::AlreadyClaimedToday)
+        );
+        stats.claim_daily(&game, 3 * 86_400).unwrap();
+    }
+
+    #[test]
+    fn onboarding_refund_is_paid_once_and_only_from_a_paid_removal() {
+        let mut mark = zeroed_mark();
+        assert_eq!(mark.onboarding_due(5_000, 4_999), 0);
+        assert_eq!(mark.onboarding_due(5_000, 5_000), 5_000);
+        assert_eq!(mark.onboarding_due(0, 5_000), 0);
+        mark.onboarded = true;
+        assert_eq!(mark.onboarding_due(5_000, 1_000_000), 0);
+    }
+
+    #[test]
+    fn restore_takes_the_removal_back_from_the_original_remover() {
+        let (remover, buyer) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let mut game = zeroed_game();
+        game.total_cubes_removed = 1;
+        game.entrant_weight = 1;
+        let mut record = zeroed_record();
+        record.cube_id = "c1".to_string();
+        record.is_removed = true;
+        record.original_remover = remover;
+        // transfer_cube hands the cube on without moving the removal
+        record.removed_by = buyer;
+        let key = scoped_cube_record_pda("", "c1");
+        let no_accounts: &[AccountInfo] = &[];
+
+        let mut buyer_stats = zeroed_stats();
+        buyer_stats.player = buyer;
+        assert_eq!(
+            restore_cube(
+                &mut game,
+                &mut record,
+                key,
+                &mut buyer_stats,
+                None,
+                &mut no_accounts.iter(),
+                None,
+            )
+            .unwrap_err(),
+            error!(CubeGameError::RemoverStatsMismatch)
+        );
+
+        let mut remover_stats = zeroed_stats();
+        remover_stats.player = remover;
+        remover_stats.cubes_removed = 1;
+        restore_cube(
+            &mut game,
+            &mut record,
+            key,
+            &mut remover_stats,
+            None,
+            &mut no_accounts.iter(),
+            None,
+        )
+        .unwrap();
+        assert_eq!(remover_stats.cubes_removed, 0);
+        assert_eq!((game.total_cubes_removed, game.entrant_weight), (0, 0));
+        assert!(!record.is_removed);
+        assert_eq!(record.credited_remover(), Pubkey::default());
+    }
+
+    #[test]
+    fn region_scoped_seeds_keep_global_seeds_and_separate_kinds() {
+        assert_eq!(region_scoped_seed(b"shares", ""), b"shares".to_vec());
+        assert_eq!(region_scoped_seed(b"like", ""), b"like".to_vec());
+        assert_eq!(cube_record_seed("eu"), b"rc\x02eu".to_vec());
+        assert_eq!(region_scoped_seed(b"shares", "eu"), b"rs\x02eu".to_vec());
+        assert_ne!(
+            region_scoped_seed(b"like", "eu"),
+            region_scoped_seed(b"shares", "eu")
+        );
+    }
+
+    #[test]
+    fn undersized_game_states_need_migration() {
+        let current = 8 + GameState::INIT_SPACE;
+        assert!(is_migrated(current));
+        assert!(is_migrated(current + 100));
+        assert!(!is_migrated(current - 1));
+        assert!(!is_migrated(0));
+    }
+
+    #[test]
+    fn leaderboard_promotion_moves_entries_across_pages() {
+        let mut meta = LeaderboardMeta {
+            page_count: 2,
+            pages: vec![PageSummary::default(); 2],
+            bump: 0,
+        };
+        let mut pages: Vec<LeaderboardPage> = (0..2)
+            .map(|page| LeaderboardPage {
+                page,
+                entries: Vec::new(),
+                bump: 0,
+            })
+            .collect();
+        let players: Vec<Pubkey> = (0..=LEADERBOARD_PAGE_SIZE + 1)
+            .map(|_| Pubkey::new_unique())
+            .collect();
+        // Fill page 1 with scores 100, 99, .., then two players on page 2
+        for (i, player) in players.iter().enumerate() {
+            let score = 100 - i as u64;
+            assert_eq!(
+                rank_on_leaderboard(&mut meta, &mut pages, *player, 0, score).unwrap(),
+                score
+            );
+        }
+        assert_eq!(pages[0].entries.len(), LEADERBOARD_PAGE_SIZE);
+        assert_eq!(pages[1].entries.len(), 2);
+        let climber = players[LEADERBOARD_PAGE_SIZE + 1];
+        let displaced = players[LEADERBOARD_PAGE_SIZE - 1];
+        assert_eq!(pages[1].entries[1].player, climber);
+
+        // The page-2 player climbs to second place on page 1
+        rank_on_leaderboard(&mut meta, &mut pages, climber, 89, 99).unwrap();
+        assert_eq!(pages[0].entries[2].player, climber);
+        assert_eq!(pages[0].entries.len(), LEADERBOARD_PAGE_SIZE);
+        // Page 1's lowest player drops to the top of page 2
+        assert_eq!(pages[1].entries[0].player, displaced);
+        assert_eq!(pages[1].entries.len(), 2);
+        assert!(!pages[1].entries.iter().any(|e| e.player == climber));
+        assert_eq!(meta.pages[0], pages[0].summary());
+        assert_eq!(meta.pages[1], pages[1].summary());
+        assert!(meta.pages[0].min_score >= meta.pages[1].max_score);
+
+        // Page 2 alone cannot take a climb that reaches page 1
+        let mut page_two = vec![pages[1].clone()];
+        assert_eq!(
+            rank_on_leaderboard(&mut meta, &mut page_two, displaced, 91, 200).unwrap_err(),
+            error!(CubeGameError::LeaderboardPageMissing)
+        );
+    }
+
+    #[test]
+    fn revealed_attributes_come_from_the_slot_hash_not_the_cube_id() {
+        let cube_id = "4:5:6";
+        let (rarity, color) = reveal_attributes(&[7; 32], cube_id);
+        assert_eq!(reveal_attributes(&[7; 32], cube_id), (rarity, color));
+        assert!(color <= 0x00FF_FFFF);
+        assert!([1, 3, 10, 25].contains(&rarity));
+        // A different slot hash rolls a different colour for the same cube
+        assert_ne!(reveal_attributes(&[8; 32], cube_id).1, color);
+
+        let mut record = zeroed_record();
+        let mut game = zeroed_game();
+        game.palette_seed = 9;
+        assert_eq!(record.ranked_score(cube_id), cube_score(cube_id));
+        assert_eq!(
+            record.removal_color(&game, cube_id, 0),
+            cube_color(cube_id, 9)
+        );
+        record.revealed = true;
+        record.rarity = rarity;
+        record.revealed_color = color;
+        assert_eq!(record.ranked_score(cube_id), rarity);
+        assert_eq!(record.removal_color(&game, cube_id, 0x123456), color);
+    }
+
+    #[test]
+    fn action_nonces_replay_only_on_the_cube_they_removed() {
+        let mut stats = zeroed_stats();
+        assert!(!stats.is_replay("1:2:3", 7));
+        stats.last_action = action_key("1:2:3", 7);
+        assert!(stats.is_replay("1:2:3", 7));
+        assert!(!stats.is_replay("1:2:3", 8));
+        assert!(!stats.is_replay("1:2:4", 7));
+        // Nonce 0 opts out of replay protection
+        stats.last_action = action_key("1:2:3", 0);
+        assert!(!stats.is_replay("1:2:3", 0));
+    }
+
+    #[test]
+    fn split_payments_take_only_what_the_price_needs() {
+        let mut game = zeroed_game();
+        // 0.3 lamports per token
+        game.split_rate = SPLIT_RATE_SCALE * 3 / 10;
+
+        // 334 tokens are the fewest worth the price, at 100 lamports
+        let split = game.settle_split(100, 5, 1_000).unwrap();
+        assert_eq!(
+            split,
+            SplitSettlement {
+                tokens: 334,
+                sol_due: 0,
+                dust: 5
+            }
+        );
+
+        // Tokens worth less than the price leave SOL to cover; the value
+        // rounds down, so an extra lamport offered is dust
+        let split = game.settle_split(100, 71, 100).unwrap();
+        assert_eq!(
+            split,
+            SplitSettlement {
+                tokens: 100,
+                sol_due: 70,
+                dust: 1
+            }
+        );
+        assert_eq!(
+            game.settle_split(100, 69, 100).unwrap_err(),
+            error!(CubeGameError::SplitUnderpaid)
+        );
+
+        // A free removal takes no tokens at all
+        let split = game.settle_split(0, 0, 1_000).unwrap();
+        assert_eq!(
+            split,
+            SplitSettlement {
+                tokens: 0,
+                sol_due: 0,
+                dust: 0
+            }
+        );
+    }
+
+    #[test]
+    fn overpaid_legs_are_owed_back() {
+        assert_eq!(overpayment(100, &[10, 5, 0, 85]).unwrap(), 0);
+        assert_eq!(overpayment(100, &[10, 5, 0, 87]).unwrap(), 2);
+        assert_eq!(overpayment(100, &[60]).unwrap(), 0);
+        assert_eq!(
+            overpayment(0, &[u64::MAX, 1]).unwrap_err(),
+            error!(CubeGameError::MathOverflow)
+        );
+    }
+
+    #[test]
+    fn removal_tickets_only_verify_for_their_own_game() {
+        let signer = Pubkey::new_unique();
+        let (game, player) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let ticket = RemovalTicket::new(game, player, "1:2:3", 100);
+        assert_eq!(ticket.program_id, crate::ID);
+        let message = ticket.try_to_vec().unwrap();
+        let data = ed25519_data(&signer, &[7u8; 64], &message);
+        assert!(check_ed25519_data(&data, &signer, &message, None).is_ok());
+
+        let elsewhere = RemovalTicket::new(Pubkey::new_unique(), player, "1:2:3", 100);
+        let forked = RemovalTicket {
+            program_id: Pubkey::new_unique(),
+            ..ticket
+        };
+        for other in [elsewhere, forked] {
+            assert_eq!(
+                check_ed25519_data(&data, &signer, &other.try_to_vec().unwrap(), None).unwrap_err(),
+                error!(CubeGameError::InvalidSignature)
+            );
+        }
+    }
+
+    #[test]
+    fn members_pay_the_discounted_price_and_others_pay_in_full() {
+        let mut game = zeroed_game();
+        game.member_discount_bps = 2_500;
+        assert_eq!(game.member_price(1_000, true).unwrap(), 750);
+        assert_eq!(game.member_price(1_000, false).unwrap(), 1_000);
+
+        // Without the NFT accounts the member path is refused outright
+        assert_eq!(
+            verify_collection_nft(&Pubkey::new_unique(), None, None, &Pubkey::new_unique())
+                .unwrap_err(),
+            error!(CubeGameError::MissingRequiredNft)
+        );
+    }
+
+    #[test]
+    fn reveals_must_match_the_commitment_and_wait_out_the_delay() {
+        let player = Pubkey::new_unique();
+        let salt = [3u8; 32];
+        let commitment = RemovalCommitment {
+            player,
+            commitment: removal_commitment_hash(&player, "1:2:3", &salt),
+            commit_slot: 100,
+            bump: 0,
+        };
+        assert!(commitment
+            .check_reveal(&player, "1:2:3", &salt, 105, 5)
+            .is_ok());
+
+        let mismatch = error!(CubeGameError::CommitmentMismatch);
+        assert_eq!(
+            commitment
+                .check_reveal(&player, "1:2:4", &salt, 105, 5)
+                .unwrap_err(),
+            mismatch
+        );
+        assert_eq!(
+            commitment
+                .check_reveal(&player, "1:2:3", &[4u8; 32], 105, 5)
+                .unwrap_err(),
+            mismatch
+        );
+        assert_eq!(
+            commitment
+                .check_reveal(&Pubkey::new_unique(), "1:2:3", &salt, 105, 5)
+                .unwrap_err(),
+            mismatch
+        );
+        for slot in [100, 104] {
+            assert_eq!(
+                commitment
+                    .check_reveal(&player, "1:2:3", &salt, slot, 5)
+                    .unwrap_err(),
+                error!(CubeGameError::RevealTooSoon)
+            );
+        }
+    }
+
+    #[test]
+    fn expected_totals_must_match_the_removals_so_far() {
+        let mut game = zeroed_game();
+        game.total_cubes_removed = 41;
+        assert!(game.require_expected_total(None).is_ok());
+        assert!(game.require_expected_total(Some(41)).is_ok());
+        for stale in [40, 42] {
+            assert_eq!(
+                game.require_expected_total(Some(stale)).unwrap_err(),
+                error!(CubeGameError::ConcurrencyConflict)
+            );
+        }
+    }
+
+    #[test]
+    fn funding_the_treasury_to_rent_exemption_clears_the_warning() {
+        let minimum = Rent::default().minimum_balance(0);
+        let warning = treasury_unfunded(minimum / 2, minimum).unwrap();
+        assert_eq!((warning.balance, warning.minimum), (minimum / 2, minimum));
+        assert!(treasury_unfunded(minimum - 1, minimum).is_some());
+
+        // Funding the shortfall reaches the minimum and the warning stops
+        let funded = minimum / 2 + (minimum - minimum / 2);
+        assert!(treasury_unfunded(funded, minimum).is_none());
+        assert!(treasury_unfunded(funded + 1, minimum).is_none());
+    }
+
+    #[test]
+    fn founder_badges_stay_with_the_wallet_after_its_stats_close() {
+        let mut game = zeroed_game();
+        game.founder_cap = 2;
+        let mut marks = [zeroed_mark(), zeroed_mark(), zeroed_mark()];
+        let mut founders = Vec::new();
+        for mark in marks.iter_mut() {
+            let mut stats = zeroed_stats();
+            assert!(stats.register(&mut game, Some(mark)).unwrap());
+            founders.push(stats.is_founder);
+        }
+        assert_eq!(founders, [true, true, false]);
+        assert_eq!(game.total_players, 3);
+
+        // The first founder exits and comes back with fresh stats: same
+        // index, badge kept, and not counted as a new player
+        let mut reopened = zeroed_stats();
+        assert!(!reopened.register(&mut game, Some(&mut marks[0])).unwrap());
+        assert_eq!((reopened.player_index, reopened.is_founder), (1, true));
+        assert_eq!(game.total_players, 3);
+        // Registered stats need no mark
+        assert!(!reopened.register(&mut game, None).unwrap());
+
+        assert_eq!(
+            zeroed_stats().register(&mut game, None).unwrap_err(),
+            error!(CubeGameError::WalletMarkRequired)
+        );
+    }
+
+    #[test]
+    fn payout_keys_and_the_treasury_are_checked() {
+        let authority = Pubkey::new_unique();
+        let invalid = error!(CubeGameError::InvalidConfiguration);
+        assert!(require_payout_key(&Pubkey::new_unique(), &authority).is_ok());
+        assert_eq!(
+            require_payout_key(&authority, &authority).unwrap_err(),
+            invalid
+        );
+        assert_eq!(
+            require_payout_key(&treasury_pda(), &authority).unwrap_err(),
+            invalid
+        );
+
+        let system = anchor_lang::system_program::ID;
+        assert!(require_system_treasury(&system, 0).is_ok());
+        for (owner, len) in [(crate::ID, 0), (system, 8)] {
+            assert_eq!(
+                require_system_treasury(&owner, len).unwrap_err(),
+                error!(CubeGameError::InvalidTreasury)
+            );
+        }
+    }
+
+    #[test]
+    fn analytics_average_counts_priced_removals_and_each_wallet_once() {
+        let mut analytics =
+            Analytics::try_deserialize_unchecked(&mut &[0u8; 8 + Analytics::INIT_SPACE][..])
+                .unwrap();
+        assert_eq!(analytics.average_price(), 0);
+
+        let mut game = zeroed_game();
+        let mut mark = zeroed_mark();
+        let mut stats = zeroed_stats();
+        let new = stats.register(&mut game, Some(&mut mark)).unwrap();
+        analytics.record_removal(100, 100, new).unwrap();
+        let new = stats.register(&mut game, Some(&mut mark)).unwrap();
+        analytics.record_removal(300, 300, new).unwrap();
+        assert_eq!(analytics.average_price(), 200);
+
+        // The same wallet back with fresh stats is not a new player
+        let mut reopened = zeroed_stats();
+        let new = reopened.register(&mut game, Some(&mut mark)).unwrap();
+        analytics.record_removal(50, 50, new).unwrap();
+        assert_eq!(analytics.unique_players, 1);
+        assert_eq!((analytics.price_sum, analytics.price_count), (450, 3));
+        assert_eq!(analytics.average_price(), 150);
+    }
+
+    #[test]
+    fn queue_deposits_return_when_served_or_leaving_and_forfeit_on_expiry() {
+        let mut queue =
+            RemovalQueue::try_deserialize_unchecked(&mut &[0u8; 8 + RemovalQueue::INIT_SPACE][..])
+                .unwrap();
+        let (a, b, c) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        queue.join(a, 10, 100).unwrap();
+        queue.join(b, 10, 105).unwrap();
+        queue.join(c, 10, 106).unwrap();
+        assert_eq!(
+            queue.join(a, 10, 107).unwrap_err(),
+            error!(CubeGameError::AlreadyQueued)
+        );
+
+        // Second in line is turned away while the first's turn lasts
+        assert_eq!(
+            queue.serve(&b, 110).unwrap_err(),
+            error!(CubeGameError::NotFrontOfQueue)
+        );
+        // The first's turn runs out and forfeits; the second is served
+        queue.expire_turns(130, 30);
+        assert_eq!(queue.forfeited, 10);
+        assert_eq!(queue.front_since, 130);
+        assert_eq!(queue.serve(&b, 131).unwrap(), 10);
+
+        // The last player leaves and takes their deposit back
+        assert_eq!(queue.leave(&c, 132).unwrap(), 10);
+        assert!(queue.entries.is_empty());
+        assert_eq!(
+            queue.leave(&c, 133).unwrap_err(),
+            error!(CubeGameError::NotQueued)
+        );
+        assert_eq!(queue.serve(&a, 134).unwrap(), 0);
+    }
+
+    #[test]
+    fn player_history_wraps_over_its_oldest_entry() {
+        let mut history = PlayerHistory::try_deserialize_unchecked(
+            &mut &[0u8; 8 + PlayerHistory::INIT_SPACE][..],
+        )
+        .unwrap();
+        let mut stats = zeroed_stats();
+        // Without history enabled nothing is needed or recorded
+        record_history(&stats, None, "0", 1, 0).unwrap();
+
+        stats.history_enabled = true;
+        assert_eq!(
+            record_history(&stats, None, "0", 1, 0).unwrap_err(),
+            error!(CubeGameError::PlayerHistoryRequired)
+        );
+        for i in 0..PLAYER_HISTORY_LEN as i64 + 2 {
+            record_history(&stats, Some(&mut history), &i.to_string(), i as u64, i).unwrap();
+        }
+        assert_eq!(history.entries.len(), PLAYER_HISTORY_LEN);
+        // The two newest overwrote the two oldest; head holds the newest
+        assert_eq!(history.head, 1);
+        assert_eq!(history.entries[1].timestamp, PLAYER_HISTORY_LEN as i64 + 1);
+        assert_eq!(history.entries[0].timestamp, PLAYER_HISTORY_LEN as i64);
+        assert_eq!(history.entries[2].timestamp, 2);
+    }
+
+    #[test]
+    fn only_a_first_level_cpi_names_its_caller() {
+        use anchor_lang::solana_program::instruction::TRANSACTION_LEVEL_STACK_HEIGHT;
+
+        let top = Pubkey::new_unique();
+        assert_eq!(
+            direct_cpi_caller(TRANSACTION_LEVEL_STACK_HEIGHT + 1, top),
+            Some(top)
+        );
+        // The top-level program called something else, which called us
+        assert_eq!(
+            direct_cpi_caller(TRANSACTION_LEVEL_STACK_HEIGHT + 2, top),
+            None
+        );
+        assert_eq!(direct_cpi_caller(TRANSACTION_LEVEL_STACK_HEIGHT, top), None);
+    }
+
+    #[test]
+    fn real_removals_need_an_unfrozen_aged_player_out_of_practice() {
+        let mut game = zeroed_game();
+        game.min_player_age = 60;
+        let mut stats = zeroed_stats();
+        stats.registered_at = 100;
+        assert!(stats.require_can_remove(&game, 160).is_ok());
+        assert_eq!(
+            stats.require_can_remove(&game, 159).unwrap_err(),
+            error!(CubeGameError::PlayerTooNew)
+        );
+
+        stats.practice_mode = true;
+        assert_eq!(
+            stats.require_can_remove(&game, 160).unwrap_err(),
+            error!(CubeGameError::PracticeModeOn)
+        );
+        stats.frozen = true;
+        assert_eq!(
+            stats.require_can_remove(&game, 160).unwrap_err(),
+            error!(CubeGameError::PlayerFrozen)
+        );
+    }
+
+    #[test]
+    fn split_burn_takes_its_share_of_the_charged_tokens_only() {
+        let mut game = zeroed_game();
+        game.split_rate = SPLIT_RATE_SCALE;
+        let split = game.settle_split(100, 0, 1_000).unwrap();
+        assert_eq!(split.tokens, 100);
+        assert_eq!(split.burn_split(0).unwrap(), (0, 100));
+        assert_eq!(split.burn_split(2_500).unwrap(), (25, 75));
+        assert_eq!(split.burn_split(10_000).unwrap(), (100, 0));
+    }
+
+    fn pyth_price_data(price: i64, expo: i32, publish_time: i64, status: u32) -> Vec<u8> {
+        let mut data = vec![0u8; 240];
+        data[0..4].copy_from_slice(&PYTH_MAGIC.to_le_bytes());
+        data[8..12].copy_from_slice(&PYTH_PRICE_ACCOUNT.to_le_bytes());
+        data[20..24].copy_from_slice(&expo.to_le_bytes());
+        data[96..104].copy_from_slice(&publish_time.to_le_bytes());
+        data[208..216].copy_from_slice(&price.to_le_bytes());
+        data[224..228].copy_from_slice(&status.to_le_bytes());
+        data
+    }
+
+    #[test]
+    fn usd_prices_convert_only_fresh_positive_quotes() {
+        // $2.50 a token at expo -8, a $5 cube, a 6-decimal mint
+        let fresh = pyth_price_data(250_000_000, -8, 1_000, PYTH_STATUS_TRADING);
+        let quote = OraclePrice::from_pyth(&fresh).unwrap();
+        assert_eq!(
+            quote.tokens_for_usd(5_000_000, 6, 1_060, 60).unwrap(),
+            2_000_000
+        );
+        // A millionth of a dollar still costs a whole base unit
+        assert_eq!(quote.tokens_for_usd(1, 0, 1_000, 60).unwrap(), 1);
+
+        assert_eq!(
+            quote.tokens_for_usd(5_000_000, 6, 1_061, 60).unwrap_err(),
+            error!(CubeGameError::StalePrice)
+        );
+        for price in [0, -250_000_000] {
+            let quote = OraclePrice::from_pyth(&pyth_price_data(price, -8, 1_000, 1)).unwrap();
+            assert_eq!(
+                quote.tokens_for_usd(5_000_000, 6, 1_000, 60).unwrap_err(),
+                error!(CubeGameError::InvalidOraclePrice)
+            );
+        }
+        assert_eq!(
+            OraclePrice::from_pyth(&pyth_price_data(250_000_000, -8, 1_000, 0)).unwrap_err(),
+            error!(CubeGameError::InvalidOraclePrice)
+        );
+        let mut not_pyth = fresh.clone();
+        not_pyth[0] = 0;
+        assert_eq!(
+            OraclePrice::from_pyth(&not_pyth).unwrap_err(),
+            error!(CubeGameError::InvalidPriceFeed)
+        );
+        assert_eq!(
+            OraclePrice::from_pyth(&fresh[..200]).unwrap_err(),
+            error!(CubeGameError::InvalidPriceFeed)
+        );
+    }
+
+    #[test]
+    fn committed_boards_materialize_only_proven_cubes() {
+        use anchor_lang::solana_program::keccak::hashv;
+
+        let leaf = |id: &str| hashv(&[&[0x00], id.as_bytes()]).0;
+        let (a, b) = (leaf("1:1:1"), leaf("2:2:2"));
+        let (lo, hi) = if a <= b { (a, b) } else { (b, a) };
+        let mut game = zeroed_game();
+        game.board_root = hashv(&[&[0x01], &lo, &hi]).0;
+        game.board_size = 2;
+
+        let record = zeroed_record();
+        game.materialize(&record, "1:1:1", &[b]).unwrap();
+        assert_eq!(game.active_cube_records, 1);
+        for (id, proof) in [("1:1:1", [a]), ("3:3:3", [b])] {
+            assert_eq!(
+                game.materialize(&record, id, &proof).unwrap_err(),
+                error!(CubeGameError::NotOnBoard)
+            );
+        }
+        assert_eq!(game.active_cube_records, 1);
+
+        game.total_cubes_removed = 1;
+        assert_eq!(game.completion_bps(), 5_000);
+        assert!(game.require_board_open().is_ok());
+        game.total_cubes_removed = 2;
+        assert!(game.board_cleared() && game.is_over(0));
+        assert_eq!(
+            game.require_board_open().unwrap_err(),
+            error!(CubeGameError::BoardComplete)
+        );
+        game.max_cubes = 1;
+        assert_eq!(game.cube_limit(), 1);
+    }
+
+    #[test]
+    fn removal_quotes_charge_rent_only_for_accounts_the_removal_creates() {
+        let rent = Rent::default();
+        let record_rent = rent.minimum_balance(8 + CubeRecord::INIT_SPACE);
+        let stats_rent = rent.minimum_balance(8 + PlayerStats::INIT_SPACE);
+        // A first-time player on an untouched cube pays for both
+        assert_eq!(
+            creation_rent(&rent, false, false),
+            (record_rent, stats_rent)
+        );
+        assert_eq!(creation_rent(&rent, true, false), (0, stats_rent));
+        assert_eq!(creation_rent(&rent, false, true), (record_rent, 0));
+        // A returning player on a known cube pays the price alone
+        assert_eq!(creation_rent(&rent, true, true), (0, 0));
+
+        // A region's curve price replaces the base price
+        let mut game = zeroed_game();
+        game.price_per_cube = 100;
+        assert_eq!(effective_price(&game, None, false, 0).unwrap(), 100);
+        assert_eq!(effective_price(&game, Some(250), false, 0).unwrap(), 250);
+        assert!(matches!(
+            load_priced_region(&game, None, ""),
+            Ok((None, None))
+        ));
+        game.region_pricing = true;
+        assert_eq!(
+            load_priced_region(&game, None, "north").err(),
+            Some(error!(CubeGameError::RegionRequired))
+        );
+    }
+
+    #[test]
+    fn ranked_leaderboards_order_by_score_not_removals() {
+        let mut grinder = zeroed_stats();
+        grinder.player = Pubkey::new_unique();
+        grinder.cubes_removed = 5;
+        grinder.score = 5;
+        let mut collector = zeroed_stats();
+        collector.player = Pubkey::new_unique();
+        collector.cubes_removed = 2;
+        collector.score = 2 * rarity_score(0);
+        assert!(collector.score > grinder.score);
+
+        for (ranked, leader) in [(true, collector.player), (false, grinder.player)] {
+            let mut meta = LeaderboardMeta {
+                page_count: 1,
+                pages: vec![PageSummary::default()],
+                bump: 0,
+            };
+            let mut pages = vec![LeaderboardPage {
+                page: 0,
+                entries: Vec::new(),
+                bump: 0,
+            }];
+            for stats in [&grinder, &collector] {
+                let score = stats.leaderboard_score(ranked);
+                assert_eq!(
+                    rank_on_leaderboard(&mut meta, &mut pages, stats.player, 0, score).unwrap(),
+                    score
+                );
+            }
+            assert_eq!(pages[0].entries.len(), 2);
+            assert_eq!(pages[0].entries[0].player, leader);
+            assert!(pages[0].entries[0].score > pages[0].entries[1].score);
+            assert_eq!(meta.pages[0], pages[0].summary());
+        }
+    }
+
+    #[test]
+    fn message_wall_wraps_over_its_oldest_message() {
+        let mut wall =
+            MessageWall::try_deserialize_unchecked(&mut &[0u8; 8 + MessageWall::INIT_SPACE][..])
+                .unwrap();
+        let player = Pubkey::new_unique();
+        for i in 0..MESSAGE_WALL_LEN as i64 + 2 {
+            let message = format!("gg {i}");
+            validate_message(&message).unwrap();
+            wall.push(WallEntry {
+                player,
+                message,
+                timestamp: i,
+            });
+            if i == 0 {
+                assert_eq!((wall.head, wall.entries.len()), (0, 1));
+            }
+        }
+        assert_eq!(wall.entries.len(), MESSAGE_WALL_LEN);
+        // The two newest overwrote the two oldest; head holds the newest
+        assert_eq!(wall.head, 1);
+        assert_eq!(
+            wall.entries[1].message,
+            format!("gg {}", MESSAGE_WALL_LEN + 1)
+        );
+        assert_eq!(wall.entries[0].timestamp, MESSAGE_WALL_LEN as i64);
+        assert_eq!(wall.entries[2].message, "gg 2");
+    }
+
+    #[test]
+    fn completion_bps_tracks_progress_to_the_final_cube() {
+        let mut game = zeroed_game();
+        game.total_cubes_removed = 5;
+        // An unbounded board reports no progress
+        assert_eq!(game.completion_bps(), 0);
+
+        game.max_cubes = 3;
+        for (removed, bps) in [(0, 0), (1, 3_333), (2, 6_666), (3, 10_000), (4, 10_000)] {
+            game.total_cubes_removed = removed;
+            assert_eq!(game.completion_bps(), bps);
+        }
+        game.max_cubes = 1_000_000;
+        game.total_cubes_removed = 999_999;
+        assert_eq!(game.completion_bps(), 9_999);
+        game.total_cubes_removed = 1_000_000;
+        assert_eq!(game.completion_bps(), 10_000);
+    }
+
+    #[test]
+    fn check_cubes_reports_touched_records_and_skips_the_rest() {
+        let record_data = |cube_id: &str, is_removed: bool| {
+            let mut record = zeroed_record();
+            record.cube_id = cube_id.to_string();
+            record.is_removed = is_removed;
+            let mut data = Vec::new();
+            record.try_serialize(&mut data).unwrap();
+            data
+        };
+        let keys: Vec<Pubkey> = (0..4).map(|_| Pubkey::new_unique()).collect();
+        let mut lamports = [0u64; 4];
+        let mut data = [
+            record_data("1:1:1", true),
+            record_data("2:2:2", false),
+            record_data("", false),
+            record_data("3:3:3", true),
+        ];
+        let foreign = Pubkey::new_unique();
+        let owners = [crate::ID, crate::ID, crate::ID, foreign];
+        let infos: Vec<AccountInfo> = keys
+            .iter()
+            .zip(lamports.iter_mut())
+            .zip(data.iter_mut())
+            .zip(owners.iter())
+            .map(|(((key, lamports), data), owner)| {
+                AccountInfo::new(key, false, false, lamports, data, owner, false, 0)
+            })
+            .collect();
+
+        let statuses: Vec<(String, bool)> = infos
+            .iter()
+            .filter_map(cube_status)
+            .map(|status| (status.cube_id, status.is_removed))
+            .collect();
+        // The untouched record and the account owned elsewhere are omitted
+        assert_eq!(
+            statuses,
+            vec![("1:1:1".to_string(), true), ("2:2:2".to_string(), false)]
+        );
+    }
+
+    #[test]
+    fn cube_ids_must_carry_the_required_prefix() {
+        let mut game = zeroed_game();
+        // A blank prefix admits any id
+        assert!(game.validate_cube_id("1:2:3").is_ok());
+
+        game.required_prefix = "s2-".to_string();
+        assert!(game.validate_cube_id("s2-1:2:3").is_ok());
+        for cube_id in ["1:2:3", "s1-1:2:3", "S2-1:2:3", "s2"] {
+            assert_eq!(
+                game.validate_cube_id(cube_id).unwrap_err(),
+                error!(CubeGameError::InvalidCubeId)
+            );
+        }
+    }
+
+    #[test]
+    fn activity_feed_keeps_the_newest_action_at_head() {
+        let mut feed =
+            ActivityFeed::try_deserialize_unchecked(&mut &[0u8; 8 + ActivityFeed::INIT_SPACE][..])
+                .unwrap();
+        let actor = Pubkey::new_unique();
+        let kinds = [ACTIVITY_REMOVAL, ACTIVITY_RESTORE, ACTIVITY_TRANSFER];
+        for i in 0..ACTIVITY_FEED_LEN as i64 * 2 + 3 {
+            let kind = kinds[i as usize % kinds.len()];
+            feed.push(ActivityEntry::new(kind, actor, &i.to_string(), i));
+            let newest = &feed.entries[feed.head as usize];
+            assert_eq!((newest.timestamp, newest.action_type), (i, kind));
+        }
+        assert_eq!(feed.entries.len(), ACTIVITY_FEED_LEN);
+        // Twice round the ring plus three: head sits at index 2
+        assert_eq!(feed.head, 2);
+        assert_eq!(feed.entries[3].timestamp, ACTIVITY_FEED_LEN as i64 + 3);
+
+        let entry = ActivityEntry::new(ACTIVITY_REMOVAL, actor, "1234567890:1234567890", 0);
+        assert_eq!(&entry.cube_id, b"1234567890:12345");
+        assert_eq!(
+            &ActivityEntry::new(0, actor, "1:2", 0).cube_id[..4],
+            b"1:2\0"
+        );
+    }
+
+    #[test]
+    fn late_removals_extend_the_game_until_the_cap() {
+        let mut game = zeroed_game();
+        game.ends_at = 1_000;
+        game.anti_snipe_window = 60;
+        game.anti_snipe_extension = 30;
+        game.max_extensions = 2;
+
+        // Outside the window nothing moves
+        game.require_open_and_extend(900).unwrap();
+        assert_eq!((game.ends_at, game.extensions_used), (1_000, 0));
+
+        game.require_open_and_extend(950).unwrap();
+        assert_eq!((game.ends_at, game.extensions_used), (1_030, 1));
+        game.require_open_and_extend(1_020).unwrap();
+        assert_eq!((game.ends_at, game.extensions_used), (1_060, 2));
+        // The cap is reached: the removal lands but the end stays put
+        game.require_open_and_extend(1_059).unwrap();
+        assert_eq!((game.ends_at, game.extensions_used), (1_060, 2));
+        assert_eq!(
+            game.require_open_and_extend(1_060).unwrap_err(),
+            error!(CubeGameError::GameEnded)
+        );
+    }
+
+    #[test]
+    fn gated_removals_need_a_verified_nft_from_the_collection() {
+        use anchor_lang::solana_program::program_pack::Pack;
+        use anchor_spl::metadata::mpl_token_metadata::{accounts::Metadata, types};
+
+        let owner = Pubkey::new_unique();
+        let collection = Pubkey::new_unique();
+        let mint = Pubkey::new_unique();
+        let mut token_data = vec![0u8; anchor_spl::token::spl_token::state::Account::LEN];
+        anchor_spl::token::spl_token::state::Account {
+            mint,
+            owner,
+            amount: 1,
+            state: anchor_spl::token::spl_token::state::AccountState::Initialized,
+            ..Default::default()
+        }
+        .pack_into_slice(&mut token_data);
+        let metadata_data = |key: Pubkey, verified: bool| {
+            Metadata {
+                key: types::Key::MetadataV1,
+                update_authority: Pubkey::new_unique(),
+                mint,
+                name: String::new(),
+                symbol: String::new(),
+                uri: String::new(),
+                seller_fee_basis_points: 0,
+                creators: None,
+                primary_sale_happened: false,
+                is_mutable: false,
+                edition_nonce: None,
+                token_standard: None,
+                collection: Some(types::Collection { verified, key }),
+                uses: None,
+                collection_details: None,
+                programmable_config: None,
+            }
+            .try_to_vec()
+            .unwrap()
+        };
+
+        let token_key = Pubkey::new_unique();
+        let token_program = anchor_spl::token::ID;
+        let mut token_lamports = 0;
+        let token_info = AccountInfo::new(
+            &token_key,
+            false,
+            false,
+            &mut token_lamports,
+            &mut token_data,
+            &token_program,
+            false,
+            0,
+        );
+        let token_account = Account::<TokenAccount>::try_from(&token_info).unwrap();
+        let (metadata_key, _) = Metadata::find_pda(&mint);
+        let metadata_program = anchor_spl::metadata::ID;
+        let cases = [
+            (collection, true, Ok(())),
+            (
+                Pubkey::new_unique(),
+                true,
+                err!(CubeGameError::MissingRequiredNft),
+            ),
+            (collection, false, err!(CubeGameError::MissingRequiredNft)),
+        ];
+        for (key, verified, expected) in cases {
+            let mut data = metadata_data(key, verified);
+            let mut lamports = 0;
+            let info = AccountInfo::new(
+                &metadata_key,
+                false,
+                false,
+                &mut lamports,
+                &mut data,
+                &metadata_program,
+                false,
+                0,
+            );
+            let metadata = Account::<MetadataAccount>::try_from(&info).unwrap();
+            assert_eq!(
+                verify_collection_nft(&owner, Some(&token_account), Some(&metadata), &collection),
+                expected
+            );
+            // Someone else's token account proves nothing
+            assert_eq!(
+                verify_collection_nft(
+                    &Pubkey::new_unique(),
+                    Some(&token_account),
+                    Some(&metadata),
+                    &collection
+                )
+                .unwrap_err(),
+                error!(CubeGameError::MissingRequiredNft)
+            );
+        }
+        assert_eq!(
+            verify_collection_nft(&owner, Some(&token_account), None, &collection).unwrap_err(),
+            error!(CubeGameError::MissingRequiredNft)
+        );
+    }
+
+    #[test]
+    fn refund_reserves_accumulate_and_refunds_draw_on_them_first() {
+        let mut game = zeroed_game();
+        game.refund_reserve_bps = 1_000;
+        game.insurance_bps = 500;
+        let mut pool =
+            RefundPool::try_deserialize_unchecked(&mut &[0u8; 8 + RefundPool::INIT_SPACE][..])
+                .unwrap();
+        for amount in [1_000, 2_000, 999] {
+            let shares = game.payment_shares(amount).unwrap();
+            assert_eq!(
+                shares.reserve
+                    + shares.insurance
+                    + shares.burn
+                    + shares.dao_tax
+                    + shares.to_treasury,
+                amount
+            );
+            pool.total_reserved += shares.reserve;
+        }
+        // 10% of each payment, rounded down, with the remainder banked
+        assert_eq!(pool.total_reserved, 100 + 200 + 99);
+        assert_eq!(
+            game.payment_shares(999).unwrap(),
+            PaymentShares {
+                reserve: 99,
+                insurance: 49,
+                burn: 0,
+                dao_tax: 0,
+                to_treasury: 851,
+            }
+        );
+
+        // The pool pays what it can and the treasury only tops it up
+        assert_eq!(refund_sources(150, 399), (150, 0));
+        assert_eq!(refund_sources(500, 399), (399, 101));
+        assert_eq!(refund_sources(500, 0), (0, 500));
+    }
+
+    #[test]
+    fn quadratic_prices_follow_the_curve() {
+        let mut game = zeroed_game();
+        game.price_per_cube = 500;
+        game.quadratic_coeff = 7;
+        for (removed, price) in [(0, 500), (1, 507), (10, 1_200)] {
+            game.total_cubes_removed = removed;
+            assert_eq!(effective_price(&game, None, false, 0).unwrap(), price);
+        }
+        // The term overflows long before the count does
+        game.total_cubes_removed = 1 << 31;
+        assert_eq!(
+            effective_price(&game, None, false, 0).unwrap_err(),
+            error!(CubeGameError::MathOverflow)
+        );
+    }
+
+    #[test]
+    fn memos_round_trip_through_the_cube_record() {
+        assert!(validate_memo("").is_ok());
+        assert_eq!(
+            validate_memo(&"m".repeat(MAX_MEMO_LEN + 1)).unwrap_err(),
+            error!(CubeGameError::MemoTooLong)
+        );
+        for memo in [
+            String::new(),
+            "order-42".to_string(),
+            "m".repeat(MAX_MEMO_LEN),
+        ] {
+            validate_memo(&memo).unwrap();
+            let mut record = zeroed_record();
+            record.cube_id = "1:2:3".to_string();
+            record.memo = memo.clone();
+            let mut data = Vec::new();
+            record.try_serialize(&mut data).unwrap();
+            assert!(data.len() <= 8 + CubeRecord::INIT_SPACE);
+            let stored = CubeRecord::try_deserialize(&mut data.as_slice()).unwrap();
+            assert_eq!(stored.memo, memo);
+        }
+    }
+
+    #[test]
+    fn staking_moves_spare_treasury_lamports_into_a_delegated_stake_account() {
+        use anchor_lang::solana_program::program_utils::limited_deserialize;
+        use anchor_lang::solana_program::stake::instruction::StakeInstruction;
+        use anchor_lang::solana_program::system_instruction::SystemInstruction;
+
+        let mut game = zeroed_game();
+        game.stake_liquid_reserve = 300;
+        game.require_stakeable(700, 1_000).unwrap();
+        assert_eq!(
+            game.require_stakeable(701, 1_000).unwrap_err(),
+            error!(CubeGameError::InsufficientLiquidity)
+        );
+        game.refund_all_mode = true;
+        game.paid_liability = 400;
+        assert_eq!(
+            game.require_stakeable(700, 1_000).unwrap_err(),
+            error!(CubeGameError::RefundLiabilityOutstanding)
+        );
+
+        let (treasury, stake_account, vote) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let ixs = stake_instructions(&treasury, &stake_account, &vote, 700);
+        assert_eq!(ixs.len(), 3);
+        // The treasury funds the new stake account with the staked lamports
+        match limited_deserialize(&ixs[0].data, 1_024).unwrap() {
+            SystemInstruction::CreateAccount {
+                lamports, owner, ..
+            } => assert_eq!((lamports, owner), (700, stake::program::ID)),
+            other => panic!("unexpected {other:?}"),
+        }
+        assert_eq!(ixs[0].accounts[0].pubkey, treasury);
+        assert_eq!(ixs[0].accounts[1].pubkey, stake_account);
+        match limited_deserialize(&ixs[1].data, 1_024).unwrap() {
+            StakeInstruction::Initialize(authorized, _) => {
+                assert_eq!(
+                    (authorized.staker, authorized.withdrawer),
+                    (treasury, treasury)
+                )
+            }
+            other => panic!("unexpected {other:?}"),
+        }
+        assert!(matches!(
+            limited_deserialize(&ixs[2].data, 1_024).unwrap(),
+            StakeInstruction::DelegateStake
+        ));
+        assert_eq!(ixs[2].accounts[1].pubkey, vote);
+
+        // Deactivating starts the cooldown under the treasury's authority
+        let ix = stake::instruction::deactivate_stake(&stake_account, &treasury);
+        assert!(matches!(
+            limited_deserialize(&ix.data, 1_024).unwrap(),
+            StakeInstruction::Deactivate
+        ));
+        assert!(ix
+            .accounts
+            .iter()
+            .any(|meta| meta.pubkey == treasury && meta.is_signer));
+    }
+
+    #[test]
+    fn batches_apply_each_color_and_memo_to_its_own_cube() {
+        let cube_ids: Vec<String> = ["1:1:1", "2:2:2", "3:3:3"].map(String::from).into();
+        let colors = vec![0xff0000, 0x00ff00, 0x0000ff];
+        let memos: Vec<String> = ["a", "bb", ""].map(String::from).into();
+        require_batch_aligned(&cube_ids, &colors, &memos, 3).unwrap();
+        for (ids, colors, memos, accounts) in [
+            (&cube_ids[..], &colors[..2], &memos[..], 3),
+            (&cube_ids[..], &colors[..], &memos[..1], 3),
+            (&cube_ids[..], &colors[..], &memos[..], 4),
+            (&cube_ids[..0], &colors[..0], &memos[..0], 0),
+        ] {
+            assert_eq!(
+                require_batch_aligned(ids, colors, memos, accounts).unwrap_err(),
+                error!(CubeGameError::BatchArgMismatch)
+            );
+        }
+
+        let mut game = zeroed_game();
+        let records: Vec<CubeRecord> = (0..3)
+            .map(|i| {
+                let mut record = zeroed_record();
+                record.memo = memos[i].clone();
+                record.color = record.removal_color(&game, &cube_ids[i], colors[i]);
+                record
+            })
+            .collect();
+        for (i, record) in records.iter().enumerate() {
+            assert_eq!((record.color, &record.memo), (colors[i], &memos[i]));
+        }
+        // A palette seed overrides the chosen colors
+        game.palette_seed = 7;
+        assert_eq!(
+            records[0].removal_color(&game, &cube_ids[0], colors[0]),
+            cube_color(&cube_ids[0], 7)
+        );
+    }
+
+    #[test]
+    fn proof_of_work_needs_enough_leading_zero_bits() {
+        let mut game = zeroed_game();
+        let player = Pubkey::new_unique();
+        // Difficulty 0 takes any nonce
+        game.verify_proof_of_work("1:2:3", &player, 0).unwrap();
+
+        game.pow_difficulty = 8;
+        let nonce = (0u64..)
+            .find(|nonce| game.verify_proof_of_work("1:2:3", &player, *nonce).is_ok())
+            .unwrap();
+        let hash = anchor_lang::solana_program::keccak::hashv(&[
+            b"1:2:3",
+            player.as_ref(),
+            &nonce.to_le_bytes(),
+        ]);
+        assert_eq!(hash.0[0], 0);
+        // A nonce that was not mined is rejected
+        let failing = (0u64..)
+            .find(|nonce| game.verify_proof_of_work("1:2:3", &player, *nonce).is_err())
+            .unwrap();
+        assert_eq!(
+            game.verify_proof_of_work("1:2:3", &player, failing)
+                .unwrap_err(),
+            error!(CubeGameError::InvalidProofOfWork)
+        );
+        game.pow_difficulty = 255;
+        assert_eq!(
+            game.verify_proof_of_work("1:2:3", &player, nonce)
+                .unwrap_err(),
+            error!(CubeGameError::InvalidProofOfWork)
+        );
+    }
+
+    #[test]
+    fn currency_meta_round_trips_into_the_info_event() {
+        let mut game = zeroed_game();
+        game.set_currency("USDC".to_string(), 6).unwrap();
+        assert_eq!(
+            game.set_currency("x".repeat(17), 9).unwrap_err(),
+            error!(CubeGameError::CurrencyLabelTooLong)
+        );
+
+        let mut data = Vec::new();
+        game.try_serialize(&mut data).unwrap();
+        let stored = GameState::try_deserialize(&mut data.as_slice()).unwrap();
+        let info = stored.info().unwrap();
+        assert_eq!(
+            (info.currency_label.as_str(), info.currency_decimals),
+            ("USDC", 6)
+        );
+        game.set_currency("x".repeat(16), 9).unwrap();
+        assert_eq!(game.info().unwrap().currency_label.len(), 16);
+    }
+
+    #[test]
+    fn committed_boards_accept_proven_cubes_and_lock_once_started() {
+        use anchor_lang::solana_program::keccak::hashv;
+
+        let leaf = |id: &str| hashv(&[&[0x00], id.as_bytes()]).0;
+        let node = |a: [u8; 32], b: [u8; 32]| {
+            let (lo, hi) = if a <= b { (a, b) } else { (b, a) };
+            hashv(&[&[0x01], &lo, &hi]).0
+        };
+        let leaves = ["0:0:0", "0:0:1", "0:1:0", "0:1:1"].map(leaf);
+        let (left, right) = (node(leaves[0], leaves[1]), node(leaves[2], leaves[3]));
+        let root = node(left, right);
+
+        let mut game = zeroed_game();
+        // Uncommitted boards take any cube without a proof
+        game.verify_on_board("9:9:9", &[]).unwrap();
+        game.commit_board_root(root, 4).unwrap();
+        game.verify_on_board("0:1:0", &[leaves[3], left]).unwrap();
+        game.verify_on_board("0:0:1", &[leaves[0], right]).unwrap();
+        for (cube_id, proof) in [
+            ("9:9:9", vec![leaves[3], left]),
+            ("0:1:0", vec![leaves[2], left]),
+            ("0:1:0", vec![leaves[3]]),
+            ("0:1:0", vec![]),
+        ] {
+            assert_eq!(
+                game.verify_on_board(cube_id, &proof).unwrap_err(),
+                error!(CubeGameError::NotOnBoard)
+            );
+        }
+
+        // The commitment can be replaced until the first removal
+        game.commit_board_root(left, 2).unwrap();
+        game.total_cubes_removed = 1;
+        assert_eq!(
+            game.commit_board_root(root, 4).unwrap_err(),
+            error!(CubeGameError::BoardLocked)
+        );
+        assert_eq!((game.board_root, game.board_size), (left, 2));
+    }
+
+    #[test]
+    fn withdrawals_are_capped_per_window_until_it_resets() {
+        let mut game = zeroed_game();
+        // No limit configured: anything goes
+        game.consume_withdraw_allowance(u64::MAX, 0).unwrap();
+
+        game.withdraw_limit_per_window = 1_000;
+        game.withdraw_window_seconds = 3_600;
+        game.consume_withdraw_allowance(600, 10_000).unwrap();
+        game.consume_withdraw_allowance(400, 11_000).unwrap();
+        assert_eq!(
+            game.consume_withdraw_allowance(1, 13_599).unwrap_err(),
+            error!(CubeGameError::WithdrawRateLimited)
+        );
+        assert_eq!(game.window_withdrawn, 1_000);
+        // A new window begins once the last one has fully elapsed
+        game.consume_withdraw_allowance(1_000, 13_600).unwrap();
+        assert_eq!((game.window_start, game.window_withdrawn), (13_600, 1_000));
+        assert_eq!(
+            game.consume_withdraw_allowance(1_001, 20_000).unwrap_err(),
+            error!(CubeGameError::WithdrawRateLimited)
+        );
+    }
+
+    #[test]
+    fn sol_burns_send_their_share_to_the_incinerator() {
+        assert_eq!(
+            anchor_lang::solana_program::incinerator::ID.to_string(),
+            "1nc1nerator11111111111111111111111111111111"
+        );
+        let mut game = zeroed_game();
+        // No burn configured: the treasury banks everything
+        assert_eq!(game.payment_shares(1_000).unwrap().to_treasury, 1_000);
+
+        game.sol_burn_bps = 2_500;
+        game.require_valid_split().unwrap();
+        let mut incinerator = 0;
+        let mut treasury = 0;
+        for price in [1_000, 333] {
+            let shares = game.payment_shares(price).unwrap();
+            incinerator += shares.burn;
+            treasury += shares.to_treasury;
+        }
+        // The burn rounds down and the treasury keeps the remainder
+        assert_eq!((incinerator, treasury), (250 + 83, 750 + 250));
+
+        game.sol_burn_bps = 10_001;
+        assert_eq!(
+            game.require_valid_split().unwrap_err(),
+            error!(CubeGameError::InvalidBps)
+        );
+    }
+
+    #[test]
+    fn a_regressed_clock_counts_as_no_time_passing() {
+        assert_eq!(elapsed(1_000, 1_005), 0);
+        assert_eq!(elapsed(1_005, 1_000), 5);
+        assert_eq!(elapsed(i64::MIN, i64::MAX), 0);
+
+        let mut record = zeroed_record();
+        record.stamp_removed_at(1_000);
+        record.stamp_removed_at(995);
+        assert_eq!(record.removed_at, 1_000);
+        record.stamp_removed_at(1_010);
+        assert_eq!(record.removed_at, 1_010);
+
+        let mut game = zeroed_game();
+        game.min_global_interval_seconds = 10;
+        game.require_global_pace(1_000).unwrap();
+        assert_eq!(
+            game.require_global_pace(990).unwrap_err(),
+            error!(CubeGameError::GlobalPaceLimited)
+        );
+        // A regressed clock does not open a new withdrawal window
+        game.withdraw_limit_per_window = 100;
+        game.withdraw_window_seconds = 60;
+        game.consume_withdraw_allowance(100, 1_000).unwrap();
+        assert_eq!(
+            game.consume_withdraw_allowance(1, 900).unwrap_err(),
+            error!(CubeGameError::WithdrawRateLimited)
+        );
+        assert_eq!(game.window_start, 1_000);
+    }
+
+    #[test]
+    fn frozen_players_are_blocked_until_unfrozen_with_stats_intact() {
+        let game = zeroed_game();
+        let player = Pubkey::new_unique();
+        let mut stats = zeroed_stats();
+        stats.player = player;
+        stats.cubes_removed = 7;
+        stats.total_spent = 700;
+        stats.require_can_remove(&game, 0).unwrap();
+
+        stats.set_frozen(player, true);
+        assert_eq!(
+            stats.require_can_remove(&game, 0).unwrap_err(),
+            error!(CubeGameError::PlayerFrozen)
+        );
+        stats.set_frozen(player, false);
+        stats.require_can_remove(&game, 0).unwrap();
+        assert_eq!((stats.cubes_removed, stats.total_spent), (7, 700));
+
+        // A wallet with no stats yet gets a frozen stub
+        let mut stub = zeroed_stats();
+        stub.set_frozen(player, true);
+        assert_eq!(
+            (stub.player, stub.frozen, stub.cubes_removed),
+            (player, true, 0)
+        );
+        assert_eq!(
+            stub.require_can_remove(&game, 0).unwrap_err(),
+            error!(CubeGameError::PlayerFrozen)
+        );
+    }
+
+    #[test]
+    fn a_large_deposit_crosses_every_threshold_it_passes() {
+        let mut game = zeroed_game();
+        game.next_threshold = 100;
+        // Unset step: no milestones
+        assert!(game.cross_thresholds(1_000).is_empty());
+
+        game.threshold_step = 100;
+        assert!(game.cross_thresholds(99).is_empty());
+        assert_eq!(game.cross_thresholds(250), vec![100, 200]);
+        assert_eq!(game.next_threshold, 300);
+        assert!(game.cross_thresholds(299).is_empty());
+        // A huge jump reports at most MAX_THRESHOLD_EVENTS and catches up later
+        assert_eq!(game.cross_thresholds(10_000).len(), MAX_THRESHOLD_EVENTS);
+        assert_eq!(game.next_threshold, 300 + 100 * MAX_THRESHOLD_EVENTS as u64);
+    }
+
+    #[test]
+    fn zone_two_stays_locked_until_zone_one_is_cleared() {
+        let zone_data = |zone_id: u32, prefix: &str, unlocked: bool| {
+            let zone = Zone {
+                zone_id,
+                prefix: prefix.to_string(),
+                cubes_total: 2,
+                cubes_removed: 0,
+                unlocked,
+                bump: 0,
+            };
+            let mut data = Vec::new();
+            zone.try_serialize(&mut data).unwrap();
+            data
+        };
+        let mut game = zeroed_game();
+        game.zone_count = 2;
+        let (first_key, second_key) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let (mut first_lamports, mut second_lamports) = (0u64, 0u64);
+        let mut first_data = zone_data(0, "z1-", true);
+        let mut second_data = zone_data(1, "z2-", false);
+        let owner = crate::ID;
+        let first_info = AccountInfo::new(
+            &first_key,
+            false,
+            true,
+            &mut first_lamports,
+            &mut first_data,
+            &owner,
+            false,
+            0,
+        );
+        let second_info = AccountInfo::new(
+            &second_key,
+            false,
+            true,
+            &mut second_lamports,
+            &mut second_data,
+            &owner,
+            false,
+            0,
+        );
+        let mut first: Account<Zone> = Account::try_from(&first_info).unwrap();
+        let mut second: Account<Zone> = Account::try_from(&second_info).unwrap();
+
+        assert_eq!(
+            require_zone_open(&game, Some(&second), "z2-1:1:1").unwrap_err(),
+            error!(CubeGameError::ZoneLocked)
+        );
+        assert_eq!(
+            require_zone_open(&game, Some(&first), "z2-1:1:1").unwrap_err(),
+            error!(CubeGameError::CubeNotInZone)
+        );
+        assert_eq!(
+            require_zone_open(&game, None, "z1-1:1:1").unwrap_err(),
+            error!(CubeGameError::ZoneAccountRequired)
+        );
+
+        require_zone_open(&game, Some(&first), "z1-1:1:1").unwrap();
+        credit_zone(&game, Some(&mut first), Some(&mut second)).unwrap();
+        // One cube left in zone 1, so zone 2 is still closed
+        assert!(!second.unlocked);
+        require_zone_open(&game, Some(&first), "z1-1:1:2").unwrap();
+        credit_zone(&game, Some(&mut first), Some(&mut second)).unwrap();
+
+        assert!(second.unlocked);
+        require_zone_open(&game, Some(&second), "z2-1:1:1").unwrap();
+        // A cleared zone takes no more removals
+        assert_eq!(
+            require_zone_open(&game, Some(&first), "z1-1:1:3").unwrap_err(),
+            error!(CubeGameError::ZoneLocked)
+        );
+
+        // Without zones every removal is allowed
+        game.zone_count = 0;
+        require_zone_open(&game, None, "anything").unwrap();
+    }
+
+    #[test]
+    fn idempotent_removal_only_forgives_the_same_player() {
+        let (player, rival) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let mut record = zeroed_record();
+        // A cube nobody removed goes through the normal removal
+        assert!(!record.removed_by_caller("1:2:3", &player).unwrap());
+
+        record.cube_id = "1:2:3".to_string();
+        record.is_removed = true;
+        record.removed_by = player;
+        assert!(record.removed_by_caller("1:2:3", &player).unwrap());
+        assert_eq!(
+            record.removed_by_caller("1:2:3", &rival).unwrap_err(),
+            error!(CubeGameError::CubeAlreadyRemoved)
+        );
+        // The retry still has to name the cube the record holds
+        assert_eq!(
+            record.removed_by_caller("3:2:1", &player).unwrap_err(),
+            error!(CubeGameError::CubeRecordCorrupt)
+        );
+    }
+
+    #[test]
+    fn compact_removal_event_carries_the_keccak_of_the_cube_id() {
+        let player = Pubkey::new_unique();
+        let event = CubeRemovedCompact::new("12:34:56", player, 7);
+        assert_eq!(
+            event.cube_hash,
+            anchor_lang::solana_program::keccak::hashv(&[b"12:34:56"]).0
+        );
+        assert_eq!((event.player, event.seq), (player, 7));
+        // The payload is the same size whatever the cube id
+        let long = CubeRemovedCompact::new(&"9".repeat(64), player, 7);
+        assert_eq!(event.try_to_vec().unwrap().len(), 72);
+        assert_eq!(long.try_to_vec().unwrap().len(), 72);
+    }
+
+    #[test]
+    fn dao_tax_splits_an_indivisible_price_without_losing_lamports() {
+        let mut game = zeroed_game();
+        game.dao_tax_bps = 333;
+        // 3.33% of 1,001 is 33.33, so the DAO takes 33 and the main
+        // treasury the remaining 968 of what the player pays
+        let shares = game.payment_shares(1_001).unwrap();
+        assert_eq!((shares.dao_tax, shares.to_treasury), (33, 968));
+        assert_eq!(shares.dao_tax + shares.to_treasury, 1_001);
+
+        game.dao_tax_bps = 0;
+        assert_eq!(game.payment_shares(1_001).unwrap().to_treasury, 1_001);
+
+        // The tax shares the split budget with the other cuts
+        game.dao_tax_bps = 6_000;
+        game.insurance_bps = 4_000;
+        assert!(game.require_valid_split().is_ok());
+        game.insurance_bps = 4_001;
+        assert_eq!(
+            game.require_valid_split().unwrap_err(),
+            error!(CubeGameError::InvalidBps)
+        );
+    }
+
+    #[test]
+    fn each_snapshot_label_gets_its_own_record() {
+        for label in ["season-1", "season-2"] {
+            validate_snapshot_label(label).unwrap();
+        }
+        assert_ne!(snapshot_pda("season-1"), snapshot_pda("season-2"));
+        // Reusing a label lands on the existing record, which `init` refuses
+        assert_eq!(snapshot_pda("season-1"), snapshot_pda("season-1"));
+
+        for label in ["", &"x".repeat(33)] {
+            assert_eq!(
+                validate_snapshot_label(label).unwrap_err(),
+                error!(CubeGameError::InvalidSnapshotLabel)
+            );
+        }
+        validate_snapshot_label(&"x".repeat(32)).unwrap();
+    }
+
+    #[test]
+    fn a_price_voucher_shields_its_uses_from_a_price_rise() {
+        let mut voucher = PriceVoucher {
+            player: Pubkey::new_unique(),
+            locked_price: 1_000,
+            uses_remaining: 2,
+            expires_at: 500,
+            bump: 0,
+        };
+        // Below the locked price the voucher is kept for later
+        assert_eq!(voucher.apply(800, 100), 800);
+        assert_eq!(voucher.uses_remaining, 2);
+
+        // After `set_price` doubles the price, both uses pay the old one
+        assert_eq!(voucher.apply(2_000, 100), 1_000);
+        assert_eq!(voucher.apply(2_000, 200), 1_000);
+        assert_eq!(voucher.uses_remaining, 0);
+        assert_eq!(voucher.apply(2_000, 300), 2_000);
+
+        // An expired voucher no longer applies
+        voucher.uses_remaining = 1;
+        assert_eq!(voucher.apply(2_000, 500), 2_000);
+        assert_eq!(voucher.uses_remaining, 1);
+    }
+
+    #[test]
+    fn a_zero_price_removal_skips_the_transfer() {
+        let keys = [Pubkey::new_unique(), treasury_pda(), System::id()];
+        let mut lamports = [5_000u64, 0, 0];
+        let mut data = [Vec::new(), Vec::new(), Vec::new()];
+        let owner = System::id();
+        let infos: Vec<AccountInfo> = keys
+            .iter()
+            .zip(lamports.iter_mut())
+            .zip(data.iter_mut())
+            .map(|((key, lamports), data)| {
+                AccountInfo::new(key, false, true, lamports, data, &owner, false, 0)
+            })
+            .collect();
+        let route = || PaymentRoute {
+            payer: &infos[0],
+            treasury: &infos[1],
+            system_program: &infos[2],
+            refund_pool: None,
+            insurance_vault: None,
+            incinerator: None,
+            dao_treasury: None,
+            ledger: None,
+        };
+        let mut game = zeroed_game();
+        // A missing ledger would fail any real payment, so reaching it
+        // shows the transfer path was taken
+        game.ledger_enabled = true;
+        assert_eq!(collect_payment(&game, route(), 0).unwrap(), 0);
+        assert_eq!(
+            collect_payment(&game, route(), 1).unwrap_err(),
+            error!(CubeGameError::LedgerRequired)
+        );
+        assert_eq!((infos[0].lamports(), infos[1].lamports()), (5_000, 0));
+
+        // Nothing was deposited, so the withdraw cooldown does not restart
+        game.note_deposit(0, 0, 100);
+        assert_eq!(game.last_deposit_at, 0);
+    }
+
+    #[test]
+    fn an_idle_player_ranks_below_an_equally_counted_active_one() {
+        let day = 86_400;
+        let now = 30 * day;
+        let mut active = zeroed_stats();
+        active.cubes_removed = 100;
+        active.last_removal_at = now;
+        let mut idle = zeroed_stats();
+        idle.cubes_removed = 100;
+        idle.last_removal_at = now - 10 * day;
+
+        // 5% a day for ten idle days halves the score
+        assert_eq!(active.active_score(500, now), 100);
+        assert_eq!(idle.active_score(500, now), 50);
+        assert!(idle.active_score(500, now) < active.active_score(500, now));
+        // The decay bottoms out at zero and never touches the raw count
+        assert_eq!(idle.active_score(500, now + 30 * day), 0);
+        assert_eq!(idle.cubes_removed, 100);
+        // Without a decay rate both rank the same
+        assert_eq!(idle.active_score(0, now), active.active_score(0, now));
+    }
+
+    #[test]
+    fn update_config_applies_a_batch_and_validates_every_field() {
+        let mut game = zeroed_game();
+        let update = GameConfigUpdate {
+            price_per_cube: Some(2_000),
+            transfer_lock_seconds: Some(600),
+            ..Default::default()
+        };
+        game.apply_config(&update, false).unwrap();
+        assert_eq!(
+            (game.price_per_cube, game.transfer_lock_seconds),
+            (2_000, 600)
+        );
+        assert_eq!(game.price_epoch, 1);
+        // Fields left out keep their values
+        assert_eq!(game.max_removals_per_tx, 0);
+
+        // A bad field anywhere in the batch fails the whole instruction
+        let invalid = [
+            GameConfigUpdate {
+                price_per_cube: Some(3_000),
+                transfer_lock_seconds: Some(-1),
+                ..Default::default()
+            },
+            GameConfigUpdate {
+                price_per_cube: Some(3_000),
+                credit_discount_bps: Some(10_001),
+                ..Default::default()
+            },
+            GameConfigUpdate {
+                price_per_cube: Some(3_000),
+                refund_reserve_bps: Some(100),
+                ..Default::default()
+            },
+            GameConfigUpdate {
+                price_per_cube: Some(3_000),
+                sol_burn_bps: Some(10_001),
+                ..Default::default()
+            },
+        ];
+        let errors = [
+            CubeGameError::InvalidDuration,
+            CubeGameError::InvalidBps,
+            CubeGameError::RefundPoolRequired,
+            CubeGameError::InvalidBps,
+        ];
+        for (update, expected) in invalid.iter().zip(errors) {
+            assert_eq!(
+                zeroed_game().apply_config(update, false).unwrap_err(),
+                error!(expected)
+            );
+        }
+    }
+
+    #[test]
+    fn only_high_value_removals_need_an_earlier_intent() {
+        let mut game = zeroed_game();
+        // No threshold, no confirmation
+        assert!(!game.needs_confirmation(u64::MAX));
+
+        game.high_value_threshold = 1_000;
+        assert!(!game.needs_confirmation(1_000));
+        assert!(game.needs_confirmation(1_001));
+
+        let intent = RemovalIntent {
+            player: Pubkey::new_unique(),
+            cube_id: "1:2:3".to_string(),
+            slot: 50,
+            bump: 0,
+        };
+        intent.require_confirmed(51).unwrap();
+        for slot in [49, 50] {
+            assert_eq!(
+                intent.require_confirmed(slot).unwrap_err(),
+                error!(CubeGameError::ConfirmationRequired)
+            );
+        }
+    }
+
+    #[test]
+    fn withdrawals_wait_out_the_cooldown_after_a_deposit() {
+        let mut game = zeroed_game();
+        game.withdraw_cooldown_seconds = 3_600;
+        game.note_deposit(1_000, 1_000, 10_000);
+        assert_eq!(game.last_deposit_at, 10_000);
+
+        for now in [10_000, 13_599] {
+            assert_eq!(
+                game.require_withdraw_cooled(now).unwrap_err(),
+                error!(CubeGameError::WithdrawCoolingPeriod)
+            );
+        }
+        game.require_withdraw_cooled(13_600).unwrap();
+
+        // A later deposit starts the cooldown over
+        game.note_deposit(1, 1_001, 13_600);
+        assert!(game.require_withdraw_cooled(13_600).is_err());
+    }
+
+    #[test]
+    fn a_burst_of_removals_raises_the_price_until_the_window_passes() {
+        let mut game = zeroed_game();
+        game.price_per_cube = 1_000;
+        game.rush_window_seconds = 60;
+        game.rush_bps_per_removal = 100;
+        game.rush_max_bps = 500;
+
+        let mut prices = Vec::new();
+        for now in 1_000..1_008 {
+            prices.push(effective_price(&game, None, false, now).unwrap());
+            game.note_rush(now);
+        }
+        // Each removal adds 1% for the next one, capped at 5%
+        assert_eq!(
+            prices,
+            vec![1_000, 1_010, 1_020, 1_030, 1_040, 1_050, 1_050, 1_050]
+        );
+        assert_eq!(game.recent_removals(1_059), 8);
+
+        // Once the window lapses the price relaxes and counting restarts
+        assert_eq!(effective_price(&game, None, false, 1_060).unwrap(), 1_000);
+        game.note_rush(1_060);
+        assert_eq!(
+            (game.rush_window_start, game.recent_removals(1_060)),
+            (1_060, 1)
+        );
+        assert_eq!(effective_price(&game, None, false, 1_061).unwrap(), 1_010);
+    }
+
+    #[test]
+    fn a_delegate_removes_within_its_limit_and_no_further() {
+        let mut delegation = Delegation {
+            player: Pubkey::new_unique(),
+            delegate: Pubkey::new_unique(),
+            spend_limit: 2_500,
+            bump: 0,
+        };
+        delegation.spend(1_000).unwrap();
+        delegation.spend(1_000).unwrap();
+        assert_eq!(delegation.spend_limit, 500);
+        // A removal over what is left is refused without touching the limit
+        assert_eq!(
+            delegation.spend(1_000).unwrap_err(),
+            error!(CubeGameError::DelegateLimitExceeded)
+        );
+        assert_eq!(delegation.spend_limit, 500);
+        delegation.spend(500).unwrap();
+        assert_eq!(delegation.spend_limit, 0);
+    }
+
+    #[test]
+    fn insurance_accumulates_and_stays_locked_until_its_time() {
+        let vault = InsuranceVault {
+            locked_until: 1_000,
+            total_insured: 0,
+            total_released: 0,
+            bump: 0,
+        };
+        let mut vault_data = Vec::new();
+        vault.try_serialize(&mut vault_data).unwrap();
+        let keys = [
+            Pubkey::new_unique(),
+            treasury_pda(),
+            System::id(),
+            Pubkey::new_unique(),
+        ];
+        let mut lamports = [1_000_000u64, 0, 0, 0];
+        let mut data = [Vec::new(), Vec::new(), Vec::new(), vault_data];
+        let owners = [System::id(), System::id(), System::id(), crate::ID];
+        let infos: Vec<AccountInfo> = keys
+            .iter()
+            .zip(lamports.iter_mut())
+            .zip(data.iter_mut())
+            .zip(owners.iter())
+            .map(|(((key, lamports), data), owner)| {
+                AccountInfo::new(key, false, true, lamports, data, owner, false, 0)
+            })
+            .collect();
+        let mut vault: Account<InsuranceVault> = Account::try_from(&infos[3]).unwrap();
+
+        let mut game = zeroed_game();
+        game.insurance_bps = 500;
+        for amount in [1_000, 2_000, 999] {
+            let route = PaymentRoute {
+                payer: &infos[0],
+                treasury: &infos[1],
+                system_program: &infos[2],
+                refund_pool: None,
+                insurance_vault: Some(&mut vault),
+                incinerator: None,
+                dao_treasury: None,
+                ledger: None,
+            };
+            collect_payment(&game, route, amount).unwrap();
+        }
+        // 5% of each payment, rounded down
+        assert_eq!(vault.total_insured, 50 + 100 + 49);
+
+        assert_eq!(
+            vault.require_unlocked(999).unwrap_err(),
+            error!(CubeGameError::InsuranceLocked)
+        );
+        vault.require_unlocked(1_000).unwrap();
+    }
+
+    #[test]
+    fn a_payment_missing_a_destination_fails_before_moving_anything() {
+        let mut pool_data = Vec::new();
+        RefundPool::try_deserialize_unchecked(&mut &[0u8; 8 + RefundPool::INIT_SPACE][..])
+            .unwrap()
+            .try_serialize(&mut pool_data)
+            .unwrap();
+        let keys = [
+            Pubkey::new_unique(),
+            treasury_pda(),
+            System::id(),
+            Pubkey::new_unique(),
+        ];
+        let mut lamports = [1_000_000u64, 0, 0, 0];
+        let mut data = [Vec::new(), Vec::new(), Vec::new(), pool_data];
+        let owners = [System::id(), System::id(), System::id(), crate::ID];
+        let infos: Vec<AccountInfo> = keys
+            .iter()
+            .zip(lamports.iter_mut())
+            .zip(data.iter_mut())
+            .zip(owners.iter())
+            .map(|(((key, lamports), data), owner)| {
+                AccountInfo::new(key, false, true, lamports, data, owner, false, 0)
+            })
+            .collect();
+        let mut pool: Account<RefundPool> = Account::try_from(&infos[3]).unwrap();
+
+        let mut game = zeroed_game();
+        game.refund_reserve_bps = 1_000;
+        game.insurance_bps = 500;
+        let route = PaymentRoute {
+            payer: &infos[0],
+            treasury: &infos[1],
+            system_program: &infos[2],
+            refund_pool: Some(&mut pool),
+            insurance_vault: None,
+            incinerator: None,
+            dao_treasury: None,
+            ledger: None,
+        };
+        assert_eq!(
+            collect_payment(&game, route, 1_000).unwrap_err(),
+            error!(CubeGameError::InsuranceVaultRequired)
+        );
+        // The refund pool share, which comes first, was never taken
+        assert_eq!(pool.total_reserved, 0);
+        assert_eq!(infos[0].lamports(), 1_000_000);
+    }
+
+    #[test]
+    fn the_record_cap_blocks_new_records_but_not_respawned_ones() {
+        let mut game = zeroed_game();
+        game.max_cube_records = 2;
+        let fresh = zeroed_record();
+        game.materialize(&fresh, "1:1:1", &[]).unwrap();
+        game.materialize(&fresh, "1:1:2", &[]).unwrap();
+        assert_eq!(game.active_cube_records, 2);
+        assert_eq!(
+            game.materialize(&fresh, "1:1:3", &[]).unwrap_err(),
+            error!(CubeGameError::RecordLimitReached)
+        );
+
+        // A respawned cube reuses its record, so the cap does not apply
+        let mut respawned = zeroed_record();
+        respawned.cube_id = "1:1:1".to_string();
+        game.materialize(&respawned, "1:1:1", &[]).unwrap();
+        assert_eq!(game.active_cube_records, 2);
+
+        // Closing a record frees a slot
+        game.note_record_closed();
+        game.materialize(&fresh, "1:1:3", &[]).unwrap();
+    }
+
+    #[test]
+    fn tiers_upgrade_once_per_threshold_and_never_drop() {
+        let mut game = zeroed_game();
+        game.tier_cube_thresholds = [2, 4, 6];
+        game.tier_spend_thresholds = [0, 1_000, 1_000];
+        let mut stats = zeroed_stats();
+        let player = Pubkey::new_unique();
+
+        let mut upgrades = Vec::new();
+        for _ in 0..8 {
+            stats.cubes_removed += 1;
+            stats.total_spent += 200;
+            if stats.promote_tier(&game, player) {
+                upgrades.push((stats.cubes_removed, stats.tier));
+            }
+        }
+        // Gold needs 1,000 spent as well as 4 cubes, so it comes at 5
+        assert_eq!(upgrades, vec![(2, 1), (5, 2), (6, 3)]);
+
+        // Raising the thresholds later does not take a tier away
+        game.tier_cube_thresholds = [20, 40, 60];
+        stats.cubes_removed += 1;
+        assert!(!stats.promote_tier(&game, player));
+        assert_eq!(stats.tier, 3);
+    }
+
+    #[test]
+    fn the_priority_fee_floor_applies_only_when_set() {
+        let mut game = zeroed_game();
+        // Off: any attestation, including none, is accepted
+        game.require_priority_fee(0).unwrap();
+
+        game.min_priority_fee = 5_000;
+        for fee in [0, 4_999] {
+            assert_eq!(
+                game.require_priority_fee(fee).unwrap_err(),
+                error!(CubeGameError::PriorityFeeTooLow)
+            );
+        }
+        game.require_priority_fee(5_000).unwrap();
+        game.require_priority_fee(u64::MAX).unwrap();
+    }
+
+    #[test]
+    fn batch_refunds_pay_each_recipient_within_the_budget() {
+        let (alice, bob, carol) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let mut record = zeroed_record();
+        record.is_removed = true;
+        record.payments = vec![
+            CubeContribution {
+                contributor: alice,
+                amount: 600,
+            },
+            CubeContribution {
+                contributor: bob,
+                amount: 400,
+            },
+        ];
+
+        let refunds = [600, 150];
+        assert_eq!(batch_refund_total(&refunds, 4, 1_000).unwrap(), 750);
+        record.take_refund(&alice, 600).unwrap();
+        record.take_refund(&bob, 150).unwrap();
+        let left: Vec<u64> = record.payments.iter().map(|p| p.amount).collect();
+        assert_eq!(left, vec![0, 250]);
+
+        // More than the treasury can spare rejects the whole batch
+        assert_eq!(
+            batch_refund_total(&refunds, 4, 749).unwrap_err(),
+            error!(CubeGameError::InsufficientTreasuryBalance)
+        );
+        assert_eq!(
+            batch_refund_total(&refunds, 3, 1_000).unwrap_err(),
+            error!(CubeGameError::BatchArgMismatch)
+        );
+        let too_many = vec![1; MAX_BATCH_REFUNDS + 1];
+        assert_eq!(
+            batch_refund_total(&too_many, too_many.len() * 2, u64::MAX).unwrap_err(),
+            error!(CubeGameError::TooManyAccounts)
+        );
+        // Nobody is refunded more than they paid, or for a cube they did not pay
+        assert_eq!(
+            record.take_refund(&bob, 251).unwrap_err(),
+            error!(CubeGameError::NothingToRefund)
+        );
+        assert_eq!(
+            record.take_refund(&carol, 1).unwrap_err(),
+            error!(CubeGameError::NotCubeOwner)
+        );
+    }
+
+    #[test]
+    fn a_three_hit_cube_falls_on_the_third_hit_for_its_full_price() {
+        let mut record = zeroed_record();
+        let mut hits = Vec::new();
+        for _ in 0..3 {
+            hits.push(record.hit_price(3, 1_000));
+            record.hits_taken += 1;
+        }
+        // Two hits leave the cube standing; the third pays the remainder
+        assert_eq!(hits, vec![(333, false), (333, false), (334, true)]);
+        assert_eq!(hits.iter().map(|(paid, _)| paid).sum::<u64>(), 1_000);
+
+        // Changing the game's setting mid-cube does not move the goal
+        let mut record = zeroed_record();
+        record.hit_price(3, 1_000);
+        record.hits_taken = 1;
+        assert_eq!(record.hit_price(1, 1_000), (333, false));
+
+        // Single-hit cubes, including an unset setting, pay in full
+        assert_eq!(zeroed_record().hit_price(0, 1_000), (1_000, true));
+    }
+
+    #[test]
+    fn removals_in_one_region_raise_only_its_price() {
+        let region_data = |region_id: &str| {
+            let region = Region {
+                region_id: region_id.to_string(),
+                base_price: 1_000,
+                increment: 50,
+                cubes_removed: 0,
+                bump: 0,
+                region_authority: Pubkey::default(),
+                paused: false,
+            };
+            let mut data = Vec::new();
+            region.try_serialize(&mut data).unwrap();
+            data
+        };
+        let keys = [Pubkey::new_unique(), Pubkey::new_unique()];
+        let mut lamports = [0u64; 2];
+        let mut data = [region_data("a"), region_data("b")];
+        let owner = crate::ID;
+        let infos: Vec<AccountInfo> = keys
+            .iter()
+            .zip(lamports.iter_mut())
+            .zip(data.iter_mut())
+            .map(|((key, lamports), data)| {
+                AccountInfo::new(key, false, true, lamports, data, &owner, false, 0)
+            })
+            .collect();
+        let (a, b) = (
+            UncheckedAccount::try_from(&infos[0]),
+            UncheckedAccount::try_from(&infos[1]),
+        );
+        let game = zeroed_game();
+        let price = |info: &UncheckedAccount, region_id: &str| {
+            load_priced_region(&game, Some(info), region_id)
+                .unwrap()
+                .1
+                .unwrap()
+        };
+
+        for _ in 0..3 {
+            let (mut region, _) = load_priced_region(&game, Some(&a), "a").unwrap();
+            credit_region(region.as_mut(), Some(&a), 1).unwrap();
+        }
+        // 1,000 plus 50 for each removal in region a
+        assert_eq!(price(&a, "a"), 1_150);
+        assert_eq!(price(&b, "b"), 1_000);
+    }
+
+    #[test]
+    fn an_expired_cube_is_free_and_an_unexpiring_one_always_charges() {
+        let mut game = zeroed_game();
+        game.price_per_cube = 1_000;
+        let mut record = zeroed_record();
+        record.expires_at = 500;
+        assert_eq!(
+            record.removal_price(&game, None, false, 499).unwrap(),
+            1_000
+        );
+        assert_eq!(record.removal_price(&game, None, false, 500).unwrap(), 0);
+        assert_eq!(
+            record.removal_price(&game, Some(700), false, 900).unwrap(),
+            0
+        );
+
+        record.expires_at = 0;
+        assert_eq!(
+            record.removal_price(&game, None, false, i64::MAX).unwrap(),
+            1_000
+        );
+    }
+
+    #[test]
+    fn scaled_stats_apply_the_display_multiplier() {
+        let mut game = zeroed_game();
+        for (bps, cubes_removed) in [(10_000, 1_234), (15_000, 1_234), (2_500, 7), (0, 99)] {
+            game.stats_display_multiplier_bps = bps;
+            assert_eq!(
+                game.scaled_stat(cubes_removed).unwrap(),
+                cubes_removed * bps as u64 / 10_000
+            );
+        }
+        game.stats_display_multiplier_bps = 15_000;
+        assert_eq!(game.scaled_stat(1_234).unwrap(), 1_851);
+        // Clients scaling on their own read the same multiplier
+        assert_eq!(game.info().unwrap().stats_display_multiplier_bps, 15_000);
+    }
+
+    #[test]
+    fn a_price_change_invalidates_removals_priced_before_it() {
+        let mut game = zeroed_game();
+        let quoted = game.price_epoch;
+        game.require_price_epoch(Some(quoted)).unwrap();
+
+        game.set_price(2_000);
+        assert_eq!(game.price_epoch, quoted + 1);
+        assert_eq!(
+            game.require_price_epoch(Some(quoted)).unwrap_err(),
+            error!(CubeGameError::PriceEpochMismatch)
+        );
+        game.require_price_epoch(Some(quoted + 1)).unwrap();
+        // Clients that do not care about the epoch are not checked
+        game.require_price_epoch(None).unwrap();
+    }
+
+    #[test]
+    fn a_funded_bounty_pays_the_remover_everything_funded() {
+        let mut bounty =
+            Bounty::try_deserialize_unchecked(&mut &[0u8; 8 + Bounty::INIT_SPACE][..]).unwrap();
+        let (alice, bob) = (Pubkey::new_unique(), Pubkey::new_unique());
+        bounty.fund(alice, 300).unwrap();
+        bounty.fund(bob, 500).unwrap();
+        bounty.fund(alice, 200).unwrap();
+        // Repeat funding tops up the funder's share
+        assert_eq!(bounty.funders.len(), 2);
+        assert_eq!(bounty.total, 1_000);
+
+        assert_eq!(bounty.claim(), 1_000);
+        assert_eq!(bounty.total, 0);
+        // Once paid there is no share left to refund
+        assert_eq!(
+            bounty.withdraw_share(&alice).unwrap_err(),
+            error!(CubeGameError::NoBountyShare)
+        );
+
+        // An unclaimed bounty refunds each funder their own share
+        bounty.fund(alice, 300).unwrap();
+        bounty.fund(bob, 500).unwrap();
+        assert_eq!(bounty.withdraw_share(&bob).unwrap(), 500);
+        assert_eq!(bounty.total, 300);
+
+        for _ in 1..MAX_BOUNTY_FUNDERS {
+            bounty.fund(Pubkey::new_unique(), 1).unwrap();
+        }
+        assert_eq!(
+            bounty.fund(Pubkey::new_unique(), 1).unwrap_err(),
+            error!(CubeGameError::TooManyBountyFunders)
+        );
+    }
+
+    #[test]
+    fn rapid_removals_build_a_combo_and_a_gap_resets_it() {
+        let mut game = zeroed_game();
+        game.combo_timer_seconds = 10;
+        game.combo_step_bonus = 100;
+        game.combo_max_bonus = 150;
+        let mut stats = zeroed_stats();
+
+        let bonuses: Vec<(u32, u64)> = [1_000, 1_005, 1_014]
+            .iter()
+            .map(|now| {
+                let combo = stats.extend_combo(game.combo_timer_seconds, *now);
+                (combo, game.combo_timer_bonus(combo))
+            })
+            .collect();
+        // The first removal opens the combo, later ones pay a capped bonus
+        assert_eq!(bonuses, vec![(1, 0), (2, 100), (3, 150)]);
+
+        // Ten seconds after the last removal the combo has lapsed
+        assert_eq!(stats.extend_combo(game.combo_timer_seconds, 1_024), 1);
+        assert_eq!(stats.combo_expires_at, 1_034);
+    }
+
+    #[test]
+    fn a_withdraw_below_the_hot_target_refills_from_the_cold_reserve() {
+        let mut game = zeroed_game();
+        game.target_hot_balance = 10_000;
+        // Withdrawing 4,000 from a treasury at the target
+        let treasury_balance = 10_000 - 4_000;
+        assert_eq!(game.hot_refill(treasury_balance, 50_000), 4_000);
+        // A short reserve refills what it can
+        assert_eq!(game.hot_refill(treasury_balance, 1_500), 1_500);
+        assert_eq!(game.hot_refill(treasury_balance, 0), 0);
+        // Nothing moves while the treasury is at or above the target
+        assert_eq!(game.hot_refill(10_000, 50_000), 0);
+        assert_eq!(game.hot_refill(12_000, 50_000), 0);
+    }
+
+    #[test]
+    fn a_removal_past_its_deadline_is_rejected() {
+        assert_eq!(
+            require_before_deadline(1_000, 1_001).unwrap_err(),
+            error!(CubeGameError::DeadlineExceeded)
+        );
+        require_before_deadline(1_000, 1_000).unwrap();
+        require_before_deadline(1_000, 999).unwrap();
+        // No deadline, no check
+        require_before_deadline(0, i64::MAX).unwrap();
+    }
+
+    #[test]
+    fn removals_beyond_the_slot_cap_wait_for_the_next_slot() {
+        let mut game = zeroed_game();
+        game.max_removals_per_slot = 2;
+        game.throttle_slot_at(40).unwrap();
+        game.throttle_slot_at(40).unwrap();
+        assert_eq!(
+            game.throttle_slot_at(40).unwrap_err(),
+            error!(CubeGameError::SlotThrottled)
+        );
+        // The rejected removal took no room in the slot
+        assert_eq!(game.removals_this_slot, 2);
+
+        game.throttle_slot_at(41).unwrap();
+        assert_eq!((game.current_slot, game.removals_this_slot), (41, 1));
+
+        game.max_removals_per_slot = 0;
+        for _ in 0..5 {
+            game.throttle_slot_at(41).unwrap();
+        }
+    }
+
+    #[test]
+    fn referral_earnings_accrue_until_claimed() {
+        let mut referrer = zeroed_stats();
+        for price in [1_000, 2_000, 1_500] {
+            referrer.accrue_referral(price, 500).unwrap();
+        }
+        // 5% of each referred removal
+        assert_eq!(referrer.referral_earnings, 50 + 100 + 75);
+
+        assert_eq!(referrer.take_referral_earnings().unwrap(), 225);
+        assert_eq!(
+            (referrer.referral_earnings, referrer.referral_claimed),
+            (0, 225)
+        );
+        assert_eq!(
+            referrer.take_referral_earnings().unwrap_err(),
+            error!(CubeGameError::NoReferralEarnings)
+        );
+
+        referrer.accrue_referral(2_000, 500).unwrap();
+        assert_eq!(referrer.take_referral_earnings().unwrap(), 100);
+        assert_eq!(referrer.referral_claimed, 325);
+    }
+
+    #[test]
+    fn back_to_back_removals_are_paced_board_wide() {
+        let mut game = zeroed_game();
+        game.min_global_interval_seconds = 30;
+        game.require_global_pace(1_000).unwrap();
+        for now in [1_000, 1_029] {
+            assert_eq!(
+                game.require_global_pace(now).unwrap_err(),
+                error!(CubeGameError::GlobalPaceLimited)
+            );
+        }
+        // A paced-out removal does not push the next one back
+        assert_eq!(game.last_global_removal_at, 1_000);
+        game.require_global_pace(1_030).unwrap();
+        assert_eq!(game.last_global_removal_at, 1_030);
+
+        game.min_global_interval_seconds = 0;
+        game.require_global_pace(1_030).unwrap();
+        game.require_global_pace(1_030).unwrap();
+    }
+
+    #[test]
+    fn milestone_rewards_mint_exactly_when_a_milestone_is_crossed() {
+        let mut game = zeroed_game();
+        game.milestone_every = 10;
+        game.milestone_reward_amount = 5_000;
+        // No reward mint configured, nothing to mint
+        assert_eq!(game.milestone_reward(9, 10).unwrap(), 0);
+
+        game.reward_mint = Pubkey::new_unique();
+        let minted: Vec<u64> = (0..21)
+            .map(|before| game.milestone_reward(before, before + 1).unwrap())
+            .collect();
+        let crossings: Vec<usize> = minted
+            .iter()
+            .enumerate()
+            .filter(|(_, amount)| **amount > 0)
+            .map(|(before, _)| before + 1)
+            .collect();
+        assert_eq!(crossings, vec![10, 20]);
+        assert!(minted.iter().all(|amount| *amount == 0 || *amount == 5_000));
+        // Crossing two milestones at once mints both rewards
+        assert_eq!(game.milestone_reward(9, 21).unwrap(), 10_000);
+
+        assert_eq!(
+            mint_milestone_reward(None, None, None, None, 5_000).unwrap_err(),
+            error!(CubeGameError::RewardAccountsRequired)
+        );
+    }
+
+    #[test]
+    fn a_pricing_reset_leaves_exactly_the_base_price() {
+        let mut game = zeroed_game();
+        game.price_per_cube = 1_000;
+        game.price_in_points = 7;
+        game.point_value_lamports = 300;
+        game.quadratic_coeff = 3;
+        game.total_cubes_removed = 40;
+        game.rush_window_seconds = 60;
+        game.rush_window_start = 100;
+        game.recent_removal_count = 5;
+        game.rush_bps_per_removal = 200;
+        game.rush_max_bps = 1_000;
+        game.surge_multiplier_bps = 30_000;
+        game.surge_until = 1_000;
+        game.credit_discount_bps = 2_500;
+        assert_ne!(effective_price(&game, None, false, 120).unwrap(), 800);
+
+        let epoch = game.price_epoch;
+        game.reset_pricing(800);
+        for now in [0, 120, 999, 5_000] {
+            assert_eq!(effective_price(&game, None, false, now).unwrap(), 800);
+        }
+        assert_eq!(game.price_epoch, epoch + 1);
+        assert_eq!(game.credit_discount_bps, 0);
+    }
+
+    #[test]
+    fn each_player_can_like_a_removed_cube_once() {
+        let (alice, bob) = (Pubkey::new_unique(), Pubkey::new_unique());
+        // A second like by the same player lands on their existing `Like`,
+        // which `init` refuses
+        assert_eq!(like_pda("", "1:2:3", &alice), like_pda("", "1:2:3", &alice));
+        assert_ne!(like_pda("", "1:2:3", &alice), like_pda("", "1:2:3", &bob));
+        assert_ne!(like_pda("", "1:2:3", &alice), like_pda("", "1:2:4", &alice));
+        assert_ne!(
+            like_pda("", "1:2:3", &alice),
+            like_pda("r1", "1:2:3", &alice)
+        );
+
+        let mut record = zeroed_record();
+        assert_eq!(
+            record.add_like().unwrap_err(),
+            error!(CubeGameError::CubeNotRemoved)
+        );
+        record.is_removed = true;
+        assert_eq!(record.add_like().unwrap(), 1);
+        assert_eq!(record.add_like().unwrap(), 2);
+    }
+
+    #[test]
+    fn removals_fill_the_inventory_and_the_full_policy_applies() {
+        let empty = || {
+            Inventory::try_deserialize_unchecked(&mut &[0u8; 8 + Inventory::INIT_SPACE][..])
+                .unwrap()
+        };
+        let ids: Vec<String> = (0..=MAX_INVENTORY_ITEMS)
+            .map(|i| format!("{i}:0:0"))
+            .collect();
+
+        let mut ring = empty();
+        for cube_id in &ids[..MAX_INVENTORY_ITEMS] {
+            ring.add(cube_id, true).unwrap();
+        }
+        assert_eq!(ring.items, ids[..MAX_INVENTORY_ITEMS]);
+        // A full ring drops its oldest item for the new one
+        ring.add(&ids[MAX_INVENTORY_ITEMS], true).unwrap();
+        assert_eq!(ring.items, ids[1..]);
+
+        let mut capped = empty();
+        for cube_id in &ids[..MAX_INVENTORY_ITEMS] {
+            capped.add(cube_id, false).unwrap();
+        }
+        assert_eq!(
+            capped.add(&ids[MAX_INVENTORY_ITEMS], false).unwrap_err(),
+            error!(CubeGameError::InventoryFull)
+        );
+        assert_eq!(capped.items, ids[..MAX_INVENTORY_ITEMS]);
+    }
+
+    #[test]
+    fn an_over_withdrawal_fails_cleanly_without_recording_anything() {
+        let mut game = zeroed_game();
+        game.withdraw_limit_per_window = 10_000;
+        game.withdraw_window_seconds = 60;
+        assert_eq!(
+            game.authorize_withdrawal(5_001, 5_000, 1_000).unwrap_err(),
+            error!(CubeGameError::InsufficientTreasuryBalance)
+        );
+        assert_eq!(game.window_withdrawn, 0);
+
+        game.authorize_withdrawal(5_000, 5_000, 1_000).unwrap();
+        assert_eq!(game.window_withdrawn, 5_000);
+        assert_eq!(
+            game.authorize_withdrawal(u64::MAX, 0, 1_000).unwrap_err(),
+            error!(CubeGameError::InsufficientTreasuryBalance)
+        );
+        assert_eq!(game.window_withdrawn, 5_000);
+    }
+
+    #[test]
+    fn withdrawals_wait_for_the_removal_threshold() {
+        let mut game = zeroed_game();
+        // No threshold, an empty game may withdraw
+        game.require_withdraw_threshold().unwrap();
+
+        game.min_cubes_before_withdraw = 100;
+        game.total_cubes_removed = 99;
+        assert_eq!(
+            game.require_withdraw_threshold().unwrap_err(),
+            error!(CubeGameError::WithdrawThresholdNotMet)
+        );
+        game.total_cubes_removed = 100;
+        game.require_withdraw_threshold().unwrap();
+        game.total_cubes_removed = 101;
+        game.require_withdraw_threshold().unwrap();
+    }
+
+    #[test]
+    fn a_corrupted_stored_bump_fails_with_a_clear_error() {
+        let mut game = zeroed_game();
+        game.bump = Pubkey::find_program_address(&[b"game_state"], &crate::ID).1;
+        game.treasury_bump = Pubkey::find_program_address(&[b"treasury"], &crate::ID).1;
+        game.verify_pda_bumps().unwrap();
+
+        let (bump, treasury_bump) = (game.bump, game.treasury_bump);
+        game.bump = bump.wrapping_sub(1);
+        assert_eq!(
+            game.verify_pda_bumps().unwrap_err(),
+            error!(CubeGameError::GameStatePdaMismatch)
+        );
+        game.bump = bump;
+        game.treasury_bump = treasury_bump.wrapping_sub(1);
+        assert_eq!(
+            game.verify_pda_bumps().unwrap_err(),
+            error!(CubeGameError::TreasuryPdaMismatch)
+        );
+    }
+
+    #[test]
+    fn practice_removals_stay_in_the_sandbox() {
+        let player = Pubkey::new_unique();
+        let mut stats = zeroed_stats();
+        let mut record = PracticeRecord {
+            is_removed: false,
+            bump: 0,
+        };
+        assert_eq!(
+            stats.practice_remove(&mut record).unwrap_err(),
+            error!(CubeGameError::PracticeModeOff)
+        );
+
+        stats.practice_mode = true;
+        stats.practice_remove(&mut record).unwrap();
+        assert_eq!(
+            stats.practice_remove(&mut record).unwrap_err(),
+            error!(CubeGameError::CubeAlreadyRemoved)
+        );
+        // Only the sandbox count moves
+        assert_eq!((stats.practice_removals, stats.cubes_removed), (1, 0));
+
+        // The practice record lives outside the real record's namespace, so
+        // the real cube is still open to everyone
+        let (practice_pda, _) =
+            Pubkey::find_program_address(&[b"practice", player.as_ref(), b"1:2:3"], &crate::ID);
+        assert_ne!(practice_pda, scoped_cube_record_pda("", "1:2:3"));
+        zeroed_game()
+            .materialize(&zeroed_record(), "1:2:3", &[])
+            .unwrap();
+    }
+
+    #[test]
+    fn the_pause_authority_can_pause_but_not_withdraw_and_vice_versa() {
+        let (owner, pauser, withdrawer) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let mut game = zeroed_game();
+        game.authority = owner;
+        game.pause_authority = pauser;
+        game.withdraw_authority = withdrawer;
+        game.bump = Pubkey::find_program_address(&[b"game_state"], &crate::ID).1;
+        let mut game_data = Vec::new();
+        game.try_serialize(&mut game_data).unwrap();
+
+        // Run `SetPaused` or `Withdraw` account validation with `signer`
+        let validate = |signer: Pubkey, withdraw: bool| -> Result<()> {
+            // The absent optional ledger and cold reserve are both passed as
+            // the program id
+            let keys = [
+                game_state_pda(),
+                treasury_pda(),
+                crate::ID,
+                crate::ID,
+                signer,
+                System::id(),
+            ];
+            let mut lamports = [0u64; 6];
+            let mut data = [game_data.clone(), vec![], vec![], vec![], vec![], vec![]];
+            let owners = [
+                crate::ID,
+                System::id(),
+                System::id(),
+                System::id(),
+                System::id(),
+                System::id(),
+            ];
+            let infos: Vec<AccountInfo> = keys
+                .iter()
+                .zip(lamports.iter_mut())
+                .zip(data.iter_mut())
+                .zip(owners.iter())
+                .enumerate()
+                .map(|(i, (((key, lamports), data), owner))| {
+                    AccountInfo::new(key, i == 4, i != 5, lamports, data, owner, i == 5, 0)
+                })
+                .collect();
+            let mut reallocs = std::collections::BTreeSet::new();
+            if withdraw {
+                let mut accounts = &infos[..];
+                Withdraw::try_accounts(
+                    &crate::ID,
+                    &mut accounts,
+                    &[],
+                    &mut WithdrawBumps::default(),
+                    &mut reallocs,
+                )
+                .map(|_| ())
+            } else {
+                let paused = [infos[0].clone(), infos[4].clone()];
+                let mut accounts = &paused[..];
+                SetPaused::try_accounts(
+                    &crate::ID,
+                    &mut accounts,
+                    &[],
+                    &mut SetPausedBumps::default(),
+                    &mut reallocs,
+                )
+                .map(|_| ())
+            }
+        };
+
+        validate(pauser, false).unwrap();
+        validate(withdrawer, true).unwrap();
+        for (signer, withdraw) in [(pauser, true), (withdrawer, false), (owner, false)] {
+            assert_eq!(
+                validate(signer, withdraw).unwrap_err(),
+                error!(CubeGameError::Unauthorized)
+            );
+        }
+    }
+
+    #[test]
+    fn a_sequenced_cube_waits_for_its_predecessor() {
+        let mut first = zeroed_record();
+        first.cube_id = "a".to_string();
+        let mut second = zeroed_record();
+        second.cube_id = "b".to_string();
+        second.prev_cube_id = Some("a".to_string());
+        let first_key = scoped_cube_record_pda("r", "a");
+
+        // A cube without a predecessor is never held back
+        first.require_predecessor_removed("r", None).unwrap();
+
+        // Out of order: the predecessor is missing or still standing
+        for predecessor in [None, Some((first_key, &first))] {
+            assert_eq!(
+                second
+                    .require_predecessor_removed("r", predecessor)
+                    .unwrap_err(),
+                error!(CubeGameError::PredecessorNotRemoved)
+            );
+        }
+
+        // In order: once the predecessor is removed the cube can go
+        first.is_removed = true;
+        second
+            .require_predecessor_removed("r", Some((first_key, &first)))
+            .unwrap();
+
+        // The predecessor must be the one from the same region
+        assert_eq!(
+            second
+                .require_predecessor_removed(
+                    "r",
+                    Some((scoped_cube_record_pda("other", "a"), &first))
+                )
+                .unwrap_err(),
+            error!(CubeGameError::CubeRecordMismatch)
+        );
+    }
+
+    #[test]
+    fn a_region_authority_manages_only_its_own_region() {
+        let (owner, delegate) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let mut game = zeroed_game();
+        game.authority = owner;
+        game.bump = Pubkey::find_program_address(&[b"game_state"], &crate::ID).1;
+        let mut game_data = Vec::new();
+        game.try_serialize(&mut game_data).unwrap();
+        // Region "a" is delegated, region "b" is left to the owner
+        let region = |region_id: &str| {
+            let (key, bump) =
+                Pubkey::find_program_address(&[b"region", region_id.as_bytes()], &crate::ID);
+            let region = Region {
+                region_id: region_id.to_string(),
+                base_price: 1_000,
+                increment: 0,
+                cubes_removed: 0,
+                bump,
+                region_authority: if region_id == "a" {
+                    delegate
+                } else {
+                    Pubkey::default()
+                },
+                paused: false,
+            };
+            let mut data = Vec::new();
+            region.try_serialize(&mut data).unwrap();
+            (key, data)
+        };
+
+        // Run `RegionAdmin` (`set_region_price`) account validation for
+        // `region_id`, or `SetPrice` when it is `None`
+        let validate = |signer: Pubkey, region_id: Option<&str>| -> Result<()> {
+            let (region_key, region_data) = region(region_id.unwrap_or("a"));
+            let keys = [game_state_pda(), region_key, signer];
+            let mut lamports = [0u64; 3];
+            let mut data = [game_data.clone(), region_data, vec![]];
+            let owners = [crate::ID, crate::ID, System::id()];
+            let infos: Vec<AccountInfo> = keys
+                .iter()
+                .zip(lamports.iter_mut())
+                .zip(data.iter_mut())
+                .zip(owners.iter())
+                .enumerate()
+                .map(|(i, (((key, lamports), data), owner))| {
+                    AccountInfo::new(key, i == 2, true, lamports, data, owner, false, 0)
+                })
+                .collect();
+            let mut reallocs = std::collections::BTreeSet::new();
+            match region_id {
+                Some(region_id) => {
+                    let mut accounts = &infos[..];
+                    RegionAdmin::try_accounts(
+                        &crate::ID,
+                        &mut accounts,
+                        &region_id.to_string().try_to_vec().unwrap(),
+                        &mut RegionAdminBumps::default(),
+                        &mut reallocs,
+                    )
+                    .map(|_| ())
+                }
+                None => {
+                    let global = [infos[0].clone(), infos[2].clone()];
+                    let mut accounts = &global[..];
+                    SetPrice::try_accounts(
+                        &crate::ID,
+                        &mut accounts,
+                        &[],
+                        &mut SetPriceBumps::default(),
+                        &mut reallocs,
+                    )
+                    .map(|_| ())
+                }
+            }
+        };
+
+        validate(delegate, Some("a")).unwrap();
+        for region_id in [Some("b"), None] {
+            assert_eq!(
+                validate(delegate, region_id).unwrap_err(),
+                error!(CubeGameError::Unauthorized)
+            );
+        }
+        // The owner can always override
+        for region_id in [Some("a"), Some("b"), None] {
+            validate(owner, region_id).unwrap();
+        }
+    }
+
+    #[test]
+    fn a_subscription_covers_removals_until_it_expires() {
+        let mut pass = Subscription {
+            player: Pubkey::new_unique(),
+            tier: 0,
+            expires_at: 1_000,
+            removals_remaining: 2,
+            bump: 0,
+        };
+        // A quota pass covers removals while it lasts, then the player pays
+        assert!(pass.use_removal(999));
+        assert!(pass.use_removal(999));
+        assert!(!pass.use_removal(999));
+        assert_eq!(pass.removals_remaining, 0);
+
+        // An unlimited pass ignores the quota until it expires
+        pass.tier = SUBSCRIPTION_UNLIMITED;
+        assert!(pass.use_removal(999));
+        assert!(!pass.use_removal(1_000));
+
+        // An expired quota pass falls back to charging
+        // without spending its quota
+        pass.tier = 0;
+        pass.removals_remaining = 5;
+        assert!(!pass.use_removal(1_000));
+        assert_eq!(pass.removals_remaining, 5);
+    }
+
+    #[test]
+    fn only_a_nearly_broke_player_is_warned() {
+        let mut game = zeroed_game();
+        let player = Pubkey::new_unique();
+        // Off by default
+        assert!(game.low_balance_warning(player, 0).is_none());
+
+        game.low_balance_threshold = 10_000_000;
+        let warning = game.low_balance_warning(player, 9_999_999).unwrap();
+        assert_eq!(warning.player, player);
+        assert_eq!(warning.balance, 9_999_999);
+        assert_eq!(warning.threshold, 10_000_000);
+        assert!(game.low_balance_warning(player, 10_000_000).is_none());
+        assert!(game.low_balance_warning(player, 5_000_000_000).is_none());
+    }
+
+    #[test]
+    fn a_just_registered_player_waits_out_the_minimum_age() {
+        let mut game = zeroed_game();
+        game.min_player_age = 3_600;
+        let mut stats = zeroed_stats();
+        // Never registered
+        assert_eq!(
+            stats.require_aged(&game, 1_000_000).unwrap_err(),
+            error!(CubeGameError::PlayerTooNew)
+        );
+
+        stats.stamp_registration(Pubkey::new_unique(), 10_000);
+        for now in [10_000, 13_599] {
+            assert_eq!(
+                stats.require_aged(&game, now).unwrap_err(),
+                error!(CubeGameError::PlayerTooNew)
+            );
+        }
+        stats.require_aged(&game, 13_600).unwrap();
+
+        // Registering again doesn't restart the clock
+        stats.stamp_registration(stats.player, 13_600);
+        assert_eq!(stats.registered_at, 10_000);
+        stats.require_aged(&game, 13_600).unwrap();
+
+        // No gate while min_player_age is unset
+        game.min_player_age = 0;
+        zeroed_stats().require_aged(&game, 0).unwrap();
+    }
+
+    #[test]
+    fn pick_entrant_respects_weights_for_a_fixed_seed() {
+        let a = Pubkey::new_unique();
+        let b = Pubkey::new_unique();
+        let entrants = [(a, 1), (b, 3)];
+        let picks: Vec<_> = (0..4)
+            .map(|ticket| pick_entrant(&entrants, 0, ticket))
+            .collect();
+        assert_eq!(picks, [Some(a), Some(b), Some(b), Some(b)]);
+
+        let seed = [7u8; 32];
+        let ticket = draw_ticket(&seed, 4);
+        assert_eq!(ticket, u64::from_le_bytes([7; 8]) % 4);
+        assert_eq!(pick_entrant(&entrants, 0, ticket), Some(b));
+    }
+
+    #[test]
+    fn pick_entrant_continues_across_batches() {
+        let a = Pubkey::new_unique();
+        let b = Pubkey::new_unique();
+        assert_eq!(pick_entrant(&[(a, 2)], 0, 3), None);
+        assert_eq!(pick_entrant(&[(b, 2)], 2, 3), Some(b));
+        assert_eq!(pick_entrant(&[(b, 2)], 2, 1), None);
+    }
+
+    #[test]
+    fn credit_share_merges_payers_and_caps_wallets() {
+        let a = Pubkey::new_unique();
+        let mut shares = Vec::new();
+        credit_share(&mut shares, a, 5).unwrap();
+        credit_share(&mut shares, a, 7).unwrap();
+        assert_eq!(shares.len(), 1);
+        assert_eq!(shares[0].amount, 12);
+
+        for _ in 1..MAX_CUBE_CONTRIBUTORS {
+            credit_share(&mut shares, Pubkey::new_unique(), 1).unwrap();
+        }
+        assert!(credit_share(&mut shares, Pubkey::new_unique(), 1).is_err());
+        credit_share(&mut shares, a, 1).unwrap();
+        assert_eq!(shares[0].amount, 13);
+    }
+
+    fn slot_hashes_data(entries: &[(u64, u8)]) -> Vec<u8> {
+        let mut data = (entries.len() as u64).to_le_bytes().to_vec();
+        for (slot, fill) in entries {
+            data.extend_from_slice(&slot.to_le_bytes());
+            data.extend_from_slice(&[*fill; 32]);
+        }
+        data
+    }
+
+    #[test]
+    fn slot_hash_after_takes_the_first_later_slot() {
+        let data = slot_hashes_data(&[(105, 5), (103, 3), (100, 1)]);
+        assert_eq!(slot_hash_after(&data, 101), Some([3; 32]));
+        assert_eq!(slot_hash_after(&data, 103), Some([5; 32]));
+        // Nothing after the newest slot yet
+        assert_eq!(slot_hash_after(&data, 105), None);
+        // The slot after 99 may have left the window
+        assert_eq!(slot_hash_after(&data, 99), None);
+        assert_eq!(slot_hash_after(&data[..20], 101), None);
+    }
 }